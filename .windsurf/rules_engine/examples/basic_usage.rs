@@ -1,28 +1,66 @@
 //! Basic usage example for the Windsurf Rules Engine
 
-use windsurf_rules_engine::prelude::*;
-use windsurf_rules_engine::engine::RulesEngine;
+use windsurf_rules::prelude::*;
+use windsurf_rules::engine::RulesEngine;
+use windsurf_rules::HyperEngine;
 use std::time::Instant;
 
 fn main() {
     println!("🚀 Starting Windsurf Rules Engine example...");
     
-    // Create a new engine instance
+    // Example 1: Evaluate a rule set against a fact context
     let mut engine = RulesEngine::new();
-    
-    // Example 1: Process data using SIMD acceleration
+    engine
+        .add_definition(RuleDefinition {
+            id: "adult".to_string(),
+            name: "Adult customer".to_string(),
+            expression: "customer.age >= 18".to_string(),
+            priority: 1,
+            ..RuleDefinition::default()
+        })
+        .expect("valid rule");
+    engine
+        .add_definition(RuleDefinition {
+            id: "vip".to_string(),
+            name: "VIP customer".to_string(),
+            expression: "customer.tier in ['gold', 'platinum'] && customer.orders > 10".to_string(),
+            priority: 2,
+            then_actions: vec![Action::set("customer.discount", "0.1")],
+            else_actions: vec![Action::message("{customer.orders} orders, not VIP yet")],
+            ..RuleDefinition::default()
+        })
+        .expect("valid rule");
+
+    let customer: Value = [
+        ("age", Value::from(34)),
+        ("tier", Value::from("gold")),
+        ("orders", Value::from(4)),
+    ]
+    .into_iter()
+    .collect();
+    let context = RuleContext::new().with_fact("customer", customer);
+
+    let results = engine.execute(&context).expect("execution succeeds");
+    println!("✅ Evaluated {} rules in {:?}", results.outcomes.len(), results.total_duration);
+    println!("   Matched: {:?}", results.matched_rules());
+    for (rule_id, effect) in results.effects() {
+        println!("   Effect from {}: {:?}", rule_id, effect);
+    }
+
+    // Example 2: Process data using SIMD acceleration
+    let mut hyper = HyperEngine::new();
     let input = [1.0; 16];
     let mut output = [0.0; 16];
     
     let start = Instant::now();
-    engine.process_data(&input, &mut output);
+    hyper.process_data(&input, &mut output);
     let duration = start.elapsed();
     
-    println!("✅ Processed {} elements in {:?}", input.len(), duration);
+    println!("\n✅ Processed {} elements in {:?}", input.len(), duration);
     println!("   Input:  {:?}", &input[..8]);
     println!("   Output: {:?}", &output[..8]);
     
-    // Example 2: Parallel processing with work-stealing
+    // Example 3: Parallel processing with work-stealing
     use std::sync::atomic::{AtomicU64, Ordering};
     use std::sync::Arc;
     
@@ -41,15 +79,15 @@ fn main() {
     }
     
     let start = Instant::now();
-    engine.process_parallel(tasks);
+    hyper.process_parallel(tasks);
     let duration = start.elapsed();
     
     println!("✅ Completed {} tasks in {:?}", num_tasks, duration);
     println!("   Final counter value: {}", counter.load(Ordering::Relaxed));
     
-    // Example 3: Memory pool usage
+    // Example 4: Memory pool usage
     println!("\n🚀 Allocating memory from the pool...");
-    let block = engine.get_memory_block();
+    let block = hyper.get_memory_block();
     println!("✅ Allocated block of {} bytes", block.len());
     
     println!("\n✨ Example completed successfully!");
@@ -0,0 +1,430 @@
+//! Read/write dependencies between rules
+//!
+//! A rule depends on another when it reads a fact path the other rule's
+//! actions set or remove. Paths overlap when one is a prefix of the other:
+//! writing `customer` affects a rule reading `customer.age`, and writing
+//! `customer.tier` affects a rule reading `customer`. Rules in a dependency
+//! cycle can keep re-activating each other under forward chaining; an acyclic
+//! graph splits into strata whose rules are independent of one another.
+//!
+//! ```
+//! use windsurf_rules::prelude::*;
+//! use windsurf_rules::analysis::DependencyGraph;
+//!
+//! let rules = [
+//!     RuleDefinition::new("gold")
+//!         .with_expression("customer.spend > 1000")
+//!         .then(Action::set("customer.tier", "'gold'")),
+//!     RuleDefinition::new("free_shipping")
+//!         .with_expression("customer.tier == 'gold'")
+//!         .then(Action::set("order.shipping", "0")),
+//!     RuleDefinition::new("adult").with_expression("customer.age >= 18"),
+//! ]
+//! .map(|rule| rule.compile().unwrap());
+//!
+//! let graph = DependencyGraph::new(rules.iter().map(|rule| rule as &dyn Rule));
+//! assert_eq!(graph.dependents("gold"), ["free_shipping"]);
+//! assert_eq!(graph.strata()?, [vec!["gold", "adult"], vec!["free_shipping"]]);
+//! # Ok::<(), windsurf_rules::Error>(())
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+use crate::error::{Error, Result};
+use crate::expr::Path;
+use crate::rule::evaluate::Rule;
+
+/// One rule reading a fact path another rule writes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Dependency {
+    /// Id of the rule whose action writes the path
+    pub writer: String,
+    /// Id of the rule that reads it
+    pub reader: String,
+    /// The path as written by `writer`
+    pub path: String,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    id: String,
+    reads: Vec<Path>,
+    writes: Vec<Path>,
+}
+
+/// The read/write dependency graph of a rule set
+#[derive(Debug, Clone, Default)]
+pub struct DependencyGraph {
+    nodes: Vec<Node>,
+    /// Readers of each node's writes, by node index
+    successors: Vec<BTreeSet<usize>>,
+    dependencies: Vec<Dependency>,
+}
+
+impl DependencyGraph {
+    /// Builds the graph of `rules` from their [`Rule::reads`] and
+    /// [`Rule::writes`]; rules keep the order they are given in
+    pub fn new<'a>(rules: impl IntoIterator<Item = &'a dyn Rule>) -> Self {
+        let nodes: Vec<Node> = rules
+            .into_iter()
+            .map(|rule| Node {
+                id: rule.id().to_string(),
+                reads: distinct(rule.reads()),
+                writes: distinct(rule.writes()),
+            })
+            .collect();
+
+        let mut successors = vec![BTreeSet::new(); nodes.len()];
+        let mut dependencies = Vec::new();
+        for (writer, node) in nodes.iter().enumerate() {
+            for path in &node.writes {
+                for (reader, other) in nodes.iter().enumerate() {
+                    if other.reads.iter().any(|read| overlaps(path, read)) {
+                        successors[writer].insert(reader);
+                        dependencies.push(Dependency {
+                            writer: node.id.clone(),
+                            reader: other.id.clone(),
+                            path: path.to_string(),
+                        });
+                    }
+                }
+            }
+        }
+
+        Self {
+            nodes,
+            successors,
+            dependencies,
+        }
+    }
+
+    /// Number of rules in the graph
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns true if the graph has no rules
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Ids of all rules, in the order they were given
+    pub fn rule_ids(&self) -> impl Iterator<Item = &str> {
+        self.nodes.iter().map(|node| node.id.as_str())
+    }
+
+    /// Distinct paths the rule reads, or `None` for an unknown rule
+    pub fn reads(&self, rule_id: &str) -> Option<&[Path]> {
+        self.node(rule_id)
+            .map(|index| self.nodes[index].reads.as_slice())
+    }
+
+    /// Distinct paths the rule writes, or `None` for an unknown rule
+    pub fn writes(&self, rule_id: &str) -> Option<&[Path]> {
+        self.node(rule_id)
+            .map(|index| self.nodes[index].writes.as_slice())
+    }
+
+    /// Every dependency, grouped by writer in rule order
+    pub fn dependencies(&self) -> &[Dependency] {
+        &self.dependencies
+    }
+
+    /// Ids of the rules reading what `rule_id` writes
+    pub fn dependents(&self, rule_id: &str) -> Vec<&str> {
+        self.node(rule_id)
+            .map(|index| self.ids(self.successors[index].iter().copied()))
+            .unwrap_or_default()
+    }
+
+    /// Ids of the rules writing what `rule_id` reads
+    pub fn prerequisites(&self, rule_id: &str) -> Vec<&str> {
+        match self.node(rule_id) {
+            Some(index) => self.ids(
+                (0..self.nodes.len()).filter(|&writer| self.successors[writer].contains(&index)),
+            ),
+            None => Vec::new(),
+        }
+    }
+
+    /// Groups of rules that depend on each other in a cycle, including rules
+    /// that read what they write themselves; each group is in rule order
+    pub fn cycles(&self) -> Vec<Vec<&str>> {
+        let mut cycles: Vec<Vec<usize>> = self
+            .components()
+            .into_iter()
+            .filter(|component| {
+                component.len() > 1 || self.successors[component[0]].contains(&component[0])
+            })
+            .collect();
+        for cycle in &mut cycles {
+            cycle.sort_unstable();
+        }
+        cycles.sort_unstable();
+        cycles.into_iter().map(|cycle| self.ids(cycle)).collect()
+    }
+
+    /// Returns true if any rule can re-activate itself through its writes
+    pub fn has_cycles(&self) -> bool {
+        !self.cycles().is_empty()
+    }
+
+    /// Splits the rules into strata: every rule comes after all the rules it
+    /// depends on, and rules within a stratum do not depend on each other.
+    /// A rule that reads a prefix of what it writes, such as reading `order`
+    /// and writing `order.discount`, does not hold up its own stratum. Fails
+    /// with [`Error::DependencyCycle`] on a cycle of two or more rules, or on
+    /// a rule reading the exact path it writes.
+    pub fn strata(&self) -> Result<Vec<Vec<&str>>> {
+        let blocking = self.cycles().into_iter().find(|cycle| match cycle[..] {
+            [rule_id] => self.node(rule_id).is_some_and(|index| {
+                let node = &self.nodes[index];
+                node.writes.iter().any(|write| node.reads.contains(write))
+            }),
+            _ => true,
+        });
+        if let Some(cycle) = blocking {
+            return Err(Error::DependencyCycle(
+                cycle.into_iter().map(str::to_string).collect(),
+            ));
+        }
+
+        let mut remaining = vec![0usize; self.nodes.len()];
+        for (writer, successors) in self.successors.iter().enumerate() {
+            for &reader in successors.iter().filter(|&&reader| reader != writer) {
+                remaining[reader] += 1;
+            }
+        }
+        let mut stratum: Vec<usize> = (0..self.nodes.len())
+            .filter(|&index| remaining[index] == 0)
+            .collect();
+        let mut strata = Vec::new();
+        while !stratum.is_empty() {
+            let mut next = Vec::new();
+            for &writer in &stratum {
+                for &reader in self.successors[writer]
+                    .iter()
+                    .filter(|&&reader| reader != writer)
+                {
+                    remaining[reader] -= 1;
+                    if remaining[reader] == 0 {
+                        next.push(reader);
+                    }
+                }
+            }
+            next.sort_unstable();
+            strata.push(self.ids(stratum));
+            stratum = next;
+        }
+        Ok(strata)
+    }
+
+    fn node(&self, rule_id: &str) -> Option<usize> {
+        self.nodes.iter().position(|node| node.id == rule_id)
+    }
+
+    fn ids(&self, indices: impl IntoIterator<Item = usize>) -> Vec<&str> {
+        indices
+            .into_iter()
+            .map(|index| self.nodes[index].id.as_str())
+            .collect()
+    }
+
+    /// Strongly connected components, using Tarjan's algorithm
+    fn components(&self) -> Vec<Vec<usize>> {
+        components(&self.successors)
+    }
+}
+
+/// Strongly connected components of the graph given by each node's
+/// successors, using Tarjan's algorithm
+fn components(successors: &[BTreeSet<usize>]) -> Vec<Vec<usize>> {
+    let mut tarjan = Tarjan {
+        successors,
+        index: vec![None; successors.len()],
+        lowlink: vec![0; successors.len()],
+        on_stack: vec![false; successors.len()],
+        stack: Vec::new(),
+        next_index: 0,
+        components: Vec::new(),
+    };
+    for node in 0..successors.len() {
+        if tarjan.index[node].is_none() {
+            tarjan.visit(node);
+        }
+    }
+    tarjan.components
+}
+
+struct Tarjan<'a> {
+    successors: &'a [BTreeSet<usize>],
+    index: Vec<Option<usize>>,
+    lowlink: Vec<usize>,
+    on_stack: Vec<bool>,
+    stack: Vec<usize>,
+    next_index: usize,
+    components: Vec<Vec<usize>>,
+}
+
+impl Tarjan<'_> {
+    /// Visits every node reachable from `root`. The depth-first search keeps
+    /// its own stack of nodes and their unvisited successors, so long
+    /// dependency chains cannot overflow the thread's stack.
+    fn visit(&mut self, root: usize) {
+        let successors = self.successors;
+        self.open(root);
+        let mut calls = vec![(root, successors[root].iter())];
+        while let Some((node, pending)) = calls.last_mut() {
+            let node = *node;
+            if let Some(&successor) = pending.next() {
+                match self.index[successor] {
+                    None => {
+                        self.open(successor);
+                        calls.push((successor, successors[successor].iter()));
+                    }
+                    Some(index) if self.on_stack[successor] => {
+                        self.lowlink[node] = self.lowlink[node].min(index);
+                    }
+                    Some(_) => {}
+                }
+                continue;
+            }
+
+            calls.pop();
+            if let Some(&(parent, _)) = calls.last() {
+                self.lowlink[parent] = self.lowlink[parent].min(self.lowlink[node]);
+            }
+            if Some(self.lowlink[node]) == self.index[node] {
+                let mut component = Vec::new();
+                while let Some(member) = self.stack.pop() {
+                    self.on_stack[member] = false;
+                    component.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+                self.components.push(component);
+            }
+        }
+    }
+
+    fn open(&mut self, node: usize) {
+        self.index[node] = Some(self.next_index);
+        self.lowlink[node] = self.next_index;
+        self.next_index += 1;
+        self.stack.push(node);
+        self.on_stack[node] = true;
+    }
+}
+
+fn distinct(paths: Vec<&Path>) -> Vec<Path> {
+    let mut distinct: Vec<Path> = Vec::with_capacity(paths.len());
+    for path in paths {
+        if !distinct.contains(path) {
+            distinct.push(path.clone());
+        }
+    }
+    distinct
+}
+
+/// Whether writing `written` can change the value read at `read`
+fn overlaps(written: &Path, read: &Path) -> bool {
+    let common = written.segments.len().min(read.segments.len());
+    written.segments[..common] == read.segments[..common]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::{Action, CompiledRule, Rule as RuleDefinition};
+
+    fn rule(id: &str, expression: &str, writes: &[&str]) -> CompiledRule {
+        writes
+            .iter()
+            .fold(
+                RuleDefinition::new(id).with_expression(expression),
+                |rule, path| rule.then(Action::set(*path, "true")),
+            )
+            .compile()
+            .unwrap()
+    }
+
+    fn graph(rules: &[CompiledRule]) -> DependencyGraph {
+        DependencyGraph::new(rules.iter().map(|rule| rule as &dyn Rule))
+    }
+
+    #[test]
+    fn test_prefix_paths_create_dependencies() {
+        let rules = [
+            rule("score", "customer.age > 18", &["customer.flags.adult"]),
+            rule("tier", "customer.flags != null", &["shipping"]),
+            rule("audit", "shipping.cost == 0", &[]),
+        ];
+        let graph = graph(&rules);
+        assert_eq!(graph.dependents("score"), ["tier"]);
+        assert_eq!(graph.prerequisites("audit"), ["tier"]);
+        assert_eq!(graph.dependencies()[1].path, "shipping");
+        assert_eq!(
+            graph.strata().unwrap(),
+            [vec!["score"], vec!["tier"], vec!["audit"]]
+        );
+        assert!(graph.dependents("unknown").is_empty());
+    }
+
+    #[test]
+    fn test_cycles_are_detected() {
+        let rules = [
+            rule("a", "x.value > 0", &["y.value"]),
+            rule("b", "y.value > 0", &["z.value"]),
+            rule("c", "z.value > 0", &["x.value"]),
+            rule("counter", "n.count < 10", &["n.count"]),
+            rule("independent", "w > 0", &[]),
+        ];
+        let graph = graph(&rules);
+        assert_eq!(graph.cycles(), [vec!["a", "b", "c"], vec!["counter"]]);
+        assert!(matches!(
+            graph.strata(),
+            Err(Error::DependencyCycle(cycle)) if cycle == ["a", "b", "c"]
+        ));
+    }
+
+    #[test]
+    fn test_strata_allow_prefix_self_loops() {
+        let counter = [rule("counter", "n.count < 10", &["n.count"])];
+        assert!(matches!(
+            graph(&counter).strata(),
+            Err(Error::DependencyCycle(cycle)) if cycle == ["counter"]
+        ));
+
+        let rules = [
+            rule(
+                "discount",
+                "order != null && order.total > 100",
+                &["order.discount"],
+            ),
+            rule("shipping", "order.discount > 0", &["shipping.cost"]),
+        ];
+        let graph = graph(&rules);
+        assert_eq!(graph.cycles(), [vec!["discount"]]);
+        assert_eq!(
+            graph.strata().unwrap(),
+            [vec!["discount"], vec!["shipping"]]
+        );
+    }
+
+    #[test]
+    fn test_components_of_long_chains() {
+        // Deep enough to overflow the stack of a recursive search
+        let length = 200_000;
+        let mut successors: Vec<BTreeSet<usize>> =
+            (1..length).map(|next| BTreeSet::from([next])).collect();
+        successors.push(BTreeSet::from([0]));
+        let cycle = components(&successors);
+        assert_eq!(cycle.len(), 1);
+        assert_eq!(cycle[0].len(), length);
+
+        successors[length - 1].clear();
+        assert_eq!(components(&successors).len(), length);
+    }
+}
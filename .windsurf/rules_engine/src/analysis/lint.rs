@@ -0,0 +1,456 @@
+//! Linting of rule definitions
+//!
+//! The [`Linter`] checks a rule set for mistakes that parse and evaluate fine
+//! but are almost certainly unintended. Every finding is a [`Diagnostic`]
+//! with the offending rule's id; findings about a condition also carry its
+//! span. Codes and default severities:
+//!
+//! | Code | Severity | Finding |
+//! |------|----------|---------|
+//! | `duplicate-id` | error | an id already used by an earlier rule |
+//! | `empty-id`, `empty-expression` | error | a blank id or expression |
+//! | `empty-name` | warning | a blank name |
+//! | `syntax-error` | error | an expression or action that does not parse |
+//! | `always-true`, `always-false` | warning | a condition, or an operand of `&&`/`||`, with a constant value |
+//! | `shadowed-rule` | warning | a rule preempted by a more general rule of its activation group |
+//! | `suspicious-priority` | warning | an extreme priority, or a tie deciding an activation group by definition order |
+//!
+//! With a [`Schema`], type-checking diagnostics are included as well.
+//! Severities can be overridden per code, e.g. to fail CI on warnings:
+//!
+//! ```
+//! use windsurf_rules::prelude::*;
+//! use windsurf_rules::analysis::Linter;
+//!
+//! let rules = [
+//!     RuleDefinition::new("always").with_expression("1 == 1"),
+//!     RuleDefinition::new("always").with_expression("customer.age >= 18"),
+//! ];
+//! let diagnostics = Linter::new()
+//!     .with_severity("always-true", Severity::Error)
+//!     .lint(&rules);
+//! let codes: Vec<&str> = diagnostics.iter().map(|d| d.code.as_str()).collect();
+//! assert_eq!(codes, ["always-true", "duplicate-id"]);
+//! assert!(diagnostics.iter().all(Diagnostic::is_error));
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use crate::analysis::Schema;
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::expr::{function, BinaryOp, Evaluator, Expr, ExprKind, UnaryOp};
+use crate::rule::evaluate::diagnostic_for;
+use crate::rule::{CompiledRule, Rule as RuleDefinition, RuleContext, Value};
+
+/// Default limit above which a priority's magnitude is reported
+pub const DEFAULT_MAX_PRIORITY: i32 = 1_000;
+
+/// Checks rule definitions for likely mistakes
+#[derive(Debug, Clone)]
+pub struct Linter {
+    schema: Option<Schema>,
+    max_priority: i32,
+    /// Severity overrides by code; `None` silences the code
+    overrides: BTreeMap<String, Option<Severity>>,
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Self {
+            schema: None,
+            max_priority: DEFAULT_MAX_PRIORITY,
+            overrides: BTreeMap::new(),
+        }
+    }
+}
+
+impl Linter {
+    /// Creates a linter with every check enabled at its default severity
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also type-checks each rule against `schema`
+    pub fn with_schema(mut self, schema: Schema) -> Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    /// Reports priorities whose magnitude exceeds `max_priority`
+    pub fn with_max_priority(mut self, max_priority: i32) -> Self {
+        self.max_priority = max_priority;
+        self
+    }
+
+    /// Reports diagnostics with `code` at `severity`
+    pub fn with_severity(mut self, code: impl Into<String>, severity: Severity) -> Self {
+        self.overrides.insert(code.into(), Some(severity));
+        self
+    }
+
+    /// Stops reporting diagnostics with `code`
+    pub fn allow(mut self, code: impl Into<String>) -> Self {
+        self.overrides.insert(code.into(), None);
+        self
+    }
+
+    /// Lints `rules` in order; diagnostics are grouped by rule
+    pub fn lint(&self, rules: &[RuleDefinition]) -> Vec<Diagnostic> {
+        let mut seen = HashSet::new();
+        let mut shadowed = HashSet::new();
+        let mut checked: Vec<Option<Checked<'_>>> = Vec::with_capacity(rules.len());
+        // Diagnostics of each rule, including those found while checking later ones
+        let mut buckets: Vec<Vec<Diagnostic>> = Vec::with_capacity(rules.len());
+
+        for (index, rule) in rules.iter().enumerate() {
+            let mut found = Vec::new();
+            if rule.id.trim().is_empty() {
+                found.push(Diagnostic::error("empty-id", "rule id is empty"));
+            } else if !seen.insert(rule.id.as_str()) {
+                found.push(Diagnostic::error(
+                    "duplicate-id",
+                    format!("rule id `{}` is already used by an earlier rule", rule.id),
+                ));
+            }
+            if rule.name.trim().is_empty() {
+                found.push(Diagnostic::warning("empty-name", "rule name is empty"));
+            }
+            if rule
+                .priority
+                .checked_abs()
+                .is_none_or(|p| p > self.max_priority)
+            {
+                found.push(Diagnostic::warning(
+                    "suspicious-priority",
+                    format!(
+                        "priority {} is outside the range -{max}..={max}",
+                        rule.priority,
+                        max = self.max_priority
+                    ),
+                ));
+            }
+
+            let compiled = if rule.expression.trim().is_empty() {
+                found.push(Diagnostic::error(
+                    "empty-expression",
+                    "rule expression is empty",
+                ));
+                None
+            } else {
+                match rule.clone().compile() {
+                    Ok(compiled) => Some(compiled),
+                    Err(err) => {
+                        found.push(diagnostic_for(&err));
+                        None
+                    }
+                }
+            };
+
+            let current = compiled.map(|compiled| Checked::new(index, rule, compiled));
+            if let Some(current) = &current {
+                check_constants(current.compiled.condition(), &mut found);
+                if rule.is_active {
+                    self.check_activation_group(
+                        current,
+                        &checked,
+                        &mut shadowed,
+                        &mut found,
+                        &mut buckets,
+                    );
+                }
+                if let Some(schema) = &self.schema {
+                    found.extend(schema.check_rule(&current.compiled));
+                }
+            }
+            checked.push(current);
+            buckets.push(found);
+        }
+
+        rules
+            .iter()
+            .zip(buckets)
+            .flat_map(|(rule, found)| {
+                found
+                    .into_iter()
+                    .filter_map(|diagnostic| self.apply_override(diagnostic))
+                    .map(|diagnostic| match diagnostic.rule_id {
+                        Some(_) => diagnostic,
+                        None => diagnostic.with_rule(rule.id.clone()),
+                    })
+            })
+            .collect()
+    }
+
+    /// Compares `current` with the earlier active rules of its activation
+    /// group; each rule is reported as shadowed at most once, in its own
+    /// bucket of `earlier_found` if it is an earlier rule
+    fn check_activation_group(
+        &self,
+        current: &Checked<'_>,
+        earlier: &[Option<Checked<'_>>],
+        shadowed: &mut HashSet<String>,
+        found: &mut Vec<Diagnostic>,
+        earlier_found: &mut [Vec<Diagnostic>],
+    ) {
+        let Some(group) = &current.definition.activation_group else {
+            return;
+        };
+        let rivals = earlier.iter().flatten().filter(|other| {
+            other.definition.is_active && other.definition.activation_group.as_ref() == Some(group)
+        });
+        for other in rivals {
+            // Under priority order, ties go to the earlier definition
+            let other_first = other.definition.priority >= current.definition.priority;
+            let (first, second) = if other_first {
+                (other, current)
+            } else {
+                (current, other)
+            };
+            if first.conjuncts.is_subset(&second.conjuncts) {
+                if !shadowed.insert(second.definition.id.clone()) {
+                    continue;
+                }
+                let diagnostic = Diagnostic::warning(
+                    "shadowed-rule",
+                    format!(
+                        "rule `{}` never fires: `{}` fires first in activation group `{}` whenever it matches",
+                        second.definition.id, first.definition.id, group
+                    ),
+                );
+                let span = second.compiled.condition().span;
+                let bucket = if other_first {
+                    &mut *found
+                } else {
+                    &mut earlier_found[other.index]
+                };
+                bucket.push(diagnostic.with_span(span));
+            } else if other.definition.priority == current.definition.priority {
+                found.push(Diagnostic::warning(
+                    "suspicious-priority",
+                    format!(
+                        "rules `{}` and `{}` share priority {} in activation group `{}`, so definition order decides which fires",
+                        other.definition.id, current.definition.id, current.definition.priority, group
+                    ),
+                ));
+            }
+        }
+    }
+
+    fn apply_override(&self, mut diagnostic: Diagnostic) -> Option<Diagnostic> {
+        match self.overrides.get(&diagnostic.code) {
+            Some(None) => None,
+            Some(Some(severity)) => {
+                diagnostic.severity = *severity;
+                Some(diagnostic)
+            }
+            None => Some(diagnostic),
+        }
+    }
+}
+
+/// Lints `rules` with the default [`Linter`]
+pub fn lint(rules: &[RuleDefinition]) -> Vec<Diagnostic> {
+    Linter::new().lint(rules)
+}
+
+/// A rule that compiled, with the conjuncts of its condition
+struct Checked<'a> {
+    /// Position of the rule in the linted slice
+    index: usize,
+    definition: &'a RuleDefinition,
+    compiled: CompiledRule,
+    /// Non-constant conjuncts, as source text
+    conjuncts: BTreeSet<String>,
+}
+
+impl<'a> Checked<'a> {
+    fn new(index: usize, definition: &'a RuleDefinition, compiled: CompiledRule) -> Self {
+        let conjuncts = compiled
+            .condition()
+            .conjuncts()
+            .into_iter()
+            .filter(|conjunct| constant(conjunct) != Some(Value::Bool(true)))
+            .map(ToString::to_string)
+            .collect();
+        Self {
+            index,
+            definition,
+            compiled,
+            conjuncts,
+        }
+    }
+}
+
+/// Reports `expr` if it is constant, otherwise its constant `&&`/`||` operands
+fn check_constants(expr: &Expr, found: &mut Vec<Diagnostic>) {
+    if let Some(Value::Bool(value)) = constant(expr) {
+        let code = if value { "always-true" } else { "always-false" };
+        found.push(
+            Diagnostic::warning(code, format!("`{}` is always {}", expr, value))
+                .with_span(expr.span),
+        );
+        return;
+    }
+    match &expr.kind {
+        ExprKind::Binary {
+            op: BinaryOp::And | BinaryOp::Or,
+            lhs,
+            rhs,
+        } => {
+            check_constants(lhs, found);
+            check_constants(rhs, found);
+        }
+        ExprKind::Unary {
+            op: UnaryOp::Not,
+            operand,
+        } => check_constants(operand, found),
+        _ => {}
+    }
+}
+
+/// The value of `expr` if it does not depend on any fact or on when it is
+/// evaluated
+fn constant(expr: &Expr) -> Option<Value> {
+    if function::is_constant(expr) {
+        return Evaluator::new(&RuleContext::new()).evaluate(expr).ok();
+    }
+    match &expr.kind {
+        ExprKind::Binary {
+            op: op @ (BinaryOp::And | BinaryOp::Or),
+            lhs,
+            rhs,
+        } => {
+            let short_circuit = Value::Bool(*op == BinaryOp::Or);
+            match (constant(lhs), constant(rhs)) {
+                (Some(value), _) | (_, Some(value)) if value == short_circuit => Some(value),
+                (Some(Value::Bool(_)), Some(Value::Bool(_))) => {
+                    Some(Value::Bool(*op == BinaryOp::And))
+                }
+                _ => None,
+            }
+        }
+        ExprKind::Unary {
+            op: UnaryOp::Not,
+            operand,
+        } => match constant(operand) {
+            Some(Value::Bool(value)) => Some(Value::Bool(!value)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::{Field, Type};
+    use crate::expr::Span;
+
+    fn codes(diagnostics: &[Diagnostic]) -> Vec<(&str, &str)> {
+        diagnostics
+            .iter()
+            .map(|d| (d.rule_id.as_deref().unwrap_or(""), d.code.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_definition_lints() {
+        let rules = [
+            RuleDefinition::new("a").with_expression("x > 1"),
+            RuleDefinition::new("a").with_expression("x > 2"),
+            RuleDefinition::new("")
+                .with_expression("x > 3")
+                .with_name("unnamed id"),
+            RuleDefinition::new("blank")
+                .with_expression("")
+                .with_name(""),
+            RuleDefinition::new("broken").with_expression("x >"),
+            RuleDefinition::new("loud")
+                .with_expression("x > 4")
+                .with_priority(i32::MAX),
+        ];
+        assert_eq!(
+            codes(&lint(&rules)),
+            [
+                ("a", "duplicate-id"),
+                ("", "empty-id"),
+                ("blank", "empty-name"),
+                ("blank", "empty-expression"),
+                ("broken", "syntax-error"),
+                ("loud", "suspicious-priority"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_constant_conditions() {
+        let rules = [
+            RuleDefinition::new("literal").with_expression("1 == 1"),
+            RuleDefinition::new("operand").with_expression("x.a > 1 && (2 > 3 || x.b)"),
+            RuleDefinition::new("clock")
+                .with_expression("now() > timestamp('2030-01-01T00:00:00Z') || 2 < 1"),
+            RuleDefinition::new("fine").with_expression("x.a == x.b"),
+            // Unknown when `x.a` is null, so not constant
+            RuleDefinition::new("self").with_expression("x.a <= x.a"),
+        ];
+        let diagnostics = lint(&rules);
+        assert_eq!(
+            codes(&diagnostics),
+            [
+                ("literal", "always-true"),
+                ("operand", "always-false"),
+                ("clock", "always-false"),
+            ]
+        );
+        assert_eq!(diagnostics[1].span, Some(Span::new(12, 17)));
+        assert_eq!(diagnostics[1].message, "`2 > 3` is always false");
+        assert_eq!(diagnostics[2].message, "`2 < 1` is always false");
+    }
+
+    #[test]
+    fn test_activation_group_lints() {
+        let grouped = |id: &str, expression: &str, priority: i32| {
+            RuleDefinition::new(id)
+                .with_expression(expression)
+                .with_priority(priority)
+                .in_activation_group("discount")
+        };
+        let rules = [
+            grouped("vip", "customer.vip", 10),
+            grouped("vip_big", "customer.total > 100 && customer.vip", 5),
+            grouped("big", "customer.total > 100", 10),
+            grouped("fallback", "true", 20),
+        ];
+        let diagnostics = lint(&rules);
+        assert_eq!(
+            codes(&diagnostics),
+            [
+                ("vip", "shadowed-rule"),
+                ("vip_big", "shadowed-rule"),
+                ("big", "suspicious-priority"),
+                ("big", "shadowed-rule"),
+                ("fallback", "always-true"),
+            ]
+        );
+        assert_eq!(diagnostics[0].span, Some(Span::new(0, 12)));
+        assert_eq!(diagnostics[1].span, Some(Span::new(0, 36)));
+    }
+
+    #[test]
+    fn test_overrides_and_schema() {
+        let schema = Schema::new().with_fact("x", Type::map([("a", Field::new(Type::Int))]));
+        let rules = [
+            RuleDefinition::new("typed")
+                .with_expression("x.a == 'one'")
+                .with_name(""),
+            RuleDefinition::new("ok").with_expression("x.a > 1"),
+        ];
+        let diagnostics = Linter::new()
+            .with_schema(schema)
+            .allow("empty-name")
+            .with_severity("impossible-comparison", Severity::Error)
+            .lint(&rules);
+        assert_eq!(codes(&diagnostics), [("typed", "impossible-comparison")]);
+        assert!(diagnostics[0].is_error());
+    }
+}
@@ -0,0 +1,11 @@
+//! Static analysis of rule sets
+//!
+//! Analysis passes look at rules without evaluating them against facts.
+
+pub mod graph;
+pub mod lint;
+pub mod types;
+
+pub use self::graph::{Dependency, DependencyGraph};
+pub use self::lint::{lint, Linter};
+pub use self::types::{Field, Schema, Type, TypeChecker};
@@ -0,0 +1,867 @@
+//! Static type checking of rule expressions against a fact schema
+//!
+//! A [`Schema`] declares the type and nullability of every fact field. The
+//! [`TypeChecker`] infers the type of each expression node the way the
+//! evaluator would compute its value and reports, as [`Diagnostic`]s:
+//!
+//! - `unknown-field` (error): a path the schema does not declare
+//! - `unknown-function` (error): a call to a function that is neither a
+//!   built-in nor in the registry given with [`TypeChecker::with_functions`];
+//!   without a registry, unknown calls are typed as `any`
+//! - `type-mismatch` (error): an operation that would fail at evaluation
+//!   time, such as ordering a string against a number or a non-bool condition
+//! - `impossible-comparison` (warning): an equality or membership test that
+//!   can never hold, such as a string compared with a number
+//! - `nullable-operand` (warning): a nullable field used where `null` would
+//!   make the result unknown or, as an argument of a registered function,
+//!   make evaluation fail
+//!
+//! Schemas deserialize from YAML or JSON:
+//!
+//! ```
+//! use windsurf_rules::analysis::{Schema, TypeChecker};
+//! use windsurf_rules::expr::parse;
+//!
+//! let schema: Schema = serde_yaml::from_str(r#"
+//! customer:
+//!   type: map
+//!   fields:
+//!     age: { type: int }
+//!     tier: { type: string, nullable: true }
+//! "#).unwrap();
+//!
+//! let diagnostics = TypeChecker::new(&schema).check_condition(&parse("customer.age == 'gold'")?);
+//! assert_eq!(diagnostics[0].code, "impossible-comparison");
+//! # Ok::<(), windsurf_rules::Error>(())
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::diagnostic::Diagnostic;
+use crate::expr::eval::{coalesce_arity, exists_arity, COALESCE, EXISTS};
+use crate::expr::function;
+use crate::expr::{
+    BinaryOp, Expr, ExprKind, FunctionRegistry, Literal, Path, PathSegment, Span, UnaryOp,
+};
+use crate::rule::action::{CompiledAction, TemplatePart};
+use crate::rule::{CompiledRule, Value};
+
+/// The type of a fact field or expression
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Type {
+    /// Any value; never reported as a mismatch
+    #[default]
+    Any,
+    /// Only `null`
+    Null,
+    /// `true` or `false`
+    Bool,
+    /// 64-bit signed integer
+    Int,
+    /// 64-bit floating point number
+    Float,
+    /// Exact decimal number
+    Decimal,
+    /// UTF-8 string
+    String,
+    /// Point in time
+    Timestamp,
+    /// Signed length of time
+    Duration,
+    /// List whose items share one type
+    List {
+        /// Type of every item
+        #[serde(default)]
+        items: Box<Field>,
+    },
+    /// Map with a fixed set of fields
+    Map {
+        /// The declared fields
+        #[serde(default)]
+        fields: BTreeMap<String, Field>,
+    },
+}
+
+impl Type {
+    /// A list of `items`
+    pub fn list(items: Field) -> Self {
+        Type::List {
+            items: Box::new(items),
+        }
+    }
+
+    /// A map with the given fields
+    pub fn map<I, S>(fields: I) -> Self
+    where
+        I: IntoIterator<Item = (S, Field)>,
+        S: Into<String>,
+    {
+        Type::Map {
+            fields: fields
+                .into_iter()
+                .map(|(name, field)| (name.into(), field))
+                .collect(),
+        }
+    }
+
+    /// Returns true for `int`, `float` and `decimal`
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, Type::Int | Type::Float | Type::Decimal)
+    }
+
+    /// Name of the type, as used in diagnostics; matches [`Value::type_name`]
+    ///
+    /// [`Value::type_name`]: crate::rule::Value::type_name
+    pub fn name(&self) -> &'static str {
+        match self {
+            Type::Any => "any",
+            Type::Null => "null",
+            Type::Bool => "bool",
+            Type::Int => "int",
+            Type::Float => "float",
+            Type::Decimal => "decimal",
+            Type::String => "string",
+            Type::Timestamp => "timestamp",
+            Type::Duration => "duration",
+            Type::List { .. } => "list",
+            Type::Map { .. } => "map",
+        }
+    }
+
+    /// Whether values of the two types can ever be equal
+    fn overlaps(&self, other: &Type) -> bool {
+        match (self, other) {
+            (Type::Any, _) | (_, Type::Any) => true,
+            (a, b) if a.is_numeric() && b.is_numeric() => true,
+            (a, b) => std::mem::discriminant(a) == std::mem::discriminant(b),
+        }
+    }
+
+    /// Whether two values of these types can be ordered with `<` and friends
+    fn orderable(&self, other: &Type) -> bool {
+        match (self, other) {
+            (Type::Any, _) | (_, Type::Any) => true,
+            (a, b) if a.is_numeric() && b.is_numeric() => true,
+            (Type::String, Type::String)
+            | (Type::Bool, Type::Bool)
+            | (Type::Timestamp, Type::Timestamp)
+            | (Type::Duration, Type::Duration) => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::List { items } => write!(f, "list<{}>", items),
+            other => f.write_str(other.name()),
+        }
+    }
+}
+
+/// A type together with whether `null` is allowed
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Field {
+    /// The field's type
+    #[serde(flatten)]
+    pub ty: Type,
+    /// Whether the field may be `null` or missing
+    #[serde(default)]
+    pub nullable: bool,
+}
+
+impl Field {
+    /// A non-nullable field of type `ty`
+    pub fn new(ty: Type) -> Self {
+        Self {
+            ty,
+            nullable: false,
+        }
+    }
+
+    /// Allows the field to be `null` or missing
+    pub fn nullable(mut self) -> Self {
+        self.nullable = true;
+        self
+    }
+
+    fn null() -> Self {
+        Field::new(Type::Null).nullable()
+    }
+
+    /// Whether `value` is a valid value of this field; `int` values are
+    /// accepted where `float` or `decimal` is declared
+    pub fn admits(&self, value: &Value) -> bool {
+        match (&self.ty, value) {
+            (Type::Any, _) => true,
+            (Type::Null, Value::Null) => true,
+            (_, Value::Null) => self.nullable,
+            (Type::Float | Type::Decimal, Value::Int(_)) => true,
+            (Type::List { items }, Value::List(values)) => values.iter().all(|v| items.admits(v)),
+            (Type::Map { fields }, Value::Map(map)) => fields
+                .iter()
+                .all(|(name, field)| field.admits(map.get(name).unwrap_or(&Value::Null))),
+            (ty, value) => ty.name() == value.type_name(),
+        }
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.ty {
+            Type::Null | Type::Any => write!(f, "{}", self.ty),
+            _ if self.nullable => write!(f, "{}?", self.ty),
+            _ => write!(f, "{}", self.ty),
+        }
+    }
+}
+
+impl From<Type> for Field {
+    fn from(ty: Type) -> Self {
+        Field::new(ty)
+    }
+}
+
+/// The declared facts, by root name
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Schema {
+    /// Declared type of each root fact
+    pub facts: BTreeMap<String, Field>,
+}
+
+impl Schema {
+    /// Creates an empty schema
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declares a root fact
+    pub fn with_fact(mut self, name: impl Into<String>, field: impl Into<Field>) -> Self {
+        self.facts.insert(name.into(), field.into());
+        self
+    }
+
+    /// Type-checks a compiled rule's condition and actions
+    pub fn check_rule(&self, rule: &CompiledRule) -> Vec<Diagnostic> {
+        TypeChecker::new(self).check_rule(rule)
+    }
+}
+
+/// Infers expression types against a [`Schema`] and collects diagnostics
+#[derive(Debug)]
+pub struct TypeChecker<'a> {
+    schema: &'a Schema,
+    functions: Option<&'a FunctionRegistry>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> TypeChecker<'a> {
+    /// Creates a checker for `schema`
+    pub fn new(schema: &'a Schema) -> Self {
+        Self {
+            schema,
+            functions: None,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// Checks function calls against the signatures in `functions`
+    pub fn with_functions(mut self, functions: &'a FunctionRegistry) -> Self {
+        self.functions = Some(functions);
+        self
+    }
+
+    /// Checks a rule condition, which must produce a bool
+    pub fn check_condition(mut self, condition: &Expr) -> Vec<Diagnostic> {
+        self.condition(condition);
+        self.diagnostics
+    }
+
+    /// Checks a rule's condition and actions; diagnostics carry the rule id
+    pub fn check_rule(mut self, rule: &CompiledRule) -> Vec<Diagnostic> {
+        self.condition(rule.condition());
+        for action in rule.then_actions().iter().chain(rule.else_actions()) {
+            self.action(action);
+        }
+        let rule_id = &rule.definition().id;
+        self.diagnostics
+            .into_iter()
+            .map(|diagnostic| diagnostic.with_rule(rule_id.clone()))
+            .collect()
+    }
+
+    /// Infers the type of an expression, returning it with any diagnostics
+    pub fn infer(mut self, expr: &Expr) -> (Field, Vec<Diagnostic>) {
+        let field = self.expr(expr);
+        (field, self.diagnostics)
+    }
+
+    fn report(&mut self, diagnostic: Diagnostic, span: Span) {
+        self.diagnostics.push(diagnostic.with_span(span));
+    }
+
+    fn condition(&mut self, condition: &Expr) {
+        let field = self.expr(condition);
+        self.expect_bool(&field, condition.span, "condition");
+    }
+
+    fn expect_bool(&mut self, field: &Field, span: Span, what: &str) {
+        match field.ty {
+            Type::Bool | Type::Any if field.nullable && field.ty != Type::Any => self.report(
+                Diagnostic::warning(
+                    "nullable-operand",
+                    format!("{} is nullable and unknown when null", what),
+                ),
+                span,
+            ),
+            Type::Bool | Type::Any => {}
+            _ => self.report(
+                Diagnostic::error(
+                    "type-mismatch",
+                    format!("{} must be bool, found {}", what, field),
+                ),
+                span,
+            ),
+        }
+    }
+
+    fn action(&mut self, action: &CompiledAction) {
+        match action {
+            CompiledAction::Set { path, value } => {
+                let target = self.path(path, value.span);
+                let field = self.expr(value);
+                if !assignable(&field, &target) {
+                    self.report(
+                        Diagnostic::error(
+                            "type-mismatch",
+                            format!("cannot assign {} to `{}` of type {}", field, path, target),
+                        ),
+                        value.span,
+                    );
+                }
+            }
+            CompiledAction::Remove { path } => {
+                self.path(path, Span::default());
+            }
+            CompiledAction::Emit { payload, .. } => payload.values().for_each(|expr| {
+                self.expr(expr);
+            }),
+            CompiledAction::Message { parts } => parts.iter().for_each(|part| {
+                if let TemplatePart::Expr(expr) = part {
+                    self.expr(expr);
+                }
+            }),
+            CompiledAction::Call { args, .. } => args.iter().for_each(|expr| {
+                self.expr(expr);
+            }),
+            CompiledAction::Native(_) => {}
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Field {
+        match &expr.kind {
+            ExprKind::Literal(literal) => match literal {
+                Literal::Null => Field::null(),
+                Literal::Bool(_) => Type::Bool.into(),
+                Literal::Int(_) => Type::Int.into(),
+                Literal::Float(_) => Type::Float.into(),
+                Literal::String(_) => Type::String.into(),
+            },
+            ExprKind::Path(path) => self.path(path, expr.span),
+            ExprKind::List(items) => {
+                let items: Vec<Field> = items.iter().map(|item| self.expr(item)).collect();
+                let item = match items.split_first() {
+                    Some((first, rest)) if rest.iter().all(|item| item.ty == first.ty) => Field {
+                        ty: first.ty.clone(),
+                        nullable: items.iter().any(|item| item.nullable),
+                    },
+                    _ => Field::default(),
+                };
+                Type::list(item).into()
+            }
+            ExprKind::Call { function, args } => {
+                let fields: Vec<Field> = args.iter().map(|arg| self.expr(arg)).collect();
+                self.call(function, &fields, args, expr.span)
+            }
+            ExprKind::Unary { op, operand } => {
+                let field = self.expr(operand);
+                match op {
+                    UnaryOp::Not => {
+                        self.expect_bool(&field, operand.span, "operand of `!`");
+                        Type::Bool.into()
+                    }
+                    UnaryOp::Neg
+                        if field.ty.is_numeric() || matches!(field.ty, Type::Duration | Type::Any) =>
+                    {
+                        self.nullable_operand(&field, *op, operand.span);
+                        field
+                    }
+                    UnaryOp::Neg => {
+                        self.report(
+                            Diagnostic::error(
+                                "type-mismatch",
+                                format!("cannot apply `{}` to {}", op, field),
+                            ),
+                            expr.span,
+                        );
+                        Field::default()
+                    }
+                }
+            }
+            ExprKind::Binary { op, lhs, rhs } => {
+                let left = self.expr(lhs);
+                let right = self.expr(rhs);
+                self.binary(*op, (&left, lhs.span), (&right, rhs.span), expr.span)
+            }
+        }
+    }
+
+    fn binary(
+        &mut self,
+        op: BinaryOp,
+        lhs: (&Field, Span),
+        rhs: (&Field, Span),
+        span: Span,
+    ) -> Field {
+        let ((left, left_span), (right, right_span)) = (lhs, rhs);
+        let mismatch = |op: BinaryOp| {
+            Diagnostic::error(
+                "type-mismatch",
+                format!("cannot apply `{}` to {} and {}", op, left, right),
+            )
+        };
+
+        match op {
+            BinaryOp::And | BinaryOp::Or => {
+                self.expect_bool(left, left_span, &format!("operand of `{}`", op));
+                self.expect_bool(right, right_span, &format!("operand of `{}`", op));
+                Type::Bool.into()
+            }
+            BinaryOp::Eq | BinaryOp::Ne => {
+                let (null, other) = match (&left.ty, &right.ty) {
+                    (Type::Null, _) => (true, right),
+                    (_, Type::Null) => (true, left),
+                    _ => (false, left),
+                };
+                let always = if op == BinaryOp::Eq { "false" } else { "true" };
+                if null && !other.nullable && other.ty != Type::Any {
+                    self.report(
+                        Diagnostic::warning(
+                            "impossible-comparison",
+                            format!("{} is never null, so `{}` is always {}", other, op, always),
+                        ),
+                        span,
+                    );
+                } else if !null && !left.ty.overlaps(&right.ty) {
+                    self.report(
+                        Diagnostic::warning(
+                            "impossible-comparison",
+                            format!(
+                                "comparing {} with {} using `{}` is always {}",
+                                left.ty, right.ty, op, always
+                            ),
+                        ),
+                        span,
+                    );
+                }
+                Type::Bool.into()
+            }
+            BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => {
+                if !left.ty.orderable(&right.ty) {
+                    self.report(mismatch(op), span);
+                } else {
+                    self.nullable_operand(left, op, left_span);
+                    self.nullable_operand(right, op, right_span);
+                }
+                Type::Bool.into()
+            }
+            BinaryOp::In | BinaryOp::NotIn => {
+                match &right.ty {
+                    Type::Any => {}
+                    Type::List { items } => {
+                        if !left.ty.overlaps(&items.ty) && left.ty != Type::Null {
+                            self.report(
+                                Diagnostic::warning(
+                                    "impossible-comparison",
+                                    format!("{} can never be found in {}", left.ty, right.ty),
+                                ),
+                                span,
+                            );
+                        }
+                    }
+                    Type::String | Type::Map { .. }
+                        if matches!(left.ty, Type::String | Type::Any) => {}
+                    _ => self.report(mismatch(op), span),
+                }
+                self.nullable_operand(right, op, right_span);
+                Type::Bool.into()
+            }
+            BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => {
+                let ty = match (&left.ty, &right.ty) {
+                    (Type::Any, _) | (_, Type::Any) => Some(Type::Any),
+                    (Type::Int, Type::Int) if op == BinaryOp::Div => Some(Type::Float),
+                    (Type::Int, Type::Int) => Some(Type::Int),
+                    (Type::Int | Type::Decimal, Type::Int | Type::Decimal) => Some(Type::Decimal),
+                    (a, b) if a.is_numeric() && b.is_numeric() => Some(Type::Float),
+                    (Type::String, Type::String) if op == BinaryOp::Add => Some(Type::String),
+                    (Type::List { .. }, Type::List { .. }) if op == BinaryOp::Add => {
+                        Some(Type::list(Field::default()))
+                    }
+                    (Type::Timestamp, Type::Timestamp) if op == BinaryOp::Sub => {
+                        Some(Type::Duration)
+                    }
+                    (Type::Timestamp, Type::Duration)
+                        if matches!(op, BinaryOp::Add | BinaryOp::Sub) =>
+                    {
+                        Some(Type::Timestamp)
+                    }
+                    (Type::Duration, Type::Timestamp) if op == BinaryOp::Add => {
+                        Some(Type::Timestamp)
+                    }
+                    (Type::Duration, Type::Duration) => match op {
+                        BinaryOp::Add | BinaryOp::Sub => Some(Type::Duration),
+                        BinaryOp::Div => Some(Type::Float),
+                        _ => None,
+                    },
+                    (Type::Duration, n) if n.is_numeric() && matches!(op, BinaryOp::Mul | BinaryOp::Div) => {
+                        Some(Type::Duration)
+                    }
+                    (n, Type::Duration) if n.is_numeric() && op == BinaryOp::Mul => {
+                        Some(Type::Duration)
+                    }
+                    _ => None,
+                };
+                match ty {
+                    Some(ty) => {
+                        self.nullable_operand(left, op, left_span);
+                        self.nullable_operand(right, op, right_span);
+                        ty.into()
+                    }
+                    None => {
+                        self.report(mismatch(op), span);
+                        Field::default()
+                    }
+                }
+            }
+        }
+    }
+
+    fn call(&mut self, name: &str, fields: &[Field], args: &[Expr], span: Span) -> Field {
+        if name == EXISTS {
+            if !matches!(args, [Expr { kind: ExprKind::Path(_), .. }]) {
+                self.report(Diagnostic::error("type-mismatch", exists_arity()), span);
+            }
+            return Type::Bool.into();
+        }
+        if name == COALESCE {
+            return self.coalesce(fields, span);
+        }
+        let Some(function) = function::lookup(self.functions, name) else {
+            if self.functions.is_none() {
+                return Field::default();
+            }
+            self.report(
+                Diagnostic::error("unknown-function", format!("unknown function `{}`", name)),
+                span,
+            );
+            return Field::default();
+        };
+        if !function.arity().contains(&fields.len()) {
+            self.report(
+                Diagnostic::error("type-mismatch", function.arity_mismatch(fields.len())),
+                span,
+            );
+        }
+        // Built-ins return null for a null required argument instead of failing
+        let builtin = self.functions.is_none_or(|functions| !functions.contains(name));
+        for (i, ((field, param), arg)) in fields.iter().zip(function.params()).zip(args).enumerate() {
+            if !assignable(field, param) {
+                self.report(
+                    Diagnostic::error(
+                        "type-mismatch",
+                        format!("argument {} of `{}` must be {}, found {}", i + 1, name, param, field),
+                    ),
+                    arg.span,
+                );
+            } else if !param.nullable
+                && field.nullable
+                && !matches!(field.ty, Type::Any | Type::Null)
+            {
+                let effect = if builtin { "makes the result unknown" } else { "fails to evaluate" };
+                self.report(
+                    Diagnostic::warning(
+                        "nullable-operand",
+                        format!("{} argument of `{}` {} when null", field, name, effect),
+                    ),
+                    arg.span,
+                );
+            }
+        }
+        function.returns().clone()
+    }
+
+    /// `coalesce` has the arguments' common type, or `any` if they differ,
+    /// and is nullable only if every argument is
+    fn coalesce(&mut self, fields: &[Field], span: Span) -> Field {
+        if fields.is_empty() {
+            self.report(Diagnostic::error("type-mismatch", coalesce_arity()), span);
+            return Field::default();
+        }
+        let mut types = fields.iter().map(|field| &field.ty).filter(|ty| **ty != Type::Null);
+        let ty = match types.next() {
+            Some(first) if types.all(|ty| ty == first) => first.clone(),
+            Some(_) => Type::Any,
+            None => Type::Null,
+        };
+        Field {
+            ty,
+            nullable: fields.iter().all(|field| field.nullable),
+        }
+    }
+
+    fn nullable_operand(&mut self, field: &Field, op: impl fmt::Display, span: Span) {
+        if field.nullable && !matches!(field.ty, Type::Any | Type::Null) {
+            self.report(
+                Diagnostic::warning(
+                    "nullable-operand",
+                    format!("{} operand of `{}` makes the result unknown when null", field, op),
+                ),
+                span,
+            );
+        }
+    }
+
+    /// Resolves a field path through the schema; unknown fields are reported
+    /// and typed as `any` so they do not cascade into further diagnostics
+    fn path(&mut self, path: &Path, span: Span) -> Field {
+        let mut segments = path.segments.iter();
+        let root = match segments.next() {
+            Some(PathSegment::Key(root)) => root,
+            _ => return Field::default(),
+        };
+        let Some(mut field) = self.schema.facts.get(root).cloned() else {
+            self.report(
+                Diagnostic::error("unknown-field", format!("unknown fact `{}`", root)),
+                span,
+            );
+            return Field::default();
+        };
+
+        for (depth, segment) in segments.enumerate() {
+            let next = match (&field.ty, segment) {
+                (Type::Any, _) => {
+                    return Field {
+                        ty: Type::Any,
+                        nullable: field.nullable,
+                    }
+                }
+                (Type::Map { fields }, PathSegment::Key(key)) => fields.get(key).cloned(),
+                (Type::List { items }, PathSegment::Index(_)) => {
+                    // Out-of-range indexes evaluate to null
+                    Some(items.as_ref().clone().nullable())
+                }
+                _ => None,
+            };
+            let prefix = Path::new(path.segments[..depth + 1].to_vec());
+            match next {
+                Some(next) => {
+                    let nullable = field.nullable || next.nullable;
+                    field = Field { nullable, ..next };
+                }
+                None if matches!(field.ty, Type::Map { .. }) => {
+                    self.report(
+                        Diagnostic::error(
+                            "unknown-field",
+                            format!("`{}` has no field `{}`", prefix, path_segment(segment)),
+                        ),
+                        span,
+                    );
+                    return Field::default();
+                }
+                None => {
+                    self.report(
+                        Diagnostic::error(
+                            "type-mismatch",
+                            format!(
+                                "cannot access `{}` on `{}` of type {}",
+                                path_segment(segment),
+                                prefix,
+                                field.ty
+                            ),
+                        ),
+                        span,
+                    );
+                    return Field::default();
+                }
+            }
+        }
+        field
+    }
+}
+
+fn path_segment(segment: &PathSegment) -> String {
+    match segment {
+        PathSegment::Key(key) => key.clone(),
+        PathSegment::Index(index) => format!("[{}]", index),
+    }
+}
+
+/// Whether a value of type `value` may be stored in a field of type `target`
+fn assignable(value: &Field, target: &Field) -> bool {
+    match (&value.ty, &target.ty) {
+        (_, Type::Any) | (Type::Any, _) => true,
+        (Type::Null, _) => target.nullable,
+        (Type::Int, Type::Float | Type::Decimal) => true,
+        (Type::List { .. }, Type::List { .. }) | (Type::Map { .. }, Type::Map { .. }) => true,
+        (a, b) => a == b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::parse;
+    use crate::rule::{Action, Rule as RuleDefinition};
+
+    fn schema() -> Schema {
+        Schema::new()
+            .with_fact(
+                "customer",
+                Type::map([
+                    ("age", Field::new(Type::Int)),
+                    ("tier", Field::new(Type::String).nullable()),
+                    ("balance", Field::new(Type::Decimal)),
+                    ("tags", Field::new(Type::list(Type::String.into()))),
+                ]),
+            )
+            .with_fact("metadata", Type::Any)
+    }
+
+    fn codes(source: &str) -> Vec<String> {
+        TypeChecker::new(&schema())
+            .check_condition(&parse(source).unwrap())
+            .into_iter()
+            .map(|diagnostic| diagnostic.code)
+            .collect()
+    }
+
+    #[test]
+    fn test_well_typed_conditions_pass() {
+        let clean = [
+            "customer.age >= 18 && customer.balance > 10.5",
+            "customer.tier == 'gold' || customer.tier == null",
+            "'vip' in customer.tags && customer.age * 2 + 1 > 40",
+            "metadata.anything.goes > 1",
+            "coalesce(customer.tier, 'basic') > 'a' && exists(customer.tier)",
+        ];
+        for source in clean {
+            assert_eq!(codes(source), Vec::<String>::new(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_mismatches_are_reported_with_spans() {
+        assert_eq!(codes("customer.nickname == 'x'"), ["unknown-field"]);
+        assert_eq!(codes("order.total > 1"), ["unknown-field"]);
+        assert_eq!(codes("customer.age > 'adult'"), ["type-mismatch"]);
+        assert_eq!(codes("customer.age + 1"), ["type-mismatch"]);
+        assert_eq!(codes("customer.age == 'adult'"), ["impossible-comparison"]);
+        assert_eq!(codes("customer.age == null"), ["impossible-comparison"]);
+        assert_eq!(codes("1 in customer.tags"), ["impossible-comparison"]);
+        assert_eq!(codes("customer.tier > 'a'"), ["nullable-operand"]);
+        assert_eq!(codes("customer.age.years > 1"), ["type-mismatch"]);
+        assert_eq!(codes("exists('customer.tier')"), ["type-mismatch"]);
+        assert_eq!(codes("coalesce(customer.tier, null) > 'a'"), ["nullable-operand"]);
+        let diagnostics = TypeChecker::new(&schema())
+            .check_condition(&parse("starts_with(customer.tier, 'g')").unwrap());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "string? argument of `starts_with` makes the result unknown when null"
+        );
+
+        let diagnostics = TypeChecker::new(&schema())
+            .check_condition(&parse("customer.age > 1 && customer.age < 'x'").unwrap());
+        assert_eq!(diagnostics[0].span, Some(Span::new(20, 38)));
+        assert_eq!(diagnostics[0].message, "cannot apply `<` to int and string");
+    }
+
+    #[test]
+    fn test_calls_are_checked_against_signatures() {
+        let mut functions = FunctionRegistry::new();
+        functions.register(crate::expr::Function::new(
+            "score",
+            [Type::String, Type::Float],
+            Type::Int,
+            |_| Ok(Value::Int(0)),
+        ));
+        let schema = schema();
+        let check = |source: &str| {
+            TypeChecker::new(&schema)
+                .with_functions(&functions)
+                .check_condition(&parse(source).unwrap())
+        };
+        assert!(check("score('gold', customer.age) > 1").is_empty());
+        let diagnostics = check("score(customer.age, 1) == 'x' || risk(1)");
+        let found: Vec<&str> = diagnostics.iter().map(|d| d.code.as_str()).collect();
+        assert_eq!(found, ["type-mismatch", "impossible-comparison", "unknown-function"]);
+        assert_eq!(diagnostics[0].message, "argument 1 of `score` must be string, found int");
+        assert_eq!(diagnostics[0].span, Some(Span::new(6, 18)));
+        assert_eq!(check("score(customer.tier, 1) > 1")[0].code, "nullable-operand");
+        assert_eq!(
+            check("score('a') > 1")[0].message,
+            "`score` takes 2 arguments, found 1"
+        );
+        // Without a registry only calls to built-ins are checked
+        assert_eq!(codes("unknown(customer.age) > 1"), Vec::<String>::new());
+        assert_eq!(codes("length(customer.tags) > 'x'"), ["type-mismatch"]);
+        assert_eq!(codes("lower(customer.age) == 'x'"), ["type-mismatch"]);
+    }
+
+    #[test]
+    fn test_rule_actions_are_checked() {
+        let rule = RuleDefinition::new("r1")
+            .with_expression("customer.age > 18")
+            .then(Action::set("customer.age", "'old'"))
+            .then(Action::set("customer.tier", "null"))
+            .otherwise(Action::set("customer.vip", "true"))
+            .compile()
+            .unwrap();
+        let diagnostics = schema().check_rule(&rule);
+        let found: Vec<&str> = diagnostics.iter().map(|d| d.code.as_str()).collect();
+        assert_eq!(found, ["type-mismatch", "unknown-field"]);
+        assert!(diagnostics
+            .iter()
+            .all(|d| d.rule_id.as_deref() == Some("r1")));
+    }
+
+    #[test]
+    fn test_schema_deserializes() {
+        let schema: Schema = serde_yaml::from_str(
+            r#"
+customer:
+  type: map
+  fields:
+    age: { type: int }
+    tags: { type: list, items: { type: string } }
+    tier: { type: string, nullable: true }
+"#,
+        )
+        .unwrap();
+        assert_eq!(schema.facts["customer"], schema_customer());
+    }
+
+    fn schema_customer() -> Field {
+        Type::map([
+            ("age", Field::new(Type::Int)),
+            ("tags", Field::new(Type::list(Type::String.into()))),
+            ("tier", Field::new(Type::String).nullable()),
+        ])
+        .into()
+    }
+}
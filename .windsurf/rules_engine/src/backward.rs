@@ -0,0 +1,588 @@
+//! Goal-directed backward chaining
+//!
+//! A goal is an expression such as `loan.approved == true`. To prove it,
+//! every path the goal reads is resolved: from the supplied facts when
+//! present, otherwise by a rule whose `set` action concludes that path (or
+//! one of its ancestors) and whose own condition can in turn be proved.
+//! The answer is a [`Proof`] tree recording which rules and facts support
+//! the goal, and which candidate rules were rejected.
+//!
+//! Resolved paths are memoized per [`GoalQuery`] in a [`Cache`]; a path
+//! whose derivation depends on itself is reported as a [`Proof::Cycle`].
+//!
+//! ```
+//! use windsurf_rules::prelude::*;
+//! use windsurf_rules::backward::BackwardChainer;
+//!
+//! let mut chainer = BackwardChainer::new();
+//! chainer.add_definition(
+//!     RuleDefinition::new("kyc_complete")
+//!         .with_expression("customer.id_verified && customer.address_verified")
+//!         .then(Action::set("customer.kyc", "true")),
+//! )?;
+//! chainer.add_definition(
+//!     RuleDefinition::new("may_trade")
+//!         .with_expression("customer.kyc && customer.age >= 18")
+//!         .then(Action::set("customer.may_trade", "true")),
+//! )?;
+//!
+//! let customer: Value = [
+//!     ("id_verified", Value::from(true)),
+//!     ("address_verified", Value::from(true)),
+//!     ("age", Value::from(30)),
+//! ]
+//! .into_iter()
+//! .collect();
+//! let facts = RuleContext::new().with_fact("customer", customer);
+//!
+//! let proof = chainer.prove("customer.may_trade", &facts)?;
+//! assert!(proof.holds());
+//! assert_eq!(proof.rules(), ["may_trade", "kyc_complete"]);
+//! # Ok::<(), windsurf_rules::Error>(())
+//! ```
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::cache::Cache;
+use crate::clock::{Clock, SystemClock};
+use crate::diagnostic::Diagnostic;
+use crate::error::{Error, Result};
+use crate::expr::{self, Evaluator, Expr, Path};
+use crate::rule::action::CompiledAction;
+use crate::rule::evaluate::{diagnostic_for, Rule};
+use crate::rule::{CompiledRule, MissingPolicy, Rule as RuleDefinition, RuleContext, Value};
+
+/// How a goal or path was (or was not) established
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Proof {
+    /// An expression, with the support of every path it reads
+    Goal {
+        /// The expression, as written back from its syntax tree
+        goal: String,
+        /// Whether the expression evaluated to `true`
+        holds: bool,
+        /// Support for each distinct path the expression reads
+        premises: Vec<Proof>,
+        /// Why the expression could not be evaluated, if it could not
+        error: Option<Diagnostic>,
+    },
+    /// A path read directly from the supplied facts
+    Fact {
+        /// The path
+        path: String,
+        /// Its value
+        value: Value,
+    },
+    /// A path concluded by a rule whose condition held
+    Derived {
+        /// The path
+        path: String,
+        /// The concluded value
+        value: Value,
+        /// The concluding rule
+        rule_id: String,
+        /// Proof of the rule's condition
+        condition: Box<Proof>,
+        /// Support for the paths read by the concluded value expression
+        inputs: Vec<Proof>,
+    },
+    /// A candidate rule that could not conclude the path
+    Rejected {
+        /// The candidate rule
+        rule_id: String,
+        /// Proof of the rule's condition
+        condition: Box<Proof>,
+        /// Why the conclusion failed although the condition held
+        error: Option<Diagnostic>,
+    },
+    /// A path that neither the facts nor any rule could supply
+    Unknown {
+        /// The path
+        path: String,
+        /// Candidate rules that were tried and rejected
+        attempts: Vec<Proof>,
+    },
+    /// A path whose derivation depends on itself
+    Cycle {
+        /// The path
+        path: String,
+    },
+}
+
+impl Proof {
+    /// Whether this node establishes its goal or path
+    pub fn holds(&self) -> bool {
+        match self {
+            Proof::Goal { holds, .. } => *holds,
+            Proof::Fact { .. } | Proof::Derived { .. } => true,
+            Proof::Rejected { .. } | Proof::Unknown { .. } | Proof::Cycle { .. } => false,
+        }
+    }
+
+    /// Ids of the rules whose conclusions support this proof, outermost first
+    pub fn rules(&self) -> Vec<&str> {
+        let mut rules = Vec::new();
+        self.visit(&mut |proof| {
+            if let Proof::Derived { rule_id, .. } = proof {
+                if !rules.contains(&rule_id.as_str()) {
+                    rules.push(rule_id.as_str());
+                }
+            }
+        });
+        rules
+    }
+
+    /// Fact paths this proof reads, in the order they were resolved
+    pub fn facts(&self) -> Vec<&str> {
+        let mut facts = Vec::new();
+        self.visit(&mut |proof| {
+            if let Proof::Fact { path, .. } = proof {
+                if !facts.contains(&path.as_str()) {
+                    facts.push(path.as_str());
+                }
+            }
+        });
+        facts
+    }
+
+    /// Visits the supporting nodes of this proof, depth first; rejected
+    /// attempts are not part of the support and are skipped
+    fn visit<'a>(&'a self, visit: &mut impl FnMut(&'a Proof)) {
+        visit(self);
+        match self {
+            Proof::Goal { premises, .. } => premises.iter().for_each(|p| p.visit(visit)),
+            Proof::Derived {
+                condition, inputs, ..
+            } => {
+                condition.visit(visit);
+                inputs.iter().for_each(|p| p.visit(visit));
+            }
+            _ => {}
+        }
+    }
+
+    fn write_tree(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        let indent = "  ".repeat(depth);
+        match self {
+            Proof::Goal {
+                goal,
+                holds,
+                premises,
+                error,
+            } => {
+                write!(f, "{}{} => {}", indent, goal, holds)?;
+                if let Some(error) = error {
+                    write!(f, " ({})", error.message)?;
+                }
+                writeln!(f)?;
+                premises.iter().try_for_each(|p| p.write_tree(f, depth + 1))
+            }
+            Proof::Fact { path, value } => writeln!(f, "{}{} = {} (fact)", indent, path, value),
+            Proof::Derived {
+                path,
+                value,
+                rule_id,
+                condition,
+                inputs,
+            } => {
+                writeln!(f, "{}{} = {} (rule `{}`)", indent, path, value, rule_id)?;
+                condition.write_tree(f, depth + 1)?;
+                inputs.iter().try_for_each(|p| p.write_tree(f, depth + 1))
+            }
+            Proof::Rejected {
+                rule_id,
+                condition,
+                error,
+            } => {
+                write!(f, "{}rejected rule `{}`", indent, rule_id)?;
+                if let Some(error) = error {
+                    write!(f, " ({})", error.message)?;
+                }
+                writeln!(f)?;
+                condition.write_tree(f, depth + 1)
+            }
+            Proof::Unknown { path, attempts } => {
+                writeln!(f, "{}{} unknown", indent, path)?;
+                attempts.iter().try_for_each(|p| p.write_tree(f, depth + 1))
+            }
+            Proof::Cycle { path } => writeln!(f, "{}{} depends on itself", indent, path),
+        }
+    }
+}
+
+impl fmt::Display for Proof {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_tree(f, 0)
+    }
+}
+
+/// A rule together with the paths its `set` actions conclude
+struct Concluding {
+    rule: CompiledRule,
+    conclusions: Vec<(Path, Expr)>,
+}
+
+/// Answers goal queries by chaining backwards through rule conclusions
+pub struct BackwardChainer {
+    rules: Vec<Concluding>,
+    clock: Arc<dyn Clock>,
+    missing: MissingPolicy,
+}
+
+impl Default for BackwardChainer {
+    fn default() -> Self {
+        Self {
+            rules: Vec::new(),
+            clock: Arc::new(SystemClock),
+            missing: MissingPolicy::default(),
+        }
+    }
+}
+
+impl BackwardChainer {
+    /// Creates a chainer with no rules
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the current time from `clock` when deciding which rules are
+    /// within their validity window; defaults to [`SystemClock`]
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Sets how goals and rule conditions treat fact paths that resolve to
+    /// nothing, for every query; defaults to [`MissingPolicy::Unknown`]
+    pub fn with_missing_policy(mut self, policy: MissingPolicy) -> Self {
+        self.missing = policy;
+        self
+    }
+
+    /// Adds a compiled rule; its `set` then-actions become its conclusions
+    pub fn add_rule(&mut self, rule: CompiledRule) -> Result<()> {
+        if self.rules.iter().any(|r| r.rule.id() == rule.id()) {
+            return Err(Error::DuplicateRule(rule.id().to_string()));
+        }
+        let conclusions = rule
+            .then_actions()
+            .iter()
+            .filter_map(|action| match action {
+                CompiledAction::Set { path, value } => Some((path.clone(), value.clone())),
+                _ => None,
+            })
+            .collect();
+        self.rules.push(Concluding { rule, conclusions });
+        // Candidates are tried highest priority first, then in definition order
+        self.rules
+            .sort_by_key(|r| std::cmp::Reverse(r.rule.priority()));
+        Ok(())
+    }
+
+    /// Compiles a rule definition and adds it
+    pub fn add_definition(&mut self, definition: RuleDefinition) -> Result<()> {
+        self.add_rule(definition.compile()?)
+    }
+
+    /// Starts a query session over `facts`; resolved paths are memoized
+    /// for the lifetime of the session, which uses the rules valid when it
+    /// starts
+    pub fn query<'a>(&'a self, facts: &'a RuleContext) -> GoalQuery<'a> {
+        GoalQuery {
+            chainer: self,
+            now: self.clock.now(),
+            facts,
+            derived: facts.clone().with_missing_policy(self.missing),
+            memo: Cache::new(None, None),
+            in_progress: Vec::new(),
+        }
+    }
+
+    /// Proves a single goal expression against `facts`
+    pub fn prove(&self, goal: &str, facts: &RuleContext) -> Result<Proof> {
+        self.query(facts).prove(goal)
+    }
+
+    /// Active rules valid at `now` concluding `path` or one of its ancestors
+    fn candidates<'a>(
+        &'a self,
+        path: &'a Path,
+        now: DateTime<Utc>,
+    ) -> impl Iterator<Item = (&'a CompiledRule, &'a Path, &'a Expr)> {
+        self.rules
+            .iter()
+            .filter(move |r| r.rule.is_active() && r.rule.is_valid_at(now))
+            .flat_map(|r| r.conclusions.iter().map(move |(p, v)| (&r.rule, p, v)))
+            .filter(|(_, conclusion, _)| path.segments.starts_with(&conclusion.segments))
+    }
+}
+
+/// A backward-chaining session over one set of facts
+pub struct GoalQuery<'a> {
+    chainer: &'a BackwardChainer,
+    now: DateTime<Utc>,
+    facts: &'a RuleContext,
+    derived: RuleContext,
+    memo: Cache<String, Proof>,
+    in_progress: Vec<String>,
+}
+
+impl GoalQuery<'_> {
+    /// Parses and proves a goal expression
+    pub fn prove(&mut self, goal: &str) -> Result<Proof> {
+        let goal = expr::parse(goal)?;
+        Ok(self.prove_expr(&goal))
+    }
+
+    /// Proves an already parsed goal expression
+    pub fn prove_expr(&mut self, goal: &Expr) -> Proof {
+        self.goal(goal).0
+    }
+
+    /// The supplied facts together with every value derived so far
+    pub fn derived(&self) -> &RuleContext {
+        &self.derived
+    }
+
+    /// Proves `goal`; the flag is false when a cycle was cut along the way,
+    /// in which case the result must not be memoized
+    fn goal(&mut self, goal: &Expr) -> (Proof, bool) {
+        let (premises, complete) = self.resolve_all(goal);
+        let (holds, error) = match Evaluator::new(&self.derived).evaluate_bool(goal) {
+            Ok(holds) => (holds, None),
+            Err(err) => (false, Some(diagnostic_for(&err))),
+        };
+        let proof = Proof::Goal {
+            goal: goal.to_string(),
+            holds,
+            premises,
+            error,
+        };
+        (proof, complete)
+    }
+
+    fn resolve_all(&mut self, expr: &Expr) -> (Vec<Proof>, bool) {
+        let mut seen = BTreeSet::new();
+        let mut complete = true;
+        let mut proofs = Vec::new();
+        for path in expr.paths() {
+            if seen.insert(path.to_string()) {
+                let (proof, resolved) = self.resolve(path);
+                complete &= resolved;
+                proofs.push(proof);
+            }
+        }
+        (proofs, complete)
+    }
+
+    fn resolve(&mut self, path: &Path) -> (Proof, bool) {
+        let key = path.to_string();
+        if let Some(value) = self.facts.lookup(path) {
+            return (
+                Proof::Fact {
+                    path: key,
+                    value: value.clone(),
+                },
+                true,
+            );
+        }
+        if let Some(proof) = self.memo.get(&key) {
+            return (proof, true);
+        }
+        if self.in_progress.contains(&key) {
+            return (Proof::Cycle { path: key }, false);
+        }
+
+        self.in_progress.push(key.clone());
+        let mut complete = true;
+        let mut attempts = Vec::new();
+        let mut derived = None;
+        let chainer = self.chainer;
+        for (rule, conclusion, value) in chainer.candidates(path, self.now) {
+            let (condition, resolved) = self.goal(rule.condition());
+            complete &= resolved;
+            if !condition.holds() {
+                attempts.push(Proof::Rejected {
+                    rule_id: rule.id().to_string(),
+                    condition: Box::new(condition),
+                    error: None,
+                });
+                continue;
+            }
+
+            let (inputs, resolved) = self.resolve_all(value);
+            complete &= resolved;
+            let concluded = Evaluator::new(&self.derived).evaluate(value);
+            let concluded = concluded.and_then(|value| self.derived.set_path(conclusion, value));
+            match concluded {
+                Ok(_) => {
+                    derived = Some(Proof::Derived {
+                        path: key.clone(),
+                        value: self.derived.lookup(path).cloned().unwrap_or_default(),
+                        rule_id: rule.id().to_string(),
+                        condition: Box::new(condition),
+                        inputs,
+                    });
+                    break;
+                }
+                Err(err) => attempts.push(Proof::Rejected {
+                    rule_id: rule.id().to_string(),
+                    condition: Box::new(condition),
+                    error: Some(diagnostic_for(&err)),
+                }),
+            }
+        }
+        self.in_progress.pop();
+
+        let proof = derived.unwrap_or(Proof::Unknown {
+            path: key.clone(),
+            attempts,
+        });
+        if complete {
+            self.memo.insert(key, proof.clone());
+        }
+        (proof, complete)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::Action;
+
+    fn rule(id: &str, expression: &str, path: &str, value: &str) -> RuleDefinition {
+        RuleDefinition::new(id)
+            .with_expression(expression)
+            .then(Action::set(path, value))
+    }
+
+    fn chainer() -> BackwardChainer {
+        let mut chainer = BackwardChainer::new();
+        chainer
+            .add_definition(rule("adult", "person.age >= 18", "person.adult", "true"))
+            .unwrap();
+        chainer
+            .add_definition(rule(
+                "resident",
+                "person.country == 'DK'",
+                "person.resident",
+                "true",
+            ))
+            .unwrap();
+        chainer
+            .add_definition(rule(
+                "may_vote",
+                "person.adult && person.resident",
+                "person.may_vote",
+                "true",
+            ))
+            .unwrap();
+        chainer
+    }
+
+    fn person(age: i64, country: &str) -> RuleContext {
+        let person: Value = [("age", Value::from(age)), ("country", Value::from(country))]
+            .into_iter()
+            .collect();
+        RuleContext::new().with_fact("person", person)
+    }
+
+    #[test]
+    fn test_prove_reports_supporting_rules_and_facts() {
+        let chainer = chainer();
+        let proof = chainer
+            .prove("person.may_vote == true", &person(30, "DK"))
+            .unwrap();
+        assert!(proof.holds());
+        assert_eq!(proof.rules(), ["may_vote", "adult", "resident"]);
+        assert_eq!(proof.facts(), ["person.age", "person.country"]);
+        assert!(proof
+            .to_string()
+            .contains("person.adult = true (rule `adult`)"));
+
+        let proof = chainer
+            .prove("person.may_vote == true", &person(30, "SE"))
+            .unwrap();
+        assert!(!proof.holds());
+        assert!(proof.rules().is_empty());
+        assert!(proof.to_string().contains("rejected rule `may_vote`"));
+    }
+
+    #[test]
+    fn test_query_memoizes_resolved_paths() {
+        let chainer = chainer();
+        let facts = person(40, "DK");
+        let mut query = chainer.query(&facts);
+        assert!(query.prove("person.adult").unwrap().holds());
+        assert_eq!(query.memo.len(), 1);
+        assert!(query
+            .prove("person.may_vote && person.adult")
+            .unwrap()
+            .holds());
+        assert_eq!(query.memo.len(), 3);
+        assert_eq!(
+            query.derived().get_path("person.may_vote").unwrap(),
+            Some(&Value::Bool(true))
+        );
+    }
+
+    #[test]
+    fn test_expired_rules_are_not_candidates() {
+        use crate::clock::ManualClock;
+        use chrono::{TimeDelta, TimeZone};
+
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let clock = Arc::new(ManualClock::new(start));
+        let mut chainer = BackwardChainer::new().with_clock(clock.clone());
+        chainer
+            .add_definition(
+                rule("adult", "person.age >= 18", "person.adult", "true")
+                    .valid_until(start + TimeDelta::days(1)),
+            )
+            .unwrap();
+
+        let facts = person(30, "DK");
+        assert!(chainer.prove("person.adult", &facts).unwrap().holds());
+        clock.advance(TimeDelta::days(2));
+        let proof = chainer.prove("person.adult", &facts).unwrap();
+        assert!(!proof.holds());
+        assert!(proof.rules().is_empty());
+    }
+
+    #[test]
+    fn test_missing_policy_applies_to_queries() {
+        let facts =
+            RuleContext::new().with_fact("person", [("age", 30)].into_iter().collect::<Value>());
+        let proof = chainer().prove("person.resident", &facts).unwrap();
+        assert!(!proof.holds());
+        assert!(!proof.to_string().contains("missing fact"));
+
+        let chainer = chainer().with_missing_policy(MissingPolicy::Error);
+        let proof = chainer.prove("person.resident", &facts).unwrap();
+        assert!(!proof.holds());
+        assert!(proof
+            .to_string()
+            .contains("(missing fact `person.country`)"));
+        assert!(chainer.prove("person.adult", &facts).unwrap().holds());
+    }
+
+    #[test]
+    fn test_cycles_are_detected() {
+        let mut chainer = BackwardChainer::new();
+        chainer
+            .add_definition(rule("chicken", "farm.egg", "farm.chicken", "true"))
+            .unwrap();
+        chainer
+            .add_definition(rule("egg", "farm.chicken", "farm.egg", "true"))
+            .unwrap();
+
+        let proof = chainer.prove("farm.chicken", &RuleContext::new()).unwrap();
+        assert!(!proof.holds());
+        assert!(proof.to_string().contains("farm.chicken depends on itself"));
+    }
+}
@@ -0,0 +1,71 @@
+//! Time sources for time-dependent rule evaluation
+//!
+//! The engine reads the current time from a [`Clock`] rather than the system
+//! directly, so tests can pin or advance time with a [`ManualClock`].
+//!
+//! ```
+//! use chrono::{TimeDelta, TimeZone, Utc};
+//! use windsurf_rules::clock::{Clock, ManualClock};
+//!
+//! let clock = ManualClock::new(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap());
+//! clock.advance(TimeDelta::days(31));
+//! assert_eq!(clock.now(), Utc.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap());
+//! ```
+
+use chrono::{DateTime, TimeDelta, Utc};
+use parking_lot::RwLock;
+use std::sync::Arc;
+
+/// A source of the current time
+pub trait Clock: Send + Sync {
+    /// The current time
+    fn now(&self) -> DateTime<Utc>;
+}
+
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> DateTime<Utc> {
+        (**self).now()
+    }
+}
+
+/// The system's wall clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock that only moves when told to; share it through an [`Arc`] to
+/// change the time seen by an engine that owns a clone
+#[derive(Debug, Default)]
+pub struct ManualClock {
+    now: RwLock<DateTime<Utc>>,
+}
+
+impl ManualClock {
+    /// Creates a clock stopped at `now`
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            now: RwLock::new(now),
+        }
+    }
+
+    /// Moves the clock to `now`, which may be in the past
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.write() = now;
+    }
+
+    /// Moves the clock forward by `delta`, or back if it is negative
+    pub fn advance(&self, delta: TimeDelta) {
+        *self.now.write() += delta;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.read()
+    }
+}
@@ -0,0 +1,446 @@
+//! Rule inheritance and reusable condition fragments
+//!
+//! A [`RuleLibrary`] holds named condition fragments and [`RuleSpec`]s. A spec
+//! may `extend` another rule, AND-ing its own condition onto the base's and
+//! overriding whatever else it sets, and may `include` fragments, which are
+//! AND-ed in before its own expression. [`RuleLibrary::resolve`] flattens the
+//! library into plain [`RuleDefinition`]s; `abstract` specs only serve as
+//! bases and produce no rule themselves.
+//!
+//! A spec inherits everything it leaves unset from its base, except its name,
+//! which defaults to its id. Tags are added to the base's and metadata keys
+//! override the base's; `then` and `else` replace the base's actions, so
+//! `then: []` drops them.
+//!
+//! ```
+//! use windsurf_rules::prelude::*;
+//! use windsurf_rules::compose::RuleLibrary;
+//!
+//! let library = RuleLibrary::from_yaml(r#"
+//! fragments:
+//!   eligible: customer.active && customer.age >= 18
+//! rules:
+//!   - id: discount
+//!     abstract: true
+//!     include: [eligible]
+//!     priority: 10
+//!     then: [{type: set, path: order.discount, value: "0.1"}]
+//!   - id: gold_discount
+//!     extends: discount
+//!     expression: customer.tier == 'gold'
+//!     then: [{type: set, path: order.discount, value: "0.2"}]
+//! "#)?;
+//!
+//! let rules = library.resolve()?;
+//! assert_eq!(rules[0].expression, "customer.active && customer.age >= 18 && customer.tier == \"gold\"");
+//! assert_eq!(rules[0].priority, 10);
+//!
+//! let mut engine = RulesEngine::new();
+//! engine.add_library(&library)?;
+//! # Ok::<(), windsurf_rules::Error>(())
+//! ```
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::error::{Error, Result};
+use crate::expr::{self, BinaryOp, Expr, ExprKind, Literal};
+use crate::rule::{Action, Calendar, Rule as RuleDefinition};
+
+/// A rule that may extend a base rule and include condition fragments
+///
+/// Unset fields are inherited from the base, see the [module docs](self).
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct RuleSpec {
+    /// Rule id
+    pub id: String,
+    /// Id of the rule this one extends
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
+    /// Names of the fragments AND-ed into the condition
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    /// Whether the spec only serves as a base and produces no rule
+    #[serde(default, rename = "abstract")]
+    pub is_abstract: bool,
+    /// Human-readable name; defaults to the id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Condition AND-ed onto the base's and the included fragments
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expression: Option<String>,
+    /// Priority
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
+    /// Whether the rule is active
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_active: Option<bool>,
+    /// Actions run when the condition matches
+    #[serde(default, rename = "then", skip_serializing_if = "Option::is_none")]
+    pub then_actions: Option<Vec<Action>>,
+    /// Actions run when the condition does not match
+    #[serde(default, rename = "else", skip_serializing_if = "Option::is_none")]
+    pub else_actions: Option<Vec<Action>>,
+    /// Agenda group
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agenda_group: Option<String>,
+    /// Activation group
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub activation_group: Option<String>,
+    /// Tags added to the base's
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Metadata merged over the base's
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+    /// Start of the validity window
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_from: Option<DateTime<Utc>>,
+    /// End of the validity window
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_until: Option<DateTime<Utc>>,
+    /// Recurring validity calendar
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub calendar: Option<Calendar>,
+}
+
+impl RuleSpec {
+    /// Creates a spec with the given id and nothing else set
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Extends the rule with the given id
+    pub fn extends(mut self, base: impl Into<String>) -> Self {
+        self.extends = Some(base.into());
+        self
+    }
+
+    /// Includes a condition fragment
+    pub fn include(mut self, fragment: impl Into<String>) -> Self {
+        self.include.push(fragment.into());
+        self
+    }
+
+    /// Marks the spec as a base that produces no rule itself
+    pub fn as_abstract(mut self) -> Self {
+        self.is_abstract = true;
+        self
+    }
+
+    /// Sets the condition expression
+    pub fn with_expression(mut self, expression: impl Into<String>) -> Self {
+        self.expression = Some(expression.into());
+        self
+    }
+
+    /// Sets the priority
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    /// Adds an action run when the condition matches, replacing the base's
+    pub fn then(mut self, action: Action) -> Self {
+        self.then_actions.get_or_insert_with(Vec::new).push(action);
+        self
+    }
+}
+
+/// Condition fragments and rule specs that refer to each other by name
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct RuleLibrary {
+    /// Reusable conditions, by name
+    #[serde(default)]
+    pub fragments: BTreeMap<String, String>,
+    /// The rule specs, in order
+    #[serde(default)]
+    pub rules: Vec<RuleSpec>,
+}
+
+/// A resolved spec, with the fragments already AND-ed into its condition
+struct Resolved {
+    rule: RuleDefinition,
+    fragments: Vec<String>,
+}
+
+impl RuleLibrary {
+    /// Creates an empty library
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a named condition fragment
+    pub fn with_fragment(mut self, name: impl Into<String>, expression: impl Into<String>) -> Self {
+        self.fragments.insert(name.into(), expression.into());
+        self
+    }
+
+    /// Appends a rule spec
+    pub fn with_rule(mut self, spec: RuleSpec) -> Self {
+        self.rules.push(spec);
+        self
+    }
+
+    /// Parses a library from YAML (or JSON)
+    pub fn from_yaml(source: &str) -> Result<Self> {
+        serde_yaml::from_str(source).map_err(|err| Error::Serialization(Box::new(err)))
+    }
+
+    /// Flattens every non-abstract spec into a rule definition, in library
+    /// order. Fails with [`Error::InheritanceCycle`] if rules extend each
+    /// other in a cycle, and with [`Error::Config`] for unknown bases and
+    /// fragments and for expressions that do not parse.
+    pub fn resolve(&self) -> Result<Vec<RuleDefinition>> {
+        let mut specs = HashMap::new();
+        for spec in &self.rules {
+            if specs.insert(spec.id.as_str(), spec).is_some() {
+                return Err(Error::DuplicateRule(spec.id.clone()));
+            }
+        }
+
+        let mut resolved = HashMap::new();
+        for spec in &self.rules {
+            self.resolve_spec(spec, &specs, &mut resolved, &mut Vec::new())?;
+        }
+        Ok(self
+            .rules
+            .iter()
+            .filter(|spec| !spec.is_abstract)
+            .map(|spec| resolved[spec.id.as_str()].rule.clone())
+            .collect())
+    }
+
+    fn resolve_spec<'a>(
+        &'a self,
+        spec: &'a RuleSpec,
+        specs: &HashMap<&'a str, &'a RuleSpec>,
+        resolved: &mut HashMap<&'a str, Resolved>,
+        chain: &mut Vec<&'a str>,
+    ) -> Result<()> {
+        if resolved.contains_key(spec.id.as_str()) {
+            return Ok(());
+        }
+        if let Some(start) = chain.iter().position(|id| *id == spec.id) {
+            let mut cycle: Vec<String> = chain[start..].iter().map(ToString::to_string).collect();
+            cycle.push(spec.id.clone());
+            return Err(Error::InheritanceCycle(cycle));
+        }
+
+        let base = match &spec.extends {
+            Some(base_id) => {
+                let base = specs.get(base_id.as_str()).ok_or_else(|| {
+                    Error::Config(format!(
+                        "rule `{}` extends unknown rule `{}`",
+                        spec.id, base_id
+                    ))
+                })?;
+                chain.push(&spec.id);
+                self.resolve_spec(base, specs, resolved, chain)?;
+                chain.pop();
+                resolved.get(base.id.as_str())
+            }
+            None => None,
+        };
+
+        let merged = self.merge(spec, base)?;
+        resolved.insert(&spec.id, merged);
+        Ok(())
+    }
+
+    /// Applies `spec` on top of its resolved base
+    fn merge(&self, spec: &RuleSpec, base: Option<&Resolved>) -> Result<Resolved> {
+        let invalid = |what: String, err: Error| {
+            Error::Config(format!(
+                "rule `{}`: {} does not parse: {}",
+                spec.id, what, err
+            ))
+        };
+
+        let mut conditions = Vec::new();
+        let mut fragments = Vec::new();
+        let base_rule = match base {
+            Some(base) => {
+                conditions.push(expr::parse(&base.rule.expression)?);
+                fragments.extend(base.fragments.iter().cloned());
+                base.rule.clone()
+            }
+            None => RuleDefinition::default(),
+        };
+        for name in &spec.include {
+            if fragments.contains(name) {
+                continue;
+            }
+            let fragment = self.fragments.get(name).ok_or_else(|| {
+                Error::Config(format!(
+                    "rule `{}` includes unknown fragment `{}`",
+                    spec.id, name
+                ))
+            })?;
+            conditions.push(
+                expr::parse(fragment)
+                    .map_err(|err| invalid(format!("fragment `{}`", name), err))?,
+            );
+            fragments.push(name.clone());
+        }
+        if let Some(expression) = &spec.expression {
+            conditions.push(
+                expr::parse(expression).map_err(|err| invalid("expression".to_string(), err))?,
+            );
+        }
+
+        let mut rule = RuleDefinition::new(spec.id.clone())
+            .with_expression(conjunction(conditions))
+            .with_priority(spec.priority.unwrap_or(base_rule.priority));
+        if let Some(name) = &spec.name {
+            rule.name = name.clone();
+        }
+        rule.is_active = spec.is_active.unwrap_or(base_rule.is_active);
+        rule.then_actions = spec.then_actions.clone().unwrap_or(base_rule.then_actions);
+        rule.else_actions = spec.else_actions.clone().unwrap_or(base_rule.else_actions);
+        rule.agenda_group = spec.agenda_group.clone().or(base_rule.agenda_group);
+        rule.activation_group = spec.activation_group.clone().or(base_rule.activation_group);
+        rule.valid_from = spec.valid_from.or(base_rule.valid_from);
+        rule.valid_until = spec.valid_until.or(base_rule.valid_until);
+        rule.calendar = spec.calendar.clone().or(base_rule.calendar);
+        rule.metadata = base_rule.metadata;
+        for tag in base_rule.tags.into_iter().chain(spec.tags.iter().cloned()) {
+            rule = rule.with_tag(tag);
+        }
+        for (key, value) in &spec.metadata {
+            rule = rule.with_metadata(key.clone(), value.clone());
+        }
+        Ok(Resolved { rule, fragments })
+    }
+}
+
+/// AND-s the conditions together, dropping literal `true`s
+fn conjunction(conditions: Vec<Expr>) -> String {
+    conditions
+        .into_iter()
+        .filter(|condition| !matches!(condition.kind, ExprKind::Literal(Literal::Bool(true))))
+        .reduce(|lhs, rhs| {
+            let span = lhs.span.merge(rhs.span);
+            Expr::new(
+                ExprKind::Binary {
+                    op: BinaryOp::And,
+                    lhs: Box::new(lhs),
+                    rhs: Box::new(rhs),
+                },
+                span,
+            )
+        })
+        .map_or_else(|| "true".to_string(), |condition| condition.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn library() -> RuleLibrary {
+        RuleLibrary::new()
+            .with_fragment("adult", "customer.age >= 18")
+            .with_fragment("active", "customer.active || customer.trial")
+            .with_rule(
+                RuleSpec::new("base")
+                    .as_abstract()
+                    .include("adult")
+                    .include("active")
+                    .with_priority(5)
+                    .then(Action::message("eligible")),
+            )
+    }
+
+    #[test]
+    fn test_library_resolves_inheritance() {
+        let mut child = RuleSpec::new("gold")
+            .extends("base")
+            .include("adult")
+            .with_expression("customer.tier == 'gold'");
+        child.tags.push("vip".to_string());
+        let grandchild = RuleSpec::new("gold_late")
+            .extends("gold")
+            .with_expression("order.late")
+            .with_priority(1);
+        let rules = library()
+            .with_rule(grandchild)
+            .with_rule(child)
+            .resolve()
+            .unwrap();
+
+        let ids: Vec<_> = rules.iter().map(|rule| rule.id.as_str()).collect();
+        assert_eq!(ids, ["gold_late", "gold"]);
+        assert_eq!(
+            rules[1].expression,
+            r#"customer.age >= 18 && (customer.active || customer.trial) && customer.tier == "gold""#
+        );
+        assert_eq!(rules[1].priority, 5);
+        assert_eq!(rules[1].then_actions, [Action::message("eligible")]);
+        assert_eq!(rules[0].priority, 1);
+        assert_eq!(rules[0].tags, ["vip"]);
+        assert!(rules[0]
+            .expression
+            .ends_with(r#"customer.tier == "gold" && order.late"#));
+    }
+
+    #[test]
+    fn test_library_overrides_actions() {
+        let mut spec = RuleSpec::new("silent").extends("base");
+        spec.then_actions = Some(Vec::new());
+        let rules = library().with_rule(spec).resolve().unwrap();
+        assert!(rules[0].then_actions.is_empty());
+        assert_eq!(rules[0].name, "silent");
+    }
+
+    #[test]
+    fn test_library_reports_invalid_references() {
+        let cyclic = library()
+            .with_rule(RuleSpec::new("a").extends("c"))
+            .with_rule(RuleSpec::new("b").extends("a"))
+            .with_rule(RuleSpec::new("c").extends("b"));
+        assert!(matches!(
+            cyclic.resolve(),
+            Err(Error::InheritanceCycle(cycle)) if cycle == ["a", "c", "b", "a"]
+        ));
+
+        let err = library()
+            .with_rule(RuleSpec::new("x").extends("y"))
+            .resolve()
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("rule `x` extends unknown rule `y`"),
+            "{}",
+            err
+        );
+        let err = library()
+            .with_rule(RuleSpec::new("x").include("vip"))
+            .resolve()
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("rule `x` includes unknown fragment `vip`"),
+            "{}",
+            err
+        );
+        let err = library()
+            .with_fragment("broken", "customer.age >=")
+            .with_rule(RuleSpec::new("x").include("broken"))
+            .resolve()
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("rule `x`: fragment `broken` does not parse"),
+            "{}",
+            err
+        );
+        assert!(matches!(
+            library().with_rule(RuleSpec::new("base")).resolve(),
+            Err(Error::DuplicateRule(id)) if id == "base"
+        ));
+    }
+}
@@ -0,0 +1,118 @@
+//! Structured diagnostics shared by evaluation and rule analysis passes
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::expr::Span;
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Severity {
+    /// Informational note
+    Info,
+    /// Something is likely wrong but evaluation can proceed
+    Warning,
+    /// Something is definitely wrong
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// A single message about a rule, optionally pointing into its expression
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    /// How serious the diagnostic is
+    pub severity: Severity,
+    /// Stable machine-readable code, e.g. `"eval-error"`
+    pub code: String,
+    /// Human-readable description
+    pub message: String,
+    /// The rule the diagnostic is about, if any
+    pub rule_id: Option<String>,
+    /// Location within the rule's expression, if any
+    pub span: Option<Span>,
+}
+
+impl Diagnostic {
+    /// Creates a diagnostic with the given severity
+    pub fn new(severity: Severity, code: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            code: code.into(),
+            message: message.into(),
+            rule_id: None,
+            span: None,
+        }
+    }
+
+    /// Creates an error diagnostic
+    pub fn error(code: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, code, message)
+    }
+
+    /// Creates a warning diagnostic
+    pub fn warning(code: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, code, message)
+    }
+
+    /// Creates an informational diagnostic
+    pub fn info(code: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::new(Severity::Info, code, message)
+    }
+
+    /// Attaches the id of the rule the diagnostic is about
+    pub fn with_rule(mut self, rule_id: impl Into<String>) -> Self {
+        self.rule_id = Some(rule_id.into());
+        self
+    }
+
+    /// Attaches a location within the rule's expression
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    /// Returns true for [`Severity::Error`] diagnostics
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{}]", self.severity, self.code)?;
+        if let Some(rule_id) = &self.rule_id {
+            write!(f, " rule `{}`", rule_id)?;
+        }
+        if let Some(span) = self.span {
+            write!(f, " at {}", span)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diagnostic_display() {
+        let diagnostic = Diagnostic::warning("always-true", "condition is always true")
+            .with_rule("r1")
+            .with_span(Span::new(0, 6));
+        assert_eq!(
+            diagnostic.to_string(),
+            "warning[always-true] rule `r1` at 0..6: condition is always true"
+        );
+        assert!(!diagnostic.is_error());
+        assert!(Severity::Error > Severity::Warning);
+    }
+}
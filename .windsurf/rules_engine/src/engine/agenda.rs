@@ -0,0 +1,252 @@
+//! Pending rule activations and conflict resolution
+//!
+//! When several rules match at once, the [`Agenda`] decides which fires
+//! first according to its [`ConflictStrategy`]. Whatever the strategy,
+//! remaining ties are broken by definition order and then by rule id, so
+//! the firing order is always deterministic. Rules sharing an activation
+//! group are mutually exclusive: once one of them fires, the pending
+//! activations of the others are withdrawn.
+
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::rule::evaluate::Rule;
+
+/// A rule whose condition became true and that has not fired since
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Activation {
+    /// Id of the activated rule
+    pub rule_id: String,
+    /// Priority (salience) of the activated rule
+    pub priority: i32,
+    /// Number of tests in the rule's condition; native rules count as zero
+    pub specificity: usize,
+    /// Position of the rule in definition order
+    pub order: usize,
+    /// Order in which the activation was created
+    pub sequence: u64,
+    /// Activation group of the activated rule
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub activation_group: Option<String>,
+}
+
+/// Comparator used by [`ConflictStrategy::Custom`]; `Less` fires first
+pub type ActivationComparator = dyn Fn(&Activation, &Activation) -> Ordering + Send + Sync;
+
+/// How the agenda picks the next activation to fire
+#[derive(Clone, Default)]
+pub enum ConflictStrategy {
+    /// Highest [`Rule::priority`] first
+    #[default]
+    Priority,
+    /// Most recently activated first
+    Recency,
+    /// Rules with the most condition tests first
+    Specificity,
+    /// Rules in the order they were added
+    DefinitionOrder,
+    /// A caller-supplied comparator
+    Custom(Arc<ActivationComparator>),
+}
+
+impl ConflictStrategy {
+    /// Creates a strategy from a comparator that returns `Less` for the
+    /// activation that should fire first
+    pub fn custom<F>(compare: F) -> Self
+    where
+        F: Fn(&Activation, &Activation) -> Ordering + Send + Sync + 'static,
+    {
+        ConflictStrategy::Custom(Arc::new(compare))
+    }
+
+    /// Orders two activations; `Less` means `a` fires before `b`
+    pub fn compare(&self, a: &Activation, b: &Activation) -> Ordering {
+        let ordering = match self {
+            ConflictStrategy::Priority => b.priority.cmp(&a.priority),
+            ConflictStrategy::Recency => b.sequence.cmp(&a.sequence),
+            ConflictStrategy::Specificity => b.specificity.cmp(&a.specificity),
+            ConflictStrategy::DefinitionOrder => Ordering::Equal,
+            ConflictStrategy::Custom(compare) => compare(a, b),
+        };
+        ordering
+            .then(a.order.cmp(&b.order))
+            .then_with(|| a.rule_id.cmp(&b.rule_id))
+    }
+}
+
+impl fmt::Debug for ConflictStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConflictStrategy::Priority => f.write_str("Priority"),
+            ConflictStrategy::Recency => f.write_str("Recency"),
+            ConflictStrategy::Specificity => f.write_str("Specificity"),
+            ConflictStrategy::DefinitionOrder => f.write_str("DefinitionOrder"),
+            ConflictStrategy::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+/// The set of pending activations
+#[derive(Debug, Clone, Default)]
+pub struct Agenda {
+    activations: Vec<Activation>,
+    strategy: ConflictStrategy,
+    next_sequence: u64,
+}
+
+impl Agenda {
+    /// Creates an empty agenda using [`ConflictStrategy::Priority`]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an empty agenda using `strategy`
+    pub fn with_strategy(strategy: ConflictStrategy) -> Self {
+        Self {
+            strategy,
+            ..Self::default()
+        }
+    }
+
+    /// The conflict-resolution strategy in use
+    pub fn strategy(&self) -> &ConflictStrategy {
+        &self.strategy
+    }
+
+    /// Replaces the conflict-resolution strategy
+    pub fn set_strategy(&mut self, strategy: ConflictStrategy) {
+        self.strategy = strategy;
+    }
+
+    /// Number of pending activations
+    pub fn len(&self) -> usize {
+        self.activations.len()
+    }
+
+    /// Returns true if nothing is waiting to fire
+    pub fn is_empty(&self) -> bool {
+        self.activations.is_empty()
+    }
+
+    /// Pending activations in firing order
+    pub fn iter(&self) -> impl Iterator<Item = &Activation> {
+        let mut pending: Vec<&Activation> = self.activations.iter().collect();
+        pending.sort_by(|a, b| self.strategy.compare(a, b));
+        pending.into_iter()
+    }
+
+    /// Activates the rule at definition position `order`, replacing any
+    /// pending activation of the same rule
+    pub fn push(&mut self, order: usize, rule: &dyn Rule) {
+        self.remove(order);
+        self.activations.push(Activation {
+            rule_id: rule.id().to_string(),
+            priority: rule.priority(),
+            specificity: rule
+                .condition()
+                .map_or(0, |condition| condition.conjuncts().len()),
+            order,
+            sequence: self.next_sequence,
+            activation_group: rule.activation_group().map(str::to_string),
+        });
+        self.next_sequence += 1;
+    }
+
+    /// Withdraws the pending activation of the rule at position `order`
+    pub fn remove(&mut self, order: usize) {
+        self.activations
+            .retain(|activation| activation.order != order);
+    }
+
+    /// Removes and returns the activation that should fire next, withdrawing
+    /// the other activations of its activation group
+    pub fn pop(&mut self) -> Option<Activation> {
+        let index = (0..self.activations.len()).min_by(|&a, &b| {
+            self.strategy
+                .compare(&self.activations[a], &self.activations[b])
+        })?;
+        let activation = self.activations.remove(index);
+        if let Some(group) = &activation.activation_group {
+            self.activations
+                .retain(|other| other.activation_group.as_ref() != Some(group));
+        }
+        Some(activation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::Rule as RuleDefinition;
+
+    fn agenda(strategy: ConflictStrategy) -> Agenda {
+        build(strategy, None)
+    }
+
+    fn build(strategy: ConflictStrategy, activation_group: Option<&str>) -> Agenda {
+        let rules = [
+            ("low", "a", 0),
+            ("high", "a && b && c", 10),
+            ("also_low", "a && b", 0),
+        ];
+        let mut agenda = Agenda::with_strategy(strategy);
+        for (order, (id, expression, priority)) in rules.into_iter().enumerate() {
+            let mut rule = RuleDefinition::new(id)
+                .with_expression(expression)
+                .with_priority(priority);
+            rule.activation_group = activation_group.map(str::to_string);
+            let rule = rule.compile().unwrap();
+            agenda.push(order, &rule);
+        }
+        agenda
+    }
+
+    fn order(agenda: &Agenda) -> Vec<&str> {
+        agenda.iter().map(|a| a.rule_id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_strategies_order_activations() {
+        assert_eq!(
+            order(&agenda(ConflictStrategy::Priority)),
+            ["high", "low", "also_low"]
+        );
+        assert_eq!(
+            order(&agenda(ConflictStrategy::Recency)),
+            ["also_low", "high", "low"]
+        );
+        assert_eq!(
+            order(&agenda(ConflictStrategy::Specificity)),
+            ["high", "also_low", "low"]
+        );
+        assert_eq!(
+            order(&agenda(ConflictStrategy::DefinitionOrder)),
+            ["low", "high", "also_low"]
+        );
+
+        let by_id = ConflictStrategy::custom(|a, b| a.rule_id.cmp(&b.rule_id));
+        assert_eq!(order(&agenda(by_id)), ["also_low", "high", "low"]);
+    }
+
+    #[test]
+    fn test_ties_break_by_definition_order() {
+        let mut agenda = agenda(ConflictStrategy::custom(|_, _| Ordering::Equal));
+        agenda.remove(0);
+        assert_eq!(agenda.pop().map(|a| a.rule_id), Some("high".to_string()));
+        assert_eq!(
+            agenda.pop().map(|a| a.rule_id),
+            Some("also_low".to_string())
+        );
+        assert!(agenda.is_empty());
+    }
+
+    #[test]
+    fn test_activation_group_fires_once() {
+        let mut agenda = build(ConflictStrategy::Priority, Some("pricing"));
+        assert_eq!(agenda.len(), 3);
+        assert_eq!(agenda.pop().map(|a| a.rule_id), Some("high".to_string()));
+        assert!(agenda.is_empty());
+    }
+}
@@ -0,0 +1,99 @@
+//! Explanations of individual rule decisions
+//!
+//! [`RulesEngine::explain`](crate::engine::RulesEngine::explain) re-evaluates
+//! one rule against a context and returns an [`Explanation`]: the rule's
+//! result, why it was skipped if the engine would not have evaluated it, and
+//! for expression rules a [`Trace`] of every sub-expression and fact read.
+//! Explanations serialize as structured data and print as a plain-text
+//! report.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::expr::Trace;
+use crate::rule::RuleResult;
+
+/// Why the engine would not evaluate a rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// The rule is marked inactive
+    Inactive,
+    /// The rule was disabled on the engine
+    Disabled,
+    /// The engine clock is outside the rule's validity window or calendar
+    OutsideValidity,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SkipReason::Inactive => "it is inactive",
+            SkipReason::Disabled => "it is disabled",
+            SkipReason::OutsideValidity => "it is outside its validity window",
+        })
+    }
+}
+
+/// How a rule reached its decision for one context
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Explanation {
+    /// Id of the explained rule
+    pub rule_id: String,
+    /// Revision of the rule, if it is versioned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<u64>,
+    /// Set if the engine would have skipped the rule; the rule is still
+    /// evaluated so the explanation shows what it would have decided
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skipped: Option<SkipReason>,
+    /// The rule's result, without running its actions
+    pub result: RuleResult,
+    /// Trace of the rule's condition; `None` for native rules
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace: Option<Trace>,
+}
+
+impl Explanation {
+    /// Returns true if the rule's condition matched
+    pub fn matched(&self) -> bool {
+        self.result.matched
+    }
+
+    /// The explanation as a human-readable report
+    pub fn report(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "rule `{}`", self.rule_id)?;
+        if let Some(revision) = self.revision {
+            write!(f, " (revision {})", revision)?;
+        }
+        let outcome = if self.result.has_errors() {
+            "failed"
+        } else if self.result.matched {
+            "matched"
+        } else if self.result.unknown {
+            "had an unknown condition"
+        } else {
+            "did not match"
+        };
+        match self.skipped {
+            Some(reason) => writeln!(f, " would have {} but was skipped: {}", outcome, reason)?,
+            None => writeln!(f, " {}", outcome)?,
+        }
+        for diagnostic in &self.result.diagnostics {
+            writeln!(f, "{}", diagnostic)?;
+        }
+        for (name, value) in &self.result.outputs {
+            writeln!(f, "output {} = {}", name, value)?;
+        }
+        match &self.trace {
+            Some(trace) => write!(f, "{}", trace),
+            None => writeln!(f, "native rule; no condition trace"),
+        }
+    }
+}
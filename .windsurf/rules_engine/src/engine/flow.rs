@@ -0,0 +1,354 @@
+//! Rule flow between agenda groups
+//!
+//! A [`RuleFlow`] focuses agenda groups one at a time. Each group runs
+//! against the context as left by the groups before it: its fact effects are
+//! applied before the flow moves on, and branch and loop conditions are
+//! evaluated against the updated context, with the engine's functions, clock
+//! and missing-fact policy. Conditions are parsed when the flow is built, so
+//! a flow with a malformed condition fails to build. Flows deserialize from
+//! the same configuration files as rules:
+//!
+//! ```
+//! use windsurf_rules::engine::RuleFlow;
+//!
+//! let flow: RuleFlow = serde_yaml::from_str(r#"
+//! type: sequence
+//! steps:
+//!   - { type: group, name: validate }
+//!   - type: branch
+//!     condition: "order.valid"
+//!     then: { type: group, name: pricing }
+//!     otherwise: { type: group, name: rejection }
+//! "#).unwrap();
+//! assert_eq!(flow.groups(), ["validate", "pricing", "rejection"]);
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+use crate::engine::{ExecutionResult, RulesEngine};
+use crate::error::{Error, Result};
+use crate::expr::{self, Expr};
+use crate::rule::RuleContext;
+
+/// Default iteration limit of [`RuleFlow::Loop`]
+pub const DEFAULT_MAX_ITERATIONS: usize = 100;
+
+fn default_max_iterations() -> usize {
+    DEFAULT_MAX_ITERATIONS
+}
+
+/// A flow of agenda groups
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RuleFlow {
+    /// Run the rules of one agenda group
+    Group {
+        /// Name of the agenda group
+        name: String,
+    },
+    /// Run steps one after another
+    Sequence {
+        /// The steps, in order
+        steps: Vec<RuleFlow>,
+    },
+    /// Run one of two steps depending on a condition
+    Branch {
+        /// Expression deciding which step runs
+        #[serde(with = "condition")]
+        condition: Expr,
+        /// Step run when the condition holds
+        then: Box<RuleFlow>,
+        /// Step run otherwise, if any
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        otherwise: Option<Box<RuleFlow>>,
+    },
+    /// Repeat a step while a condition holds
+    Loop {
+        /// Expression checked before every iteration
+        #[serde(with = "condition")]
+        condition: Expr,
+        /// Step repeated while the condition holds
+        body: Box<RuleFlow>,
+        /// Iterations after which the loop fails with [`Error::LoopLimit`]
+        #[serde(default = "default_max_iterations")]
+        max_iterations: usize,
+    },
+}
+
+impl RuleFlow {
+    /// A step running one agenda group
+    pub fn group(name: impl Into<String>) -> Self {
+        RuleFlow::Group { name: name.into() }
+    }
+
+    /// A step running agenda groups in order
+    pub fn sequence<I, S>(groups: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        RuleFlow::Sequence {
+            steps: groups.into_iter().map(Self::group).collect(),
+        }
+    }
+
+    /// A step running `then` when `condition` holds, otherwise `otherwise`;
+    /// fails if the condition does not parse
+    pub fn branch(condition: &str, then: RuleFlow, otherwise: Option<RuleFlow>) -> Result<Self> {
+        Ok(RuleFlow::Branch {
+            condition: expr::parse(condition)?,
+            then: Box::new(then),
+            otherwise: otherwise.map(Box::new),
+        })
+    }
+
+    /// A step repeating `body` while `condition` holds; fails if the
+    /// condition does not parse
+    pub fn repeat_while(condition: &str, body: RuleFlow) -> Result<Self> {
+        Ok(RuleFlow::Loop {
+            condition: expr::parse(condition)?,
+            body: Box::new(body),
+            max_iterations: DEFAULT_MAX_ITERATIONS,
+        })
+    }
+
+    /// Every agenda group the flow can reach, in definition order
+    pub fn groups(&self) -> Vec<&str> {
+        let mut groups = Vec::new();
+        self.collect_groups(&mut groups);
+        groups
+    }
+
+    fn collect_groups<'a>(&'a self, groups: &mut Vec<&'a str>) {
+        match self {
+            RuleFlow::Group { name } => {
+                if !groups.contains(&name.as_str()) {
+                    groups.push(name);
+                }
+            }
+            RuleFlow::Sequence { steps } => {
+                steps.iter().for_each(|step| step.collect_groups(groups))
+            }
+            RuleFlow::Branch {
+                then, otherwise, ..
+            } => {
+                then.collect_groups(groups);
+                if let Some(otherwise) = otherwise {
+                    otherwise.collect_groups(groups);
+                }
+            }
+            RuleFlow::Loop { body, .. } => body.collect_groups(groups),
+        }
+    }
+
+    /// Runs the flow on `engine`, applying each group's fact effects to `context`
+    pub fn run(&self, engine: &RulesEngine, context: &mut RuleContext) -> Result<FlowResult> {
+        let mut result = FlowResult::default();
+        self.run_step(engine, context, &mut result)?;
+        Ok(result)
+    }
+
+    fn run_step(
+        &self,
+        engine: &RulesEngine,
+        context: &mut RuleContext,
+        result: &mut FlowResult,
+    ) -> Result<()> {
+        match self {
+            RuleFlow::Group { name } => {
+                let execution = engine.execute_group(name, context)?;
+                execution.apply_to(context)?;
+                result.runs.push((name.clone(), execution));
+            }
+            RuleFlow::Sequence { steps } => {
+                for step in steps {
+                    step.run_step(engine, context, result)?;
+                }
+            }
+            RuleFlow::Branch {
+                condition,
+                then,
+                otherwise,
+            } => {
+                if engine.holds(condition, context)? {
+                    then.run_step(engine, context, result)?;
+                } else if let Some(otherwise) = otherwise {
+                    otherwise.run_step(engine, context, result)?;
+                }
+            }
+            RuleFlow::Loop {
+                condition,
+                body,
+                max_iterations,
+            } => {
+                let mut iterations = 0;
+                while engine.holds(condition, context)? {
+                    if iterations == *max_iterations {
+                        return Err(Error::LoopLimit(*max_iterations));
+                    }
+                    body.run_step(engine, context, result)?;
+                    iterations += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Serializes a condition as its expression source
+mod condition {
+    use serde::de::Error as _;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::expr::{self, Expr};
+
+    pub fn serialize<S: Serializer>(condition: &Expr, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(condition)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Expr, D::Error> {
+        let source = String::deserialize(deserializer)?;
+        expr::parse(&source).map_err(D::Error::custom)
+    }
+}
+
+/// The outcome of one [`RuleFlow::run`] call
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FlowResult {
+    /// Every group execution, in the order the flow ran them
+    pub runs: Vec<(String, ExecutionResult)>,
+}
+
+impl FlowResult {
+    /// Names of the groups that ran, in order; a group appears once per run
+    pub fn groups(&self) -> Vec<&str> {
+        self.runs.iter().map(|(group, _)| group.as_str()).collect()
+    }
+
+    /// Ids of the fired rules across all groups, in firing order
+    pub fn fired_rules(&self) -> Vec<&str> {
+        self.runs
+            .iter()
+            .flat_map(|(_, execution)| execution.fired_rules())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::Type;
+    use crate::expr::Function;
+    use crate::rule::{Action, MissingPolicy, Rule as RuleDefinition, Value};
+
+    fn engine() -> RulesEngine {
+        let mut engine = RulesEngine::new();
+        let rules = [
+            RuleDefinition::new("check")
+                .with_expression("order.total > 0")
+                .in_agenda_group("validate")
+                .then(Action::set("order.valid", "true"))
+                .otherwise(Action::set("order.valid", "false")),
+            RuleDefinition::new("retry")
+                .with_expression("true")
+                .in_agenda_group("retry")
+                .then(Action::set("order.attempts", "order.attempts + 1")),
+            RuleDefinition::new("reject")
+                .with_expression("true")
+                .in_agenda_group("rejection")
+                .then(Action::set("order.status", "'rejected'")),
+        ];
+        for rule in rules {
+            engine.add_definition(rule).unwrap();
+        }
+        engine
+    }
+
+    fn order(total: i64) -> RuleContext {
+        let order: Value = [("total", total), ("attempts", 0)].into_iter().collect();
+        RuleContext::new().with_fact("order", order)
+    }
+
+    #[test]
+    fn test_flow_sequences_branches_and_loops() {
+        let flow = RuleFlow::Sequence {
+            steps: vec![
+                RuleFlow::group("validate"),
+                RuleFlow::branch(
+                    "order.valid",
+                    RuleFlow::repeat_while("order.attempts < 3", RuleFlow::group("retry")).unwrap(),
+                    Some(RuleFlow::group("rejection")),
+                )
+                .unwrap(),
+            ],
+        };
+
+        let mut valid = order(10);
+        let result = flow.run(&engine(), &mut valid).unwrap();
+        assert_eq!(result.groups(), ["validate", "retry", "retry", "retry"]);
+        assert_eq!(
+            valid.get_path("order.attempts").unwrap(),
+            Some(&Value::Int(3))
+        );
+
+        let mut invalid = order(0);
+        let result = flow.run(&engine(), &mut invalid).unwrap();
+        assert_eq!(result.fired_rules(), ["reject"]);
+        assert_eq!(
+            invalid.get_path("order.status").unwrap(),
+            Some(&Value::from("rejected"))
+        );
+    }
+
+    #[test]
+    fn test_loop_limit() {
+        let flow = RuleFlow::Loop {
+            condition: expr::parse("order.total > 0").unwrap(),
+            body: Box::new(RuleFlow::group("retry")),
+            max_iterations: 5,
+        };
+        assert!(matches!(
+            flow.run(&engine(), &mut order(1)),
+            Err(Error::LoopLimit(5))
+        ));
+    }
+
+    #[test]
+    fn test_conditions_parse_when_the_flow_is_built() {
+        assert!(matches!(
+            RuleFlow::branch("order.total >", RuleFlow::group("retry"), None),
+            Err(Error::Syntax { .. })
+        ));
+        let err = serde_yaml::from_str::<RuleFlow>(
+            "{type: loop, condition: 'order.total >', body: {type: group, name: retry}}",
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("expected"), "{}", err);
+
+        let flow = RuleFlow::branch("order.total > 0", RuleFlow::group("retry"), None).unwrap();
+        let yaml = serde_yaml::to_string(&flow).unwrap();
+        assert!(yaml.contains("condition: order.total > 0"), "{}", yaml);
+        assert_eq!(serde_yaml::from_str::<RuleFlow>(&yaml).unwrap(), flow);
+    }
+
+    #[test]
+    fn test_conditions_use_the_engine_configuration() {
+        let flow =
+            |condition| RuleFlow::branch(condition, RuleFlow::group("rejection"), None).unwrap();
+
+        let strict = engine().with_missing_policy(MissingPolicy::Error);
+        assert!(matches!(
+            flow("order.missing == null").run(&strict, &mut order(1)),
+            Err(Error::Evaluation { .. })
+        ));
+
+        let mut engine = engine();
+        engine.register_function(Function::new("double", [Type::Int], Type::Int, |args| {
+            Ok(Value::Int(args[0].as_i64().unwrap_or(0) * 2))
+        }));
+        let result = flow("double(order.total) == 4")
+            .run(&engine, &mut order(2))
+            .unwrap();
+        assert_eq!(result.fired_rules(), ["reject"]);
+    }
+}
@@ -0,0 +1,1155 @@
+//! High-level rules engine facade
+//!
+//! [`RulesEngine`] owns a set of rules, evaluates the active ones against a
+//! [`RuleContext`] and collects the outcome of every rule into an
+//! [`ExecutionResult`]. Rule actions run against the input context and are
+//! reported as effects; [`ExecutionResult::apply_to`] applies them. Matched
+//! rules fire in the order chosen by the engine's [`ConflictStrategy`].
+//! Rules can also be run one agenda group at a time, sequenced by a
+//! [`RuleFlow`], or stratum by stratum in dependency order with
+//! [`RulesEngine::execute_stratified`].
+//!
+//! ```
+//! use windsurf_rules::prelude::*;
+//! use windsurf_rules::engine::RulesEngine;
+//!
+//! let mut engine = RulesEngine::new();
+//! engine.add_definition(RuleDefinition {
+//!     id: "adult".into(),
+//!     name: "Adult customer".into(),
+//!     expression: "customer.age >= 18".into(),
+//!     ..RuleDefinition::default()
+//! })?;
+//!
+//! let customer: Value = [("age", 30)].into_iter().collect();
+//! let context = RuleContext::new().with_fact("customer", customer);
+//! let results = engine.execute(&context)?;
+//! assert_eq!(results.matched_rules(), ["adult"]);
+//! # Ok::<(), windsurf_rules::Error>(())
+//! ```
+
+pub mod agenda;
+pub mod explain;
+pub mod flow;
+
+pub use self::agenda::{Activation, Agenda, ConflictStrategy};
+pub use self::explain::{Explanation, SkipReason};
+pub use self::flow::{FlowResult, RuleFlow};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::analysis::{DependencyGraph, Schema, TypeChecker};
+use crate::clock::{Clock, SystemClock};
+use crate::compose::RuleLibrary;
+use crate::diagnostic::Diagnostic;
+use crate::error::{Error, Result};
+use crate::expr::datetime::now_function;
+use crate::expr::{Evaluator, Expr, Function, FunctionRegistry, Tracer};
+use crate::parallel::ParallelExecutor;
+use crate::rule::evaluate::{error_diagnostic, Rule};
+use crate::rule::{
+    CallbackRegistry, Effect, MissingPolicy, Rule as RuleDefinition, RuleContext, RuleResult,
+    Value,
+};
+use crate::selector::Selector;
+use crate::table::DecisionTable;
+use crate::template::{ParameterSet, RuleTemplate};
+
+/// The outcome of evaluating a single rule during [`RulesEngine::execute`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleOutcome {
+    /// Id of the evaluated rule
+    pub rule_id: String,
+    /// Revision of the rule that was evaluated, if it is versioned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<u64>,
+    /// What the rule returned
+    pub result: RuleResult,
+    /// How long the evaluation took
+    pub duration: Duration,
+}
+
+/// The combined outcome of one [`RulesEngine::execute`] call
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExecutionResult {
+    /// Outcome of every evaluated rule, in evaluation order
+    pub outcomes: Vec<RuleOutcome>,
+    /// Ids of the matched rules whose then-actions ran, in firing order
+    #[serde(default)]
+    pub fired: Vec<String>,
+    /// Wall-clock time of the whole execution
+    pub total_duration: Duration,
+}
+
+impl ExecutionResult {
+    /// Ids of the rules that matched, in evaluation order
+    pub fn matched_rules(&self) -> Vec<&str> {
+        self.outcomes
+            .iter()
+            .filter(|outcome| outcome.result.matched)
+            .map(|outcome| outcome.rule_id.as_str())
+            .collect()
+    }
+
+    /// Ids of the rules whose condition was unknown because of missing or
+    /// null facts, in evaluation order
+    pub fn unknown_rules(&self) -> Vec<&str> {
+        self.outcomes
+            .iter()
+            .filter(|outcome| outcome.result.unknown)
+            .map(|outcome| outcome.rule_id.as_str())
+            .collect()
+    }
+
+    /// Ids of the fired rules, in firing order
+    pub fn fired_rules(&self) -> Vec<&str> {
+        self.fired.iter().map(String::as_str).collect()
+    }
+
+    /// Returns true if the rule with the given id was evaluated and matched
+    pub fn is_matched(&self, rule_id: &str) -> bool {
+        self.get(rule_id)
+            .is_some_and(|outcome| outcome.result.matched)
+    }
+
+    /// Returns the outcome of the rule with the given id, if it was evaluated
+    pub fn get(&self, rule_id: &str) -> Option<&RuleOutcome> {
+        self.outcomes
+            .iter()
+            .find(|outcome| outcome.rule_id == rule_id)
+    }
+
+    /// Outputs of all matched rules, keyed by rule id
+    pub fn outputs(&self) -> BTreeMap<&str, &BTreeMap<String, Value>> {
+        self.outcomes
+            .iter()
+            .filter(|outcome| outcome.result.matched && !outcome.result.outputs.is_empty())
+            .map(|outcome| (outcome.rule_id.as_str(), &outcome.result.outputs))
+            .collect()
+    }
+
+    /// All diagnostics produced during execution
+    pub fn diagnostics(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.outcomes
+            .iter()
+            .flat_map(|outcome| &outcome.result.diagnostics)
+    }
+
+    /// Effects produced by rule actions, with the id of the producing rule;
+    /// then-action effects come in firing order, after all else-action effects
+    pub fn effects(&self) -> impl Iterator<Item = (&str, &Effect)> {
+        let unmatched = self
+            .outcomes
+            .iter()
+            .filter(|outcome| !outcome.result.matched);
+        let fired = self.fired.iter().filter_map(|rule_id| self.get(rule_id));
+        unmatched.chain(fired).flat_map(|outcome| {
+            outcome
+                .result
+                .effects
+                .iter()
+                .map(move |effect| (outcome.rule_id.as_str(), effect))
+        })
+    }
+
+    /// Applies all fact effects to `context`, in the order of [`effects`](Self::effects)
+    pub fn apply_to(&self, context: &mut RuleContext) -> Result<()> {
+        context.apply_all(self.effects().map(|(_, effect)| effect))
+    }
+
+    /// Per-rule evaluation times, in evaluation order
+    pub fn timings(&self) -> impl Iterator<Item = (&str, Duration)> {
+        self.outcomes
+            .iter()
+            .map(|outcome| (outcome.rule_id.as_str(), outcome.duration))
+    }
+}
+
+/// Agenda group of rules that do not name one
+pub const DEFAULT_AGENDA_GROUP: &str = "main";
+
+/// Default number of rules a batch must hold before it is evaluated on the
+/// engine's executor
+pub const DEFAULT_PARALLEL_THRESHOLD: usize = 64;
+
+/// Owns a rule set and evaluates it against fact contexts
+pub struct RulesEngine {
+    rules: Vec<Arc<dyn Rule>>,
+    callbacks: CallbackRegistry,
+    strategy: ConflictStrategy,
+    executor: Option<Arc<ParallelExecutor>>,
+    parallel_threshold: usize,
+    schema: Option<Schema>,
+    diagnostics: Vec<Diagnostic>,
+    clock: Arc<dyn Clock>,
+    disabled: HashSet<String>,
+    functions: Arc<FunctionRegistry>,
+    missing: MissingPolicy,
+}
+
+impl Default for RulesEngine {
+    fn default() -> Self {
+        Self {
+            rules: Vec::new(),
+            callbacks: CallbackRegistry::default(),
+            strategy: ConflictStrategy::default(),
+            executor: None,
+            parallel_threshold: DEFAULT_PARALLEL_THRESHOLD,
+            schema: None,
+            diagnostics: Vec::new(),
+            clock: Arc::new(SystemClock),
+            disabled: HashSet::new(),
+            functions: Arc::default(),
+            missing: MissingPolicy::default(),
+        }
+    }
+}
+
+impl RulesEngine {
+    /// Creates an engine with no rules
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how the engine orders matched rules for firing
+    pub fn with_strategy(mut self, strategy: ConflictStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Evaluates rule conditions on `executor` whenever at least `threshold`
+    /// rules are evaluated against the same context; actions still run in
+    /// firing order on the calling thread
+    pub fn with_executor(mut self, executor: Arc<ParallelExecutor>, threshold: usize) -> Self {
+        self.executor = Some(executor);
+        self.parallel_threshold = threshold;
+        self
+    }
+
+    /// Type-checks every rule definition added from now on against `schema`
+    pub fn with_schema(mut self, schema: Schema) -> Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    /// Reads the current time from `clock` when deciding which rules are
+    /// within their validity window and in `now()`; defaults to
+    /// [`SystemClock`]
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self.register_function(now_function(self.clock.clone()));
+        self
+    }
+
+    /// Sets how the rule set treats fact paths that resolve to nothing, for
+    /// every context the engine evaluates; defaults to
+    /// [`MissingPolicy::Unknown`]
+    pub fn with_missing_policy(mut self, policy: MissingPolicy) -> Self {
+        self.missing = policy;
+        self
+    }
+
+    /// Adds a rule; fails if a rule with the same id is already registered
+    pub fn add_rule<R: Rule + 'static>(&mut self, rule: R) -> Result<()> {
+        if self.rule(rule.id()).is_some() {
+            return Err(Error::DuplicateRule(rule.id().to_string()));
+        }
+        self.rules.push(Arc::new(rule));
+        Ok(())
+    }
+
+    /// Compiles a rule definition and adds it, surfacing syntax errors
+    /// immediately; with a schema, type errors fail with [`Error::TypeCheck`]
+    /// and type warnings are kept in [`diagnostics`](Self::diagnostics)
+    pub fn add_definition(&mut self, definition: RuleDefinition) -> Result<()> {
+        self.add_definitions(std::iter::once(definition))
+    }
+
+    /// Compiles and checks every definition as [`add_definition`] does, then
+    /// adds them all; if any of them fails, none is added
+    ///
+    /// [`add_definition`]: Self::add_definition
+    pub fn add_definitions(
+        &mut self,
+        definitions: impl IntoIterator<Item = RuleDefinition>,
+    ) -> Result<()> {
+        let mut rules = Vec::new();
+        let mut warnings = Vec::new();
+        let mut ids = HashSet::new();
+        for definition in definitions {
+            let rule = definition.compile()?;
+            if self.rule(rule.id()).is_some() || !ids.insert(rule.id().to_string()) {
+                return Err(Error::DuplicateRule(rule.id().to_string()));
+            }
+            if let Some(schema) = &self.schema {
+                let (errors, rest): (Vec<_>, Vec<_>) = TypeChecker::new(schema)
+                    .with_functions(&self.functions)
+                    .check_rule(&rule)
+                    .into_iter()
+                    .partition(Diagnostic::is_error);
+                if !errors.is_empty() {
+                    return Err(Error::TypeCheck(errors));
+                }
+                warnings.extend(rest);
+            }
+            rules.push(Arc::new(rule) as Arc<dyn Rule>);
+        }
+        self.rules.extend(rules);
+        self.diagnostics.extend(warnings);
+        Ok(())
+    }
+
+    /// Compiles every row of a decision table and adds the resulting rules;
+    /// if any row fails, none is added
+    pub fn add_table(&mut self, table: &DecisionTable) -> Result<()> {
+        self.add_definitions(table.compile()?)
+    }
+
+    /// Instantiates a rule template once per parameter set and adds the
+    /// resulting rules; if any instance fails, none is added
+    pub fn add_template(&mut self, template: &RuleTemplate, sets: &[ParameterSet]) -> Result<()> {
+        self.add_definitions(template.instantiate(sets)?)
+    }
+
+    /// Resolves the inheritance and fragments of a rule library and adds
+    /// the resulting rules; if any rule fails, none is added
+    pub fn add_library(&mut self, library: &RuleLibrary) -> Result<()> {
+        self.add_definitions(library.resolve()?)
+    }
+
+    /// Non-fatal diagnostics found while adding the current rules
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Registers a callback that `call` actions can invoke by name
+    pub fn register_callback<F>(&mut self, name: impl Into<String>, callback: F)
+    where
+        F: Fn(&RuleContext, &[Value]) -> Result<Value> + Send + Sync + 'static,
+    {
+        self.callbacks.register(name, callback);
+    }
+
+    /// Registers a function that rule expressions can call, replacing any
+    /// function of the same name; functions a context carries itself take
+    /// precedence over the engine's for that context. Register functions
+    /// before adding the rules that call them, so the schema check sees them.
+    pub fn register_function(&mut self, function: Function) {
+        Arc::make_mut(&mut self.functions).register(function);
+    }
+
+    /// The functions registered on the engine
+    pub fn functions(&self) -> &FunctionRegistry {
+        &self.functions
+    }
+
+    /// Removes the rule with the given id, returning it if it was registered
+    pub fn remove_rule(&mut self, rule_id: &str) -> Option<Arc<dyn Rule>> {
+        let index = self.rules.iter().position(|rule| rule.id() == rule_id)?;
+        self.diagnostics
+            .retain(|diagnostic| diagnostic.rule_id.as_deref() != Some(rule_id));
+        self.disabled.remove(rule_id);
+        Some(self.rules.remove(index))
+    }
+
+    /// Returns the rule with the given id
+    pub fn rule(&self, rule_id: &str) -> Option<&dyn Rule> {
+        self.rules
+            .iter()
+            .find(|rule| rule.id() == rule_id)
+            .map(|rule| rule.as_ref())
+    }
+
+    /// Iterates over all registered rules in insertion order
+    pub fn rules(&self) -> impl Iterator<Item = &dyn Rule> {
+        self.rules.iter().map(|rule| rule.as_ref())
+    }
+
+    /// Registered rules matching `selector`, in insertion order
+    pub fn select(&self, selector: &Selector) -> Vec<&dyn Rule> {
+        self.rules().filter(|rule| selector.matches(*rule)).collect()
+    }
+
+    /// Disables the rules matching `selector` so that no execution evaluates
+    /// them, and returns how many were enabled before
+    pub fn disable(&mut self, selector: &Selector) -> usize {
+        let ids: Vec<String> = self
+            .select(selector)
+            .into_iter()
+            .map(|rule| rule.id().to_string())
+            .collect();
+        let before = self.disabled.len();
+        self.disabled.extend(ids);
+        self.disabled.len() - before
+    }
+
+    /// Re-enables the rules matching `selector` and returns how many were
+    /// disabled before
+    pub fn enable(&mut self, selector: &Selector) -> usize {
+        let ids: Vec<String> = self
+            .select(selector)
+            .into_iter()
+            .map(|rule| rule.id().to_string())
+            .collect();
+        ids.iter().filter(|id| self.disabled.remove(*id)).count()
+    }
+
+    /// Returns true if the rule has been disabled with [`disable`](Self::disable)
+    pub fn is_disabled(&self, rule_id: &str) -> bool {
+        self.disabled.contains(rule_id)
+    }
+
+    /// Number of registered rules
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// Returns true if no rules are registered
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Evaluates every active rule against `context`, then fires the matched
+    /// rules in conflict-resolution order; rules that evaluated cleanly but
+    /// did not match run their else-actions instead. Disabled rules and rules
+    /// outside their validity window at the engine clock's current time are
+    /// skipped.
+    pub fn execute(&self, context: &RuleContext) -> Result<ExecutionResult> {
+        self.execute_matching(context, |_| true)
+    }
+
+    /// Like [`execute`](Self::execute), but only for the rules of one agenda
+    /// group; rules without a group belong to [`DEFAULT_AGENDA_GROUP`]
+    pub fn execute_group(&self, group: &str, context: &RuleContext) -> Result<ExecutionResult> {
+        self.execute_matching(context, |rule| {
+            rule.agenda_group().unwrap_or(DEFAULT_AGENDA_GROUP) == group
+        })
+    }
+
+    /// Like [`execute`](Self::execute), but only for the rules matching
+    /// `selector`
+    pub fn execute_selected(&self, selector: &Selector, context: &RuleContext) -> Result<ExecutionResult> {
+        self.execute_matching(context, |rule| selector.matches(rule))
+    }
+
+    /// Explains how one rule decides for `context`: its result, a trace of
+    /// its condition, and why the engine would skip it, if it would. Actions
+    /// are not run. Returns `None` for an unknown rule.
+    pub fn explain(&self, rule_id: &str, context: &RuleContext) -> Option<Explanation> {
+        let rule = self.rule(rule_id)?;
+        let context = &*self.bind(context);
+        Some(Explanation {
+            rule_id: rule.id().to_string(),
+            revision: rule.revision(),
+            skipped: self.skip_reason(rule, self.clock.now()),
+            result: rule.evaluate(context),
+            trace: rule
+                .condition()
+                .map(|condition| Tracer::new(context).trace(condition)),
+        })
+    }
+
+    /// Evaluates the active rules in dependency order: each stratum of the
+    /// [`dependency_graph`](Self::dependency_graph) is executed like
+    /// [`execute`](Self::execute) and its fact effects are applied to
+    /// `context` before the next stratum runs. Fails with
+    /// [`Error::DependencyCycle`] if the rules depend on each other in a cycle.
+    pub fn execute_stratified(&self, context: &mut RuleContext) -> Result<ExecutionResult> {
+        let started = Instant::now();
+        let graph = self.dependency_graph();
+        let positions: HashMap<&str, usize> = self
+            .rules
+            .iter()
+            .enumerate()
+            .map(|(order, rule)| (rule.id(), order))
+            .collect();
+
+        let mut result = ExecutionResult::default();
+        for stratum in graph.strata()? {
+            let orders: Vec<usize> = stratum.iter().map(|id| positions[id]).collect();
+            let batch = self.run_batch(&orders, context);
+            batch.apply_to(context)?;
+            result.outcomes.extend(batch.outcomes);
+            result.fired.extend(batch.fired);
+        }
+        result.total_duration = started.elapsed();
+        Ok(self.record(result))
+    }
+
+    /// The read/write dependency graph of the active rules that are valid
+    /// now
+    pub fn dependency_graph(&self) -> DependencyGraph {
+        let now = self.clock.now();
+        DependencyGraph::new(
+            self.rules
+                .iter()
+                .filter(|rule| self.is_live(rule.as_ref(), now))
+                .map(|rule| rule.as_ref()),
+        )
+    }
+
+    fn execute_matching(
+        &self,
+        context: &RuleContext,
+        selected: impl Fn(&dyn Rule) -> bool,
+    ) -> Result<ExecutionResult> {
+        let started = Instant::now();
+        let now = self.clock.now();
+        let orders: Vec<usize> = (0..self.rules.len())
+            .filter(|&order| {
+                let rule = self.rules[order].as_ref();
+                self.is_live(rule, now) && selected(rule)
+            })
+            .collect();
+        let mut result = self.run_batch(&orders, context);
+        result.total_duration = started.elapsed();
+        Ok(self.record(result))
+    }
+
+    /// Whether `rule` is active, enabled and valid at `now`
+    fn is_live(&self, rule: &dyn Rule, now: DateTime<Utc>) -> bool {
+        self.skip_reason(rule, now).is_none()
+    }
+
+    fn skip_reason(&self, rule: &dyn Rule, now: DateTime<Utc>) -> Option<SkipReason> {
+        if !rule.is_active() {
+            Some(SkipReason::Inactive)
+        } else if self.disabled.contains(rule.id()) {
+            Some(SkipReason::Disabled)
+        } else if !rule.is_valid_at(now) {
+            Some(SkipReason::OutsideValidity)
+        } else {
+            None
+        }
+    }
+
+    /// Evaluates the rules at `orders` against `context`, then fires the
+    /// matched ones; `total_duration` is left for the caller to fill in
+    fn run_batch(&self, orders: &[usize], context: &RuleContext) -> ExecutionResult {
+        let context = &*self.bind(context);
+        let mut agenda = Agenda::with_strategy(self.strategy.clone());
+        let mut positions = HashMap::new();
+        let mut outcomes = Vec::new();
+        for (&order, (mut result, duration)) in orders.iter().zip(self.evaluate_all(orders, context)) {
+            let rule = self.rules[order].as_ref();
+            let consequences_started = Instant::now();
+            if result.matched && !result.has_errors() {
+                agenda.push(order, rule);
+                positions.insert(order, outcomes.len());
+            } else if !result.has_errors() && !result.unknown {
+                self.run_consequences(rule, &mut result, context);
+            }
+            outcomes.push(RuleOutcome {
+                rule_id: rule.id().to_string(),
+                revision: rule.revision(),
+                result,
+                duration: duration + consequences_started.elapsed(),
+            });
+        }
+
+        let mut fired = Vec::new();
+        while let Some(activation) = agenda.pop() {
+            let outcome = &mut outcomes[positions[&activation.order]];
+            let fire_started = Instant::now();
+            self.run_consequences(
+                self.rules[activation.order].as_ref(),
+                &mut outcome.result,
+                context,
+            );
+            outcome.duration += fire_started.elapsed();
+            fired.push(activation.rule_id);
+        }
+
+        ExecutionResult {
+            outcomes,
+            fired,
+            total_duration: Duration::ZERO,
+        }
+    }
+
+    /// Evaluates a flow condition with the engine's functions, clock and
+    /// missing-fact policy
+    fn holds(&self, condition: &Expr, context: &RuleContext) -> Result<bool> {
+        Evaluator::new(&self.bind(context)).evaluate_bool(condition)
+    }
+
+    /// Applies the engine's missing-fact policy to `context` and attaches the
+    /// engine's functions, merged under any the context carries itself
+    fn bind<'c>(&self, context: &'c RuleContext) -> Cow<'c, RuleContext> {
+        let functions = match context.functions() {
+            _ if self.functions.is_empty() => None,
+            None => Some(self.functions.clone()),
+            Some(own) => {
+                let mut merged = FunctionRegistry::clone(&self.functions);
+                merged.extend(own);
+                Some(Arc::new(merged))
+            }
+        };
+        if functions.is_none() && context.missing_policy() == self.missing {
+            return Cow::Borrowed(context);
+        }
+        let mut context = context.clone().with_missing_policy(self.missing);
+        if let Some(functions) = functions {
+            context = context.with_functions(functions);
+        }
+        Cow::Owned(context)
+    }
+
+    /// Evaluates the rules at `orders`, on the executor if the batch is large
+    /// enough, returning results in the same order
+    fn evaluate_all(&self, orders: &[usize], context: &RuleContext) -> Vec<(RuleResult, Duration)> {
+        let evaluate = |rule: &Arc<dyn Rule>, context: &RuleContext| {
+            let started = Instant::now();
+            (rule.evaluate(context), started.elapsed())
+        };
+        let rules = orders.iter().map(|&order| &self.rules[order]);
+        match &self.executor {
+            Some(executor) if orders.len() >= self.parallel_threshold => {
+                executor.map_chunks(rules.cloned().collect(), context, evaluate)
+            }
+            _ => rules.map(|rule| evaluate(rule, context)).collect(),
+        }
+    }
+
+    /// Records execution metrics, if enabled
+    fn record(&self, result: ExecutionResult) -> ExecutionResult {
+        #[cfg(feature = "metrics")]
+        {
+            crate::metrics::record_timing("rules.execute", result.total_duration, None);
+            crate::metrics::increment_counter(
+                "rules.evaluated",
+                result.outcomes.len() as u64,
+                None,
+            );
+            crate::metrics::increment_counter(
+                "rules.matched",
+                result.matched_rules().len() as u64,
+                None,
+            );
+        }
+        result
+    }
+
+    /// Runs a rule's then- or else-actions, recording their effects or failure
+    fn run_consequences(&self, rule: &dyn Rule, result: &mut RuleResult, context: &RuleContext) {
+        match rule.consequences(result.matched, context, &self.callbacks) {
+            Ok(effects) => result.effects = effects,
+            Err(err) => {
+                let mut diagnostic = error_diagnostic(rule.id(), &err);
+                diagnostic.code = "action-error".to_string();
+                result.diagnostics.push(diagnostic);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::Action;
+
+
+    struct Discount;
+
+    impl Rule for Discount {
+        fn evaluate(&self, context: &RuleContext) -> RuleResult {
+            let total = context.get("total").and_then(Value::as_f64).unwrap_or(0.0);
+            RuleResult::new(total > 100.0).with_output("discount", total * 0.1)
+        }
+
+        fn id(&self) -> &str {
+            "discount"
+        }
+    }
+
+    #[test]
+    fn test_execute_reports_matches_and_outputs() {
+        let mut engine = RulesEngine::new();
+        engine
+            .add_definition(RuleDefinition::new("big_order").with_expression("total > 100"))
+            .unwrap();
+        engine
+            .add_definition(RuleDefinition::new("small_order").with_expression("total <= 100"))
+            .unwrap();
+        engine.add_rule(Discount).unwrap();
+
+        let results = engine
+            .execute(&RuleContext::new().with_fact("total", 200))
+            .unwrap();
+        assert_eq!(results.matched_rules(), ["big_order", "discount"]);
+        assert!(!results.is_matched("small_order"));
+        assert_eq!(
+            results.outputs()["discount"]["discount"],
+            Value::Float(20.0)
+        );
+        assert_eq!(results.timings().count(), 3);
+    }
+
+    #[test]
+    fn test_inactive_rules_are_skipped() {
+        let mut engine = RulesEngine::new();
+        let mut inactive = RuleDefinition::new("inactive").with_expression("true");
+        inactive.is_active = false;
+        engine.add_definition(inactive).unwrap();
+        engine.add_definition(RuleDefinition::new("active").with_expression("true")).unwrap();
+
+        let results = engine.execute(&RuleContext::new()).unwrap();
+        assert_eq!(results.outcomes.len(), 1);
+        assert!(results.get("inactive").is_none());
+    }
+
+    #[test]
+    fn test_actions_produce_effects() {
+        let mut engine = RulesEngine::new();
+        engine.register_callback("notify", |_, args| Ok(args[0].clone()));
+        engine
+            .add_definition(
+                RuleDefinition::new("gold").with_expression("total > 100")
+                    .then(Action::set("customer.tier", "'gold'"))
+                    .then(Action::call("notify", vec!["total".into()])),
+            )
+            .unwrap();
+        engine
+            .add_definition(
+                RuleDefinition::new("broken")
+                    .with_expression("true")
+                    .then(Action::call("missing", vec![])),
+            )
+            .unwrap();
+
+        let mut context = RuleContext::new().with_fact("total", 200);
+        let results = engine.execute(&context).unwrap();
+        assert_eq!(results.effects().filter(|(id, _)| *id == "gold").count(), 2);
+        assert_eq!(results.diagnostics().next().unwrap().code, "action-error");
+
+        results.apply_to(&mut context).unwrap();
+        assert_eq!(
+            context.get_path("customer.tier").unwrap(),
+            Some(&Value::from("gold"))
+        );
+    }
+
+    #[test]
+    fn test_fired_rules_follow_strategy() {
+        let rules = [
+            ("plain", "total > 0", 0),
+            ("urgent", "total > 0 && rush", 10),
+            ("miss", "total < 0", 99),
+        ];
+        let build = |strategy| {
+            let mut engine = RulesEngine::new().with_strategy(strategy);
+            for (id, expression, priority) in rules {
+                engine
+                    .add_definition(
+                        RuleDefinition::new(id).with_expression(expression).with_priority(priority),
+                    )
+                    .unwrap();
+            }
+            engine
+        };
+        let context = RuleContext::new()
+            .with_fact("total", 5)
+            .with_fact("rush", true);
+
+        let by_priority = build(ConflictStrategy::Priority).execute(&context).unwrap();
+        assert_eq!(by_priority.fired_rules(), ["urgent", "plain"]);
+        assert_eq!(by_priority.matched_rules(), ["plain", "urgent"]);
+        let by_order = build(ConflictStrategy::DefinitionOrder)
+            .execute(&context)
+            .unwrap();
+        assert_eq!(by_order.fired_rules(), ["plain", "urgent"]);
+    }
+
+    #[test]
+    fn test_groups_partition_rules() {
+        let mut engine = RulesEngine::new();
+        engine.add_definition(RuleDefinition::new("plain").with_expression("true")).unwrap();
+        engine
+            .add_definition(
+                RuleDefinition::new("gold")
+                    .with_expression("true")
+                    .in_agenda_group("pricing")
+                    .in_activation_group("tier"),
+            )
+            .unwrap();
+        engine
+            .add_definition(
+                RuleDefinition::new("silver")
+                    .with_expression("true")
+                    .in_agenda_group("pricing")
+                    .in_activation_group("tier"),
+            )
+            .unwrap();
+
+        let pricing = engine.execute_group("pricing", &RuleContext::new()).unwrap();
+        assert_eq!(pricing.matched_rules(), ["gold", "silver"]);
+        assert_eq!(pricing.fired_rules(), ["gold"]);
+        let main = engine.execute_group(DEFAULT_AGENDA_GROUP, &RuleContext::new()).unwrap();
+        assert_eq!(main.fired_rules(), ["plain"]);
+    }
+
+    #[test]
+    fn test_stratified_execution_sees_earlier_effects() {
+        let executor = Arc::new(ParallelExecutor::with_config(crate::parallel::ParallelConfig {
+            num_workers: 2,
+            queue_size: 64,
+            enable_work_stealing: true,
+        }));
+        let mut engine = RulesEngine::new().with_executor(executor, 2);
+        engine
+            .add_definition(
+                RuleDefinition::new("free_shipping").with_expression("customer.tier == 'gold'")
+                    .then(Action::set("order.shipping", "0")),
+            )
+            .unwrap();
+        engine
+            .add_definition(
+                RuleDefinition::new("gold")
+                    .with_expression("customer.spend > 1000")
+                    .then(Action::set("customer.tier", "'gold'")),
+            )
+            .unwrap();
+        engine
+            .add_definition(RuleDefinition::new("adult").with_expression("customer.age >= 18"))
+            .unwrap();
+
+        let customer: Value = [("spend", 1500), ("age", 30)].into_iter().collect();
+        let mut context = RuleContext::new().with_fact("customer", customer);
+        assert_eq!(engine.execute(&context).unwrap().fired_rules(), ["gold", "adult"]);
+
+        let result = engine.execute_stratified(&mut context).unwrap();
+        assert_eq!(result.fired_rules(), ["gold", "adult", "free_shipping"]);
+        assert_eq!(context.get_path("order.shipping").unwrap(), Some(&Value::Int(0)));
+
+        engine
+            .add_definition(
+                RuleDefinition::new("downgrade")
+                    .with_expression("order.shipping == 0")
+                    .then(Action::set("customer.spend", "0")),
+            )
+            .unwrap();
+        assert!(matches!(
+            engine.execute_stratified(&mut context),
+            Err(Error::DependencyCycle(_))
+        ));
+    }
+
+    #[test]
+    fn test_schema_checks_rules_on_load() {
+        use crate::analysis::{Field, Type};
+
+        let schema = Schema::new().with_fact(
+            "customer",
+            Type::map([
+                ("age", Field::new(Type::Int)),
+                ("tier", Field::new(Type::String).nullable()),
+            ]),
+        );
+        let mut engine = RulesEngine::new().with_schema(schema);
+        assert!(matches!(
+            engine.add_definition(
+                RuleDefinition::new("bad").with_expression("customer.age > 'adult'")
+            ),
+            Err(Error::TypeCheck(diagnostics)) if diagnostics[0].code == "type-mismatch"
+        ));
+        engine
+            .add_definition(RuleDefinition::new("tiered").with_expression("customer.tier > 'a'"))
+            .unwrap();
+        assert_eq!(engine.len(), 1);
+        assert_eq!(engine.diagnostics()[0].code, "nullable-operand");
+        engine.remove_rule("tiered");
+        assert!(engine.diagnostics().is_empty());
+    }
+
+    #[test]
+    fn test_rules_outside_validity_window_are_skipped() {
+        use crate::clock::ManualClock;
+        use crate::rule::Calendar;
+        use chrono::{TimeDelta, TimeZone, Utc, Weekday};
+
+        let launch = Utc.with_ymd_and_hms(2024, 11, 29, 0, 0, 0).unwrap();
+        let clock = Arc::new(ManualClock::new(launch - TimeDelta::days(1)));
+        let mut engine = RulesEngine::new().with_clock(clock.clone());
+        engine
+            .add_definition(
+                RuleDefinition::new("black_friday").with_expression("true")
+                    .valid_from(launch)
+                    .valid_until(launch + TimeDelta::days(4)),
+            )
+            .unwrap();
+        engine
+            .add_definition(
+                RuleDefinition::new("weekend").with_expression("true")
+                    .with_calendar(Calendar::new().on_weekdays([Weekday::Sat, Weekday::Sun])),
+            )
+            .unwrap();
+        let context = RuleContext::new();
+
+        assert!(engine.execute(&context).unwrap().outcomes.is_empty());
+        clock.advance(TimeDelta::days(1));
+        assert_eq!(engine.execute(&context).unwrap().matched_rules(), ["black_friday"]);
+        clock.advance(TimeDelta::days(1));
+        assert_eq!(
+            engine.execute(&context).unwrap().matched_rules(),
+            ["black_friday", "weekend"]
+        );
+        clock.set(launch + TimeDelta::days(4));
+        assert!(engine.execute(&context).unwrap().outcomes.is_empty());
+        assert!(engine.dependency_graph().is_empty());
+    }
+
+    #[test]
+    fn test_now_follows_the_engine_clock() {
+        use crate::clock::ManualClock;
+        use chrono::{TimeDelta, TimeZone, Utc};
+
+        let opened = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let clock = Arc::new(ManualClock::new(opened + TimeDelta::days(30)));
+        let mut engine = RulesEngine::new().with_clock(clock.clone());
+        engine
+            .add_definition(
+                RuleDefinition::new("established")
+                    .with_expression("now() - account.opened > days(90)"),
+            )
+            .unwrap();
+        let context = RuleContext::new().with_fact(
+            "account",
+            [("opened", Value::from(opened))].into_iter().collect::<Value>(),
+        );
+
+        assert!(engine.execute(&context).unwrap().matched_rules().is_empty());
+        clock.advance(TimeDelta::days(61));
+        assert_eq!(engine.execute(&context).unwrap().matched_rules(), ["established"]);
+    }
+
+    #[test]
+    fn test_unknown_conditions_are_reported_separately() {
+        let rule = || {
+            RuleDefinition::new("vip")
+                .with_expression("customer.tier == 'gold' || customer.spend > 1000")
+                .otherwise(crate::rule::Action::message("not a vip"))
+        };
+        let context = RuleContext::new().with_fact(
+            "customer",
+            [("tier", "silver")].into_iter().collect::<Value>(),
+        );
+
+        let mut engine = RulesEngine::new();
+        engine.add_definition(rule()).unwrap();
+        let result = engine.execute(&context).unwrap();
+        assert!(result.matched_rules().is_empty());
+        assert_eq!(result.unknown_rules(), ["vip"]);
+        // Neither branch runs for an unknown condition
+        assert_eq!(result.effects().count(), 0);
+        assert!(engine.explain("vip", &context).unwrap().report().contains("unknown"));
+
+        let mut engine = RulesEngine::new().with_missing_policy(MissingPolicy::False);
+        engine.add_definition(rule()).unwrap();
+        let result = engine.execute(&context).unwrap();
+        assert!(result.unknown_rules().is_empty());
+        assert_eq!(result.effects().count(), 1);
+
+        let mut engine = RulesEngine::new().with_missing_policy(MissingPolicy::Error);
+        engine.add_definition(rule()).unwrap();
+        let result = engine.execute(&context).unwrap();
+        assert_eq!(
+            result.diagnostics().next().unwrap().message,
+            "missing fact `customer.spend`"
+        );
+
+        // Built-ins propagate a missing argument like the operators do
+        let mut engine = RulesEngine::new();
+        engine
+            .add_definition(
+                RuleDefinition::new("email").with_expression("starts_with(customer.email, 'a')"),
+            )
+            .unwrap();
+        let result = engine.execute(&context).unwrap();
+        assert_eq!(result.unknown_rules(), ["email"]);
+        assert_eq!(result.diagnostics().count(), 0);
+    }
+
+    #[test]
+    fn test_selectors_pick_and_disable_rules() {
+        let mut engine = RulesEngine::new();
+        for (id, tag, owner) in [
+            ("velocity", "fraud", "team-a"),
+            ("geo", "fraud", "team-b"),
+            ("promo", "pricing", "team-b"),
+        ] {
+            engine
+                .add_definition(
+                    RuleDefinition::new(id)
+                        .with_expression("true")
+                        .with_tag(tag)
+                        .with_metadata("owner", owner),
+                )
+                .unwrap();
+        }
+        let fraud = Selector::parse("tag:fraud").unwrap();
+        let context = RuleContext::new();
+
+        let ids = |rules: Vec<&dyn Rule>| {
+            rules.iter().map(|rule| rule.id().to_string()).collect::<Vec<_>>()
+        };
+        assert_eq!(ids(engine.select(&fraud)), ["velocity", "geo"]);
+        assert_eq!(
+            engine
+                .execute_selected(&Selector::parse("owner=team-b").unwrap(), &context)
+                .unwrap()
+                .matched_rules(),
+            ["geo", "promo"]
+        );
+
+        assert_eq!(engine.disable(&fraud), 2);
+        assert_eq!(engine.disable(&fraud), 0);
+        assert!(engine.is_disabled("geo"));
+        assert_eq!(engine.execute(&context).unwrap().matched_rules(), ["promo"]);
+        assert_eq!(engine.enable(&Selector::parse("owner=team-a").unwrap()), 1);
+        assert_eq!(engine.execute(&context).unwrap().matched_rules(), ["velocity", "promo"]);
+    }
+
+    #[test]
+    fn test_explain_traces_rule_decisions() {
+        let mut engine = RulesEngine::new();
+        engine
+            .add_definition(
+                RuleDefinition::new("big_order")
+                    .with_expression("total > 100 && (vip || total > 500)"),
+            )
+            .unwrap();
+        engine.add_rule(Discount).unwrap();
+        engine.disable(&Selector::parse("id=discount").unwrap());
+        let context = RuleContext::new().with_fact("total", 200).with_fact("vip", true);
+
+        let explanation = engine.explain("big_order", &context).unwrap();
+        assert!(explanation.matched());
+        assert_eq!(explanation.skipped, None);
+        let trace = explanation.trace.as_ref().unwrap();
+        assert_eq!(trace.skipped()[0].expression, "total > 500");
+        assert_eq!(trace.facts.len(), 2);
+        assert!(explanation
+            .report()
+            .starts_with("rule `big_order` matched\ntotal > 100 && (vip || total > 500) => true\n"));
+
+        let native = engine.explain("discount", &context).unwrap();
+        assert_eq!(native.skipped, Some(SkipReason::Disabled));
+        assert_eq!(
+            native.report(),
+            "rule `discount` would have matched but was skipped: it is disabled\n\
+             output discount = 20\n\
+             native rule; no condition trace\n"
+        );
+        assert!(engine.explain("unknown", &context).is_none());
+    }
+
+    #[test]
+    fn test_registered_functions_are_callable_from_rules() {
+        use crate::analysis::{Field, Type};
+        use crate::expr::PathSegment;
+
+        let customer = Type::map([
+            ("age", Field::new(Type::Int)),
+            ("risk", Field::new(Type::Int).nullable()),
+        ]);
+        let mut engine =
+            RulesEngine::new().with_schema(Schema::new().with_fact("customer", customer.clone()));
+        engine.register_function(
+            Function::new("risk_score", [customer], Type::Int, |args| {
+                let age = args[0].get_path(&[PathSegment::Key("age".into())]);
+                let young = age.and_then(Value::as_f64).unwrap_or(0.0) < 25.0;
+                Ok(Value::Int(if young { 80 } else { 20 }))
+            })
+            .pure()
+            .deterministic(),
+        );
+        engine
+            .add_definition(
+                RuleDefinition::new("high_risk").with_expression("risk_score(customer) > 50")
+                    .then(Action::set("customer.risk", "risk_score(customer)")),
+            )
+            .unwrap();
+        assert!(matches!(
+            engine
+                .add_definition(RuleDefinition::new("typo").with_expression("risk(customer) > 50")),
+            Err(Error::TypeCheck(diagnostics)) if diagnostics[0].code == "unknown-function"
+        ));
+
+        let young = RuleContext::new()
+            .with_fact("customer", [("age", 19)].into_iter().collect::<Value>());
+        let results = engine.execute(&young).unwrap();
+        assert_eq!(results.fired_rules(), ["high_risk"]);
+        let mut context = young.clone();
+        results.apply_to(&mut context).unwrap();
+        assert_eq!(context.get_path("customer.risk").unwrap(), Some(&Value::Int(80)));
+        let trace = engine.explain("high_risk", &young).unwrap().trace.unwrap();
+        assert_eq!(trace.root.children[0].expression, "risk_score(customer)");
+        assert_eq!(trace.root.children[0].value(), Some(&Value::Int(80)));
+
+        // A context's own functions are merged with the engine's, and win by name
+        let mut own = FunctionRegistry::new();
+        own.register(Function::new("bonus", [Type::Int], Type::Int, |args| Ok(args[0].clone())));
+        let results = engine.execute(&young.clone().with_functions(own.clone())).unwrap();
+        assert_eq!(results.fired_rules(), ["high_risk"]);
+        own.register(Function::new("risk_score", [Type::Any], Type::Int, |_| Ok(Value::Int(0))));
+        let results = engine.execute(&young.with_functions(own)).unwrap();
+        assert!(results.fired_rules().is_empty());
+    }
+
+    #[test]
+    fn test_add_and_remove_rules() {
+        let mut engine = RulesEngine::new();
+        engine.add_definition(RuleDefinition::new("r1").with_expression("true")).unwrap();
+        assert!(matches!(
+            engine.add_definition(RuleDefinition::new("r1").with_expression("false")),
+            Err(Error::DuplicateRule(id)) if id == "r1"
+        ));
+        assert!(matches!(
+            engine.add_definition(RuleDefinition::new("r2").with_expression("true &&")),
+            Err(Error::Syntax { .. })
+        ));
+        assert_eq!(engine.len(), 1);
+
+        let template = RuleTemplate::new("limit", "order.total > {{limit}}").with_parameter(
+            "limit",
+            crate::template::Parameter::new(crate::analysis::Type::Int),
+        );
+        let sets = template
+            .parameter_sets_from_yaml("[{limit: 10}, {limit: 20}]")
+            .unwrap();
+        engine.add_definition(RuleDefinition::new("limit_2").with_expression("true")).unwrap();
+        assert!(matches!(
+            engine.add_template(&template, &sets),
+            Err(Error::DuplicateRule(id)) if id == "limit_2"
+        ));
+        assert!(matches!(
+            engine.add_definitions([
+                RuleDefinition::new("r2").with_expression("true"),
+                RuleDefinition::new("r3").with_expression("true &&"),
+            ]),
+            Err(Error::Syntax { .. })
+        ));
+        assert!(matches!(
+            engine.add_definitions([
+                RuleDefinition::new("r2").with_expression("true"),
+                RuleDefinition::new("r2").with_expression("false"),
+            ]),
+            Err(Error::DuplicateRule(id)) if id == "r2"
+        ));
+        assert_eq!(engine.len(), 2);
+        assert!(engine.rule("limit_1").is_none() && engine.rule("r2").is_none());
+        engine.remove_rule("limit_2");
+        engine.add_template(&template, &sets).unwrap();
+        assert_eq!(engine.len(), 3);
+        engine.remove_rule("limit_1");
+        engine.remove_rule("limit_2");
+        assert!(engine.remove_rule("r1").is_some());
+        assert!(engine.remove_rule("r1").is_none());
+        assert!(engine.is_empty());
+    }
+}
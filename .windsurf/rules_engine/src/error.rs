@@ -4,6 +4,9 @@ use std::fmt;
 use std::error::Error as StdError;
 use thiserror::Error;
 
+use crate::diagnostic::Diagnostic;
+use crate::expr::Span;
+
 /// FFI error type (dummy implementation when cpp feature is disabled)
 #[cfg(not(feature = "cpp"))]
 #[derive(Debug)]
@@ -45,6 +48,70 @@ pub enum Error {
     /// Executor queue is full
     #[error("Executor queue is full")]
     ExecutorFull,
+
+    /// A rule with the same id is already registered
+    #[error("Duplicate rule id: {0}")]
+    DuplicateRule(String),
+
+    /// Forward chaining kept firing rules without reaching quiescence
+    #[error("Inference did not reach quiescence within {0} rule firings")]
+    InferenceLimit(usize),
+
+    /// A rule failed type checking against the engine's schema
+    #[error("Type checking failed: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+    TypeCheck(Vec<Diagnostic>),
+
+    /// Rules depend on each other's writes in a cycle
+    #[error("Rule dependency cycle between {}", .0.join(", "))]
+    DependencyCycle(Vec<String>),
+
+    /// Rules extend each other in a cycle
+    #[error("Rule inheritance cycle: {}", .0.join(" -> "))]
+    InheritanceCycle(Vec<String>),
+
+    /// A rule flow loop kept repeating past its iteration limit
+    #[error("Rule flow loop did not finish within {0} iterations")]
+    LoopLimit(usize),
+
+    /// A fact path could not be written or removed
+    #[error("Invalid fact path `{path}`: {reason}")]
+    InvalidPath {
+        /// The offending path
+        path: String,
+        /// Why the path could not be used
+        reason: String,
+    },
+
+    /// Rule expression could not be parsed
+    #[error("Syntax error at line {line}, column {column}: {message}")]
+    Syntax {
+        /// Description of what went wrong
+        message: String,
+        /// Location of the offending token in the expression source
+        span: Span,
+        /// 1-based line of the offending token
+        line: usize,
+        /// 1-based column of the offending token
+        column: usize,
+    },
+
+    /// Rule expression has no SQL translation
+    #[error("Cannot translate to SQL at {span}: {message}")]
+    SqlTranslation {
+        /// Which construct cannot be translated, and why
+        message: String,
+        /// Location of the offending sub-expression
+        span: Span,
+    },
+
+    /// Rule expression failed at runtime
+    #[error("Evaluation error at {span}: {message}")]
+    Evaluation {
+        /// Description of what went wrong
+        message: String,
+        /// Location of the failing sub-expression
+        span: Span,
+    },
 }
 
 impl From<String> for Error {
@@ -0,0 +1,395 @@
+//! Typed abstract syntax tree for rule expressions
+
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Write};
+
+/// Byte range of a node in the original expression source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct Span {
+    /// Byte offset of the first character
+    pub start: usize,
+    /// Byte offset one past the last character
+    pub end: usize,
+}
+
+impl Span {
+    /// Creates a new span covering `start..end`
+    pub const fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// Returns the smallest span covering both `self` and `other`
+    pub fn merge(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+
+    /// Returns the 1-based line and column of the span start within `source`
+    pub fn line_col(&self, source: &str) -> (usize, usize) {
+        let prefix = &source[..self.start.min(source.len())];
+        let line = prefix.matches('\n').count() + 1;
+        let column = prefix
+            .rfind('\n')
+            .map_or(prefix.chars().count(), |nl| prefix[nl + 1..].chars().count())
+            + 1;
+        (line, column)
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
+/// A parsed expression node together with its source span
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Expr {
+    /// The kind of expression
+    pub kind: ExprKind,
+    /// Where the expression appears in the source
+    pub span: Span,
+}
+
+impl Expr {
+    /// Creates a new expression node
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+
+    /// Visits every field path referenced by this expression, in source order
+    pub fn walk_paths<'a>(&'a self, visit: &mut impl FnMut(&'a Path)) {
+        match &self.kind {
+            ExprKind::Literal(_) => {}
+            ExprKind::Path(path) => visit(path),
+            ExprKind::List(items) | ExprKind::Call { args: items, .. } => {
+                items.iter().for_each(|item| item.walk_paths(visit))
+            }
+            ExprKind::Unary { operand, .. } => operand.walk_paths(visit),
+            ExprKind::Binary { lhs, rhs, .. } => {
+                lhs.walk_paths(visit);
+                rhs.walk_paths(visit);
+            }
+        }
+    }
+
+    /// Returns every field path referenced by this expression, in source order
+    pub fn paths(&self) -> Vec<&Path> {
+        let mut paths = Vec::new();
+        self.walk_paths(&mut |path| paths.push(path));
+        paths
+    }
+
+    /// Splits a chain of top-level `&&` into its operands, left to right;
+    /// any other expression is a single conjunct
+    pub fn conjuncts(&self) -> Vec<&Expr> {
+        match &self.kind {
+            ExprKind::Binary { op: BinaryOp::And, lhs, rhs } => {
+                let mut conjuncts = lhs.conjuncts();
+                conjuncts.extend(rhs.conjuncts());
+                conjuncts
+            }
+            _ => vec![self],
+        }
+    }
+}
+
+/// The different kinds of expression nodes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ExprKind {
+    /// A constant value such as `42`, `"gold"` or `true`
+    Literal(Literal),
+    /// A reference to a fact such as `customer.address.city`
+    Path(Path),
+    /// A list literal such as `[1, 2, 3]`
+    List(Vec<Expr>),
+    /// A call to a registered function such as `risk_score(customer)`
+    Call {
+        /// Name the function is registered under
+        function: String,
+        /// Argument expressions, in order
+        args: Vec<Expr>,
+    },
+    /// A prefix operator applied to a single operand
+    Unary {
+        /// The operator
+        op: UnaryOp,
+        /// The operand
+        operand: Box<Expr>,
+    },
+    /// An infix operator applied to two operands
+    Binary {
+        /// The operator
+        op: BinaryOp,
+        /// Left-hand operand
+        lhs: Box<Expr>,
+        /// Right-hand operand
+        rhs: Box<Expr>,
+    },
+}
+
+/// Constant values that can appear literally in an expression
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Literal {
+    /// The `null` literal
+    Null,
+    /// `true` or `false`
+    Bool(bool),
+    /// A 64-bit signed integer
+    Int(i64),
+    /// A 64-bit floating point number
+    Float(f64),
+    /// A quoted string
+    String(String),
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Literal::Null => f.write_str("null"),
+            Literal::Bool(b) => write!(f, "{}", b),
+            Literal::Int(i) => write!(f, "{}", i),
+            Literal::Float(x) if x.fract() == 0.0 && x.is_finite() => write!(f, "{:.1}", x),
+            Literal::Float(x) => write!(f, "{}", x),
+            Literal::String(s) => write_quoted(f, s),
+        }
+    }
+}
+
+/// Writes `s` as a double-quoted string literal, escaping only what the
+/// lexer can read back
+fn write_quoted(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\t' => f.write_str("\\t")?,
+            '\r' => f.write_str("\\r")?,
+            '\0' => f.write_str("\\0")?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+/// A single step in a field path
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum PathSegment {
+    /// A named map key, e.g. `address` in `customer.address`
+    Key(String),
+    /// A list index, e.g. `0` in `orders[0]`
+    Index(usize),
+}
+
+/// A dotted reference into the fact context, e.g. `customer.orders[0].total`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Path {
+    /// The segments of the path, starting at the root fact
+    pub segments: Vec<PathSegment>,
+}
+
+impl Path {
+    /// Creates a path from its segments
+    pub fn new(segments: Vec<PathSegment>) -> Self {
+        Self { segments }
+    }
+
+    /// Returns the name of the root fact this path starts at
+    pub fn root(&self) -> Option<&str> {
+        match self.segments.first() {
+            Some(PathSegment::Key(key)) => Some(key),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, segment) in self.segments.iter().enumerate() {
+            match segment {
+                PathSegment::Key(key) if i == 0 => f.write_str(key)?,
+                PathSegment::Key(key) => write!(f, ".{}", key)?,
+                PathSegment::Index(index) => write!(f, "[{}]", index)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Prefix operators
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum UnaryOp {
+    /// Logical negation (`!` or `not`)
+    Not,
+    /// Arithmetic negation (`-`)
+    Neg,
+}
+
+impl fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            UnaryOp::Not => "!",
+            UnaryOp::Neg => "-",
+        })
+    }
+}
+
+/// Infix operators, grouped by precedence level
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BinaryOp {
+    /// Logical disjunction (`||` or `or`)
+    Or,
+    /// Logical conjunction (`&&` or `and`)
+    And,
+    /// Equality (`==`)
+    Eq,
+    /// Inequality (`!=`)
+    Ne,
+    /// Less than (`<`)
+    Lt,
+    /// Less than or equal (`<=`)
+    Le,
+    /// Greater than (`>`)
+    Gt,
+    /// Greater than or equal (`>=`)
+    Ge,
+    /// Membership (`in`)
+    In,
+    /// Negated membership (`not in`)
+    NotIn,
+    /// Addition or concatenation (`+`)
+    Add,
+    /// Subtraction (`-`)
+    Sub,
+    /// Multiplication (`*`)
+    Mul,
+    /// Division (`/`)
+    Div,
+    /// Remainder (`%`)
+    Rem,
+}
+
+impl BinaryOp {
+    /// Binding strength of the operator; higher binds tighter
+    pub fn precedence(self) -> u8 {
+        match self {
+            BinaryOp::Or => 1,
+            BinaryOp::And => 2,
+            BinaryOp::Eq
+            | BinaryOp::Ne
+            | BinaryOp::Lt
+            | BinaryOp::Le
+            | BinaryOp::Gt
+            | BinaryOp::Ge
+            | BinaryOp::In
+            | BinaryOp::NotIn => 4,
+            BinaryOp::Add | BinaryOp::Sub => 5,
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => 6,
+        }
+    }
+
+    /// Whether this is one of the comparison or membership operators
+    pub fn is_comparison(self) -> bool {
+        self.precedence() == 4
+    }
+
+    /// Whether this is one of the logical connectives
+    pub fn is_logical(self) -> bool {
+        matches!(self, BinaryOp::And | BinaryOp::Or)
+    }
+
+    /// Whether this is one of the arithmetic operators
+    pub fn is_arithmetic(self) -> bool {
+        self.precedence() >= 5
+    }
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BinaryOp::Or => "||",
+            BinaryOp::And => "&&",
+            BinaryOp::Eq => "==",
+            BinaryOp::Ne => "!=",
+            BinaryOp::Lt => "<",
+            BinaryOp::Le => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Ge => ">=",
+            BinaryOp::In => "in",
+            BinaryOp::NotIn => "not in",
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Rem => "%",
+        })
+    }
+}
+
+/// Precedence of `!`/`not`, which sits between `&&` and the comparisons
+const NOT_PRECEDENCE: u8 = 3;
+/// Precedence of unary minus, which binds tighter than every infix operator
+const NEG_PRECEDENCE: u8 = 7;
+
+impl Expr {
+    fn precedence(&self) -> u8 {
+        match &self.kind {
+            ExprKind::Binary { op, .. } => op.precedence(),
+            ExprKind::Unary { op: UnaryOp::Not, .. } => NOT_PRECEDENCE,
+            ExprKind::Unary { op: UnaryOp::Neg, .. } => NEG_PRECEDENCE,
+            _ => u8::MAX,
+        }
+    }
+
+    fn fmt_operand(&self, f: &mut fmt::Formatter<'_>, min_precedence: u8) -> fmt::Result {
+        if self.precedence() < min_precedence {
+            write!(f, "({})", self)
+        } else {
+            write!(f, "{}", self)
+        }
+    }
+}
+
+fn fmt_items(f: &mut fmt::Formatter<'_>, items: &[Expr]) -> fmt::Result {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            f.write_str(", ")?;
+        }
+        write!(f, "{}", item)?;
+    }
+    Ok(())
+}
+
+/// Formats the expression back into source form, adding only the parentheses
+/// needed to preserve its structure
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ExprKind::Literal(literal) => write!(f, "{}", literal),
+            ExprKind::Path(path) => write!(f, "{}", path),
+            ExprKind::List(items) => {
+                f.write_str("[")?;
+                fmt_items(f, items)?;
+                f.write_str("]")
+            }
+            ExprKind::Call { function, args } => {
+                write!(f, "{}(", function)?;
+                fmt_items(f, args)?;
+                f.write_str(")")
+            }
+            ExprKind::Unary { op, operand } => {
+                write!(f, "{}", op)?;
+                operand.fmt_operand(f, self.precedence())
+            }
+            ExprKind::Binary { op, lhs, rhs } => {
+                let precedence = op.precedence();
+                // Comparisons are non-associative, so nested ones always need parentheses
+                let lhs_min = if op.is_comparison() { precedence + 1 } else { precedence };
+                lhs.fmt_operand(f, lhs_min)?;
+                write!(f, " {} ", op)?;
+                rhs.fmt_operand(f, precedence + 1)
+            }
+        }
+    }
+}
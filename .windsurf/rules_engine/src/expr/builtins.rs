@@ -0,0 +1,421 @@
+//! Built-in functions available to every expression
+//!
+//! The built-ins are always callable, whether or not a context carries a
+//! [`FunctionRegistry`]; a function registered under the same name takes
+//! precedence. All of them except `now()` are pure and deterministic, so the
+//! [`PartialEvaluator`](crate::expr::PartialEvaluator) folds them, but they
+//! are cheap enough that their results are not memoized. The date, time and
+//! duration functions are listed in [`datetime`](crate::expr::datetime).
+//!
+//! Like the operators, a built-in called with `null` for a required argument,
+//! such as a missing fact, returns `null`, which makes a condition unknown.
+//! `any`, `all` and `count` treat `null` items as unknown the way `||` and
+//! `&&` do: `any([null, true])` is `true`, but `any([null, false])` is `null`.
+//!
+//! | Function                  | Result                                                  |
+//! |---------------------------|---------------------------------------------------------|
+//! | `contains(a, b)`          | `b in a`: substring, list item or map key               |
+//! | `starts_with(s, prefix)`  | whether `s` starts with `prefix`                        |
+//! | `ends_with(s, suffix)`    | whether `s` ends with `suffix`                          |
+//! | `lower(s)`, `upper(s)`    | `s` in lower or upper case                              |
+//! | `trim(s)`                 | `s` without leading and trailing whitespace             |
+//! | `split(s, separator)`     | the parts of `s` as a list of strings                   |
+//! | `length(x)`               | characters in a string, items in a list or map          |
+//! | `matches(s, pattern)`     | whether the regex `pattern` matches anywhere in `s`     |
+//! | `capture(s, pattern)`     | the first match and its groups, or `null`               |
+//! | `any(list)`, `all(list)`  | whether any or all of a list of bools are true          |
+//! | `count(list)`             | how many of a list of bools are true; `null` if unknown |
+//! | `sum(list)`               | the sum of a list of numbers; `0` when empty            |
+//! | `min(list)`, `max(list)`  | the smallest or largest item; `null` when empty         |
+//! | `distinct(list)`          | the list without repeated items, in first-seen order    |
+//! | `in(x, list)`             | `x in list`                                             |
+//! | `decimal(s)`              | `s` parsed as an exact decimal number                   |
+//!
+//! Regex patterns use the syntax of the [`regex`] crate and are compiled once
+//! and cached.
+//!
+//! ```
+//! use windsurf_rules::expr::{evaluate, parse};
+//! use windsurf_rules::rule::{RuleContext, Value};
+//!
+//! let context = RuleContext::new().with_fact("email", " Ada@Example.com ");
+//! // Expression strings need the regex backslash escaped
+//! let expr = parse(r"matches(lower(trim(email)), '@example\\.com$') && length(email) > 5")?;
+//! assert_eq!(evaluate(&expr, &context)?, Value::Bool(true));
+//! # Ok::<(), windsurf_rules::Error>(())
+//! ```
+
+use lazy_static::lazy_static;
+use parking_lot::RwLock;
+use regex::Regex;
+use std::collections::HashMap;
+
+use crate::analysis::{Field, Type};
+use crate::clock::SystemClock;
+use crate::error::{Error, Result};
+use crate::expr::ast::{BinaryOp, Span};
+use crate::expr::datetime;
+use crate::expr::eval::{apply_binary, kleene};
+use crate::expr::function::{Function, FunctionRegistry};
+use crate::rule::Value;
+
+/// Maximum number of compiled regex patterns kept in the cache
+pub const REGEX_CACHE_CAPACITY: usize = 256;
+
+lazy_static! {
+    static ref BUILTINS: FunctionRegistry = {
+        let mut functions = FunctionRegistry::new();
+        for function in definitions().into_iter().chain(datetime::definitions()) {
+            functions.register(function.pure().deterministic());
+        }
+        functions.register(datetime::now_function(SystemClock));
+        functions
+    };
+    static ref REGEX_CACHE: RwLock<HashMap<String, Regex>> = RwLock::new(HashMap::new());
+}
+
+/// The built-in functions
+pub fn builtins() -> &'static FunctionRegistry {
+    &BUILTINS
+}
+
+fn definitions() -> Vec<Function> {
+    let string = || Field::new(Type::String);
+    let any = Field::default;
+    let bools = || Field::new(Type::list(Field::new(Type::Bool).nullable()));
+    let list = || Field::new(Type::list(Field::default()));
+    vec![
+        Function::new("contains", [any(), any()], Type::Bool, |args| {
+            membership(&args[1], &args[0])
+        }),
+        Function::new("starts_with", [string(), string()], Type::Bool, |args| {
+            let (s, prefix) = (str_arg(&args[0]), str_arg(&args[1]));
+            Ok(Value::Bool(s.starts_with(prefix)))
+        }),
+        Function::new("ends_with", [string(), string()], Type::Bool, |args| {
+            let (s, suffix) = (str_arg(&args[0]), str_arg(&args[1]));
+            Ok(Value::Bool(s.ends_with(suffix)))
+        }),
+        Function::new("lower", [string()], Type::String, |args| {
+            Ok(Value::String(str_arg(&args[0]).to_lowercase()))
+        }),
+        Function::new("upper", [string()], Type::String, |args| {
+            Ok(Value::String(str_arg(&args[0]).to_uppercase()))
+        }),
+        Function::new("trim", [string()], Type::String, |args| {
+            Ok(Value::String(str_arg(&args[0]).trim().to_string()))
+        }),
+        Function::new(
+            "split",
+            [string(), string()],
+            Type::list(string()),
+            |args| {
+                let (s, separator) = (str_arg(&args[0]), str_arg(&args[1]));
+                Ok(Value::List(s.split(separator).map(Value::from).collect()))
+            },
+        ),
+        Function::new("length", [any()], Type::Int, |args| {
+            let length = match &args[0] {
+                Value::String(s) => s.chars().count(),
+                Value::List(items) => items.len(),
+                Value::Map(map) => map.len(),
+                other => return Err(expected("a string, list or map", other)),
+            };
+            Ok(Value::Int(length as i64))
+        }),
+        Function::new("matches", [string(), string()], Type::Bool, |args| {
+            let regex = compile(str_arg(&args[1]))?;
+            Ok(Value::Bool(regex.is_match(str_arg(&args[0]))))
+        }),
+        Function::new(
+            "capture",
+            [string(), string()],
+            Field::new(Type::list(string().nullable())).nullable(),
+            |args| {
+                let regex = compile(str_arg(&args[1]))?;
+                Ok(match regex.captures(str_arg(&args[0])) {
+                    Some(captures) => Value::List(
+                        captures
+                            .iter()
+                            .map(|group| group.map(|group| group.as_str()).into())
+                            .collect(),
+                    ),
+                    None => Value::Null,
+                })
+            },
+        ),
+        Function::new(
+            "any",
+            [bools()],
+            Field::new(Type::Bool).nullable(),
+            |args| Ok(fold_logic(list_arg(&args[0]), true)),
+        ),
+        Function::new(
+            "all",
+            [bools()],
+            Field::new(Type::Bool).nullable(),
+            |args| Ok(fold_logic(list_arg(&args[0]), false)),
+        ),
+        Function::new(
+            "count",
+            [bools()],
+            Field::new(Type::Int).nullable(),
+            |args| {
+                let items = list_arg(&args[0]);
+                Ok(if items.iter().any(Value::is_null) {
+                    Value::Null
+                } else {
+                    Value::Int(items.iter().filter(|v| is_true(v)).count() as i64)
+                })
+            },
+        ),
+        Function::new("sum", [list()], Field::default(), |args| {
+            list_arg(&args[0])
+                .iter()
+                .try_fold(Value::Int(0), |total, item| {
+                    if !item.is_numeric() {
+                        return Err(expected("numbers", item));
+                    }
+                    unspanned(apply_binary(
+                        BinaryOp::Add,
+                        total,
+                        item.clone(),
+                        Span::default(),
+                    ))
+                })
+        }),
+        Function::new("min", [list()], Field::default(), |args| {
+            extreme(list_arg(&args[0]), std::cmp::Ordering::Less)
+        }),
+        Function::new("max", [list()], Field::default(), |args| {
+            extreme(list_arg(&args[0]), std::cmp::Ordering::Greater)
+        }),
+        Function::new("distinct", [list()], Type::list(Field::default()), |args| {
+            let mut distinct: Vec<Value> = Vec::new();
+            for item in list_arg(&args[0]) {
+                if !distinct.iter().any(|seen| seen.equals(item)) {
+                    distinct.push(item.clone());
+                }
+            }
+            Ok(Value::List(distinct))
+        }),
+        Function::new("in", [any(), list()], Type::Bool, |args| {
+            membership(&args[0], &args[1])
+        }),
+        Function::new("decimal", [string()], Type::Decimal, |args| {
+            let source = str_arg(&args[0]);
+            source
+                .trim()
+                .parse()
+                .map(Value::Decimal)
+                .map_err(|_| Error::Custom(format!("invalid decimal `{}`", source)))
+        }),
+    ]
+}
+
+/// Returns the compiled `pattern`, compiling and caching it on first use
+fn compile(pattern: &str) -> Result<Regex> {
+    if let Some(regex) = REGEX_CACHE.read().get(pattern) {
+        return Ok(regex.clone());
+    }
+    let regex = Regex::new(pattern)
+        .map_err(|err| Error::Custom(format!("invalid regex `{}`: {}", pattern, err)))?;
+    let mut cache = REGEX_CACHE.write();
+    if cache.len() >= REGEX_CACHE_CAPACITY {
+        cache.clear();
+    }
+    cache.insert(pattern.to_string(), regex.clone());
+    Ok(regex)
+}
+
+fn membership(needle: &Value, haystack: &Value) -> Result<Value> {
+    unspanned(apply_binary(
+        BinaryOp::In,
+        needle.clone(),
+        haystack.clone(),
+        Span::default(),
+    ))
+}
+
+/// Drops the placeholder span of an operator error so the call reports it at
+/// its own span
+fn unspanned(result: Result<Value>) -> Result<Value> {
+    result.map_err(|err| match err {
+        Error::Evaluation { message, .. } => Error::Custom(message),
+        other => other,
+    })
+}
+
+fn extreme(items: &[Value], wanted: std::cmp::Ordering) -> Result<Value> {
+    let mut best: Option<&Value> = None;
+    for item in items {
+        best = match best {
+            None => Some(item),
+            Some(current) => match item.compare(current) {
+                Some(ordering) if ordering == wanted => Some(item),
+                Some(_) => Some(current),
+                None => {
+                    return Err(Error::Custom(format!(
+                        "cannot compare {} with {}",
+                        item.type_name(),
+                        current.type_name()
+                    )))
+                }
+            },
+        };
+    }
+    Ok(best.cloned().unwrap_or(Value::Null))
+}
+
+fn expected(what: &str, found: &Value) -> Error {
+    Error::Custom(format!(
+        "expected {}, found {} `{}`",
+        what,
+        found.type_name(),
+        found
+    ))
+}
+
+/// Combines a list of bools with `||` (`short_circuit` true) or `&&` under
+/// three-valued logic
+fn fold_logic(items: &[Value], short_circuit: bool) -> Value {
+    items.iter().fold(Value::Bool(!short_circuit), |acc, item| {
+        kleene(acc.as_bool(), item.as_bool(), short_circuit)
+    })
+}
+
+fn is_true(value: &Value) -> bool {
+    matches!(value, Value::Bool(true))
+}
+
+// Arguments have passed the declared type check before the closures run
+
+fn str_arg(value: &Value) -> &str {
+    value.as_str().unwrap_or_default()
+}
+
+fn list_arg(value: &Value) -> &[Value] {
+    match value {
+        Value::List(items) => items,
+        _ => &[],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::{evaluate, parse};
+    use crate::rule::RuleContext;
+
+    fn eval(source: &str) -> Result<Value> {
+        let order: Value = [
+            ("email", Value::from(" Ada@Example.COM ")),
+            ("items", Value::from(vec![3, 1, 2, 3])),
+            ("tags", Value::from("vip,eu,new")),
+        ]
+        .into_iter()
+        .collect();
+        evaluate(
+            &parse(source).unwrap(),
+            &RuleContext::new().with_fact("order", order),
+        )
+    }
+
+    #[test]
+    fn test_string_and_regex_functions() {
+        let cases = [
+            ("lower(trim(order.email))", Value::from("ada@example.com")),
+            ("upper('eu')", Value::from("EU")),
+            (
+                "split(order.tags, ',')",
+                Value::from(vec!["vip", "eu", "new"]),
+            ),
+            ("length(split(order.tags, ','))", Value::Int(3)),
+            ("length('æøå')", Value::Int(3)),
+            (
+                "contains(order.tags, 'eu') && starts_with(order.tags, 'vip')",
+                Value::Bool(true),
+            ),
+            ("ends_with(order.tags, 'vip')", Value::Bool(false)),
+            (
+                "matches(order.email, '(?i)@example\\\\.com')",
+                Value::Bool(true),
+            ),
+            (
+                "capture(trim(order.email), '^(\\\\w+)@(x)?')",
+                Value::List(vec!["Ada@".into(), "Ada".into(), Value::Null]),
+            ),
+            ("capture('abc', '\\\\d')", Value::Null),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(eval(source).unwrap(), expected, "{}", source);
+        }
+        let error = eval("matches('a', '(')").unwrap_err().to_string();
+        assert!(
+            error.contains("`matches` failed: invalid regex `(`"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_collection_functions() {
+        let cases = [
+            ("sum(order.items)", Value::Int(9)),
+            ("sum([1, 2.5])", Value::Float(3.5)),
+            ("sum([])", Value::Int(0)),
+            ("min(order.items) + max(order.items)", Value::Int(4)),
+            ("max([])", Value::Null),
+            ("distinct(order.items)", Value::from(vec![3, 1, 2])),
+            ("count([true, false, 1 < 2])", Value::Int(2)),
+            ("any([false, 1 > 2]) || all([])", Value::Bool(true)),
+            (
+                "in(2, order.items) && contains(order.items, 1)",
+                Value::Bool(true),
+            ),
+            ("in('x', [])", Value::Bool(false)),
+            (
+                "decimal(' 9.95 ') * 2",
+                Value::Decimal("19.90".parse().unwrap()),
+            ),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(eval(source).unwrap(), expected, "{}", source);
+        }
+        for (source, fragment) in [
+            ("sum(['a'])", "expected numbers, found string"),
+            ("min([1, 'a'])", "cannot compare string with int"),
+            ("any([1])", "argument 1 of `any` must be list<bool?>"),
+            ("length(1)", "expected a string, list or map"),
+            ("decimal('ten')", "`decimal` failed: invalid decimal `ten`"),
+            (
+                "contains(1, 2)",
+                "`contains` failed: cannot apply `in` to int and int",
+            ),
+        ] {
+            let error = eval(source).unwrap_err().to_string();
+            assert!(error.contains(fragment), "{}: {}", source, error);
+        }
+    }
+
+    #[test]
+    fn test_null_arguments_make_the_result_unknown() {
+        let cases = [
+            ("starts_with(order.missing, 'a')", Value::Null),
+            ("lower(order.missing) == 'a'", Value::Bool(false)),
+            ("length(order.missing) > 1", Value::Null),
+            ("matches(order.email, order.missing)", Value::Null),
+            ("any([order.missing, true])", Value::Bool(true)),
+            ("any([order.missing, false])", Value::Null),
+            ("all([order.missing, false])", Value::Bool(false)),
+            ("all([true, order.missing])", Value::Null),
+            ("count([true, order.missing])", Value::Null),
+            ("contains(order.missing, 'a')", Value::Null),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(eval(source).unwrap(), expected, "{}", source);
+        }
+        // Arity is still checked
+        assert!(eval("lower(order.missing, 1)").is_err());
+    }
+}
@@ -0,0 +1,386 @@
+//! Built-in date, time and duration functions
+//!
+//! Timestamps are stored in UTC. Functions that depend on the calendar take
+//! an optional trailing time zone, an IANA name such as `Europe/Oslo`, and
+//! default to UTC. Durations are written with the constructors below or in
+//! ISO 8601 form with `duration('P90D')`, and combine with timestamps through
+//! the arithmetic operators: `now() - customer.created_at > days(90)`.
+//!
+//! | Function                           | Result                                                |
+//! |------------------------------------|-------------------------------------------------------|
+//! | `now()`                            | the current time, read from the engine's clock        |
+//! | `timestamp(s, format?, tz?)`       | `s` parsed as RFC 3339, or with a chrono `format`     |
+//! | `format_timestamp(ts, format, tz?)`| `ts` formatted with a chrono `format`                 |
+//! | `year(ts, tz?)`, `month`, `day`    | calendar fields; months count from 1                  |
+//! | `hour(ts, tz?)`, `minute`          | clock fields                                          |
+//! | `weekday(ts, tz?)`                 | day of the week, from 1 (Monday) to 7 (Sunday)        |
+//! | `start_of_day(ts, tz?)`            | midnight of the day `ts` falls on                     |
+//! | `add_months(ts, n, tz?)`           | `ts` moved by `n` calendar months, clamping the day   |
+//! | `weeks(n)`, `days`, `hours`, `minutes`, `seconds` | a duration of `n` units                |
+//! | `duration(s)`                      | `s` parsed as an ISO 8601 duration                    |
+//! | `in_weeks(d)`, `in_days`, `in_hours`, `in_minutes`, `in_seconds` | `d` as a fractional number of units |
+//!
+//! With a `format`, `timestamp` accepts input with an offset (`%z`), a date
+//! and time read in `tz`, or a bare date taken as midnight in `tz`. Local
+//! times skipped by a daylight saving change are an error.
+//!
+//! ```
+//! use chrono::{TimeZone, Utc};
+//! use windsurf_rules::expr::{evaluate, parse};
+//! use windsurf_rules::rule::{RuleContext, Value};
+//!
+//! let context = RuleContext::new()
+//!     .with_fact("opened", Value::from(Utc.with_ymd_and_hms(2024, 3, 31, 22, 30, 0).unwrap()));
+//! let expr = parse("weekday(opened, 'Europe/Oslo') == 1 && timestamp('2024-06-30', '%Y-%m-%d') - opened > days(90)")?;
+//! assert_eq!(evaluate(&expr, &context)?, Value::Bool(true));
+//! # Ok::<(), windsurf_rules::Error>(())
+//! ```
+
+use chrono::{
+    DateTime, Datelike, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Timelike, Utc,
+};
+use chrono_tz::Tz;
+use std::fmt::Write;
+
+use crate::analysis::{Field, Type};
+use crate::clock::Clock;
+use crate::error::{Error, Result};
+use crate::expr::function::Function;
+use crate::rule::duration::{as_secs_f64, from_secs_f64, parse_duration};
+use crate::rule::Value;
+
+/// Extracts one calendar or clock field from a local time
+type CalendarField = fn(&DateTime<Tz>) -> i64;
+
+/// Creates the `now()` function reading from `clock`
+///
+/// The built-in `now()` reads the system clock;
+/// [`RulesEngine::with_clock`](crate::RulesEngine::with_clock) registers one
+/// reading the engine's clock instead. The function is pure but not
+/// deterministic, so calls are neither memoized nor folded.
+pub fn now_function(clock: impl Clock + 'static) -> Function {
+    Function::new("now", Vec::<Field>::new(), Type::Timestamp, move |_| {
+        Ok(Value::Timestamp(clock.now()))
+    })
+    .pure()
+}
+
+/// The pure and deterministic date and time built-ins
+pub(crate) fn definitions() -> Vec<Function> {
+    let string = || Field::new(Type::String);
+    let timestamp = || Field::new(Type::Timestamp);
+    let zone = || Field::new(Type::String).nullable();
+
+    let mut functions = vec![
+        Function::new(
+            "timestamp",
+            [string(), string().nullable(), zone()],
+            Type::Timestamp,
+            |args| {
+                let source = args[0].as_str().unwrap_or_default();
+                parse_timestamp(source, args[1].as_str(), time_zone(&args[2])?)
+                    .map(Value::Timestamp)
+            },
+        ),
+        Function::new(
+            "format_timestamp",
+            [timestamp(), string(), zone()],
+            Type::String,
+            |args| {
+                let local = local_time(&args[0], &args[2])?;
+                let format = args[1].as_str().unwrap_or_default();
+                let mut formatted = String::new();
+                write!(formatted, "{}", local.format(format))
+                    .map_err(|_| Error::Custom(format!("invalid format `{}`", format)))?;
+                Ok(Value::String(formatted))
+            },
+        ),
+        Function::new(
+            "start_of_day",
+            [timestamp(), zone()],
+            Type::Timestamp,
+            |args| {
+                let local = local_time(&args[0], &args[1])?;
+                let midnight = local.date_naive().and_time(NaiveTime::MIN);
+                resolve(local.timezone(), midnight).map(Value::Timestamp)
+            },
+        ),
+        Function::new(
+            "add_months",
+            [timestamp(), Field::new(Type::Int), zone()],
+            Type::Timestamp,
+            |args| {
+                let local = local_time(&args[0], &args[2])?;
+                let n = args[1].as_i64().unwrap_or_default();
+                let months = u32::try_from(n.unsigned_abs()).ok().map(Months::new);
+                let moved = match months {
+                    Some(months) if n >= 0 => local.checked_add_months(months),
+                    Some(months) => local.checked_sub_months(months),
+                    None => None,
+                };
+                moved
+                    .map(|ts| Value::Timestamp(ts.with_timezone(&Utc)))
+                    .ok_or_else(|| {
+                        Error::Custom(format!("{} months from {} is out of range", n, local))
+                    })
+            },
+        ),
+        Function::new("duration", [string()], Type::Duration, |args| {
+            let source = args[0].as_str().unwrap_or_default();
+            parse_duration(source)
+                .map(Value::Duration)
+                .ok_or_else(|| Error::Custom(format!("invalid ISO 8601 duration `{}`", source)))
+        }),
+    ];
+
+    let fields: [(&str, CalendarField); 6] = [
+        ("year", |ts| i64::from(ts.year())),
+        ("month", |ts| i64::from(ts.month())),
+        ("day", |ts| i64::from(ts.day())),
+        ("hour", |ts| i64::from(ts.hour())),
+        ("minute", |ts| i64::from(ts.minute())),
+        ("weekday", |ts| i64::from(ts.weekday().number_from_monday())),
+    ];
+    for (name, field) in fields {
+        functions.push(Function::new(
+            name,
+            [timestamp(), zone()],
+            Type::Int,
+            move |args| {
+                let local = local_time(&args[0], &args[1])?;
+                Ok(Value::Int(field(&local)))
+            },
+        ));
+    }
+
+    let units = [
+        ("weeks", "in_weeks", 7.0 * 86_400.0),
+        ("days", "in_days", 86_400.0),
+        ("hours", "in_hours", 3_600.0),
+        ("minutes", "in_minutes", 60.0),
+        ("seconds", "in_seconds", 1.0),
+    ];
+    for (constructor, accessor, size) in units {
+        functions.push(Function::new(
+            constructor,
+            [Type::Float],
+            Type::Duration,
+            move |args| {
+                let n = args[0].as_f64().unwrap_or(f64::NAN);
+                from_secs_f64(n * size)
+                    .map(Value::Duration)
+                    .ok_or_else(|| Error::Custom(format!("{} {} is out of range", n, constructor)))
+            },
+        ));
+        functions.push(Function::new(
+            accessor,
+            [Type::Duration],
+            Type::Float,
+            move |args| {
+                let duration = args[0].as_duration().unwrap_or_default();
+                Ok(Value::Float(as_secs_f64(duration) / size))
+            },
+        ));
+    }
+    functions
+}
+
+fn parse_timestamp(source: &str, format: Option<&str>, zone: Tz) -> Result<DateTime<Utc>> {
+    let Some(format) = format else {
+        return DateTime::parse_from_rfc3339(source)
+            .map(|ts| ts.with_timezone(&Utc))
+            .map_err(|err| {
+                Error::Custom(format!("cannot parse `{}` as RFC 3339: {}", source, err))
+            });
+    };
+    if let Ok(ts) = DateTime::parse_from_str(source, format) {
+        return Ok(ts.with_timezone(&Utc));
+    }
+    let naive = match NaiveDateTime::parse_from_str(source, format) {
+        Ok(naive) => naive,
+        Err(err) => match NaiveDate::parse_from_str(source, format) {
+            Ok(date) => date.and_time(NaiveTime::MIN),
+            Err(_) => {
+                return Err(Error::Custom(format!(
+                    "cannot parse `{}` with format `{}`: {}",
+                    source, format, err
+                )))
+            }
+        },
+    };
+    resolve(zone, naive)
+}
+
+/// Converts a local time in `zone` to UTC, taking the earlier instant when a
+/// daylight saving change makes it ambiguous
+fn resolve(zone: Tz, local: NaiveDateTime) -> Result<DateTime<Utc>> {
+    zone.from_local_datetime(&local)
+        .earliest()
+        .map(|ts| ts.with_timezone(&Utc))
+        .ok_or_else(|| Error::Custom(format!("{} does not exist in {}", local, zone)))
+}
+
+fn local_time(timestamp: &Value, zone: &Value) -> Result<DateTime<Tz>> {
+    let timestamp = timestamp.as_timestamp().unwrap_or_default();
+    Ok(timestamp.with_timezone(&time_zone(zone)?))
+}
+
+/// Parses an IANA time zone name; `null` means UTC
+fn time_zone(value: &Value) -> Result<Tz> {
+    match value.as_str() {
+        None => Ok(Tz::UTC),
+        Some(name) => name
+            .parse()
+            .map_err(|_| Error::Custom(format!("unknown time zone `{}`", name))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::expr::{evaluate, parse, FunctionRegistry};
+    use crate::rule::RuleContext;
+    use chrono::TimeDelta;
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    fn eval(source: &str) -> Result<Value> {
+        // Sunday 31 March 2024, 22:30 UTC: already Monday in Oslo, after the
+        // switch to summer time
+        let account: Value = [
+            ("opened", Value::from(at(2024, 3, 31, 22, 30))),
+            ("grace", Value::from(TimeDelta::days(14))),
+        ]
+        .into_iter()
+        .collect();
+        evaluate(
+            &parse(source).unwrap(),
+            &RuleContext::new().with_fact("account", account),
+        )
+    }
+
+    #[test]
+    fn test_parsing_and_calendar_functions() {
+        let cases = [
+            (
+                "timestamp('2024-01-15T10:00:00+01:00')",
+                Value::from(at(2024, 1, 15, 9, 0)),
+            ),
+            (
+                "timestamp('15/01/2024 10:00', '%d/%m/%Y %H:%M', 'Europe/Oslo')",
+                Value::from(at(2024, 1, 15, 9, 0)),
+            ),
+            (
+                "timestamp('2024-01-15', '%Y-%m-%d')",
+                Value::from(at(2024, 1, 15, 0, 0)),
+            ),
+            (
+                "format_timestamp(account.opened, '%Y-%m-%d %H:%M %Z', 'Europe/Oslo')",
+                Value::from("2024-04-01 00:30 CEST"),
+            ),
+            ("weekday(account.opened)", Value::Int(7)),
+            ("weekday(account.opened, 'Europe/Oslo')", Value::Int(1)),
+            ("month(account.opened, 'Europe/Oslo')", Value::Int(4)),
+            (
+                "year(account.opened) * 100 + day(account.opened)",
+                Value::Int(202431),
+            ),
+            ("hour(account.opened, 'Asia/Kolkata')", Value::Int(4)),
+            (
+                "start_of_day(account.opened, 'Europe/Oslo')",
+                Value::from(at(2024, 3, 31, 22, 0)),
+            ),
+            (
+                "add_months(timestamp('2024-01-31T12:00:00Z'), 1)",
+                Value::from(at(2024, 2, 29, 12, 0)),
+            ),
+            (
+                "add_months(account.opened, -12)",
+                Value::from(at(2023, 3, 31, 22, 30)),
+            ),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(eval(source).unwrap(), expected, "{}", source);
+        }
+        for (source, fragment) in [
+            (
+                "timestamp('yesterday')",
+                "cannot parse `yesterday` as RFC 3339",
+            ),
+            (
+                "timestamp('2024-03-31 02:30', '%Y-%m-%d %H:%M', 'Europe/Oslo')",
+                "does not exist",
+            ),
+            (
+                "weekday(account.opened, 'Mars/Olympus')",
+                "unknown time zone `Mars/Olympus`",
+            ),
+            (
+                "format_timestamp(account.opened, '%Q')",
+                "invalid format `%Q`",
+            ),
+        ] {
+            let error = eval(source).unwrap_err().to_string();
+            assert!(error.contains(fragment), "{}: {}", source, error);
+        }
+    }
+
+    #[test]
+    fn test_duration_arithmetic() {
+        let cases = [
+            (
+                "timestamp('2024-06-30T22:30:00Z') - account.opened",
+                Value::from(TimeDelta::days(91)),
+            ),
+            (
+                "account.opened + account.grace * 2",
+                Value::from(at(2024, 4, 28, 22, 30)),
+            ),
+            (
+                "account.opened - hours(1.5) < account.opened",
+                Value::Bool(true),
+            ),
+            ("duration('P2W') == account.grace", Value::Bool(true)),
+            ("-duration('PT90M')", Value::from(TimeDelta::minutes(-90))),
+            ("in_hours(days(1) + minutes(30))", Value::Float(24.5)),
+            ("weeks(1) / account.grace", Value::Float(0.5)),
+            ("in_days(account.grace / 4)", Value::Float(3.5)),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(eval(source).unwrap(), expected, "{}", source);
+        }
+        for (source, fragment) in [
+            (
+                "account.opened + 1",
+                "cannot apply `+` to timestamp and int",
+            ),
+            ("account.grace / 0", "division by zero"),
+            ("duration('P1M')", "invalid ISO 8601 duration `P1M`"),
+            ("days(1e300)", "out of range"),
+        ] {
+            let error = eval(source).unwrap_err().to_string();
+            assert!(error.contains(fragment), "{}: {}", source, error);
+        }
+    }
+
+    #[test]
+    fn test_now_reads_the_given_clock() {
+        let clock = std::sync::Arc::new(ManualClock::new(at(2024, 6, 1, 0, 0)));
+        let mut functions = FunctionRegistry::new();
+        functions.register(now_function(clock.clone()));
+        let context = RuleContext::new().with_functions(functions);
+        let expr = parse("now()").unwrap();
+        assert_eq!(
+            evaluate(&expr, &context).unwrap(),
+            Value::from(at(2024, 6, 1, 0, 0))
+        );
+        clock.advance(TimeDelta::days(1));
+        assert_eq!(
+            evaluate(&expr, &context).unwrap(),
+            Value::from(at(2024, 6, 2, 0, 0))
+        );
+    }
+}
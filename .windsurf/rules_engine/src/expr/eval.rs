@@ -0,0 +1,455 @@
+//! Tree-walking evaluator for rule expressions
+
+use rust_decimal::Decimal;
+
+use crate::error::{Error, Result};
+use crate::expr::ast::{BinaryOp, Expr, ExprKind, Literal, Path, Span, UnaryOp};
+use crate::expr::function;
+use crate::rule::duration::{as_secs_f64, from_secs_f64};
+use crate::rule::{MissingPolicy, RuleContext, Value};
+
+/// Name of the special form testing whether a fact path is present
+pub const EXISTS: &str = "exists";
+
+/// Name of the special form returning its first non-null argument
+pub const COALESCE: &str = "coalesce";
+
+/// Builds an evaluation error pointing at `span`
+pub(crate) fn eval_error(span: Span, message: impl Into<String>) -> Error {
+    Error::Evaluation {
+        message: message.into(),
+        span,
+    }
+}
+
+/// Evaluates an expression against a fact context
+pub fn evaluate(expr: &Expr, context: &RuleContext) -> Result<Value> {
+    Evaluator::new(context).evaluate(expr)
+}
+
+/// Evaluates expressions against a borrowed fact context
+///
+/// Missing fact paths evaluate to `null`, or fail under
+/// [`MissingPolicy::Error`]. `null` stands for an unknown value, as in SQL:
+/// comparisons, `in`, arithmetic and negation with a `null` operand are
+/// `null`, while `==` and `!=` compare `null` like any other value. `&&` and
+/// `||` short-circuit, require boolean or `null` operands and follow
+/// three-valued logic, so `null && false` is `false` and `null || false` is
+/// `null`. Arithmetic promotes `int` to `decimal` or `float` as needed and
+/// reports overflow and division by zero as errors.
+///
+/// Function calls evaluate their arguments left to right and go through the
+/// context's [`FunctionRegistry`](crate::expr::FunctionRegistry). Two special
+/// forms are evaluated by the evaluator itself: `exists(path)` is true if the
+/// path is present, even when it holds `null`, and `coalesce(a, b, ...)`
+/// evaluates its arguments until one is not `null`.
+#[derive(Debug, Clone, Copy)]
+pub struct Evaluator<'a> {
+    context: &'a RuleContext,
+}
+
+impl<'a> Evaluator<'a> {
+    /// Creates an evaluator over `context`
+    pub fn new(context: &'a RuleContext) -> Self {
+        Self { context }
+    }
+
+    /// Evaluates an expression to a value
+    pub fn evaluate(&self, expr: &Expr) -> Result<Value> {
+        match &expr.kind {
+            ExprKind::Literal(literal) => Ok(literal_value(literal)),
+            ExprKind::Path(path) => read_fact(self.context, path, expr.span),
+            ExprKind::List(items) => items
+                .iter()
+                .map(|item| self.evaluate(item))
+                .collect::<Result<Vec<_>>>()
+                .map(Value::List),
+            ExprKind::Call { function, args } if function == EXISTS => {
+                exists(self.context, args, expr.span).map(Value::Bool)
+            }
+            ExprKind::Call { function, args } if function == COALESCE => {
+                if args.is_empty() {
+                    return Err(eval_error(expr.span, coalesce_arity()));
+                }
+                for arg in args {
+                    let value = self.evaluate(arg)?;
+                    if !value.is_null() {
+                        return Ok(value);
+                    }
+                }
+                Ok(Value::Null)
+            }
+            ExprKind::Call { function, args } => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.evaluate(arg))
+                    .collect::<Result<Vec<_>>>()?;
+                apply_call(self.context, function, &args, expr.span)
+            }
+            ExprKind::Unary { op, operand } => {
+                let value = self.evaluate(operand)?;
+                apply_unary(*op, value, expr.span)
+            }
+            ExprKind::Binary { op: op @ (BinaryOp::And | BinaryOp::Or), lhs, rhs } => {
+                let short_circuit = *op == BinaryOp::Or;
+                let left = logic_operand(self.evaluate(lhs)?, lhs.span)?;
+                if left == Some(short_circuit) {
+                    return Ok(Value::Bool(short_circuit));
+                }
+                let right = logic_operand(self.evaluate(rhs)?, rhs.span)?;
+                Ok(kleene(left, right, short_circuit))
+            }
+            ExprKind::Binary { op, lhs, rhs } => {
+                let lhs = self.evaluate(lhs)?;
+                let rhs = self.evaluate(rhs)?;
+                apply_binary(*op, lhs, rhs, expr.span)
+            }
+        }
+    }
+
+    /// Evaluates an expression that must produce a boolean; an unknown
+    /// (`null`) result does not hold, so it yields `false`
+    pub fn evaluate_bool(&self, expr: &Expr) -> Result<bool> {
+        let value = self.evaluate(expr)?;
+        Ok(logic_operand(value, expr.span)?.unwrap_or(false))
+    }
+}
+
+/// Reads `path` from `context`, applying its [`MissingPolicy`]
+pub(crate) fn read_fact(context: &RuleContext, path: &Path, span: Span) -> Result<Value> {
+    match context.lookup(path) {
+        Some(value) => Ok(value.clone()),
+        None if context.missing_policy() == MissingPolicy::Error => {
+            Err(eval_error(span, format!("missing fact `{}`", path)))
+        }
+        None => Ok(Value::Null),
+    }
+}
+
+/// Evaluates the `exists` special form, whose only argument must be a path
+pub(crate) fn exists(context: &RuleContext, args: &[Expr], span: Span) -> Result<bool> {
+    match args {
+        [Expr {
+            kind: ExprKind::Path(path),
+            ..
+        }] => Ok(context.lookup(path).is_some()),
+        _ => Err(eval_error(span, exists_arity())),
+    }
+}
+
+pub(crate) fn exists_arity() -> String {
+    format!("`{}` takes a single fact path", EXISTS)
+}
+
+pub(crate) fn coalesce_arity() -> String {
+    format!("`{}` takes at least 1 argument", COALESCE)
+}
+
+/// Converts an operand of `&&`, `||` or `!` to a truth value; `null` is
+/// unknown
+pub(crate) fn logic_operand(value: Value, span: Span) -> Result<Option<bool>> {
+    match value {
+        Value::Bool(b) => Ok(Some(b)),
+        Value::Null => Ok(None),
+        other => Err(eval_error(
+            span,
+            format!("expected bool, found {} `{}`", other.type_name(), other),
+        )),
+    }
+}
+
+/// Combines two truth values with `||` (`short_circuit` true) or `&&` under
+/// three-valued logic
+pub(crate) fn kleene(left: Option<bool>, right: Option<bool>, short_circuit: bool) -> Value {
+    match (left, right) {
+        (Some(b), _) | (_, Some(b)) if b == short_circuit => Value::Bool(short_circuit),
+        (Some(_), Some(_)) => Value::Bool(!short_circuit),
+        _ => Value::Null,
+    }
+}
+
+/// Converts a literal into its runtime value
+pub fn literal_value(literal: &Literal) -> Value {
+    match literal {
+        Literal::Null => Value::Null,
+        Literal::Bool(b) => Value::Bool(*b),
+        Literal::Int(i) => Value::Int(*i),
+        Literal::Float(x) => Value::Float(*x),
+        Literal::String(s) => Value::String(s.clone()),
+    }
+}
+
+/// Calls a function from the context's registry with already evaluated
+/// arguments
+pub fn apply_call(context: &RuleContext, function: &str, args: &[Value], span: Span) -> Result<Value> {
+    function::call(context, function, args, span)
+}
+
+/// Applies a prefix operator to an already evaluated operand
+pub fn apply_unary(op: UnaryOp, value: Value, span: Span) -> Result<Value> {
+    match (op, value) {
+        (_, Value::Null) => Ok(Value::Null),
+        (UnaryOp::Not, Value::Bool(b)) => Ok(Value::Bool(!b)),
+        (UnaryOp::Neg, Value::Int(i)) => i
+            .checked_neg()
+            .map(Value::Int)
+            .ok_or_else(|| eval_error(span, "integer overflow")),
+        (UnaryOp::Neg, Value::Float(x)) => Ok(Value::Float(-x)),
+        (UnaryOp::Neg, Value::Decimal(d)) => Ok(Value::Decimal(-d)),
+        (UnaryOp::Neg, Value::Duration(d)) => Ok(Value::Duration(-d)),
+        (op, value) => Err(eval_error(
+            span,
+            format!("cannot apply `{}` to {}", op, value.type_name()),
+        )),
+    }
+}
+
+/// Applies a non-short-circuiting infix operator to already evaluated operands
+pub fn apply_binary(op: BinaryOp, lhs: Value, rhs: Value, span: Span) -> Result<Value> {
+    match op {
+        BinaryOp::And | BinaryOp::Or => {
+            let operands = (logic_operand(lhs.clone(), span), logic_operand(rhs.clone(), span));
+            match operands {
+                (Ok(a), Ok(b)) => Ok(kleene(a, b, op == BinaryOp::Or)),
+                _ => Err(type_mismatch(op, &lhs, &rhs, span)),
+            }
+        }
+        BinaryOp::Eq => Ok(Value::Bool(lhs.equals(&rhs))),
+        BinaryOp::Ne => Ok(Value::Bool(!lhs.equals(&rhs))),
+        _ if lhs.is_null() || rhs.is_null() => Ok(Value::Null),
+        BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => {
+            let ordering = lhs
+                .compare(&rhs)
+                .ok_or_else(|| type_mismatch(op, &lhs, &rhs, span))?;
+            Ok(Value::Bool(match op {
+                BinaryOp::Lt => ordering.is_lt(),
+                BinaryOp::Le => ordering.is_le(),
+                BinaryOp::Gt => ordering.is_gt(),
+                _ => ordering.is_ge(),
+            }))
+        }
+        BinaryOp::In => contains(&rhs, &lhs, span).map(Value::Bool),
+        BinaryOp::NotIn => contains(&rhs, &lhs, span).map(|found| Value::Bool(!found)),
+        BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => {
+            arithmetic(op, lhs, rhs, span)
+        }
+    }
+}
+
+fn type_mismatch(op: BinaryOp, lhs: &Value, rhs: &Value, span: Span) -> Error {
+    eval_error(
+        span,
+        format!(
+            "cannot apply `{}` to {} and {}",
+            op,
+            lhs.type_name(),
+            rhs.type_name()
+        ),
+    )
+}
+
+fn contains(haystack: &Value, needle: &Value, span: Span) -> Result<bool> {
+    match (haystack, needle) {
+        (Value::List(items), needle) => Ok(items.iter().any(|item| item.equals(needle))),
+        (Value::String(s), Value::String(sub)) => Ok(s.contains(sub.as_str())),
+        (Value::Map(map), Value::String(key)) => Ok(map.contains_key(key)),
+        (haystack, needle) => Err(type_mismatch(BinaryOp::In, needle, haystack, span)),
+    }
+}
+
+fn arithmetic(op: BinaryOp, lhs: Value, rhs: Value, span: Span) -> Result<Value> {
+    let overflow = || eval_error(span, format!("arithmetic overflow in `{}`", op));
+    let div_zero = || eval_error(span, "division by zero");
+
+    match (lhs, rhs) {
+        (Value::Int(a), Value::Int(b)) => match op {
+            BinaryOp::Add => a.checked_add(b).map(Value::Int).ok_or_else(overflow),
+            BinaryOp::Sub => a.checked_sub(b).map(Value::Int).ok_or_else(overflow),
+            BinaryOp::Mul => a.checked_mul(b).map(Value::Int).ok_or_else(overflow),
+            // Integer division yields a float so `7 / 2` is `3.5`, as business users expect
+            BinaryOp::Div if b == 0 => Err(div_zero()),
+            BinaryOp::Div => Ok(Value::Float(a as f64 / b as f64)),
+            _ if b == 0 => Err(div_zero()),
+            _ => a.checked_rem(b).map(Value::Int).ok_or_else(overflow),
+        },
+        (a @ (Value::Int(_) | Value::Decimal(_)), b @ (Value::Int(_) | Value::Decimal(_))) => {
+            let to_decimal = |v: &Value| match v {
+                Value::Int(i) => Decimal::from(*i),
+                Value::Decimal(d) => *d,
+                _ => Decimal::ZERO,
+            };
+            let (a, b) = (to_decimal(&a), to_decimal(&b));
+            if matches!(op, BinaryOp::Div | BinaryOp::Rem) && b.is_zero() {
+                return Err(div_zero());
+            }
+            let result = match op {
+                BinaryOp::Add => a.checked_add(b),
+                BinaryOp::Sub => a.checked_sub(b),
+                BinaryOp::Mul => a.checked_mul(b),
+                BinaryOp::Div => a.checked_div(b),
+                _ => a.checked_rem(b),
+            };
+            result.map(Value::Decimal).ok_or_else(overflow)
+        }
+        (a, b) if a.is_numeric() && b.is_numeric() => {
+            let (a, b) = (a.as_f64().unwrap_or(f64::NAN), b.as_f64().unwrap_or(f64::NAN));
+            if matches!(op, BinaryOp::Div | BinaryOp::Rem) && b == 0.0 {
+                return Err(div_zero());
+            }
+            let result = match op {
+                BinaryOp::Add => a + b,
+                BinaryOp::Sub => a - b,
+                BinaryOp::Mul => a * b,
+                BinaryOp::Div => a / b,
+                _ => a % b,
+            };
+            // Non-finite floats cannot be written back as literals
+            if !result.is_finite() {
+                return Err(overflow());
+            }
+            Ok(Value::Float(result))
+        }
+        (Value::String(a), Value::String(b)) if op == BinaryOp::Add => Ok(Value::String(a + &b)),
+        (Value::List(mut a), Value::List(b)) if op == BinaryOp::Add => {
+            a.extend(b);
+            Ok(Value::List(a))
+        }
+        (Value::Timestamp(a), Value::Timestamp(b)) if op == BinaryOp::Sub => {
+            Ok(Value::Duration(a.signed_duration_since(b)))
+        }
+        (Value::Timestamp(t), Value::Duration(d)) | (Value::Duration(d), Value::Timestamp(t))
+            if op == BinaryOp::Add =>
+        {
+            t.checked_add_signed(d).map(Value::Timestamp).ok_or_else(overflow)
+        }
+        (Value::Timestamp(t), Value::Duration(d)) if op == BinaryOp::Sub => {
+            t.checked_sub_signed(d).map(Value::Timestamp).ok_or_else(overflow)
+        }
+        (Value::Duration(a), Value::Duration(b)) => match op {
+            BinaryOp::Add => a.checked_add(&b).map(Value::Duration).ok_or_else(overflow),
+            BinaryOp::Sub => a.checked_sub(&b).map(Value::Duration).ok_or_else(overflow),
+            BinaryOp::Div if b.is_zero() => Err(div_zero()),
+            BinaryOp::Div => Ok(Value::Float(as_secs_f64(a) / as_secs_f64(b))),
+            _ => Err(type_mismatch(op, &Value::Duration(a), &Value::Duration(b), span)),
+        },
+        (Value::Duration(d), n) | (n, Value::Duration(d))
+            if n.is_numeric() && op == BinaryOp::Mul =>
+        {
+            let factor = n.as_f64().unwrap_or(f64::NAN);
+            from_secs_f64(as_secs_f64(d) * factor).map(Value::Duration).ok_or_else(overflow)
+        }
+        (Value::Duration(d), n) if n.is_numeric() && op == BinaryOp::Div => {
+            let divisor = n.as_f64().unwrap_or(f64::NAN);
+            if divisor == 0.0 {
+                return Err(div_zero());
+            }
+            from_secs_f64(as_secs_f64(d) / divisor).map(Value::Duration).ok_or_else(overflow)
+        }
+        (a, b) => Err(type_mismatch(op, &a, &b, span)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::parse;
+
+    fn context() -> RuleContext {
+        let customer: Value = [
+            ("age", Value::from(42)),
+            ("tier", Value::from("gold")),
+            ("balance", Value::Decimal(Decimal::new(1050, 2))),
+            ("active", Value::from(true)),
+        ]
+        .into_iter()
+        .collect();
+        RuleContext::new().with_fact("customer", customer)
+    }
+
+    fn eval(source: &str) -> Result<Value> {
+        evaluate(&parse(source).unwrap(), &context())
+    }
+
+    #[test]
+    fn test_evaluate_comparisons_and_logic() {
+        let truthy = [
+            "1 == 1",
+            "customer.age >= 18 && customer.active",
+            "customer.tier in ['gold', 'silver']",
+            "customer.tier not in ['bronze']",
+            "'ol' in customer.tier",
+            "customer.missing == null",
+            "!(customer.age < 18) || customer.missing > 1",
+        ];
+        for source in truthy {
+            assert_eq!(eval(source).unwrap(), Value::Bool(true), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_evaluate_arithmetic() {
+        assert_eq!(eval("customer.age * 2 + 1").unwrap(), Value::Int(85));
+        assert_eq!(eval("7 / 2").unwrap(), Value::Float(3.5));
+        assert_eq!(eval("7 % 4").unwrap(), Value::Int(3));
+        assert_eq!(
+            eval("customer.balance + 1").unwrap(),
+            Value::Decimal(Decimal::new(1150, 2))
+        );
+        assert_eq!(eval("'a' + 'b'").unwrap(), Value::from("ab"));
+    }
+
+    #[test]
+    fn test_evaluate_three_valued_logic() {
+        let cases = [
+            ("customer.missing > 1", Value::Null),
+            ("customer.missing + 1 in [1]", Value::Null),
+            ("!(customer.missing < 1)", Value::Null),
+            ("customer.missing > 1 && false", Value::Bool(false)),
+            ("customer.missing > 1 && true", Value::Null),
+            ("customer.missing > 1 || true", Value::Bool(true)),
+            ("null || false", Value::Null),
+            ("customer.missing == null", Value::Bool(true)),
+            ("coalesce(customer.missing, customer.tier, 1)", Value::from("gold")),
+            ("coalesce(customer.missing, null)", Value::Null),
+            ("exists(customer.age) && !exists(customer.missing)", Value::Bool(true)),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(eval(source).unwrap(), expected, "{}", source);
+        }
+        let explicit = RuleContext::new().with_fact("nickname", Value::Null);
+        assert_eq!(
+            evaluate(&parse("exists(nickname)").unwrap(), &explicit).unwrap(),
+            Value::Bool(true)
+        );
+        let strict = context().with_missing_policy(MissingPolicy::Error);
+        for (source, expected) in [
+            ("customer.missing > 1", "missing fact `customer.missing`"),
+            ("exists(1)", "`exists` takes a single fact path"),
+            ("coalesce()", "`coalesce` takes at least 1 argument"),
+        ] {
+            let error = evaluate(&parse(source).unwrap(), &strict).unwrap_err();
+            assert!(error.to_string().contains(expected), "{}: {}", source, error);
+        }
+    }
+
+    #[test]
+    fn test_evaluate_errors_carry_span() {
+        let cases = [
+            ("1 / 0", "division by zero"),
+            ("1.0 / 0", "division by zero"),
+            ("2.5 % 0.0", "division by zero"),
+            ("customer.tier > 3", "cannot apply `>` to string and int"),
+            ("customer.age && true", "expected bool"),
+            ("9223372036854775807 + 1", "overflow"),
+        ];
+        for (source, fragment) in cases {
+            match eval(source) {
+                Err(Error::Evaluation { message, span }) => {
+                    assert!(message.contains(fragment), "{}: {}", source, message);
+                    assert!(span.end <= source.len());
+                }
+                other => panic!("{}: expected evaluation error, got {:?}", source, other),
+            }
+        }
+    }
+}
@@ -0,0 +1,475 @@
+//! User-defined functions callable from expressions
+//!
+//! A [`Function`] wraps a Rust closure together with its declared parameter
+//! and return types. Functions are collected in a [`FunctionRegistry`], which
+//! is attached to a [`RuleContext`] with
+//! [`RuleContext::with_functions`] or registered on the engine with
+//! [`RulesEngine::register_function`](crate::RulesEngine::register_function).
+//! Arguments and results are checked against the declared types on every
+//! call, and the [`TypeChecker`](crate::analysis::TypeChecker) checks calls
+//! statically when given the registry.
+//!
+//! The [built-in functions](crate::expr::builtins) are available without
+//! registering them; a registered function of the same name replaces the
+//! built-in.
+//!
+//! Functions are assumed to have side effects and to depend on more than
+//! their arguments until marked [`pure`](Function::pure) and
+//! [`deterministic`](Function::deterministic). Calls to functions that are
+//! both are memoized by the registry and folded by the
+//! [`PartialEvaluator`](crate::expr::PartialEvaluator).
+//!
+//! ```
+//! use windsurf_rules::analysis::Type;
+//! use windsurf_rules::expr::{evaluate, parse, Function, FunctionRegistry};
+//! use windsurf_rules::rule::{RuleContext, Value};
+//!
+//! let mut functions = FunctionRegistry::new();
+//! functions.register(
+//!     Function::new("risk_score", [Type::Int], Type::Int, |args| {
+//!         Ok(Value::Int(args[0].as_f64().unwrap_or(0.0) as i64 / 10))
+//!     })
+//!     .pure()
+//!     .deterministic(),
+//! );
+//! let context = RuleContext::new().with_fact("age", 42).with_functions(functions);
+//! assert_eq!(evaluate(&parse("risk_score(age) + 1")?, &context)?, Value::Int(5));
+//! # Ok::<(), windsurf_rules::Error>(())
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+
+use crate::analysis::Field;
+use crate::error::{Error, Result};
+use crate::expr::ast::{Expr, ExprKind, Span};
+use crate::expr::builtins::builtins;
+use crate::expr::eval::eval_error;
+use crate::rule::{RuleContext, Value};
+
+#[cfg(feature = "caching")]
+use crate::cache::Cache;
+
+/// Maximum number of memoized results a registry keeps
+#[cfg(feature = "caching")]
+pub const MEMO_CAPACITY: usize = 10_000;
+
+/// Signature of the closures behind a [`Function`]
+pub type FunctionFn = dyn Fn(&[Value]) -> Result<Value> + Send + Sync;
+
+/// A named Rust function with a declared signature
+#[derive(Clone)]
+pub struct Function {
+    name: String,
+    params: Vec<Field>,
+    returns: Field,
+    pure: bool,
+    deterministic: bool,
+    implementation: Arc<FunctionFn>,
+}
+
+impl Function {
+    /// Creates a function taking arguments of the `params` types and
+    /// returning a `returns`; the closure only sees arguments that passed the
+    /// type check
+    ///
+    /// Trailing nullable parameters may be left out of a call; the closure
+    /// then receives `null` for them.
+    pub fn new<P, F>(
+        name: impl Into<String>,
+        params: impl IntoIterator<Item = P>,
+        returns: impl Into<Field>,
+        implementation: F,
+    ) -> Self
+    where
+        P: Into<Field>,
+        F: Fn(&[Value]) -> Result<Value> + Send + Sync + 'static,
+    {
+        Self {
+            name: name.into(),
+            params: params.into_iter().map(Into::into).collect(),
+            returns: returns.into(),
+            pure: false,
+            deterministic: false,
+            implementation: Arc::new(implementation),
+        }
+    }
+
+    /// Marks the function as free of side effects
+    pub fn pure(mut self) -> Self {
+        self.pure = true;
+        self
+    }
+
+    /// Marks the function as always returning the same result for the same
+    /// arguments
+    pub fn deterministic(mut self) -> Self {
+        self.deterministic = true;
+        self
+    }
+
+    /// The name expressions call the function by
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Declared parameter types
+    pub fn params(&self) -> &[Field] {
+        &self.params
+    }
+
+    /// Declared return type
+    pub fn returns(&self) -> &Field {
+        &self.returns
+    }
+
+    /// Range of accepted argument counts
+    pub fn arity(&self) -> RangeInclusive<usize> {
+        let optional = self.params.iter().rev().take_while(|p| p.nullable).count();
+        self.params.len() - optional..=self.params.len()
+    }
+
+    /// Whether the function is marked pure
+    pub fn is_pure(&self) -> bool {
+        self.pure
+    }
+
+    /// Whether the function is marked deterministic
+    pub fn is_deterministic(&self) -> bool {
+        self.deterministic
+    }
+
+    /// Whether results can be cached and calls folded: the function is both
+    /// pure and deterministic
+    pub fn is_memoizable(&self) -> bool {
+        self.pure && self.deterministic
+    }
+
+    /// Calls the function, checking the arguments and the result against the
+    /// declared types; failures are evaluation errors at `span`
+    pub fn call(&self, args: &[Value], span: Span) -> Result<Value> {
+        if !self.arity().contains(&args.len()) {
+            return Err(eval_error(span, self.arity_mismatch(args.len())));
+        }
+        let padded;
+        let args = if args.len() < self.params.len() {
+            padded = args
+                .iter()
+                .cloned()
+                .chain(std::iter::repeat(Value::Null))
+                .take(self.params.len())
+                .collect::<Vec<_>>();
+            &padded[..]
+        } else {
+            args
+        };
+        for (i, (arg, param)) in args.iter().zip(&self.params).enumerate() {
+            if !param.admits(arg) {
+                return Err(eval_error(
+                    span,
+                    format!(
+                        "argument {} of `{}` must be {}, found {} `{}`",
+                        i + 1,
+                        self.name,
+                        param,
+                        arg.type_name(),
+                        arg
+                    ),
+                ));
+            }
+        }
+        let value = (self.implementation)(args).map_err(|err| match err {
+            err @ Error::Evaluation { .. } => err,
+            other => eval_error(span, format!("`{}` failed: {}", self.name, other)),
+        })?;
+        if !self.returns.admits(&value) {
+            return Err(eval_error(
+                span,
+                format!(
+                    "`{}` returned {} `{}`, declared {}",
+                    self.name,
+                    value.type_name(),
+                    value,
+                    self.returns
+                ),
+            ));
+        }
+        Ok(value)
+    }
+}
+
+impl Function {
+    /// Describes a call with `found` arguments to a function taking a
+    /// different number
+    pub(crate) fn arity_mismatch(&self, found: usize) -> String {
+        let arity = self.arity();
+        let expected = if arity.start() == arity.end() {
+            arity.end().to_string()
+        } else {
+            format!("{} to {}", arity.start(), arity.end())
+        };
+        format!(
+            "`{}` takes {} argument{}, found {}",
+            self.name,
+            expected,
+            if expected == "1" { "" } else { "s" },
+            found
+        )
+    }
+}
+
+/// Prints the signature, e.g. `risk_score(map, int?) -> float`
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}(", self.name)?;
+        for (i, param) in self.params.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}", param)?;
+        }
+        write!(f, ") -> {}", self.returns)
+    }
+}
+
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Function")
+            .field("signature", &self.to_string())
+            .field("pure", &self.pure)
+            .field("deterministic", &self.deterministic)
+            .finish()
+    }
+}
+
+/// Named functions that expressions can call
+#[derive(Clone, Default)]
+pub struct FunctionRegistry {
+    functions: HashMap<String, Function>,
+    #[cfg(feature = "caching")]
+    memo: Arc<Cache<String, Value>>,
+}
+
+impl FunctionRegistry {
+    /// Creates an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `function` under its name, replacing any previous
+    /// registration
+    pub fn register(&mut self, function: Function) {
+        self.functions.insert(function.name.clone(), function);
+        // Results of a replaced function must not leak into the new one
+        #[cfg(feature = "caching")]
+        {
+            self.memo = Arc::new(Cache::new(None, Some(MEMO_CAPACITY)));
+        }
+    }
+
+    /// Registers every function of `other`, replacing functions of the same
+    /// name
+    pub fn extend(&mut self, other: &FunctionRegistry) {
+        for function in other.functions.values() {
+            self.register(function.clone());
+        }
+    }
+
+    /// Returns the function registered under `name`
+    pub fn get(&self, name: &str) -> Option<&Function> {
+        self.functions.get(name)
+    }
+
+    /// Returns true if a function is registered under `name`
+    pub fn contains(&self, name: &str) -> bool {
+        self.functions.contains_key(name)
+    }
+
+    /// Returns true if no functions are registered
+    pub fn is_empty(&self) -> bool {
+        self.functions.is_empty()
+    }
+
+    /// Names of the registered functions, sorted
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.functions.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Calls the function registered under `name`; results of memoizable
+    /// functions are cached by argument values
+    pub fn call(&self, name: &str, args: &[Value], span: Span) -> Result<Value> {
+        let function = self.get(name).ok_or_else(|| unknown_function(name, span))?;
+        #[cfg(feature = "caching")]
+        if function.is_memoizable() {
+            // Debug output keeps `1` and `1.0` apart, unlike Display
+            let key = format!("{}{:?}", name, args);
+            if let Some(value) = self.memo.get(&key) {
+                return Ok(value);
+            }
+            let value = function.call(args, span)?;
+            self.memo.insert(key, value.clone());
+            return Ok(value);
+        }
+        function.call(args, span)
+    }
+}
+
+impl fmt::Debug for FunctionRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FunctionRegistry")
+            .field("functions", &self.names())
+            .finish()
+    }
+}
+
+/// Resolves `name` in `functions`, falling back to the built-ins
+pub(crate) fn lookup<'a>(
+    functions: Option<&'a FunctionRegistry>,
+    name: &str,
+) -> Option<&'a Function> {
+    functions
+        .and_then(|functions| functions.get(name))
+        .or_else(|| builtins().get(name))
+}
+
+/// Whether `expr` reads no facts and calls only deterministic built-ins, so
+/// it evaluates to the same value every time
+pub(crate) fn is_constant(expr: &Expr) -> bool {
+    match &expr.kind {
+        ExprKind::Literal(_) => true,
+        ExprKind::Path(_) => false,
+        ExprKind::List(items) => items.iter().all(is_constant),
+        ExprKind::Call { function, args } => {
+            lookup(None, function).is_some_and(|function| function.is_deterministic())
+                && args.iter().all(is_constant)
+        }
+        ExprKind::Unary { operand, .. } => is_constant(operand),
+        ExprKind::Binary { lhs, rhs, .. } => is_constant(lhs) && is_constant(rhs),
+    }
+}
+
+/// Calls `name` through the registry attached to `context`, falling back to
+/// the built-ins, which are not memoized. Like the operators, a built-in
+/// called with `null` for a required argument returns `null`.
+pub(crate) fn call(context: &RuleContext, name: &str, args: &[Value], span: Span) -> Result<Value> {
+    match context.functions() {
+        Some(functions) if functions.contains(name) => functions.call(name, args, span),
+        _ => match builtins().get(name) {
+            Some(function) if has_null_argument(function, args) => Ok(Value::Null),
+            Some(function) => function.call(args, span),
+            None => Err(unknown_function(name, span)),
+        },
+    }
+}
+
+/// Whether a call of the right arity passes `null` for a parameter that is
+/// not nullable
+pub(crate) fn has_null_argument(function: &Function, args: &[Value]) -> bool {
+    function.arity().contains(&args.len())
+        && args
+            .iter()
+            .zip(function.params())
+            .any(|(arg, param)| arg.is_null() && !param.nullable)
+}
+
+fn unknown_function(name: &str, span: Span) -> Error {
+    eval_error(span, format!("unknown function `{}`", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::Type;
+    use crate::expr::{evaluate, parse};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn registry(calls: Arc<AtomicUsize>) -> FunctionRegistry {
+        let mut functions = FunctionRegistry::new();
+        functions.register(
+            Function::new("double", [Type::Float], Type::Float, move |args| {
+                calls.fetch_add(1, Ordering::Relaxed);
+                Ok(Value::Float(args[0].as_f64().unwrap_or(0.0) * 2.0))
+            })
+            .pure()
+            .deterministic(),
+        );
+        functions.register(Function::new(
+            "label",
+            [Field::new(Type::String).nullable()],
+            Type::Int,
+            |args| Ok(args[0].clone()),
+        ));
+        functions
+    }
+
+    #[test]
+    fn test_calls_are_type_checked() {
+        let context = RuleContext::new()
+            .with_fact("age", 21)
+            .with_functions(registry(Arc::default()));
+        let eval = |source: &str| evaluate(&parse(source).unwrap(), &context);
+        assert_eq!(eval("double(age) > 40").unwrap(), Value::Bool(true));
+        let cases = [
+            (
+                "double('x')",
+                "argument 1 of `double` must be float, found string `\"x\"`",
+            ),
+            ("double(1, 2)", "`double` takes 1 argument, found 2"),
+            (
+                "label('x')",
+                "`label` returned string `\"x\"`, declared int",
+            ),
+            ("triple(1)", "unknown function `triple`"),
+        ];
+        for (source, expected) in cases {
+            match eval(source) {
+                Err(Error::Evaluation { message, span }) => {
+                    assert_eq!(message, expected);
+                    assert_eq!(span, Span::new(0, source.len()));
+                }
+                other => panic!("{}: expected evaluation error, got {:?}", source, other),
+            }
+        }
+        // The nullable parameter admits null, but the declared int result does not
+        assert!(eval("label(missing)").is_err());
+        // ...and it may be left out entirely
+        let label = context.functions().and_then(|f| f.get("label")).unwrap();
+        assert_eq!(label.arity(), 0..=1);
+        match eval("label()") {
+            Err(Error::Evaluation { message, .. }) => {
+                assert_eq!(message, "`label` returned null `null`, declared int")
+            }
+            other => panic!("expected evaluation error, got {:?}", other),
+        }
+        assert!(eval("label('a', 'b')")
+            .unwrap_err()
+            .to_string()
+            .contains("`label` takes 0 to 1 arguments, found 2"));
+    }
+
+    #[cfg(feature = "caching")]
+    #[test]
+    fn test_memoizable_calls_are_cached() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let functions = registry(calls.clone());
+        assert!(functions.get("double").unwrap().is_memoizable());
+        assert!(!functions.get("label").unwrap().is_memoizable());
+        for _ in 0..3 {
+            functions
+                .call("double", &[Value::Int(2)], Span::default())
+                .unwrap();
+        }
+        functions
+            .call("double", &[Value::Float(2.0)], Span::default())
+            .unwrap();
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+        assert_eq!(
+            functions.get("double").unwrap().to_string(),
+            "double(float) -> float"
+        );
+    }
+}
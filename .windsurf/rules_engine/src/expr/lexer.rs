@@ -0,0 +1,356 @@
+//! Tokenizer for rule expressions
+
+use crate::error::{Error, Result};
+use crate::expr::ast::Span;
+
+/// The kinds of tokens produced by the lexer
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    /// Integer literal
+    Int(i64),
+    /// Floating point literal
+    Float(f64),
+    /// Quoted string literal with escapes resolved
+    Str(String),
+    /// Identifier (field name)
+    Ident(String),
+    /// `true`
+    True,
+    /// `false`
+    False,
+    /// `null`
+    Null,
+    /// `&&` or `and`
+    And,
+    /// `||` or `or`
+    Or,
+    /// `!` or `not`
+    Not,
+    /// `in`
+    In,
+    /// `==`
+    EqEq,
+    /// `!=`
+    NotEq,
+    /// `<`
+    Lt,
+    /// `<=`
+    Le,
+    /// `>`
+    Gt,
+    /// `>=`
+    Ge,
+    /// `+`
+    Plus,
+    /// `-`
+    Minus,
+    /// `*`
+    Star,
+    /// `/`
+    Slash,
+    /// `%`
+    Percent,
+    /// `(`
+    LParen,
+    /// `)`
+    RParen,
+    /// `[`
+    LBracket,
+    /// `]`
+    RBracket,
+    /// `,`
+    Comma,
+    /// `.`
+    Dot,
+    /// End of input
+    Eof,
+}
+
+impl TokenKind {
+    /// Short human-readable description used in error messages
+    pub fn describe(&self) -> String {
+        match self {
+            TokenKind::Int(i) => format!("integer `{}`", i),
+            TokenKind::Float(x) => format!("number `{}`", x),
+            TokenKind::Str(s) => format!("string {:?}", s),
+            TokenKind::Ident(name) => format!("identifier `{}`", name),
+            TokenKind::Eof => "end of expression".to_string(),
+            other => format!("`{}`", other.symbol()),
+        }
+    }
+
+    fn symbol(&self) -> &'static str {
+        match self {
+            TokenKind::True => "true",
+            TokenKind::False => "false",
+            TokenKind::Null => "null",
+            TokenKind::And => "&&",
+            TokenKind::Or => "||",
+            TokenKind::Not => "!",
+            TokenKind::In => "in",
+            TokenKind::EqEq => "==",
+            TokenKind::NotEq => "!=",
+            TokenKind::Lt => "<",
+            TokenKind::Le => "<=",
+            TokenKind::Gt => ">",
+            TokenKind::Ge => ">=",
+            TokenKind::Plus => "+",
+            TokenKind::Minus => "-",
+            TokenKind::Star => "*",
+            TokenKind::Slash => "/",
+            TokenKind::Percent => "%",
+            TokenKind::LParen => "(",
+            TokenKind::RParen => ")",
+            TokenKind::LBracket => "[",
+            TokenKind::RBracket => "]",
+            TokenKind::Comma => ",",
+            TokenKind::Dot => ".",
+            _ => "",
+        }
+    }
+}
+
+/// A token together with its location in the source
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    /// What was lexed
+    pub kind: TokenKind,
+    /// Where it was lexed from
+    pub span: Span,
+}
+
+/// Builds a syntax error pointing at `span` within `source`
+pub(crate) fn syntax_error(source: &str, span: Span, message: impl Into<String>) -> Error {
+    let (line, column) = span.line_col(source);
+    Error::Syntax {
+        message: message.into(),
+        span,
+        line,
+        column,
+    }
+}
+
+/// Splits an expression into tokens, always ending with [`TokenKind::Eof`]
+pub fn tokenize(source: &str) -> Result<Vec<Token>> {
+    Lexer { source, pos: 0 }.run()
+}
+
+struct Lexer<'a> {
+    source: &'a str,
+    pos: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn run(mut self) -> Result<Vec<Token>> {
+        let mut tokens = Vec::new();
+        loop {
+            self.skip_whitespace();
+            let start = self.pos;
+            let Some(c) = self.peek() else {
+                tokens.push(Token {
+                    kind: TokenKind::Eof,
+                    span: Span::new(start, start),
+                });
+                return Ok(tokens);
+            };
+
+            let kind = match c {
+                '0'..='9' => self.number()?,
+                '"' | '\'' => self.string(c)?,
+                c if c.is_alphabetic() || c == '_' => self.word(),
+                _ => self.punct(c)?,
+            };
+            tokens.push(Token {
+                kind,
+                span: Span::new(start, self.pos),
+            });
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.source[self.pos..].chars().next()
+    }
+
+    fn peek_second(&self) -> Option<char> {
+        self.source[self.pos..].chars().nth(1)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(c) if c.is_whitespace()) {
+            self.bump();
+        }
+    }
+
+    fn error(&self, start: usize, message: impl Into<String>) -> Error {
+        syntax_error(self.source, Span::new(start, self.pos.max(start + 1)), message)
+    }
+
+    fn number(&mut self) -> Result<TokenKind> {
+        let start = self.pos;
+        while matches!(self.peek(), Some('0'..='9' | '_')) {
+            self.bump();
+        }
+        let mut is_float = false;
+        // Only treat `.` as a decimal point when a digit follows, so `items.0` still lexes as a path
+        if self.peek() == Some('.') && matches!(self.peek_second(), Some('0'..='9')) {
+            is_float = true;
+            self.bump();
+            while matches!(self.peek(), Some('0'..='9' | '_')) {
+                self.bump();
+            }
+        }
+        if matches!(self.peek(), Some('e' | 'E')) {
+            is_float = true;
+            self.bump();
+            if matches!(self.peek(), Some('+' | '-')) {
+                self.bump();
+            }
+            if !matches!(self.peek(), Some('0'..='9')) {
+                return Err(self.error(start, "exponent requires at least one digit"));
+            }
+            while matches!(self.peek(), Some('0'..='9')) {
+                self.bump();
+            }
+        }
+
+        let text: String = self.source[start..self.pos].chars().filter(|&c| c != '_').collect();
+        if is_float {
+            text.parse()
+                .map(TokenKind::Float)
+                .map_err(|_| self.error(start, format!("invalid number `{}`", text)))
+        } else {
+            text.parse()
+                .map(TokenKind::Int)
+                .map_err(|_| self.error(start, format!("integer `{}` is out of range", text)))
+        }
+    }
+
+    fn string(&mut self, quote: char) -> Result<TokenKind> {
+        let start = self.pos;
+        self.bump();
+        let mut value = String::new();
+        loop {
+            match self.bump() {
+                None => return Err(self.error(start, "unterminated string literal")),
+                Some(c) if c == quote => return Ok(TokenKind::Str(value)),
+                Some('\\') => {
+                    let escape_start = self.pos - 1;
+                    let escaped = match self.bump() {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('0') => '\0',
+                        Some(c @ ('\\' | '"' | '\'')) => c,
+                        Some(other) => {
+                            return Err(self.error(
+                                escape_start,
+                                format!("unknown escape sequence `\\{}`", other),
+                            ))
+                        }
+                        None => return Err(self.error(start, "unterminated string literal")),
+                    };
+                    value.push(escaped);
+                }
+                Some(c) => value.push(c),
+            }
+        }
+    }
+
+    fn word(&mut self) -> TokenKind {
+        let start = self.pos;
+        while matches!(self.peek(), Some(c) if c.is_alphanumeric() || c == '_') {
+            self.bump();
+        }
+        match &self.source[start..self.pos] {
+            "true" => TokenKind::True,
+            "false" => TokenKind::False,
+            "null" => TokenKind::Null,
+            "and" => TokenKind::And,
+            "or" => TokenKind::Or,
+            "not" => TokenKind::Not,
+            "in" => TokenKind::In,
+            ident => TokenKind::Ident(ident.to_string()),
+        }
+    }
+
+    fn punct(&mut self, c: char) -> Result<TokenKind> {
+        let start = self.pos;
+        self.bump();
+        let next = self.peek();
+        let (kind, two_chars) = match (c, next) {
+            ('&', Some('&')) => (TokenKind::And, true),
+            ('|', Some('|')) => (TokenKind::Or, true),
+            ('=', Some('=')) => (TokenKind::EqEq, true),
+            ('!', Some('=')) => (TokenKind::NotEq, true),
+            ('<', Some('=')) => (TokenKind::Le, true),
+            ('>', Some('=')) => (TokenKind::Ge, true),
+            ('!', _) => (TokenKind::Not, false),
+            ('<', _) => (TokenKind::Lt, false),
+            ('>', _) => (TokenKind::Gt, false),
+            ('+', _) => (TokenKind::Plus, false),
+            ('-', _) => (TokenKind::Minus, false),
+            ('*', _) => (TokenKind::Star, false),
+            ('/', _) => (TokenKind::Slash, false),
+            ('%', _) => (TokenKind::Percent, false),
+            ('(', _) => (TokenKind::LParen, false),
+            (')', _) => (TokenKind::RParen, false),
+            ('[', _) => (TokenKind::LBracket, false),
+            (']', _) => (TokenKind::RBracket, false),
+            (',', _) => (TokenKind::Comma, false),
+            ('.', _) => (TokenKind::Dot, false),
+            ('=', _) => return Err(self.error(start, "unexpected `=`, did you mean `==`?")),
+            ('&', _) => return Err(self.error(start, "unexpected `&`, did you mean `&&`?")),
+            ('|', _) => return Err(self.error(start, "unexpected `|`, did you mean `||`?")),
+            (other, _) => return Err(self.error(start, format!("unexpected character `{}`", other))),
+        };
+        if two_chars {
+            self.bump();
+        }
+        Ok(kind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(source: &str) -> Vec<TokenKind> {
+        tokenize(source).unwrap().into_iter().map(|t| t.kind).collect()
+    }
+
+    #[test]
+    fn test_tokenize_operators_and_literals() {
+        assert_eq!(
+            kinds("a.b >= 1.5 && 'x' != \"y\""),
+            vec![
+                TokenKind::Ident("a".into()),
+                TokenKind::Dot,
+                TokenKind::Ident("b".into()),
+                TokenKind::Ge,
+                TokenKind::Float(1.5),
+                TokenKind::And,
+                TokenKind::Str("x".into()),
+                TokenKind::NotEq,
+                TokenKind::Str("y".into()),
+                TokenKind::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_reports_position() {
+        match tokenize("a == 1\n  && b = 2") {
+            Err(Error::Syntax { line, column, span, .. }) => {
+                assert_eq!((line, column), (2, 8));
+                assert_eq!(span.start, 14);
+            }
+            other => panic!("expected syntax error, got {:?}", other),
+        }
+    }
+}
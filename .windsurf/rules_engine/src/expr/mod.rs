@@ -0,0 +1,37 @@
+//! Expression language for rule conditions
+//!
+//! Rule expressions such as `customer.age >= 18 && customer.tier in ["gold", "silver"]`
+//! are tokenized by [`lexer`], parsed by [`parser`] and represented as the typed
+//! [`ast::Expr`] tree. Syntax errors are reported as [`crate::Error::Syntax`] with
+//! the byte span, line and column of the offending token. Parsed expressions are
+//! evaluated against a [`RuleContext`](crate::rule::RuleContext) by [`eval`].
+//!
+//! # Grammar
+//! - Literals: `null`, `true`, `false`, integers, floats and quoted strings
+//! - Field paths: `customer.address.city`, `orders[0].total`
+//! - Lists: `[1, 2, 3]`
+//! - Comparison: `==`, `!=`, `<`, `<=`, `>`, `>=`
+//! - Membership: `in`, `not in`
+//! - Boolean: `&&`/`and`, `||`/`or`, `!`/`not`
+//! - Arithmetic: `+`, `-`, `*`, `/`, `%`
+//! - Function calls: `lower(customer.tier)`, resolved against the
+//!   [`FunctionRegistry`] attached to the context and the [`builtins`]
+//! - Grouping with parentheses
+
+pub mod ast;
+pub mod builtins;
+pub mod datetime;
+pub mod eval;
+pub mod function;
+pub mod lexer;
+pub mod parser;
+pub mod partial;
+pub mod sql;
+pub mod trace;
+
+pub use self::ast::{BinaryOp, Expr, ExprKind, Literal, Path, PathSegment, Span, UnaryOp};
+pub use self::eval::{evaluate, Evaluator};
+pub use self::function::{Function, FunctionRegistry};
+pub use self::parser::parse;
+pub use self::partial::{PartialEvaluator, Residual};
+pub use self::trace::{FactRead, Trace, TraceNode, TraceOutcome, Tracer};
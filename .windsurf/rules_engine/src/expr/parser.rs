@@ -0,0 +1,458 @@
+//! Recursive-descent parser for rule expressions
+//!
+//! Operator precedence, from loosest to tightest:
+//!
+//! | Level | Operators                                   |
+//! |-------|---------------------------------------------|
+//! | 1     | `\|\|`, `or`                                |
+//! | 2     | `&&`, `and`                                 |
+//! | 3     | `!`, `not` (prefix)                         |
+//! | 4     | `==` `!=` `<` `<=` `>` `>=` `in` `not in`   |
+//! | 5     | `+` `-`                                     |
+//! | 6     | `*` `/` `%`                                 |
+//! | 7     | `-` (prefix)                                |
+//!
+//! Comparisons do not chain: `a < b < c` is a syntax error.
+
+use std::str::FromStr;
+
+use crate::error::{Error, Result};
+use crate::expr::ast::{BinaryOp, Expr, ExprKind, Literal, Path, PathSegment, Span, UnaryOp};
+use crate::expr::lexer::{syntax_error, tokenize, Token, TokenKind};
+
+impl FromStr for Path {
+    type Err = Error;
+
+    /// Parses a standalone field path such as `customer.orders[0].total`
+    fn from_str(source: &str) -> Result<Path> {
+        let expr = parse(source)?;
+        match expr.kind {
+            ExprKind::Path(path) => Ok(path),
+            _ => Err(syntax_error(source, expr.span, "expected a field path")),
+        }
+    }
+}
+
+/// Maximum nesting depth accepted before bailing out, to protect the stack
+const MAX_DEPTH: usize = 128;
+
+/// Parses an expression source string into an AST
+pub fn parse(source: &str) -> Result<Expr> {
+    let tokens = tokenize(source)?;
+    let mut parser = Parser {
+        source,
+        tokens,
+        pos: 0,
+        depth: 0,
+    };
+    let expr = parser.expression()?;
+    let trailing = parser.peek();
+    if trailing.kind != TokenKind::Eof {
+        return Err(parser.error_at(
+            trailing.span,
+            format!("unexpected {} after end of expression", trailing.kind.describe()),
+        ));
+    }
+    Ok(expr)
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> &Token {
+        // The token stream always ends with Eof, which is never consumed
+        &self.tokens[self.pos.min(self.tokens.len() - 1)]
+    }
+
+    fn peek_kind_at(&self, offset: usize) -> &TokenKind {
+        &self.tokens[(self.pos + offset).min(self.tokens.len() - 1)].kind
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.peek().clone();
+        if token.kind != TokenKind::Eof {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn eat(&mut self, kind: &TokenKind) -> bool {
+        if &self.peek().kind == kind {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, kind: TokenKind, context: &str) -> Result<Token> {
+        if self.peek().kind == kind {
+            return Ok(self.advance());
+        }
+        let found = self.peek().clone();
+        Err(self.error_at(
+            found.span,
+            format!("expected {} {}, found {}", kind.describe(), context, found.kind.describe()),
+        ))
+    }
+
+    fn error_at(&self, span: Span, message: impl Into<String>) -> Error {
+        syntax_error(self.source, span, message)
+    }
+
+    fn enter(&mut self) -> Result<()> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            let span = self.peek().span;
+            return Err(self.error_at(span, "expression is nested too deeply"));
+        }
+        Ok(())
+    }
+
+    fn expression(&mut self) -> Result<Expr> {
+        self.enter()?;
+        let expr = self.or();
+        self.depth -= 1;
+        expr
+    }
+
+    fn binary(op: BinaryOp, lhs: Expr, rhs: Expr) -> Expr {
+        let span = lhs.span.merge(rhs.span);
+        Expr::new(
+            ExprKind::Binary {
+                op,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            },
+            span,
+        )
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        let mut lhs = self.and()?;
+        while self.eat(&TokenKind::Or) {
+            let rhs = self.and()?;
+            lhs = Self::binary(BinaryOp::Or, lhs, rhs);
+        }
+        Ok(lhs)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut lhs = self.not()?;
+        while self.eat(&TokenKind::And) {
+            let rhs = self.not()?;
+            lhs = Self::binary(BinaryOp::And, lhs, rhs);
+        }
+        Ok(lhs)
+    }
+
+    fn not(&mut self) -> Result<Expr> {
+        if self.peek().kind == TokenKind::Not {
+            let start = self.advance().span;
+            self.enter()?;
+            let operand = self.not();
+            self.depth -= 1;
+            let operand = operand?;
+            let span = start.merge(operand.span);
+            return Ok(Expr::new(
+                ExprKind::Unary {
+                    op: UnaryOp::Not,
+                    operand: Box::new(operand),
+                },
+                span,
+            ));
+        }
+        self.comparison()
+    }
+
+    fn comparison_op(&mut self) -> Option<BinaryOp> {
+        let op = match self.peek().kind {
+            TokenKind::EqEq => BinaryOp::Eq,
+            TokenKind::NotEq => BinaryOp::Ne,
+            TokenKind::Lt => BinaryOp::Lt,
+            TokenKind::Le => BinaryOp::Le,
+            TokenKind::Gt => BinaryOp::Gt,
+            TokenKind::Ge => BinaryOp::Ge,
+            TokenKind::In => BinaryOp::In,
+            TokenKind::Not if self.peek_kind_at(1) == &TokenKind::In => {
+                self.advance();
+                BinaryOp::NotIn
+            }
+            _ => return None,
+        };
+        self.advance();
+        Some(op)
+    }
+
+    fn comparison(&mut self) -> Result<Expr> {
+        let lhs = self.additive()?;
+        let Some(op) = self.comparison_op() else {
+            return Ok(lhs);
+        };
+        let rhs = self.additive()?;
+        let expr = Self::binary(op, lhs, rhs);
+
+        let next = self.peek().clone();
+        if self.comparison_op().is_some() {
+            return Err(self.error_at(
+                next.span,
+                "comparison operators cannot be chained; combine them with `&&`",
+            ));
+        }
+        Ok(expr)
+    }
+
+    fn additive(&mut self) -> Result<Expr> {
+        let mut lhs = self.multiplicative()?;
+        loop {
+            let op = match self.peek().kind {
+                TokenKind::Plus => BinaryOp::Add,
+                TokenKind::Minus => BinaryOp::Sub,
+                _ => return Ok(lhs),
+            };
+            self.advance();
+            let rhs = self.multiplicative()?;
+            lhs = Self::binary(op, lhs, rhs);
+        }
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr> {
+        let mut lhs = self.unary()?;
+        loop {
+            let op = match self.peek().kind {
+                TokenKind::Star => BinaryOp::Mul,
+                TokenKind::Slash => BinaryOp::Div,
+                TokenKind::Percent => BinaryOp::Rem,
+                _ => return Ok(lhs),
+            };
+            self.advance();
+            let rhs = self.unary()?;
+            lhs = Self::binary(op, lhs, rhs);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if self.peek().kind != TokenKind::Minus {
+            return self.primary();
+        }
+        let start = self.advance().span;
+        self.enter()?;
+        let operand = self.unary();
+        self.depth -= 1;
+        let operand = operand?;
+        let span = start.merge(operand.span);
+        // Fold negative numeric literals directly so `-5` is a literal, not an operation
+        let kind = match operand.kind {
+            ExprKind::Literal(Literal::Int(i)) => ExprKind::Literal(Literal::Int(-i)),
+            ExprKind::Literal(Literal::Float(x)) => ExprKind::Literal(Literal::Float(-x)),
+            _ => ExprKind::Unary {
+                op: UnaryOp::Neg,
+                operand: Box::new(operand),
+            },
+        };
+        Ok(Expr::new(kind, span))
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
+        let token = self.advance();
+        let literal = |lit| Ok(Expr::new(ExprKind::Literal(lit), token.span));
+        match token.kind {
+            TokenKind::Int(i) => literal(Literal::Int(i)),
+            TokenKind::Float(x) => literal(Literal::Float(x)),
+            TokenKind::Str(ref s) => literal(Literal::String(s.clone())),
+            TokenKind::True => literal(Literal::Bool(true)),
+            TokenKind::False => literal(Literal::Bool(false)),
+            TokenKind::Null => literal(Literal::Null),
+            TokenKind::Ident(name) if self.peek().kind == TokenKind::LParen => {
+                self.call(name, token.span)
+            }
+            TokenKind::Ident(name) => self.path(name, token.span),
+            // `in(x, list)` is the built-in spelling of the `in` operator
+            TokenKind::In if self.peek().kind == TokenKind::LParen => {
+                self.call("in".to_string(), token.span)
+            }
+            TokenKind::LParen => {
+                let inner = self.expression()?;
+                self.expect(TokenKind::RParen, "to close `(`")?;
+                Ok(inner)
+            }
+            TokenKind::LBracket => self.list(token.span),
+            other => Err(self.error_at(
+                token.span,
+                format!("expected an expression, found {}", other.describe()),
+            )),
+        }
+    }
+
+    fn path(&mut self, root: String, start: Span) -> Result<Expr> {
+        let mut segments = vec![PathSegment::Key(root)];
+        let mut span = start;
+        loop {
+            if self.eat(&TokenKind::Dot) {
+                let token = self.advance();
+                let segment = match token.kind {
+                    TokenKind::Ident(name) => PathSegment::Key(name),
+                    TokenKind::Int(i) if i >= 0 => PathSegment::Index(i as usize),
+                    // Keywords are still valid field names after a dot, e.g. `order.in`
+                    TokenKind::True | TokenKind::False | TokenKind::Null | TokenKind::And
+                    | TokenKind::Or | TokenKind::Not | TokenKind::In => PathSegment::Key(
+                        self.source[token.span.start..token.span.end].to_string(),
+                    ),
+                    other => {
+                        return Err(self.error_at(
+                            token.span,
+                            format!("expected a field name after `.`, found {}", other.describe()),
+                        ))
+                    }
+                };
+                segments.push(segment);
+                span = span.merge(token.span);
+            } else if self.peek().kind == TokenKind::LBracket {
+                self.advance();
+                let token = self.advance();
+                let TokenKind::Int(index) = token.kind else {
+                    return Err(self.error_at(
+                        token.span,
+                        format!("expected a list index, found {}", token.kind.describe()),
+                    ));
+                };
+                if index < 0 {
+                    return Err(self.error_at(token.span, "list index cannot be negative"));
+                }
+                let close = self.expect(TokenKind::RBracket, "to close index")?;
+                segments.push(PathSegment::Index(index as usize));
+                span = span.merge(close.span);
+            } else {
+                return Ok(Expr::new(ExprKind::Path(Path::new(segments)), span));
+            }
+        }
+    }
+
+    fn list(&mut self, start: Span) -> Result<Expr> {
+        let items = self.items(&TokenKind::RBracket)?;
+        let close = self.expect(TokenKind::RBracket, "to close list")?;
+        Ok(Expr::new(ExprKind::List(items), start.merge(close.span)))
+    }
+
+    fn call(&mut self, function: String, start: Span) -> Result<Expr> {
+        self.advance();
+        let args = self.items(&TokenKind::RParen)?;
+        let close = self.expect(TokenKind::RParen, "to close argument list")?;
+        Ok(Expr::new(ExprKind::Call { function, args }, start.merge(close.span)))
+    }
+
+    /// Parses comma-separated expressions up to `close`, allowing a trailing comma
+    fn items(&mut self, close: &TokenKind) -> Result<Vec<Expr>> {
+        let mut items = Vec::new();
+        if &self.peek().kind != close {
+            loop {
+                items.push(self.expression()?);
+                if !self.eat(&TokenKind::Comma) || &self.peek().kind == close {
+                    break;
+                }
+            }
+        }
+        Ok(items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(source: &str) -> String {
+        parse(source).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_parse_precedence() {
+        assert_eq!(roundtrip("1 + 2 * 3 == 7"), "1 + 2 * 3 == 7");
+        assert_eq!(roundtrip("(1 + 2) * 3"), "(1 + 2) * 3");
+        assert_eq!(roundtrip("a || b && c"), "a || b && c");
+        assert_eq!(roundtrip("(a || b) && c"), "(a || b) && c");
+        assert_eq!(roundtrip("a - (b - c)"), "a - (b - c)");
+        assert_eq!(roundtrip("not a.active and b or c"), "!a.active && b || c");
+    }
+
+    #[test]
+    fn test_parse_literals_and_paths() {
+        let expr = parse("customer.orders[0].total >= -12.5").unwrap();
+        let ExprKind::Binary { op, lhs, rhs } = expr.kind else {
+            panic!("expected binary expression");
+        };
+        assert_eq!(op, BinaryOp::Ge);
+        assert_eq!(
+            lhs.kind,
+            ExprKind::Path(Path::new(vec![
+                PathSegment::Key("customer".into()),
+                PathSegment::Key("orders".into()),
+                PathSegment::Index(0),
+                PathSegment::Key("total".into()),
+            ]))
+        );
+        assert_eq!(rhs.kind, ExprKind::Literal(Literal::Float(-12.5)));
+        assert_eq!(expr.span, Span::new(0, 33));
+    }
+
+    #[test]
+    fn test_parse_membership() {
+        let expr = parse("tier not in ['gold', 'silver',]").unwrap();
+        assert!(matches!(expr.kind, ExprKind::Binary { op: BinaryOp::NotIn, .. }));
+        assert_eq!(expr.to_string(), "tier not in [\"gold\", \"silver\"]");
+    }
+
+    #[test]
+    fn test_parse_calls() {
+        let expr = parse("risk_score(customer, 'eu',) > max(1, limit * 2)").unwrap();
+        assert_eq!(expr.to_string(), "risk_score(customer, \"eu\") > max(1, limit * 2)");
+        let ExprKind::Binary { lhs, .. } = expr.kind else {
+            panic!("expected binary expression");
+        };
+        assert!(matches!(&lhs.kind, ExprKind::Call { function, args } if function == "risk_score" && args.len() == 2));
+        assert_eq!(lhs.span, Span::new(0, 27));
+        assert_eq!(roundtrip("now()"), "now()");
+        assert_eq!(roundtrip("in(tier, ['gold']) in [true]"), "in(tier, [\"gold\"]) in [true]");
+    }
+
+    #[test]
+    fn test_string_literals_round_trip() {
+        let cases = ["bell \u{7}", "Zoe\u{308}", "quote \" and \\ back", "tab\t\r\n\0 'single'"];
+        for s in cases {
+            let literal = Literal::String(s.to_string());
+            let text = literal.to_string();
+            assert_eq!(parse(&text).unwrap().kind, ExprKind::Literal(literal), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_parse_errors_are_positioned() {
+        let cases = [
+            ("1 ==", 4, "expected an expression"),
+            ("(a && b", 7, "to close `(`"),
+            ("a < b < c", 6, "cannot be chained"),
+            ("a b", 2, "after end of expression"),
+            ("f(1, 2", 6, "to close argument list"),
+        ];
+        for (source, offset, fragment) in cases {
+            match parse(source) {
+                Err(Error::Syntax { span, message, .. }) => {
+                    assert_eq!(span.start, offset, "{}", source);
+                    assert!(message.contains(fragment), "{}: {}", source, message);
+                }
+                other => panic!("{}: expected syntax error, got {:?}", source, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_rule_test_expression_parses() {
+        let expr = parse("1 == 1").unwrap();
+        assert_eq!(expr.paths().len(), 0);
+        assert_eq!(expr.to_string(), "1 == 1");
+    }
+}
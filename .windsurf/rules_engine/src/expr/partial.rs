@@ -0,0 +1,502 @@
+//! Partial evaluation of expressions against facts known ahead of time
+//!
+//! A [`PartialEvaluator`] is given the facts that are fixed at deployment
+//! time, such as configuration, and folds every sub-expression that depends
+//! only on them and on literals into a literal. What remains is a residual
+//! expression over the facts supplied at runtime. Applied to a rule set with
+//! [`PartialEvaluator::specialize`], rules whose condition folds to `false`
+//! are dropped. Calls to [pure and deterministic](crate::expr::Function::is_memoizable)
+//! functions registered on the known context, and of the built-ins, are
+//! folded too.
+//!
+//! Folding never changes whether an expression fails: sub-expressions that
+//! fail to evaluate, and `&&`/`||` operands whose type is unknown, are kept
+//! as written. Known values that cannot be written as literals, such as maps
+//! and decimals, are left as fact reads, so those facts must still be present
+//! at runtime.
+//!
+//! ```
+//! use windsurf_rules::expr::{parse, PartialEvaluator};
+//! use windsurf_rules::rule::{RuleContext, Value};
+//!
+//! let config = RuleContext::new()
+//!     .with_fact("config", [("threshold", 100), ("enabled", 1)].into_iter().collect::<Value>());
+//! let partial = PartialEvaluator::new(&config);
+//! let residual = partial.simplify(&parse("config.enabled == 1 && order.total > config.threshold * 2")?);
+//! assert_eq!(residual.to_string(), "order.total > 200");
+//! # Ok::<(), windsurf_rules::Error>(())
+//! ```
+
+use std::collections::BTreeMap;
+
+use crate::error::Result;
+use crate::expr::ast::{BinaryOp, Expr, ExprKind, Literal, Path, UnaryOp};
+use crate::expr::eval::{
+    apply_binary, apply_call, apply_unary, literal_value, COALESCE, EXISTS,
+};
+use crate::expr::function::{self, Function};
+use crate::expr::parse;
+use crate::rule::{Action, MissingPolicy, Rule as RuleDefinition, RuleContext, Value};
+
+/// A rule set specialized to known facts
+#[derive(Debug, Clone, Default)]
+pub struct Residual {
+    /// Rules that can still match, with simplified conditions and actions
+    pub rules: Vec<RuleDefinition>,
+    /// Ids of the rules dropped because their condition is always false
+    pub dropped: Vec<String>,
+}
+
+/// Simplifies expressions using facts known ahead of time
+#[derive(Debug, Clone, Copy)]
+pub struct PartialEvaluator<'a> {
+    known: &'a RuleContext,
+}
+
+/// The result of partially evaluating one sub-expression
+enum Partial {
+    Known(Value),
+    Residual(Expr),
+}
+
+impl<'a> PartialEvaluator<'a> {
+    /// Creates a partial evaluator; every top-level fact in `known` is treated
+    /// as fixed, including its missing fields, which read as `null`
+    pub fn new(known: &'a RuleContext) -> Self {
+        Self { known }
+    }
+
+    /// Folds everything in `expr` that does not depend on runtime facts
+    pub fn simplify(&self, expr: &Expr) -> Expr {
+        self.emit(self.partial(expr), expr)
+    }
+
+    /// Simplifies the conditions and action expressions of `rules`. Rules
+    /// whose condition is always false are dropped unless they have
+    /// else-actions; message templates and Rust closures are left as is.
+    pub fn specialize(&self, rules: impl IntoIterator<Item = RuleDefinition>) -> Result<Residual> {
+        let mut residual = Residual::default();
+        for mut rule in rules {
+            let condition = self.simplify(&rule.parse_expression()?);
+            let never = matches!(condition.kind, ExprKind::Literal(Literal::Bool(false)));
+            if never && rule.else_actions.is_empty() {
+                residual.dropped.push(rule.id);
+                continue;
+            }
+            rule.expression = condition.to_string();
+            for action in rule.then_actions.iter_mut().chain(&mut rule.else_actions) {
+                self.specialize_action(action)?;
+            }
+            residual.rules.push(rule);
+        }
+        Ok(residual)
+    }
+
+    fn specialize_action(&self, action: &mut Action) -> Result<()> {
+        let simplify = |source: &mut String| -> Result<()> {
+            *source = self.simplify(&parse(source)?).to_string();
+            Ok(())
+        };
+        match action {
+            Action::Set { value, .. } => simplify(value),
+            Action::Emit { payload, .. } => payload.values_mut().try_for_each(simplify),
+            Action::Call { args, .. } => args.iter_mut().try_for_each(simplify),
+            Action::Remove { .. } | Action::Message { .. } | Action::Native(_) => Ok(()),
+        }
+    }
+
+    fn partial(&self, expr: &Expr) -> Partial {
+        match &expr.kind {
+            ExprKind::Literal(literal) => Partial::Known(literal_value(literal)),
+            ExprKind::Path(path) => match self.known.lookup(path) {
+                Some(value) if self.is_known(path) => Partial::Known(value.clone()),
+                // Missing reads fail under the error policy; keep them
+                None if self.is_known(path)
+                    && self.known.missing_policy() != MissingPolicy::Error =>
+                {
+                    Partial::Known(Value::Null)
+                }
+                _ => Partial::Residual(expr.clone()),
+            },
+            ExprKind::List(items) => {
+                let parts: Vec<Partial> = items.iter().map(|item| self.partial(item)).collect();
+                match known_values(&parts) {
+                    Some(values) => Partial::Known(Value::List(values)),
+                    None => Partial::Residual(Expr::new(
+                        ExprKind::List(self.emit_all(parts, items)),
+                        expr.span,
+                    )),
+                }
+            }
+            ExprKind::Call { function, args } if function == EXISTS => match args.as_slice() {
+                [Expr {
+                    kind: ExprKind::Path(path),
+                    ..
+                }] if self.is_known(path) => {
+                    Partial::Known(Value::Bool(self.known.lookup(path).is_some()))
+                }
+                _ => Partial::Residual(expr.clone()),
+            },
+            ExprKind::Call { function, args } if function == COALESCE => self.coalesce(args, expr),
+            ExprKind::Call { function, args } => {
+                let parts: Vec<Partial> = args.iter().map(|arg| self.partial(arg)).collect();
+                let foldable = function::lookup(self.known.functions(), function)
+                    .is_some_and(Function::is_memoizable);
+                match known_values(&parts) {
+                    Some(values) if foldable => {
+                        match apply_call(self.known, function, &values, expr.span) {
+                            Ok(value) => Partial::Known(value),
+                            Err(_) => Partial::Residual(expr.clone()),
+                        }
+                    }
+                    _ => Partial::Residual(Expr::new(
+                        ExprKind::Call {
+                            function: function.clone(),
+                            args: self.emit_all(parts, args),
+                        },
+                        expr.span,
+                    )),
+                }
+            }
+            ExprKind::Unary { op, operand } => match self.partial(operand) {
+                Partial::Known(value) => match apply_unary(*op, value, expr.span) {
+                    Ok(value) => Partial::Known(value),
+                    Err(_) => Partial::Residual(expr.clone()),
+                },
+                residual => Partial::Residual(Expr::new(
+                    ExprKind::Unary {
+                        op: *op,
+                        operand: Box::new(self.emit(residual, operand)),
+                    },
+                    expr.span,
+                )),
+            },
+            ExprKind::Binary {
+                op: op @ (BinaryOp::And | BinaryOp::Or),
+                lhs,
+                rhs,
+            } => {
+                let short_circuit = *op == BinaryOp::Or;
+                let left = self.partial(lhs);
+                match left {
+                    Partial::Known(Value::Bool(b)) if b == short_circuit => {
+                        Partial::Known(Value::Bool(b))
+                    }
+                    Partial::Known(Value::Bool(_)) => match self.partial(rhs) {
+                        Partial::Known(value @ (Value::Bool(_) | Value::Null)) => {
+                            Partial::Known(value)
+                        }
+                        Partial::Residual(rest) if is_boolean(&rest) => Partial::Residual(rest),
+                        right => self.binary(*op, left, lhs, right, rhs, expr),
+                    },
+                    // Unknown: only the right operand can still decide
+                    Partial::Known(Value::Null) => match self.partial(rhs) {
+                        Partial::Known(Value::Bool(b)) if b == short_circuit => {
+                            Partial::Known(Value::Bool(b))
+                        }
+                        Partial::Known(Value::Bool(_) | Value::Null) => Partial::Known(Value::Null),
+                        right => self.binary(*op, left, lhs, right, rhs, expr),
+                    },
+                    // A non-boolean left operand fails at runtime; keep it
+                    Partial::Known(_) => Partial::Residual(expr.clone()),
+                    Partial::Residual(ref first) => match self.partial(rhs) {
+                        Partial::Known(Value::Bool(b))
+                            if b != short_circuit && is_boolean(first) =>
+                        {
+                            left
+                        }
+                        right => self.binary(*op, left, lhs, right, rhs, expr),
+                    },
+                }
+            }
+            ExprKind::Binary { op, lhs, rhs } => {
+                let (left, right) = (self.partial(lhs), self.partial(rhs));
+                if let (Partial::Known(a), Partial::Known(b)) = (&left, &right) {
+                    if let Ok(value) = apply_binary(*op, a.clone(), b.clone(), expr.span) {
+                        return Partial::Known(value);
+                    }
+                    return Partial::Residual(expr.clone());
+                }
+                self.binary(*op, left, lhs, right, rhs, expr)
+            }
+        }
+    }
+
+    /// Whether `path` reads a fact known ahead of time
+    fn is_known(&self, path: &Path) -> bool {
+        path.root().is_some_and(|root| self.known.get(root).is_some())
+    }
+
+    /// Drops known `null` arguments of `coalesce` and everything after the
+    /// first known non-null one, which is never evaluated
+    fn coalesce(&self, args: &[Expr], expr: &Expr) -> Partial {
+        let mut kept = Vec::new();
+        for arg in args {
+            match self.partial(arg) {
+                Partial::Known(Value::Null) => {}
+                Partial::Known(value) if kept.is_empty() => return Partial::Known(value),
+                Partial::Known(value) => {
+                    kept.push(self.emit(Partial::Known(value), arg));
+                    break;
+                }
+                Partial::Residual(rest) => kept.push(rest),
+            }
+        }
+        if kept.is_empty() && !args.is_empty() {
+            return Partial::Known(Value::Null);
+        }
+        Partial::Residual(Expr::new(
+            ExprKind::Call {
+                function: COALESCE.to_string(),
+                args: kept,
+            },
+            expr.span,
+        ))
+    }
+
+    fn binary(
+        &self,
+        op: BinaryOp,
+        left: Partial,
+        lhs: &Expr,
+        right: Partial,
+        rhs: &Expr,
+        expr: &Expr,
+    ) -> Partial {
+        Partial::Residual(Expr::new(
+            ExprKind::Binary {
+                op,
+                lhs: Box::new(self.emit(left, lhs)),
+                rhs: Box::new(self.emit(right, rhs)),
+            },
+            expr.span,
+        ))
+    }
+
+    fn emit_all(&self, parts: Vec<Partial>, originals: &[Expr]) -> Vec<Expr> {
+        parts
+            .into_iter()
+            .zip(originals)
+            .map(|(part, original)| self.emit(part, original))
+            .collect()
+    }
+
+    /// Turns a partial result back into an expression, falling back to
+    /// `original` for values that have no literal form
+    fn emit(&self, partial: Partial, original: &Expr) -> Expr {
+        match partial {
+            Partial::Residual(expr) => expr,
+            Partial::Known(value) => {
+                to_literal(&value, original).unwrap_or_else(|| original.clone())
+            }
+        }
+    }
+}
+
+/// Whether evaluating `expr` can only produce a boolean or `null` (or fail)
+fn is_boolean(expr: &Expr) -> bool {
+    match &expr.kind {
+        ExprKind::Literal(literal) => matches!(literal, Literal::Bool(_)),
+        ExprKind::Unary { op, .. } => *op == UnaryOp::Not,
+        ExprKind::Binary { op, .. } => !matches!(
+            op,
+            BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem
+        ),
+        ExprKind::Call { function, .. } => function == EXISTS,
+        ExprKind::Path(_) | ExprKind::List(_) => false,
+    }
+}
+
+fn known_values(parts: &[Partial]) -> Option<Vec<Value>> {
+    parts
+        .iter()
+        .map(|part| match part {
+            Partial::Known(value) => Some(value.clone()),
+            Partial::Residual(_) => None,
+        })
+        .collect()
+}
+
+fn to_literal(value: &Value, original: &Expr) -> Option<Expr> {
+    let kind = match value {
+        Value::Null => ExprKind::Literal(Literal::Null),
+        Value::Bool(b) => ExprKind::Literal(Literal::Bool(*b)),
+        Value::Int(i) => ExprKind::Literal(Literal::Int(*i)),
+        Value::Float(x) if x.is_finite() => ExprKind::Literal(Literal::Float(*x)),
+        Value::String(s) => ExprKind::Literal(Literal::String(s.clone())),
+        Value::List(items) => ExprKind::List(
+            items
+                .iter()
+                .map(|item| to_literal(item, original))
+                .collect::<Option<Vec<_>>>()?,
+        ),
+        _ => return None,
+    };
+    Some(Expr::new(kind, original.span))
+}
+
+impl Residual {
+    /// Ids of the remaining rules, in their original order
+    pub fn rule_ids(&self) -> Vec<&str> {
+        self.rules.iter().map(|rule| rule.id.as_str()).collect()
+    }
+
+    /// The remaining rules' simplified conditions, by rule id
+    pub fn expressions(&self) -> BTreeMap<&str, &str> {
+        self.rules
+            .iter()
+            .map(|rule| (rule.id.as_str(), rule.expression.as_str()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::Evaluator;
+
+    fn known() -> RuleContext {
+        RuleContext::new()
+            .with_fact(
+                "config",
+                [
+                    ("limit", Value::Int(100)),
+                    ("region", Value::from("eu")),
+                    ("strict", Value::Bool(true)),
+                ]
+                .into_iter()
+                .collect::<Value>(),
+            )
+            .with_fact("regions", Value::List(vec!["eu".into(), "us".into()]))
+    }
+
+    fn simplify(source: &str) -> String {
+        PartialEvaluator::new(&known())
+            .simplify(&parse(source).unwrap())
+            .to_string()
+    }
+
+    #[test]
+    fn test_known_facts_are_folded() {
+        assert_eq!(simplify("config.limit * 2 + 1"), "201");
+        assert_eq!(
+            simplify("config.region in regions && total > config.limit"),
+            "total > 100"
+        );
+        assert_eq!(simplify("config.strict || total > 1"), "true");
+        assert_eq!(simplify("total > 1 && config.strict"), "total > 1");
+        assert_eq!(simplify("total > 1 || config.strict"), "total > 1 || true");
+        assert_eq!(simplify("[config.region, country]"), "[\"eu\", country]");
+        assert_eq!(simplify("config.missing == null"), "true");
+        assert_eq!(simplify("config.missing > 1 && config.strict"), "null");
+        assert_eq!(
+            simplify("coalesce(region, config.missing, config.limit, country)"),
+            "coalesce(region, 100)"
+        );
+        assert_eq!(simplify("coalesce(config.missing, config.region)"), "\"eu\"");
+        assert_eq!(
+            simplify("exists(config.limit) && !exists(config.missing) && exists(order.id)"),
+            "exists(order.id)"
+        );
+        // Failures and operands of unknown type are kept as written
+        assert_eq!(
+            simplify("config.limit / 0 > total"),
+            "config.limit / 0 > total"
+        );
+        assert_eq!(simplify("config.strict && flag"), "true && flag");
+        assert_eq!(simplify("config.limit && flag"), "config.limit && flag");
+    }
+
+    #[test]
+    fn test_memoizable_calls_are_folded() {
+        use crate::analysis::Type;
+        use crate::expr::{Function, FunctionRegistry};
+
+        let mut functions = FunctionRegistry::new();
+        let double = |args: &[Value]| Ok(Value::Int(args[0].as_f64().unwrap_or(0.0) as i64 * 2));
+        functions.register(
+            Function::new("double", [Type::Int], Type::Int, double)
+                .pure()
+                .deterministic(),
+        );
+        functions.register(Function::new("lookup", [Type::Int], Type::Int, double));
+        let known = known().with_functions(functions);
+        let simplify = |source: &str| {
+            PartialEvaluator::new(&known)
+                .simplify(&parse(source).unwrap())
+                .to_string()
+        };
+        assert_eq!(simplify("total > double(config.limit)"), "total > 200");
+        assert_eq!(
+            simplify("lookup(config.limit) > double(total)"),
+            "lookup(100) > double(total)"
+        );
+        assert_eq!(simplify("double('x') > 1"), "double(\"x\") > 1");
+        assert_eq!(simplify("upper(config.region) == lower(region)"), "\"EU\" == lower(region)");
+    }
+
+    #[test]
+    fn test_residual_expressions_agree_with_full_evaluation() {
+        let runtime = RuleContext::new()
+            .with_fact("total", 150)
+            .with_fact("flag", true);
+        let mut full = known();
+        for (name, value) in [("total", Value::Int(150)), ("flag", Value::Bool(true))] {
+            full = full.with_fact(name, value);
+        }
+        for source in [
+            "config.region in regions && total > config.limit",
+            "!config.strict || total % 7 == 3",
+            "config.strict && flag",
+            "-config.limit < total && [1, config.limit] != []",
+            "config.missing > 1 || flag",
+            "coalesce(config.missing, total) > config.limit",
+        ] {
+            let residual = PartialEvaluator::new(&known()).simplify(&parse(source).unwrap());
+            assert_eq!(
+                Evaluator::new(&runtime).evaluate(&residual).unwrap(),
+                Evaluator::new(&full)
+                    .evaluate(&parse(source).unwrap())
+                    .unwrap(),
+                "{}",
+                source
+            );
+        }
+    }
+
+    fn emit(event: &str, name: &str, source: &str) -> Action {
+        Action::Emit {
+            event: event.into(),
+            payload: [(name.to_string(), source.to_string())]
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_specialize_drops_rules_that_never_match() {
+        let rules = vec![
+            RuleDefinition::new("us_only").with_expression("config.region == 'us' && total > 10"),
+            RuleDefinition::new("us_with_fallback")
+                .with_expression("config.region == 'us'")
+                .otherwise(Action::message("not in the US")),
+            RuleDefinition::new("over_limit")
+                .with_expression("total > config.limit")
+                .then(Action::set("order.flagged", "config.strict"))
+                .then(emit("over_limit", "limit", "config.limit")),
+        ];
+        let residual = PartialEvaluator::new(&known()).specialize(rules).unwrap();
+        assert_eq!(residual.dropped, ["us_only"]);
+        assert_eq!(residual.rule_ids(), ["us_with_fallback", "over_limit"]);
+        assert_eq!(residual.expressions()["us_with_fallback"], "false");
+        assert_eq!(residual.expressions()["over_limit"], "total > 100");
+        assert_eq!(
+            residual.rules[1].then_actions[0],
+            Action::set("order.flagged", "true")
+        );
+        assert_eq!(
+            residual.rules[1].then_actions[1],
+            emit("over_limit", "limit", "100")
+        );
+    }
+}
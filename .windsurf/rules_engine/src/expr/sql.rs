@@ -0,0 +1,767 @@
+//! Translation of rule expressions into SQL `WHERE` predicates
+//!
+//! [`SqlTranslator`] turns a parsed [`Expr`] into a [`SqlPredicate`]: SQL text
+//! with placeholders plus the values to bind to them, in order. Fact paths
+//! become columns, quoted segment by segment (`order.total` becomes
+//! `"order"."total"`) unless mapped with [`SqlTranslator::with_column`].
+//! Sub-expressions that read no facts, such as `timestamp('2024-01-01T00:00:00Z')`
+//! or `10 * 2`, are evaluated up front and bound as a single parameter.
+//!
+//! The translation keeps the engine's semantics where SQL differs: `==` and
+//! `!=` treat `null` as an ordinary value, `/` divides as floating point and
+//! `+` concatenates when either operand produces a string, such as a string
+//! literal or another concatenation; `+` between columns alone is rejected,
+//! since their types are unknown. SQL has no equivalent of a missing
+//! fact, so `exists(path)` tests for a non-null column. Constructs without a
+//! faithful translation, such as list indexing, regular expressions, calls to
+//! clock-dependent or registered functions and durations, fail with
+//! [`Error::SqlTranslation`] pointing at the offending sub-expression.
+//!
+//! ```
+//! use windsurf_rules::expr::{parse, sql::{Postgres, SqlTranslator}};
+//! use windsurf_rules::rule::Value;
+//!
+//! let expr = parse("customer.tier in ['gold', 'silver'] && order.total >= 100")?;
+//! let predicate = SqlTranslator::new(&Postgres)
+//!     .with_column("order.total", "o.total")
+//!     .translate(&expr)?;
+//! assert_eq!(predicate.sql, r#""customer"."tier" IN ($1, $2) AND o.total >= $3"#);
+//! assert_eq!(predicate.params, [Value::from("gold"), Value::from("silver"), Value::from(100)]);
+//! # Ok::<(), windsurf_rules::Error>(())
+//! ```
+
+use std::collections::HashMap;
+
+use crate::error::{Error, Result};
+use crate::expr::ast::{BinaryOp, Expr, ExprKind, Path, PathSegment, Span, UnaryOp};
+use crate::expr::eval::{evaluate, COALESCE, EXISTS};
+use crate::expr::function;
+use crate::rule::{RuleContext, Value};
+
+/// The SQL syntax that differs between databases
+///
+/// The defaults follow ANSI SQL; operands passed to the methods are already
+/// parenthesized where needed. Dialects with positional `?` placeholders must
+/// keep the operands in the order they are given.
+pub trait Dialect {
+    /// Placeholder for the parameter at `index`, counting from 1
+    fn placeholder(&self, index: usize) -> String {
+        let _ = index;
+        "?".to_string()
+    }
+
+    /// Quotes one segment of a column name
+    fn quote_identifier(&self, name: &str) -> String {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+
+    /// Null-safe equality, or inequality if `distinct`
+    fn is_distinct_from(&self, lhs: &str, rhs: &str, distinct: bool) -> String {
+        let not = if distinct { "" } else { "NOT " };
+        format!("{} IS {}DISTINCT FROM {}", lhs, not, rhs)
+    }
+
+    /// Floating point division, even of two integers
+    fn float_division(&self, lhs: &str, rhs: &str) -> String {
+        format!("CAST({} AS DOUBLE PRECISION) / {}", lhs, rhs)
+    }
+
+    /// Remainder of integer division
+    fn remainder(&self, lhs: &str, rhs: &str) -> String {
+        format!("MOD({}, {})", lhs, rhs)
+    }
+
+    /// 1-based position of `needle` in `haystack`, or 0 if absent
+    fn position(&self, needle: &str, haystack: &str) -> String {
+        format!("POSITION({} IN {})", needle, haystack)
+    }
+
+    /// Number of characters in a string
+    fn length(&self, operand: &str) -> String {
+        format!("CHAR_LENGTH({})", operand)
+    }
+}
+
+/// ANSI SQL
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Ansi;
+
+impl Dialect for Ansi {}
+
+/// PostgreSQL, with `$1`-style placeholders
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Postgres;
+
+impl Dialect for Postgres {
+    fn placeholder(&self, index: usize) -> String {
+        format!("${}", index)
+    }
+
+    fn remainder(&self, lhs: &str, rhs: &str) -> String {
+        format!("{} % {}", lhs, rhs)
+    }
+
+    fn length(&self, operand: &str) -> String {
+        format!("LENGTH({})", operand)
+    }
+}
+
+/// SQLite, with `?1`-style placeholders
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sqlite;
+
+impl Dialect for Sqlite {
+    fn placeholder(&self, index: usize) -> String {
+        format!("?{}", index)
+    }
+
+    fn is_distinct_from(&self, lhs: &str, rhs: &str, distinct: bool) -> String {
+        let not = if distinct { "NOT " } else { "" };
+        format!("{} IS {}{}", lhs, not, rhs)
+    }
+
+    fn float_division(&self, lhs: &str, rhs: &str) -> String {
+        format!("CAST({} AS REAL) / {}", lhs, rhs)
+    }
+
+    fn remainder(&self, lhs: &str, rhs: &str) -> String {
+        format!("{} % {}", lhs, rhs)
+    }
+
+    fn position(&self, needle: &str, haystack: &str) -> String {
+        format!("INSTR({}, {})", haystack, needle)
+    }
+
+    fn length(&self, operand: &str) -> String {
+        format!("LENGTH({})", operand)
+    }
+}
+
+/// A parameterized SQL predicate
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SqlPredicate {
+    /// Predicate text, with the dialect's placeholders
+    pub sql: String,
+    /// Values to bind to the placeholders, in order
+    pub params: Vec<Value>,
+}
+
+const LIST_OUTSIDE_IN: &str = "lists are only supported after `in`";
+
+/// Binding strength of SQL operators, loosest first
+mod precedence {
+    pub const OR: u8 = 1;
+    pub const AND: u8 = 2;
+    pub const NOT: u8 = 3;
+    pub const COMPARISON: u8 = 4;
+    pub const ADDITIVE: u8 = 5;
+    pub const MULTIPLICATIVE: u8 = 6;
+    pub const NEGATION: u8 = 7;
+    pub const ATOM: u8 = 8;
+}
+
+/// What a sub-expression produces, deciding whether `+` adds or concatenates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    String,
+    Other,
+    Unknown,
+}
+
+/// Translates expressions into predicates for one dialect
+pub struct SqlTranslator<'a> {
+    dialect: &'a dyn Dialect,
+    columns: HashMap<String, String>,
+}
+
+impl<'a> SqlTranslator<'a> {
+    /// Creates a translator for `dialect`
+    pub fn new(dialect: &'a dyn Dialect) -> Self {
+        Self {
+            dialect,
+            columns: HashMap::new(),
+        }
+    }
+
+    /// Maps a fact path to SQL for its column, inserted verbatim
+    pub fn with_column(mut self, path: impl Into<String>, column: impl Into<String>) -> Self {
+        self.columns.insert(path.into(), column.into());
+        self
+    }
+
+    /// Translates `expr` into a predicate
+    pub fn translate(&self, expr: &Expr) -> Result<SqlPredicate> {
+        let mut predicate = SqlPredicate::default();
+        predicate.sql = self.operand(expr, precedence::OR, &mut predicate.params)?;
+        Ok(predicate)
+    }
+
+    /// Translates `expr`, parenthesized if it binds looser than `min`
+    fn operand(&self, expr: &Expr, min: u8, params: &mut Vec<Value>) -> Result<String> {
+        let (sql, strength) = self.translate_expr(expr, params)?;
+        Ok(if strength < min {
+            format!("({})", sql)
+        } else {
+            sql
+        })
+    }
+
+    fn translate_expr(&self, expr: &Expr, params: &mut Vec<Value>) -> Result<(String, u8)> {
+        if let Some(value) = self.constant(expr)? {
+            return Ok((self.bind(value, expr.span, params)?, precedence::ATOM));
+        }
+        match &expr.kind {
+            ExprKind::Literal(_) => unreachable!("literals are constant"),
+            ExprKind::Path(path) => Ok((self.column(path, expr.span)?, precedence::ATOM)),
+            ExprKind::List(_) => Err(unsupported(expr.span, LIST_OUTSIDE_IN)),
+            ExprKind::Call { function, args } => self.call(function, args, expr.span, params),
+            ExprKind::Unary { op, operand } => Ok(match op {
+                UnaryOp::Not => (
+                    format!(
+                        "NOT {}",
+                        self.operand(operand, precedence::ADDITIVE, params)?
+                    ),
+                    precedence::NOT,
+                ),
+                UnaryOp::Neg => (
+                    format!("-{}", self.operand(operand, precedence::ATOM, params)?),
+                    precedence::NEGATION,
+                ),
+            }),
+            ExprKind::Binary { op, lhs, rhs } => self.binary(*op, lhs, rhs, params),
+        }
+    }
+
+    fn binary(
+        &self,
+        op: BinaryOp,
+        lhs: &Expr,
+        rhs: &Expr,
+        params: &mut Vec<Value>,
+    ) -> Result<(String, u8)> {
+        use precedence::*;
+
+        let (keyword, strength, lhs_min) = match op {
+            BinaryOp::Or => ("OR", OR, OR),
+            BinaryOp::And => ("AND", AND, AND),
+            BinaryOp::Lt => ("<", COMPARISON, ADDITIVE),
+            BinaryOp::Le => ("<=", COMPARISON, ADDITIVE),
+            BinaryOp::Gt => (">", COMPARISON, ADDITIVE),
+            BinaryOp::Ge => (">=", COMPARISON, ADDITIVE),
+            BinaryOp::Add => match (self.kind(lhs)?, self.kind(rhs)?) {
+                (Kind::String, _) | (_, Kind::String) => ("||", ADDITIVE, NEGATION),
+                (Kind::Other, _) | (_, Kind::Other) => ("+", ADDITIVE, ADDITIVE),
+                (Kind::Unknown, Kind::Unknown) => {
+                    return Err(unsupported(
+                        lhs.span.merge(rhs.span),
+                        "`+` between columns of unknown type could add or concatenate",
+                    ))
+                }
+            },
+            BinaryOp::Sub => ("-", ADDITIVE, ADDITIVE),
+            BinaryOp::Mul => ("*", MULTIPLICATIVE, MULTIPLICATIVE),
+            BinaryOp::Eq | BinaryOp::Ne => {
+                return self.equality(lhs, rhs, op == BinaryOp::Ne, params)
+            }
+            BinaryOp::In | BinaryOp::NotIn => {
+                return self.membership(lhs, rhs, op == BinaryOp::NotIn, params)
+            }
+            BinaryOp::Div | BinaryOp::Rem => {
+                let lhs = self.operand(lhs, MULTIPLICATIVE, params)?;
+                let rhs = self.operand(rhs, NEGATION, params)?;
+                let sql = match op {
+                    BinaryOp::Div => self.dialect.float_division(&lhs, &rhs),
+                    _ => self.dialect.remainder(&lhs, &rhs),
+                };
+                return Ok((sql, MULTIPLICATIVE));
+            }
+        };
+        // Concatenation binds differently across databases, so its operands
+        // are always atoms
+        let rhs_min = if keyword == "||" {
+            NEGATION
+        } else {
+            lhs_min.max(strength + 1)
+        };
+        let lhs = self.operand(lhs, lhs_min, params)?;
+        let rhs = self.operand(rhs, rhs_min, params)?;
+        Ok((format!("{} {} {}", lhs, keyword, rhs), strength))
+    }
+
+    /// Null-safe `==`, or `!=` if `distinct`
+    fn equality(
+        &self,
+        lhs: &Expr,
+        rhs: &Expr,
+        distinct: bool,
+        params: &mut Vec<Value>,
+    ) -> Result<(String, u8)> {
+        for (side, other) in [(lhs, rhs), (rhs, lhs)] {
+            if let Some(Value::Null) = self.constant(side)? {
+                let not = if distinct { "NOT " } else { "" };
+                let other = self.operand(other, precedence::ADDITIVE, params)?;
+                return Ok((format!("{} IS {}NULL", other, not), precedence::COMPARISON));
+            }
+        }
+        let lhs = self.operand(lhs, precedence::ADDITIVE, params)?;
+        let rhs = self.operand(rhs, precedence::ADDITIVE, params)?;
+        Ok((
+            self.dialect.is_distinct_from(&lhs, &rhs, distinct),
+            precedence::COMPARISON,
+        ))
+    }
+
+    /// `needle in haystack`, for a list or a string haystack
+    ///
+    /// A null needle makes the result unknown, but a null item only fails to
+    /// match, where SQL's `IN` would make the result unknown as well.
+    fn membership(
+        &self,
+        needle: &Expr,
+        haystack: &Expr,
+        negated: bool,
+        params: &mut Vec<Value>,
+    ) -> Result<(String, u8)> {
+        let not = if negated { "NOT " } else { "" };
+        match (self.constant(haystack)?, &haystack.kind) {
+            (Some(Value::List(values)), _) => {
+                let values: Vec<Value> = values.into_iter().filter(|v| !v.is_null()).collect();
+                if values.is_empty() {
+                    let gate = self.operand(needle, precedence::ADDITIVE, params)?;
+                    return Ok(null_gated(&gate, "1 = 0", negated));
+                }
+                let needle = self.operand(needle, precedence::ADDITIVE, params)?;
+                let items = values
+                    .into_iter()
+                    .map(|value| self.bind(value, haystack.span, params))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok((
+                    format!("{} {}IN ({})", needle, not, items.join(", ")),
+                    precedence::COMPARISON,
+                ))
+            }
+            (None, ExprKind::List(elements)) => {
+                // Any item may be a null column
+                let gate = self.operand(needle, precedence::ADDITIVE, params)?;
+                let needle = self.operand(needle, precedence::ADDITIVE, params)?;
+                let items = elements
+                    .iter()
+                    .map(|element| self.operand(element, precedence::ADDITIVE, params))
+                    .collect::<Result<Vec<_>>>()?;
+                let test = format!("COALESCE({} IN ({}), FALSE)", needle, items.join(", "));
+                Ok(null_gated(&gate, &test, negated))
+            }
+            // Columns hold scalars, so any other haystack is searched as a string
+            (Some(Value::String(_)) | None, _) => {
+                let needle = self.operand(needle, precedence::ADDITIVE, params)?;
+                let haystack = self.operand(haystack, precedence::OR, params)?;
+                let comparison = if negated { "=" } else { ">" };
+                let sql = format!(
+                    "{} {} 0",
+                    self.dialect.position(&needle, &haystack),
+                    comparison
+                );
+                Ok((sql, precedence::COMPARISON))
+            }
+            (Some(other), _) => Err(unsupported(
+                haystack.span,
+                format!("`in` needs a list or string, not {}", other.type_name()),
+            )),
+        }
+    }
+
+    fn call(
+        &self,
+        name: &str,
+        args: &[Expr],
+        span: Span,
+        params: &mut Vec<Value>,
+    ) -> Result<(String, u8)> {
+        let arity = |count: usize| {
+            if args.len() == count {
+                Ok(())
+            } else {
+                Err(unsupported(
+                    span,
+                    format!("`{}` expects {} arguments here", name, count),
+                ))
+            }
+        };
+        let sql = match name {
+            EXISTS => match args {
+                [Expr {
+                    kind: ExprKind::Path(path),
+                    span,
+                }] => {
+                    return Ok((
+                        format!("{} IS NOT NULL", self.column(path, *span)?),
+                        precedence::COMPARISON,
+                    ))
+                }
+                _ => return Err(unsupported(span, "`exists` takes a single fact path")),
+            },
+            COALESCE => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.operand(arg, precedence::OR, params))
+                    .collect::<Result<Vec<_>>>()?;
+                format!("COALESCE({})", args.join(", "))
+            }
+            "lower" | "upper" | "trim" => {
+                arity(1)?;
+                format!(
+                    "{}({})",
+                    name.to_uppercase(),
+                    self.operand(&args[0], precedence::OR, params)?
+                )
+            }
+            "length" => {
+                arity(1)?;
+                self.dialect
+                    .length(&self.operand(&args[0], precedence::OR, params)?)
+            }
+            "contains" => {
+                arity(2)?;
+                return self.membership(&args[1], &args[0], false, params);
+            }
+            "starts_with" => {
+                arity(2)?;
+                let haystack = self.operand(&args[0], precedence::OR, params)?;
+                let prefix = self.operand(&args[1], precedence::OR, params)?;
+                let sql = format!("{} = 1", self.dialect.position(&prefix, &haystack));
+                return Ok((sql, precedence::COMPARISON));
+            }
+            _ => {
+                let reason = match function::lookup(None, name) {
+                    Some(function) if !function.is_deterministic() => {
+                        format!("`{}()` depends on when it is evaluated", name)
+                    }
+                    Some(_) => format!("function `{}` has no SQL translation", name),
+                    None => format!("function `{}` is not a built-in", name),
+                };
+                return Err(unsupported(span, reason));
+            }
+        };
+        Ok((sql, precedence::ATOM))
+    }
+
+    fn column(&self, path: &Path, span: Span) -> Result<String> {
+        if let Some(column) = self.columns.get(&path.to_string()) {
+            return Ok(column.clone());
+        }
+        path.segments
+            .iter()
+            .map(|segment| match segment {
+                PathSegment::Key(key) => Ok(self.dialect.quote_identifier(key)),
+                PathSegment::Index(index) => Err(unsupported(
+                    span,
+                    format!("list index `[{}]` in `{}` has no column", index, path),
+                )),
+            })
+            .collect::<Result<Vec<_>>>()
+            .map(|segments| segments.join("."))
+    }
+
+    /// Evaluates `expr` if it reads no facts and calls only deterministic
+    /// built-ins
+    fn constant(&self, expr: &Expr) -> Result<Option<Value>> {
+        if function::is_constant(expr) {
+            evaluate(expr, &RuleContext::new()).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Whether `expr` produces a string, as far as can be told without
+    /// knowing the column types
+    fn kind(&self, expr: &Expr) -> Result<Kind> {
+        if let Some(value) = self.constant(expr)? {
+            return Ok(match value {
+                Value::String(_) => Kind::String,
+                _ => Kind::Other,
+            });
+        }
+        Ok(match &expr.kind {
+            ExprKind::Path(_) => Kind::Unknown,
+            ExprKind::Call { function, .. }
+                if matches!(function.as_str(), "lower" | "upper" | "trim") =>
+            {
+                Kind::String
+            }
+            ExprKind::Call { function, args } if function == COALESCE => {
+                let mut kind = Kind::Unknown;
+                for arg in args {
+                    match self.kind(arg)? {
+                        Kind::String => return Ok(Kind::String),
+                        Kind::Other => kind = Kind::Other,
+                        Kind::Unknown => {}
+                    }
+                }
+                kind
+            }
+            ExprKind::Binary {
+                op: BinaryOp::Add,
+                lhs,
+                rhs,
+            } => match (self.kind(lhs)?, self.kind(rhs)?) {
+                (Kind::String, _) | (_, Kind::String) => Kind::String,
+                (Kind::Other, _) | (_, Kind::Other) => Kind::Other,
+                (Kind::Unknown, Kind::Unknown) => Kind::Unknown,
+            },
+            _ => Kind::Other,
+        })
+    }
+
+    /// Adds `value` as a parameter, returning its placeholder
+    fn bind(&self, value: Value, span: Span, params: &mut Vec<Value>) -> Result<String> {
+        match value {
+            Value::Null => Ok("NULL".to_string()),
+            Value::Duration(_) => Err(unsupported(span, "durations have no SQL type")),
+            Value::List(_) => Err(unsupported(span, LIST_OUTSIDE_IN)),
+            Value::Map(_) => Err(unsupported(span, "maps have no SQL type")),
+            value => {
+                params.push(value);
+                Ok(self.dialect.placeholder(params.len()))
+            }
+        }
+    }
+}
+
+/// `test`, or unknown if `gate` is null; negated if `negated`
+fn null_gated(gate: &str, test: &str, negated: bool) -> (String, u8) {
+    let sql = format!("CASE WHEN {} IS NULL THEN NULL ELSE {} END", gate, test);
+    if negated {
+        (format!("NOT {}", sql), precedence::NOT)
+    } else {
+        (sql, precedence::ATOM)
+    }
+}
+
+fn unsupported(span: Span, message: impl Into<String>) -> Error {
+    Error::SqlTranslation {
+        message: message.into(),
+        span,
+    }
+}
+
+/// Translates `expr` for `dialect`, mapping every path to its quoted segments
+pub fn to_sql(expr: &Expr, dialect: &dyn Dialect) -> Result<SqlPredicate> {
+    SqlTranslator::new(dialect).translate(expr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::parse;
+    use chrono::{DateTime, TimeZone, Utc};
+    use rusqlite::types::Value as SqlValue;
+
+    /// Id, name, tier, age, score, email and join date of a customer
+    type Row<'a> = (
+        i64,
+        &'a str,
+        Option<&'a str>,
+        Option<i64>,
+        Option<f64>,
+        &'a str,
+        DateTime<Utc>,
+    );
+
+    fn sql(source: &str, dialect: &dyn Dialect) -> String {
+        to_sql(&parse(source).unwrap(), dialect).unwrap().sql
+    }
+
+    #[test]
+    fn test_translate_for_each_dialect() {
+        let source =
+            "!(order.total / 2 >= 10) || customer.tier != 'gold' && customer.age % 7 == null";
+        assert_eq!(
+            sql(source, &Ansi),
+            r#"NOT (CAST("order"."total" AS DOUBLE PRECISION) / ? >= ?) OR "customer"."tier" IS DISTINCT FROM ? AND MOD("customer"."age", ?) IS NULL"#
+        );
+        assert_eq!(
+            sql(source, &Postgres),
+            r#"NOT (CAST("order"."total" AS DOUBLE PRECISION) / $1 >= $2) OR "customer"."tier" IS DISTINCT FROM $3 AND "customer"."age" % $4 IS NULL"#
+        );
+        assert_eq!(
+            sql(source, &Sqlite),
+            r#"NOT (CAST("order"."total" AS REAL) / ?1 >= ?2) OR "customer"."tier" IS NOT ?3 AND "customer"."age" % ?4 IS NULL"#
+        );
+
+        let predicate = to_sql(
+            &parse("(a || b) && c.d in [1, 2 * 3] && e + 'x' == timestamp('2024-01-01T00:00:00Z')")
+                .unwrap(),
+            &Postgres,
+        )
+        .unwrap();
+        assert_eq!(
+            predicate.sql,
+            r#"("a" OR "b") AND "c"."d" IN ($1, $2) AND "e" || $3 IS NOT DISTINCT FROM $4"#
+        );
+        assert_eq!(
+            predicate.params,
+            [
+                Value::from(1),
+                Value::from(6),
+                Value::from("x"),
+                Value::from(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_untranslatable_constructs() {
+        let cases = [
+            (
+                "orders[0].total > 1",
+                "list index `[0]` in `orders[0].total` has no column",
+            ),
+            (
+                "matches(customer.email, '.*@example')",
+                "function `matches` has no SQL translation",
+            ),
+            (
+                "customer.since < now()",
+                "`now()` depends on when it is evaluated",
+            ),
+            ("risk(customer) > 1", "function `risk` is not a built-in"),
+            ("order.age > duration('P1D')", "durations have no SQL type"),
+            (
+                "order.tags == [1, 2]",
+                "lists are only supported after `in`",
+            ),
+            ("order.total in 5", "`in` needs a list or string, not int"),
+            (
+                "customer.name + customer.tier + '!' == 'q'",
+                "`+` between columns of unknown type could add or concatenate",
+            ),
+        ];
+        for (source, message) in cases {
+            match to_sql(&parse(source).unwrap(), &Ansi) {
+                Err(Error::SqlTranslation {
+                    message: actual, ..
+                }) => {
+                    assert_eq!(actual, message, "{}", source)
+                }
+                other => panic!("{}: expected a translation error, got {:?}", source, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_sqlite_agrees_with_the_evaluator() {
+        let joined = |day| Utc.with_ymd_and_hms(2024, 1, day, 0, 0, 0).unwrap();
+        let rows: [Row; 4] = [
+            (
+                1,
+                "Ann",
+                Some("gold"),
+                Some(34),
+                Some(0.9),
+                "Ann@Example.com",
+                joined(5),
+            ),
+            (
+                2,
+                "Bob",
+                Some("silver"),
+                Some(17),
+                None,
+                "bob@example.org",
+                joined(1),
+            ),
+            (3, "Cy", None, Some(45), Some(0.4), "cy@test.io", joined(20)),
+            (
+                4,
+                "Di",
+                Some("bronze"),
+                None,
+                Some(0.6),
+                "di@example.com",
+                joined(9),
+            ),
+        ];
+
+        let db = rusqlite::Connection::open_in_memory().unwrap();
+        db.execute_batch(
+            "CREATE TABLE customer (id INTEGER, name TEXT, tier TEXT, age INTEGER, score REAL, email TEXT, joined TEXT)",
+        )
+        .unwrap();
+        for (id, name, tier, age, score, email, joined) in &rows {
+            db.execute(
+                "INSERT INTO customer VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                rusqlite::params![id, name, tier, age, score, email, joined.to_rfc3339()],
+            )
+            .unwrap();
+        }
+
+        let sources = [
+            "customer.tier == 'gold'",
+            "customer.tier != 'gold'",
+            "!(customer.age >= 18) || customer.tier in ['gold', 'silver']",
+            "customer.tier not in ['gold', 'silver']",
+            "customer.age / 2 >= 10 && customer.age % 10 != 5",
+            "-customer.age < -30 || customer.score > 0.5",
+            "lower(customer.email) == 'ann@example.com' || starts_with(customer.email, 'bo')",
+            "contains(customer.email, 'example') && length(customer.name) < 3",
+            "exists(customer.tier) && coalesce(customer.score, 0) < 0.7",
+            "customer.name + '!' == 'Cy!' || customer.joined >= timestamp('2024-01-09T00:00:00Z')",
+            "customer.name + '-' + customer.tier == 'Ann-gold'",
+            "customer.name + (customer.tier + '!') == 'Bobsilver!'",
+            "customer.age + 1 + customer.age > 60",
+            "customer.age not in [17, null]",
+            "customer.tier in ['gold', null] || customer.age in [null]",
+            "!(customer.age in [null])",
+            "customer.name not in [customer.tier, 'Bob']",
+        ];
+        for source in sources {
+            let expr = parse(source).unwrap();
+            let predicate = to_sql(&expr, &Sqlite).unwrap();
+            let params: Vec<SqlValue> = predicate
+                .params
+                .iter()
+                .map(|value| match value {
+                    Value::Bool(b) => SqlValue::Integer(i64::from(*b)),
+                    Value::Int(i) => SqlValue::Integer(*i),
+                    Value::Float(x) => SqlValue::Real(*x),
+                    Value::String(s) => SqlValue::Text(s.clone()),
+                    Value::Timestamp(ts) => SqlValue::Text(ts.to_rfc3339()),
+                    other => panic!("unexpected parameter {:?}", other),
+                })
+                .collect();
+            let mut statement = db
+                .prepare(&format!(
+                    "SELECT id FROM customer WHERE {} ORDER BY id",
+                    predicate.sql
+                ))
+                .unwrap();
+            let selected: Vec<i64> = statement
+                .query_map(rusqlite::params_from_iter(params), |row| row.get(0))
+                .unwrap()
+                .collect::<rusqlite::Result<_>>()
+                .unwrap();
+
+            let matched: Vec<i64> = rows
+                .iter()
+                .filter(|(_, name, tier, age, score, email, joined)| {
+                    let customer: Value = [
+                        ("name", Value::from(*name)),
+                        ("tier", tier.map_or(Value::Null, Value::from)),
+                        ("age", age.map_or(Value::Null, Value::from)),
+                        ("score", score.map_or(Value::Null, Value::from)),
+                        ("email", Value::from(*email)),
+                        ("joined", Value::from(*joined)),
+                    ]
+                    .into_iter()
+                    // SQL NULLs stand for missing facts
+                    .filter(|(_, value)| !value.is_null())
+                    .collect();
+                    let context = RuleContext::new().with_fact("customer", customer);
+                    evaluate(&expr, &context).unwrap() == Value::Bool(true)
+                })
+                .map(|row| row.0)
+                .collect();
+            assert_eq!(selected, matched, "{} => {}", source, predicate.sql);
+        }
+    }
+}
@@ -0,0 +1,373 @@
+//! Traced evaluation recording the value of every sub-expression
+//!
+//! A [`Tracer`] evaluates an expression exactly like an
+//! [`Evaluator`](crate::expr::Evaluator), but also builds a [`TraceNode`] tree
+//! holding the outcome of every sub-expression, including the operands that
+//! `&&` and `||` skipped, and records every fact it read.
+//!
+//! ```
+//! use windsurf_rules::expr::{parse, Tracer};
+//! use windsurf_rules::rule::{RuleContext, Value};
+//!
+//! let context = RuleContext::new().with_fact("age", 15);
+//! let trace = Tracer::new(&context).trace(&parse("age >= 18 && vip")?);
+//! assert_eq!(trace.value(), Some(&Value::Bool(false)));
+//! assert_eq!(trace.skipped()[0].expression, "vip");
+//! assert_eq!(trace.facts[0].path, "age");
+//! # Ok::<(), windsurf_rules::Error>(())
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::error::{Error, Result};
+use crate::expr::ast::{BinaryOp, Expr, ExprKind, Path, Span};
+use crate::expr::eval::{
+    apply_binary, apply_call, apply_unary, coalesce_arity, eval_error, exists, kleene,
+    literal_value, logic_operand, read_fact, COALESCE, EXISTS,
+};
+use crate::rule::{RuleContext, Value};
+
+/// What a traced sub-expression produced
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", content = "value", rename_all = "snake_case")]
+pub enum TraceOutcome {
+    /// The sub-expression evaluated to a value
+    Value(Value),
+    /// Evaluating the sub-expression failed with this message
+    Error(String),
+    /// A short-circuiting operator did not need the sub-expression
+    Skipped,
+}
+
+/// The traced evaluation of one sub-expression
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceNode {
+    /// The sub-expression's source, as printed by [`Expr`]'s `Display`
+    pub expression: String,
+    /// Where the sub-expression appears in the original source
+    pub span: Span,
+    /// What it produced
+    pub outcome: TraceOutcome,
+    /// Traces of its operands, in source order; empty for skipped nodes
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<TraceNode>,
+}
+
+impl TraceNode {
+    /// The value the sub-expression produced, if it produced one
+    pub fn value(&self) -> Option<&Value> {
+        match &self.outcome {
+            TraceOutcome::Value(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Visits this node and all its descendants, depth first
+    pub fn walk<'a>(&'a self, visit: &mut impl FnMut(&'a TraceNode)) {
+        visit(self);
+        for child in &self.children {
+            child.walk(visit);
+        }
+    }
+
+    fn skipped(expr: &Expr) -> Self {
+        Self {
+            expression: expr.to_string(),
+            span: expr.span,
+            outcome: TraceOutcome::Skipped,
+            children: Vec::new(),
+        }
+    }
+}
+
+/// A fact path read during a traced evaluation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FactRead {
+    /// The path as written in the expression
+    pub path: String,
+    /// The value found, or `None` if the fact is missing
+    pub value: Option<Value>,
+}
+
+/// The result of tracing one expression
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Trace {
+    /// Trace of the whole expression
+    pub root: TraceNode,
+    /// Facts read, in evaluation order, each path once
+    pub facts: Vec<FactRead>,
+}
+
+impl Trace {
+    /// The expression's value, unless evaluation failed
+    pub fn value(&self) -> Option<&Value> {
+        self.root.value()
+    }
+
+    /// The first evaluation error, if evaluation failed
+    pub fn error(&self) -> Option<&str> {
+        match &self.root.outcome {
+            TraceOutcome::Error(message) => Some(message),
+            _ => None,
+        }
+    }
+
+    /// Sub-expressions skipped by short-circuiting, in source order
+    pub fn skipped(&self) -> Vec<&TraceNode> {
+        let mut skipped = Vec::new();
+        self.root.walk(&mut |node| {
+            if node.outcome == TraceOutcome::Skipped {
+                skipped.push(node);
+            }
+        });
+        skipped
+    }
+}
+
+impl fmt::Display for Trace {
+    /// Prints the tree with one sub-expression per line, indented by depth,
+    /// followed by the facts read
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn write_node(f: &mut fmt::Formatter<'_>, node: &TraceNode, depth: usize) -> fmt::Result {
+            write!(
+                f,
+                "{:indent$}{} => ",
+                "",
+                node.expression,
+                indent = depth * 2
+            )?;
+            match &node.outcome {
+                TraceOutcome::Value(value) => writeln!(f, "{}", value)?,
+                TraceOutcome::Error(message) => writeln!(f, "error: {}", message)?,
+                TraceOutcome::Skipped => writeln!(f, "skipped")?,
+            }
+            node.children
+                .iter()
+                .try_for_each(|child| write_node(f, child, depth + 1))
+        }
+
+        write_node(f, &self.root, 0)?;
+        if !self.facts.is_empty() {
+            writeln!(f, "facts read:")?;
+            for fact in &self.facts {
+                match &fact.value {
+                    Some(value) => writeln!(f, "  {} = {}", fact.path, value)?,
+                    None => writeln!(f, "  {} is missing", fact.path)?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Evaluates expressions while recording a [`Trace`]
+#[derive(Debug, Clone, Copy)]
+pub struct Tracer<'a> {
+    context: &'a RuleContext,
+}
+
+impl<'a> Tracer<'a> {
+    /// Creates a tracer over `context`
+    pub fn new(context: &'a RuleContext) -> Self {
+        Self { context }
+    }
+
+    /// Evaluates `expr`, recording every step
+    pub fn trace(&self, expr: &Expr) -> Trace {
+        let mut facts = Vec::new();
+        let (root, _) = self.node(expr, &mut facts);
+        Trace { root, facts }
+    }
+
+    fn node(&self, expr: &Expr, facts: &mut Vec<FactRead>) -> (TraceNode, Result<Value>) {
+        let mut children = Vec::new();
+        let result = self.evaluate(expr, &mut children, facts);
+        let outcome = match &result {
+            Ok(value) => TraceOutcome::Value(value.clone()),
+            Err(Error::Evaluation { message, .. }) => TraceOutcome::Error(message.clone()),
+            Err(error) => TraceOutcome::Error(error.to_string()),
+        };
+        let node = TraceNode {
+            expression: expr.to_string(),
+            span: expr.span,
+            outcome,
+            children,
+        };
+        (node, result)
+    }
+
+    /// Mirrors [`Evaluator::evaluate`](crate::expr::Evaluator::evaluate),
+    /// pushing the trace of each operand onto `children`
+    fn evaluate(
+        &self,
+        expr: &Expr,
+        children: &mut Vec<TraceNode>,
+        facts: &mut Vec<FactRead>,
+    ) -> Result<Value> {
+        let operand = |expr: &Expr, children: &mut Vec<TraceNode>, facts: &mut Vec<FactRead>| {
+            let (node, result) = self.node(expr, facts);
+            children.push(node);
+            result
+        };
+        match &expr.kind {
+            ExprKind::Literal(literal) => Ok(literal_value(literal)),
+            ExprKind::Path(path) => {
+                record(self.context, path, facts);
+                read_fact(self.context, path, expr.span)
+            }
+            ExprKind::List(items) => {
+                let mut values = Vec::with_capacity(items.len());
+                for item in items {
+                    values.push(operand(item, children, facts)?);
+                }
+                Ok(Value::List(values))
+            }
+            ExprKind::Call { function, args } if function == EXISTS => {
+                if let [Expr {
+                    kind: ExprKind::Path(path),
+                    ..
+                }] = args.as_slice()
+                {
+                    record(self.context, path, facts);
+                }
+                exists(self.context, args, expr.span).map(Value::Bool)
+            }
+            ExprKind::Call { function, args } if function == COALESCE => {
+                if args.is_empty() {
+                    return Err(eval_error(expr.span, coalesce_arity()));
+                }
+                let mut result = Value::Null;
+                for arg in args {
+                    if !result.is_null() {
+                        children.push(TraceNode::skipped(arg));
+                        continue;
+                    }
+                    result = operand(arg, children, facts)?;
+                }
+                Ok(result)
+            }
+            ExprKind::Call { function, args } => {
+                let mut values = Vec::with_capacity(args.len());
+                for arg in args {
+                    values.push(operand(arg, children, facts)?);
+                }
+                apply_call(self.context, function, &values, expr.span)
+            }
+            ExprKind::Unary { op, operand: inner } => {
+                let value = operand(inner, children, facts)?;
+                apply_unary(*op, value, expr.span)
+            }
+            ExprKind::Binary {
+                op: op @ (BinaryOp::And | BinaryOp::Or),
+                lhs,
+                rhs,
+            } => {
+                let short_circuit = *op == BinaryOp::Or;
+                let left = logic_operand(operand(lhs, children, facts)?, lhs.span)?;
+                if left == Some(short_circuit) {
+                    children.push(TraceNode::skipped(rhs));
+                    return Ok(Value::Bool(short_circuit));
+                }
+                let right = logic_operand(operand(rhs, children, facts)?, rhs.span)?;
+                Ok(kleene(left, right, short_circuit))
+            }
+            ExprKind::Binary { op, lhs, rhs } => {
+                let lhs = operand(lhs, children, facts)?;
+                let rhs = operand(rhs, children, facts)?;
+                apply_binary(*op, lhs, rhs, expr.span)
+            }
+        }
+    }
+}
+
+/// Records the first read of `path`, with its value or its absence
+fn record(context: &RuleContext, path: &Path, facts: &mut Vec<FactRead>) {
+    let name = path.to_string();
+    if !facts.iter().any(|fact| fact.path == name) {
+        facts.push(FactRead {
+            path: name,
+            value: context.lookup(path).cloned(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::{parse, Evaluator};
+
+    fn context() -> RuleContext {
+        RuleContext::new()
+            .with_fact("customer", [("age", 30)].into_iter().collect::<Value>())
+            .with_fact("total", 250)
+    }
+
+    #[test]
+    fn test_trace_matches_evaluator() {
+        let context = context();
+        for source in [
+            "customer.age >= 18 && total > 100",
+            "customer.age < 18 || customer.tier == 'gold'",
+            "-(total * 2) in [1, -500]",
+            "customer.age + 'x' > 1",
+            "total && true",
+            "customer.tier > 'a' || total < 100",
+            "coalesce(customer.tier, customer.age, total)",
+            "exists(customer.age) && !exists(customer.tier)",
+        ] {
+            let expr = parse(source).unwrap();
+            let trace = Tracer::new(&context).trace(&expr);
+            match Evaluator::new(&context).evaluate(&expr) {
+                Ok(value) => assert_eq!(trace.value(), Some(&value), "{}", source),
+                Err(Error::Evaluation { message, .. }) => {
+                    assert_eq!(trace.error(), Some(message.as_str()), "{}", source)
+                }
+                Err(other) => panic!("unexpected error {}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_trace_records_facts_and_skipped_branches() {
+        let context = context();
+        let trace = Tracer::new(&context)
+            .trace(&parse("customer.tier == 'gold' || customer.age > 18 || total > 1000").unwrap());
+        assert_eq!(
+            trace.facts,
+            [
+                FactRead {
+                    path: "customer.tier".into(),
+                    value: None,
+                },
+                FactRead {
+                    path: "customer.age".into(),
+                    value: Some(Value::Int(30)),
+                },
+            ]
+        );
+        let skipped: Vec<&str> = trace
+            .skipped()
+            .iter()
+            .map(|node| node.expression.as_str())
+            .collect();
+        assert_eq!(skipped, ["total > 1000"]);
+        assert_eq!(
+            trace.to_string(),
+            "\
+customer.tier == \"gold\" || customer.age > 18 || total > 1000 => true
+  customer.tier == \"gold\" || customer.age > 18 => true
+    customer.tier == \"gold\" => false
+      customer.tier => null
+      \"gold\" => \"gold\"
+    customer.age > 18 => true
+      customer.age => 30
+      18 => 18
+  total > 1000 => skipped
+facts read:
+  customer.tier is missing
+  customer.age = 30
+"
+        );
+    }
+}
@@ -68,6 +68,43 @@ pub mod memory;
 /// Rule definition and processing
 pub mod rule;
 
+/// Expression language for rule conditions
+pub mod expr;
+
+/// Time sources for time-dependent rule evaluation
+pub mod clock;
+
+/// Structured diagnostics for rule evaluation and analysis
+pub mod diagnostic;
+
+/// Rules engine facade for evaluating rule sets
+pub mod engine;
+
+/// Forward-chaining inference over a Rete-style match network
+pub mod rete;
+
+/// Static analysis of rule sets, such as read/write dependency graphs
+pub mod analysis;
+
+/// Decision tables compiled into rule sets
+pub mod table;
+
+/// Parameterized rule templates instantiated into rule sets
+pub mod template;
+
+/// Rule inheritance and reusable condition fragments
+pub mod compose;
+
+/// Versioned rule storage with revision history
+pub mod store;
+
+/// Selector queries over rule tags, metadata and attributes
+pub mod selector;
+
+/// Goal-directed backward chaining with proof trees
+#[cfg(feature = "caching")]
+pub mod backward;
+
 // Re-export the FFI module if C++ feature is enabled
 #[cfg(feature = "cpp")]
 pub use ffi::{Engine, FfiError};
@@ -76,17 +113,36 @@ pub use ffi::{Engine, FfiError};
 pub use crate::error::Error;
 pub use crate::error::Result;
 pub use crate::core::HyperEngine;
+pub use crate::engine::RulesEngine;
 
 /// Predefined rules and optimizations
+///
+/// `Rule` here is the evaluation trait implemented by both native Rust rules
+/// and rule definitions; the data struct is re-exported as `RuleDefinition`.
 pub mod prelude {
+    pub use serde::{Deserialize, Serialize};
+
     pub use crate::{
         cache::Cache,
         metrics::Metric,
         parallel::{ParallelConfig, ParallelExecutor, WorkStealingQueue, ParallelIter},
         error::Error,
         Result,
-        rule::Rule
+        rule::{
+            evaluate::Rule, Action, CompiledRule, Effect, MissingPolicy, Rule as RuleDefinition,
+            RuleContext, RuleResult, Value,
+        },
+        analysis::{DependencyGraph, Schema},
+        diagnostic::{Diagnostic, Severity},
+        engine::{ConflictStrategy, ExecutionResult, RuleFlow, RulesEngine},
+        rete::{ForwardChainer, InferenceResult},
+        table::{DecisionTable, HitPolicy},
+        template::{ParameterSet, RuleTemplate},
+        compose::{RuleLibrary, RuleSpec},
     };
+
+    #[cfg(feature = "caching")]
+    pub use crate::backward::{BackwardChainer, Proof};
 }
 
 #[cfg(test)]
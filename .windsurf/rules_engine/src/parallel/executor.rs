@@ -9,6 +9,7 @@ use std::time::Duration;
 
 use crate::parallel::work_stealing::{Task, WorkStealingQueue};
 use crate::parallel::ParallelConfig;
+use crate::rule::RuleContext;
 
 /// A parallel iterator that processes items in parallel
 pub struct ParallelIter<I, T> {
@@ -274,6 +275,49 @@ impl ParallelExecutor {
         self.num_workers
     }
     
+    /// Maps every item against one shared context in chunks spread over the
+    /// workers, returning the results in item order. Chunks the executor
+    /// does not run, e.g. while it shuts down, are mapped on the calling
+    /// thread.
+    pub(crate) fn map_chunks<T, R, F>(&self, items: Vec<T>, context: &RuleContext, map: F) -> Vec<R>
+    where
+        T: Clone + Send + 'static,
+        R: Send + 'static,
+        F: Fn(&T, &RuleContext) -> R + Copy + Send + 'static,
+    {
+        let snapshot = Arc::new(context.clone());
+        let chunk_size = items.len().div_ceil(self.num_workers.max(1)).max(1);
+        let chunks: Vec<Vec<T>> = items.chunks(chunk_size).map(<[T]>::to_vec).collect();
+
+        let (sender, receiver) = mpsc::channel();
+        for (index, chunk) in chunks.iter().cloned().enumerate() {
+            let snapshot = snapshot.clone();
+            let sender = sender.clone();
+            let task = move || {
+                let results: Vec<R> = chunk.iter().map(|item| map(item, &snapshot)).collect();
+                let _ = sender.send((index, results));
+            };
+            // Run the chunk here if the executor is shutting down
+            if let Err(task) = self.submit(task) {
+                task();
+            }
+        }
+        drop(sender);
+
+        let mut results: Vec<Option<Vec<R>>> = chunks.iter().map(|_| None).collect();
+        for (index, chunk) in receiver {
+            results[index] = Some(chunk);
+        }
+        results
+            .into_iter()
+            .zip(&chunks)
+            .flat_map(|(results, chunk)| {
+                // A task dropped by a stopped executor never reports back
+                results.unwrap_or_else(|| chunk.iter().map(|item| map(item, &snapshot)).collect())
+            })
+            .collect()
+    }
+
     /// Returns the number of pending tasks across all workers
     pub fn pending_tasks(&self) -> usize {
         // This is an estimate since we can't atomically get the exact count
@@ -0,0 +1,740 @@
+//! Forward-chaining inference
+//!
+//! [`ForwardChainer`] keeps a working memory of named facts and compiles the
+//! conditions of its rules into a shared [`ReteNetwork`]. Inserting or
+//! retracting a fact only re-evaluates the conditions that read it; rules
+//! whose condition becomes true are placed on the [`Agenda`]. [`run`] then
+//! fires activations, applying their effects back to working memory, until
+//! no activations remain.
+//!
+//! A rule fires once per activation: it is activated again only after its
+//! condition has become false and then true again. Rules without a parsed
+//! condition (native Rust rules) are re-evaluated on every change, and
+//! conditions that read no facts but call `now()` are re-evaluated at the
+//! start of every [`run`]. A rule whose condition holds while it is outside
+//! its validity window at the chainer's [`Clock`] waits, and is activated by
+//! the first [`run`] that finds its window open.
+//!
+//! [`run`]: ForwardChainer::run
+//!
+//! ```
+//! use windsurf_rules::prelude::*;
+//! use windsurf_rules::rete::ForwardChainer;
+//!
+//! let mut chainer = ForwardChainer::new();
+//! chainer.add_definition(
+//!     RuleDefinition::new("gold")
+//!         .with_expression("customer.spend > 1000")
+//!         .then(Action::set("customer.tier", "'gold'")),
+//! )?;
+//! chainer.add_definition(
+//!     RuleDefinition::new("free_shipping")
+//!         .with_expression("customer.tier == 'gold'")
+//!         .then(Action::set("order.shipping", "0")),
+//! )?;
+//!
+//! chainer.insert("customer", [("spend", 1500)].into_iter().collect::<Value>());
+//! let result = chainer.run()?;
+//! assert_eq!(result.fired_rules(), ["gold", "free_shipping"]);
+//! assert_eq!(chainer.facts().get_path("order.shipping")?, Some(&Value::Int(0)));
+//! # Ok::<(), windsurf_rules::Error>(())
+//! ```
+
+pub mod network;
+
+pub use self::network::{ReteNetwork, DEFAULT_PARALLEL_THRESHOLD};
+pub use crate::engine::agenda::{Activation, Agenda, ConflictStrategy};
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::clock::{Clock, SystemClock};
+use crate::diagnostic::Diagnostic;
+use crate::error::{Error, Result};
+use crate::expr::datetime::now_function;
+use crate::expr::Function;
+use crate::parallel::ParallelExecutor;
+use crate::rule::evaluate::{error_diagnostic, Rule};
+use crate::rule::{
+    CallbackRegistry, Effect, MissingPolicy, Rule as RuleDefinition, RuleContext, Value,
+};
+
+use self::network::{BetaId, NodeState};
+
+/// Default limit on rule firings per [`ForwardChainer::run`]
+pub const DEFAULT_MAX_FIRINGS: usize = 10_000;
+
+/// One rule firing during [`ForwardChainer::run`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Firing {
+    /// Id of the fired rule
+    pub rule_id: String,
+    /// Effects of the rule's actions, already applied to working memory
+    pub effects: Vec<Effect>,
+}
+
+/// The outcome of one [`ForwardChainer::run`] call
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InferenceResult {
+    /// Every firing, in firing order
+    pub firings: Vec<Firing>,
+    /// Condition and action failures reported since the previous run
+    pub diagnostics: Vec<Diagnostic>,
+    /// Ids of the rules whose condition is unknown in the final working
+    /// memory because of missing or null facts, in the order they were added
+    pub unknown: Vec<String>,
+    /// Wall-clock time of the run
+    pub total_duration: Duration,
+}
+
+impl InferenceResult {
+    /// Ids of the fired rules, in firing order
+    pub fn fired_rules(&self) -> Vec<&str> {
+        self.firings
+            .iter()
+            .map(|firing| firing.rule_id.as_str())
+            .collect()
+    }
+
+    /// Ids of the rules whose condition was unknown when the run finished
+    pub fn unknown_rules(&self) -> Vec<&str> {
+        self.unknown.iter().map(String::as_str).collect()
+    }
+
+    /// Effects of all firings, with the id of the producing rule
+    pub fn effects(&self) -> impl Iterator<Item = (&str, &Effect)> {
+        self.firings.iter().flat_map(|firing| {
+            firing
+                .effects
+                .iter()
+                .map(move |effect| (firing.rule_id.as_str(), effect))
+        })
+    }
+}
+
+struct RuleSlot {
+    rule: Box<dyn Rule>,
+    terminal: Option<BetaId>,
+    state: NodeState,
+    /// The condition holds but the rule was outside its validity window, so
+    /// its activation waits for the window to open
+    dormant: bool,
+}
+
+/// Forward-chaining rule engine over a Rete-style match network
+pub struct ForwardChainer {
+    network: ReteNetwork,
+    rules: Vec<RuleSlot>,
+    terminals: HashMap<BetaId, Vec<usize>>,
+    memory: RuleContext,
+    agenda: Agenda,
+    callbacks: CallbackRegistry,
+    diagnostics: Vec<Diagnostic>,
+    max_firings: usize,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for ForwardChainer {
+    fn default() -> Self {
+        Self {
+            network: ReteNetwork::new(),
+            rules: Vec::new(),
+            terminals: HashMap::new(),
+            memory: RuleContext::new(),
+            agenda: Agenda::new(),
+            callbacks: CallbackRegistry::new(),
+            diagnostics: Vec::new(),
+            max_firings: DEFAULT_MAX_FIRINGS,
+            clock: Arc::new(SystemClock),
+        }
+    }
+}
+
+impl ForwardChainer {
+    /// Creates a chainer with no rules and an empty working memory
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Evaluates alpha nodes on `executor` once a change touches at least
+    /// `threshold` of them
+    pub fn with_executor(mut self, executor: Arc<ParallelExecutor>, threshold: usize) -> Self {
+        self.network.set_executor(executor);
+        self.network.set_parallel_threshold(threshold);
+        self
+    }
+
+    /// Sets how the agenda orders simultaneous activations
+    pub fn with_strategy(mut self, strategy: ConflictStrategy) -> Self {
+        self.agenda.set_strategy(strategy);
+        self
+    }
+
+    /// Sets how many rules a single [`run`](Self::run) may fire before it
+    /// gives up with [`Error::InferenceLimit`]
+    pub fn with_max_firings(mut self, max_firings: usize) -> Self {
+        self.max_firings = max_firings;
+        self
+    }
+
+    /// Reads the current time from `clock` when deciding which rules are
+    /// within their validity window and in `now()`; defaults to
+    /// [`SystemClock`]
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self.register_function(now_function(self.clock.clone()));
+        self
+    }
+
+    /// Sets how rule conditions treat fact paths that resolve to nothing;
+    /// defaults to [`MissingPolicy::Unknown`]. Set it before adding rules.
+    pub fn with_missing_policy(mut self, policy: MissingPolicy) -> Self {
+        self.memory = std::mem::take(&mut self.memory).with_missing_policy(policy);
+        self
+    }
+
+    /// Registers a callback that `call` actions can invoke by name
+    pub fn register_callback<F>(&mut self, name: impl Into<String>, callback: F)
+    where
+        F: Fn(&RuleContext, &[Value]) -> Result<Value> + Send + Sync + 'static,
+    {
+        self.callbacks.register(name, callback);
+    }
+
+    /// Registers a function that rule expressions can call; register
+    /// functions before adding the rules that call them
+    pub fn register_function(&mut self, function: Function) {
+        let mut functions = self.memory.functions().cloned().unwrap_or_default();
+        functions.register(function);
+        self.memory = std::mem::take(&mut self.memory).with_functions(functions);
+    }
+
+    /// Adds a rule and matches it against the current working memory
+    pub fn add_rule<R: Rule + 'static>(&mut self, rule: R) -> Result<()> {
+        if self.rules.iter().any(|slot| slot.rule.id() == rule.id()) {
+            return Err(Error::DuplicateRule(rule.id().to_string()));
+        }
+
+        let index = self.rules.len();
+        let terminal = rule
+            .condition()
+            .map(|condition| self.network.add_condition(condition, &self.memory));
+        let state = match terminal {
+            Some(terminal) => {
+                self.terminals.entry(terminal).or_default().push(index);
+                self.network.beta(terminal).state().clone()
+            }
+            None => evaluate_native(&rule, &self.memory),
+        };
+        self.rules.push(RuleSlot {
+            rule: Box::new(rule),
+            terminal,
+            state: Ok(Some(false)),
+            dormant: false,
+        });
+        self.update(index, state);
+        Ok(())
+    }
+
+    /// Compiles a rule definition and adds it
+    pub fn add_definition(&mut self, definition: RuleDefinition) -> Result<()> {
+        self.add_rule(definition.compile()?)
+    }
+
+    /// Inserts or replaces a fact and propagates the change
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<Value>) {
+        let name = name.into();
+        self.memory.insert(name.clone(), value);
+        self.propagate(&name);
+    }
+
+    /// Retracts a fact and propagates the change, returning its last value
+    pub fn retract(&mut self, name: &str) -> Option<Value> {
+        let value = self.memory.remove(name)?;
+        self.propagate(name);
+        Some(value)
+    }
+
+    /// Applies an effect to working memory and propagates the change
+    pub fn apply(&mut self, effect: &Effect) -> Result<()> {
+        self.apply_all(std::slice::from_ref(effect))
+    }
+
+    /// Applies effects in order and propagates the changes; if one fails,
+    /// none of them is applied
+    pub fn apply_all(&mut self, effects: &[Effect]) -> Result<()> {
+        let mut memory = self.memory.clone();
+        memory.apply_all(effects)?;
+        self.memory = memory;
+
+        let mut roots: Vec<&str> = Vec::new();
+        for effect in effects {
+            if let Effect::SetFact { path, .. } | Effect::RemoveFact { path } = effect {
+                if let Some(root) = path.root().filter(|root| !roots.contains(root)) {
+                    roots.push(root);
+                }
+            }
+        }
+        for root in roots {
+            self.propagate(root);
+        }
+        Ok(())
+    }
+
+    /// The current working memory
+    pub fn facts(&self) -> &RuleContext {
+        &self.memory
+    }
+
+    /// Activations waiting to fire
+    pub fn agenda(&self) -> &Agenda {
+        &self.agenda
+    }
+
+    /// The compiled match network
+    pub fn network(&self) -> &ReteNetwork {
+        &self.network
+    }
+
+    /// Fires activations until the agenda is empty; a firing whose effects
+    /// cannot all be applied changes nothing and is reported as an
+    /// `action-error` diagnostic
+    pub fn run(&mut self) -> Result<InferenceResult> {
+        let started = Instant::now();
+        self.refresh();
+        let mut firings = Vec::new();
+        while !self.agenda.is_empty() {
+            if firings.len() == self.max_firings {
+                return Err(Error::InferenceLimit(self.max_firings));
+            }
+            let Some(activation) = self.agenda.pop() else {
+                break;
+            };
+            if !self.rules[activation.order]
+                .rule
+                .is_valid_at(self.clock.now())
+            {
+                self.rules[activation.order].dormant = true;
+                continue;
+            }
+
+            let effects = self.rules[activation.order]
+                .rule
+                .consequences(true, &self.memory, &self.callbacks)
+                .and_then(|effects| {
+                    self.apply_all(&effects)?;
+                    Ok(effects)
+                });
+            match effects {
+                Ok(effects) => firings.push(Firing {
+                    rule_id: activation.rule_id,
+                    effects,
+                }),
+                Err(err) => {
+                    let mut diagnostic = error_diagnostic(&activation.rule_id, &err);
+                    diagnostic.code = "action-error".to_string();
+                    self.diagnostics.push(diagnostic);
+                }
+            }
+        }
+
+        let result = InferenceResult {
+            firings,
+            diagnostics: std::mem::take(&mut self.diagnostics),
+            unknown: self
+                .rules
+                .iter()
+                .filter(|slot| slot.state == Ok(None))
+                .map(|slot| slot.rule.id().to_string())
+                .collect(),
+            total_duration: started.elapsed(),
+        };
+        #[cfg(feature = "metrics")]
+        {
+            crate::metrics::record_timing("rules.infer", result.total_duration, None);
+            crate::metrics::increment_counter("rules.fired", result.firings.len() as u64, None);
+        }
+        Ok(result)
+    }
+
+    /// Re-evaluates the conditions that depend on the clock rather than on
+    /// facts, and activates dormant rules whose validity window has opened
+    fn refresh(&mut self) {
+        let changed = self.network.refresh(&self.memory);
+        self.update_terminals(changed);
+
+        let now = self.clock.now();
+        for (index, slot) in self.rules.iter_mut().enumerate() {
+            if slot.dormant && slot.rule.is_valid_at(now) {
+                slot.dormant = false;
+                self.agenda.push(index, slot.rule.as_ref());
+            }
+        }
+    }
+
+    fn propagate(&mut self, root: &str) {
+        let changed = self.network.propagate(root, &self.memory);
+        self.update_terminals(changed);
+        for index in 0..self.rules.len() {
+            if self.rules[index].terminal.is_none() {
+                let state = evaluate_native(&self.rules[index].rule, &self.memory);
+                self.update(index, state);
+            }
+        }
+    }
+
+    fn update_terminals(&mut self, betas: Vec<BetaId>) {
+        for beta in betas {
+            let state = self.network.beta(beta).state().clone();
+            for index in self.terminals.get(&beta).cloned().unwrap_or_default() {
+                self.update(index, state.clone());
+            }
+        }
+    }
+
+    /// Records a rule's new match state, adding or withdrawing its activation;
+    /// a rule that matches outside its validity window is left dormant
+    fn update(&mut self, index: usize, state: NodeState) {
+        let state = match state {
+            Ok(None) if self.memory.missing_policy() == MissingPolicy::False => Ok(Some(false)),
+            state => state,
+        };
+        let slot = &mut self.rules[index];
+        if slot.state == state {
+            return;
+        }
+        if let Err(diagnostic) = &state {
+            self.diagnostics
+                .push(diagnostic.clone().with_rule(slot.rule.id()));
+        }
+        slot.state = state;
+
+        let matched = slot.state == Ok(Some(true)) && slot.rule.is_active();
+        slot.dormant = matched && !slot.rule.is_valid_at(self.clock.now());
+        if matched && !slot.dormant {
+            self.agenda.push(index, slot.rule.as_ref());
+        } else {
+            self.agenda.remove(index);
+        }
+    }
+}
+
+fn evaluate_native(rule: &dyn Rule, memory: &RuleContext) -> NodeState {
+    let result = rule.evaluate(memory);
+    match result.diagnostics.into_iter().find(Diagnostic::is_error) {
+        Some(diagnostic) => Err(diagnostic),
+        None if result.unknown => Ok(None),
+        None => Ok(Some(result.matched)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parallel::ParallelConfig;
+    use crate::rule::{Action, RuleResult};
+
+    #[test]
+    fn test_chaining_reaches_quiescence() {
+        let mut chainer = ForwardChainer::new();
+        chainer
+            .add_definition(
+                RuleDefinition::new("gold")
+                    .with_expression("customer.spend > 1000")
+                    .then(Action::set("customer.tier", "'gold'")),
+            )
+            .unwrap();
+        chainer
+            .add_definition(
+                RuleDefinition::new("discount")
+                    .with_expression("customer.tier == 'gold' && order.total > 100")
+                    .with_priority(5)
+                    .then(Action::set("order.discount", "order.total / 10")),
+            )
+            .unwrap();
+        chainer
+            .add_definition(
+                RuleDefinition::new("big_order")
+                    .with_expression("order.total > 100")
+                    .then(Action::emit("big_order")),
+            )
+            .unwrap();
+
+        chainer.insert("order", [("total", 200)].into_iter().collect::<Value>());
+        chainer.insert("customer", [("spend", 5000)].into_iter().collect::<Value>());
+        assert_eq!(chainer.agenda().len(), 2);
+
+        let result = chainer.run().unwrap();
+        assert_eq!(result.fired_rules(), ["gold", "discount", "big_order"]);
+        assert_eq!(
+            chainer.facts().get_path("order.discount").unwrap(),
+            Some(&Value::Float(20.0))
+        );
+        assert!(chainer.agenda().is_empty());
+        assert!(chainer.run().unwrap().firings.is_empty());
+    }
+
+    #[test]
+    fn test_strategy_orders_simultaneous_activations() {
+        let mut chainer = ForwardChainer::new().with_strategy(ConflictStrategy::Recency);
+        for id in ["first", "second", "third"] {
+            chainer
+                .add_definition(RuleDefinition::new(id).with_expression("order.total > 0"))
+                .unwrap();
+        }
+        chainer
+            .add_definition(
+                RuleDefinition::new("later").with_expression("order.total > 0 && order.paid"),
+            )
+            .unwrap();
+
+        chainer.insert("order", [("total", 1)].into_iter().collect::<Value>());
+        chainer
+            .apply(&Effect::SetFact {
+                path: "order.paid".parse().unwrap(),
+                value: Value::Bool(true),
+            })
+            .unwrap();
+        let result = chainer.run().unwrap();
+        assert_eq!(result.fired_rules(), ["later", "third", "second", "first"]);
+    }
+
+    #[test]
+    fn test_retraction_withdraws_activation() {
+        struct HasOrder;
+
+        impl Rule for HasOrder {
+            fn evaluate(&self, context: &RuleContext) -> RuleResult {
+                RuleResult::new(context.get("order").is_some())
+            }
+
+            fn id(&self) -> &str {
+                "has_order"
+            }
+        }
+
+        let mut chainer = ForwardChainer::new();
+        chainer
+            .add_definition(RuleDefinition::new("big").with_expression("order.total > 100"))
+            .unwrap();
+        chainer.add_rule(HasOrder).unwrap();
+
+        chainer.insert("order", [("total", 200)].into_iter().collect::<Value>());
+        assert_eq!(chainer.agenda().len(), 2);
+        chainer.retract("order");
+        assert!(chainer.agenda().is_empty());
+
+        // Conditions that fail to evaluate are reported with the next run
+        chainer.insert("order", [("total", "lots")].into_iter().collect::<Value>());
+        let result = chainer.run().unwrap();
+        assert_eq!(result.fired_rules(), ["has_order"]);
+        assert_eq!(result.diagnostics[0].rule_id.as_deref(), Some("big"));
+    }
+
+    #[test]
+    fn test_unknown_conditions_are_reported_separately() {
+        let mut chainer = ForwardChainer::new();
+        chainer
+            .add_definition(
+                RuleDefinition::new("vip")
+                    .with_expression("customer.tier == 'gold' || customer.spend > 1000"),
+            )
+            .unwrap();
+        chainer
+            .add_definition(
+                RuleDefinition::new("silver").with_expression("customer.tier == 'silver'"),
+            )
+            .unwrap();
+
+        chainer.insert(
+            "customer",
+            [("tier", "silver")].into_iter().collect::<Value>(),
+        );
+        let result = chainer.run().unwrap();
+        assert_eq!(result.fired_rules(), ["silver"]);
+        assert_eq!(result.unknown_rules(), ["vip"]);
+        assert!(result.diagnostics.is_empty());
+
+        chainer.insert("customer", [("spend", 2000)].into_iter().collect::<Value>());
+        let result = chainer.run().unwrap();
+        assert_eq!(result.fired_rules(), ["vip"]);
+        assert!(result.unknown_rules().is_empty());
+    }
+
+    #[test]
+    fn test_missing_policy_applies_to_working_memory() {
+        let vip = || {
+            RuleDefinition::new("vip")
+                .with_expression("customer.tier == 'gold' || customer.spend > 1000")
+        };
+        let silver = || [("tier", "silver")].into_iter().collect::<Value>();
+
+        let mut chainer = ForwardChainer::new().with_missing_policy(MissingPolicy::False);
+        chainer.add_definition(vip()).unwrap();
+        chainer.insert("customer", silver());
+        let result = chainer.run().unwrap();
+        assert!(result.unknown_rules().is_empty());
+        assert!(result.diagnostics.is_empty());
+
+        let mut chainer = ForwardChainer::new().with_missing_policy(MissingPolicy::Error);
+        chainer.add_definition(vip()).unwrap();
+        chainer.insert("customer", silver());
+        let result = chainer.run().unwrap();
+        assert!(result.unknown_rules().is_empty());
+        assert_eq!(
+            result.diagnostics.last().unwrap().message,
+            "missing fact `customer.spend`"
+        );
+    }
+
+    #[test]
+    fn test_failed_actions_leave_memory_unchanged() {
+        let mut chainer = ForwardChainer::new();
+        chainer
+            .add_definition(
+                RuleDefinition::new("flag")
+                    .with_expression("order.total > 100")
+                    .then(Action::set("order.flagged", "true"))
+                    .then(Action::set("order.total.currency", "'EUR'")),
+            )
+            .unwrap();
+        chainer
+            .add_definition(RuleDefinition::new("review").with_expression("order.flagged == true"))
+            .unwrap();
+
+        let order: Value = [("total", 200)].into_iter().collect();
+        chainer.insert("order", order.clone());
+        let result = chainer.run().unwrap();
+        assert!(result.firings.is_empty());
+        assert_eq!(result.diagnostics[0].code, "action-error");
+        assert_eq!(chainer.facts().get("order"), Some(&order));
+    }
+
+    #[test]
+    fn test_alpha_nodes_evaluate_on_executor() {
+        let executor = Arc::new(ParallelExecutor::with_config(ParallelConfig {
+            num_workers: 2,
+            queue_size: 64,
+            enable_work_stealing: true,
+        }));
+        let mut chainer = ForwardChainer::new().with_executor(executor, 1);
+        for limit in 0..20 {
+            chainer
+                .add_definition(
+                    RuleDefinition::new(format!("over_{}", limit))
+                        .with_expression(format!("reading.value > {}", limit)),
+                )
+                .unwrap();
+        }
+
+        chainer.insert("reading", [("value", 10)].into_iter().collect::<Value>());
+        assert_eq!(chainer.run().unwrap().firings.len(), 10);
+    }
+
+    #[test]
+    fn test_rules_outside_validity_window_do_not_fire() {
+        use crate::clock::ManualClock;
+        use chrono::{TimeDelta, TimeZone, Utc};
+
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let clock = Arc::new(ManualClock::new(start));
+        let mut chainer = ForwardChainer::new().with_clock(clock.clone());
+        chainer
+            .add_definition(
+                RuleDefinition::new("future")
+                    .with_expression("order.total > 0")
+                    .valid_from(start + TimeDelta::days(1)),
+            )
+            .unwrap();
+        chainer
+            .add_definition(
+                RuleDefinition::new("expiring")
+                    .with_expression("order.total > 0")
+                    .valid_until(start + TimeDelta::hours(1)),
+            )
+            .unwrap();
+
+        chainer.insert("order", [("total", 1)].into_iter().collect::<Value>());
+        assert_eq!(chainer.agenda().len(), 1);
+        clock.advance(TimeDelta::hours(2));
+        assert!(chainer.run().unwrap().firings.is_empty());
+
+        // A rule that matched before its window opened fires once it opens
+        clock.advance(TimeDelta::days(1));
+        assert_eq!(chainer.run().unwrap().fired_rules(), ["future"]);
+        assert!(chainer.run().unwrap().firings.is_empty());
+    }
+
+    #[test]
+    fn test_calendar_rules_fire_when_the_calendar_opens() {
+        use crate::clock::ManualClock;
+        use crate::rule::Calendar;
+        use chrono::{NaiveTime, TimeDelta, TimeZone, Utc};
+
+        let evening = Utc.with_ymd_and_hms(2024, 1, 1, 20, 0, 0).unwrap();
+        let clock = Arc::new(ManualClock::new(evening));
+        let mut chainer = ForwardChainer::new().with_clock(clock.clone());
+        let hours = Calendar::new().between(
+            NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+            NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+        );
+        chainer
+            .add_definition(
+                RuleDefinition::new("callback")
+                    .with_expression("ticket.open")
+                    .with_calendar(hours),
+            )
+            .unwrap();
+
+        chainer.insert("ticket", [("open", true)].into_iter().collect::<Value>());
+        assert!(chainer.run().unwrap().firings.is_empty());
+        clock.advance(TimeDelta::hours(14));
+        assert_eq!(chainer.run().unwrap().fired_rules(), ["callback"]);
+    }
+
+    #[test]
+    fn test_clock_conditions_are_rechecked_on_run() {
+        use crate::clock::ManualClock;
+        use chrono::{TimeDelta, TimeZone, Utc};
+
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let clock = Arc::new(ManualClock::new(start));
+        let mut chainer = ForwardChainer::new().with_clock(clock.clone());
+        chainer
+            .add_definition(
+                RuleDefinition::new("launched").with_expression(
+                    "order.total > 1 && now() > timestamp('2024-01-02T00:00:00Z')",
+                ),
+            )
+            .unwrap();
+
+        chainer.insert("order", [("total", 5)].into_iter().collect::<Value>());
+        assert!(chainer.run().unwrap().firings.is_empty());
+        clock.advance(TimeDelta::days(2));
+        assert_eq!(chainer.run().unwrap().fired_rules(), ["launched"]);
+    }
+
+    #[test]
+    fn test_run_stops_at_firing_limit() {
+        let mut chainer = ForwardChainer::new().with_max_firings(10);
+        chainer
+            .add_definition(
+                RuleDefinition::new("flip_on")
+                    .with_expression("!light.on")
+                    .then(Action::set("light.on", "true")),
+            )
+            .unwrap();
+        chainer
+            .add_definition(
+                RuleDefinition::new("flip_off")
+                    .with_expression("light.on")
+                    .then(Action::set("light.on", "false")),
+            )
+            .unwrap();
+
+        chainer.insert("light", [("on", false)].into_iter().collect::<Value>());
+        assert!(matches!(chainer.run(), Err(Error::InferenceLimit(10))));
+    }
+}
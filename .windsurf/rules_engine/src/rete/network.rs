@@ -0,0 +1,384 @@
+//! Alpha/beta match network
+//!
+//! Every rule condition is split into its top-level `&&` conjuncts. Each
+//! distinct conjunct becomes one alpha node, shared by all rules that test
+//! it, and indexed by the root facts it reads. Beta nodes join a parent beta
+//! node with one alpha node, so rules with a common conjunct prefix share the
+//! same chain. Node memories hold the current match state, and a fact change
+//! only re-evaluates the alpha nodes that read that fact. Alpha nodes that
+//! read no facts but call non-deterministic functions such as `now()` are
+//! re-evaluated by [`ReteNetwork::refresh`].
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+use crate::diagnostic::Diagnostic;
+use crate::expr::eval::logic_operand;
+use crate::expr::function::is_constant;
+use crate::expr::{Evaluator, Expr};
+use crate::parallel::ParallelExecutor;
+use crate::rule::evaluate::diagnostic_for;
+use crate::rule::RuleContext;
+
+/// Index of an alpha node within a [`ReteNetwork`]
+pub type AlphaId = usize;
+
+/// Index of a beta node within a [`ReteNetwork`]
+pub type BetaId = usize;
+
+/// Match state held in a node memory: whether the node's conditions hold
+/// (`None` when unknown because of missing or null facts), or why they could
+/// not be evaluated
+pub type NodeState = std::result::Result<Option<bool>, Diagnostic>;
+
+/// Default number of alpha nodes a change must touch before evaluation is
+/// handed to the parallel executor
+pub const DEFAULT_PARALLEL_THRESHOLD: usize = 64;
+
+/// A single conjunct tested against working memory
+#[derive(Debug, Clone)]
+pub struct AlphaNode {
+    condition: Arc<Expr>,
+    roots: Vec<String>,
+    successors: Vec<BetaId>,
+    state: NodeState,
+}
+
+impl AlphaNode {
+    /// The conjunct this node tests
+    pub fn condition(&self) -> &Expr {
+        &self.condition
+    }
+
+    /// Root facts the conjunct reads
+    pub fn roots(&self) -> &[String] {
+        &self.roots
+    }
+
+    /// Current match state
+    pub fn state(&self) -> &NodeState {
+        &self.state
+    }
+}
+
+/// The join of a parent beta node (or nothing) with one alpha node
+#[derive(Debug, Clone)]
+pub struct BetaNode {
+    parent: Option<BetaId>,
+    alpha: AlphaId,
+    children: Vec<BetaId>,
+    state: NodeState,
+}
+
+impl BetaNode {
+    /// The beta node this one extends, if any
+    pub fn parent(&self) -> Option<BetaId> {
+        self.parent
+    }
+
+    /// The alpha node joined at this node
+    pub fn alpha(&self) -> AlphaId {
+        self.alpha
+    }
+
+    /// Current match state of the whole conjunct prefix ending here
+    pub fn state(&self) -> &NodeState {
+        &self.state
+    }
+}
+
+/// A shared network of alpha and beta nodes
+pub struct ReteNetwork {
+    alphas: Vec<AlphaNode>,
+    betas: Vec<BetaNode>,
+    alpha_keys: HashMap<String, AlphaId>,
+    beta_keys: HashMap<(Option<BetaId>, AlphaId), BetaId>,
+    by_root: HashMap<String, Vec<AlphaId>>,
+    volatile: Vec<AlphaId>,
+    executor: Option<Arc<ParallelExecutor>>,
+    parallel_threshold: usize,
+}
+
+impl Default for ReteNetwork {
+    fn default() -> Self {
+        Self {
+            alphas: Vec::new(),
+            betas: Vec::new(),
+            alpha_keys: HashMap::new(),
+            beta_keys: HashMap::new(),
+            by_root: HashMap::new(),
+            volatile: Vec::new(),
+            executor: None,
+            parallel_threshold: DEFAULT_PARALLEL_THRESHOLD,
+        }
+    }
+}
+
+impl ReteNetwork {
+    /// Creates an empty network that evaluates alpha nodes on the calling thread
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Evaluates large batches of alpha nodes on `executor`
+    pub fn set_executor(&mut self, executor: Arc<ParallelExecutor>) {
+        self.executor = Some(executor);
+    }
+
+    /// Sets how many alpha nodes a change must touch before the executor is used
+    pub fn set_parallel_threshold(&mut self, threshold: usize) {
+        self.parallel_threshold = threshold.max(1);
+    }
+
+    /// Adds the nodes for `condition`, reusing existing ones, and returns the
+    /// terminal beta node whose state is the state of the whole condition
+    pub fn add_condition(&mut self, condition: &Expr, memory: &RuleContext) -> BetaId {
+        let mut parent = None;
+        for conjunct in condition.conjuncts() {
+            let alpha = self.alpha_node(conjunct, memory);
+            parent = Some(self.beta_node(parent, alpha));
+        }
+        parent.expect("an expression has at least one conjunct")
+    }
+
+    /// Re-evaluates the alpha nodes that read `root` and returns the beta
+    /// nodes whose state changed, parents before children
+    pub fn propagate(&mut self, root: &str, memory: &RuleContext) -> Vec<BetaId> {
+        match self.by_root.get(root).cloned() {
+            Some(ids) => self.reevaluate(ids, memory),
+            None => Vec::new(),
+        }
+    }
+
+    /// Re-evaluates the alpha nodes that read no facts but may change between
+    /// evaluations, and returns the beta nodes whose state changed, parents
+    /// before children
+    pub fn refresh(&mut self, memory: &RuleContext) -> Vec<BetaId> {
+        self.reevaluate(self.volatile.clone(), memory)
+    }
+
+    fn reevaluate(&mut self, ids: Vec<AlphaId>, memory: &RuleContext) -> Vec<BetaId> {
+        let states = self.evaluate_alphas(&ids, memory);
+
+        // Beta ids are allocated after their parents, so ascending order is topological
+        let mut pending = BTreeSet::new();
+        for (id, state) in ids.into_iter().zip(states) {
+            let alpha = &mut self.alphas[id];
+            if alpha.state != state {
+                alpha.state = state;
+                pending.extend(alpha.successors.iter().copied());
+            }
+        }
+
+        let mut changed = Vec::new();
+        while let Some(id) = pending.pop_first() {
+            let state = self.join(self.betas[id].parent, self.betas[id].alpha);
+            let beta = &mut self.betas[id];
+            if beta.state != state {
+                beta.state = state;
+                pending.extend(beta.children.iter().copied());
+                changed.push(id);
+            }
+        }
+        changed
+    }
+
+    /// Returns an alpha node
+    pub fn alpha(&self, id: AlphaId) -> &AlphaNode {
+        &self.alphas[id]
+    }
+
+    /// Returns a beta node
+    pub fn beta(&self, id: BetaId) -> &BetaNode {
+        &self.betas[id]
+    }
+
+    /// Number of distinct alpha nodes
+    pub fn alpha_count(&self) -> usize {
+        self.alphas.len()
+    }
+
+    /// Number of distinct beta nodes
+    pub fn beta_count(&self) -> usize {
+        self.betas.len()
+    }
+
+    fn alpha_node(&mut self, conjunct: &Expr, memory: &RuleContext) -> AlphaId {
+        let key = conjunct.to_string();
+        if let Some(&id) = self.alpha_keys.get(&key) {
+            return id;
+        }
+
+        let id = self.alphas.len();
+        let roots: BTreeSet<String> = conjunct
+            .paths()
+            .into_iter()
+            .filter_map(|path| path.root().map(str::to_string))
+            .collect();
+        for root in &roots {
+            self.by_root.entry(root.clone()).or_default().push(id);
+        }
+        if roots.is_empty() && !is_constant(conjunct) {
+            self.volatile.push(id);
+        }
+        self.alphas.push(AlphaNode {
+            state: evaluate(conjunct, memory),
+            condition: Arc::new(conjunct.clone()),
+            roots: roots.into_iter().collect(),
+            successors: Vec::new(),
+        });
+        self.alpha_keys.insert(key, id);
+        id
+    }
+
+    fn beta_node(&mut self, parent: Option<BetaId>, alpha: AlphaId) -> BetaId {
+        if let Some(&id) = self.beta_keys.get(&(parent, alpha)) {
+            return id;
+        }
+
+        let id = self.betas.len();
+        self.betas.push(BetaNode {
+            parent,
+            alpha,
+            children: Vec::new(),
+            state: self.join(parent, alpha),
+        });
+        if let Some(parent) = parent {
+            self.betas[parent].children.push(id);
+        }
+        self.alphas[alpha].successors.push(id);
+        self.beta_keys.insert((parent, alpha), id);
+        id
+    }
+
+    /// Combines a parent state with an alpha state the way `&&` would:
+    /// the alpha's result only matters once the parent holds or is unknown
+    fn join(&self, parent: Option<BetaId>, alpha: AlphaId) -> NodeState {
+        let alpha = &self.alphas[alpha].state;
+        match parent.map(|parent| &self.betas[parent].state) {
+            None | Some(Ok(Some(true))) => alpha.clone(),
+            Some(Ok(None)) => match alpha {
+                Ok(Some(true)) => Ok(None),
+                other => other.clone(),
+            },
+            Some(other) => other.clone(),
+        }
+    }
+
+    fn evaluate_alphas(&self, ids: &[AlphaId], memory: &RuleContext) -> Vec<NodeState> {
+        let conditions = ids.iter().map(|&id| &self.alphas[id].condition);
+        match &self.executor {
+            Some(executor) if ids.len() >= self.parallel_threshold => executor.map_chunks(
+                conditions.cloned().collect(),
+                memory,
+                |condition, memory| evaluate(condition, memory),
+            ),
+            _ => conditions
+                .map(|condition| evaluate(condition, memory))
+                .collect(),
+        }
+    }
+}
+
+fn evaluate(condition: &Expr, memory: &RuleContext) -> NodeState {
+    Evaluator::new(memory)
+        .evaluate(condition)
+        .and_then(|value| logic_operand(value, condition.span))
+        .map_err(|err| diagnostic_for(&err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::parse;
+    use crate::rule::Value;
+
+    fn add(network: &mut ReteNetwork, source: &str, memory: &RuleContext) -> BetaId {
+        network.add_condition(&parse(source).unwrap(), memory)
+    }
+
+    #[test]
+    fn test_network_shares_nodes() {
+        let memory = RuleContext::new();
+        let mut network = ReteNetwork::new();
+        let gold = add(
+            &mut network,
+            "customer.tier == 'gold' && order.total > 100",
+            &memory,
+        );
+        let silver = add(
+            &mut network,
+            "customer.tier == 'gold' && order.total > 50",
+            &memory,
+        );
+        let again = add(
+            &mut network,
+            "(customer.tier == 'gold') && order.total > 100",
+            &memory,
+        );
+
+        assert_eq!(gold, again);
+        assert_ne!(gold, silver);
+        assert_eq!(network.alpha_count(), 3);
+        assert_eq!(network.beta_count(), 3);
+        assert_eq!(network.alpha(0).roots(), ["customer"]);
+    }
+
+    #[test]
+    fn test_propagate_only_touches_changed_roots() {
+        let order: Value = [("total", 200)].into_iter().collect();
+        let mut memory = RuleContext::new().with_fact("order", order);
+        let mut network = ReteNetwork::new();
+        let terminal = add(
+            &mut network,
+            "customer.tier == 'gold' && order.total > 100",
+            &memory,
+        );
+        assert_eq!(network.beta(terminal).state(), &Ok(Some(false)));
+
+        memory.insert(
+            "customer",
+            [("tier", "gold")].into_iter().collect::<Value>(),
+        );
+        assert!(network.propagate("unrelated", &memory).is_empty());
+        assert_eq!(network.propagate("customer", &memory), [0, terminal]);
+        assert_eq!(network.beta(terminal).state(), &Ok(Some(true)));
+
+        // A failing conjunct only surfaces once the conjuncts before it hold
+        memory.insert("order", [("total", "lots")].into_iter().collect::<Value>());
+        network.propagate("order", &memory);
+        assert!(network.beta(terminal).state().is_err());
+        memory.insert(
+            "customer",
+            [("tier", "silver")].into_iter().collect::<Value>(),
+        );
+        network.propagate("customer", &memory);
+        assert_eq!(network.beta(terminal).state(), &Ok(Some(false)));
+    }
+
+    #[test]
+    fn test_unknown_conjuncts_follow_three_valued_and() {
+        let mut memory = RuleContext::new();
+        let mut network = ReteNetwork::new();
+        let terminal = add(
+            &mut network,
+            "customer.age > 18 && order.total > 100",
+            &memory,
+        );
+        assert_eq!(network.alpha(0).state(), &Ok(None));
+        assert_eq!(network.beta(terminal).state(), &Ok(None));
+
+        // A false conjunct decides the condition even after an unknown one
+        memory.insert("order", [("total", 50)].into_iter().collect::<Value>());
+        network.propagate("order", &memory);
+        assert_eq!(network.beta(terminal).state(), &Ok(Some(false)));
+
+        memory.insert("order", [("total", 200)].into_iter().collect::<Value>());
+        network.propagate("order", &memory);
+        assert_eq!(network.beta(terminal).state(), &Ok(None));
+
+        memory.insert("customer", [("age", 30)].into_iter().collect::<Value>());
+        network.propagate("customer", &memory);
+        assert_eq!(network.beta(terminal).state(), &Ok(Some(true)));
+    }
+}
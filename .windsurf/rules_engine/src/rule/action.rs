@@ -0,0 +1,475 @@
+//! Rule consequences: actions that run when a rule matches (or does not)
+//!
+//! Actions never mutate the fact context directly. Running an action produces
+//! an [`Effect`], which callers can audit, persist or apply to a context with
+//! [`RuleContext::apply`].
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+use crate::expr::{self, Evaluator, Expr, Path};
+use crate::rule::{RuleContext, Value};
+
+/// A consequence declared on a rule definition
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Action {
+    /// Sets a fact to the value of an expression; the expression may read the
+    /// fact's current value, e.g. `customer.score + 10`, to modify it
+    Set {
+        /// Field path of the fact to set
+        path: String,
+        /// Expression producing the new value
+        value: String,
+    },
+    /// Removes a fact
+    Remove {
+        /// Field path of the fact to remove
+        path: String,
+    },
+    /// Emits a named event with a payload of evaluated expressions
+    Emit {
+        /// Event name
+        event: String,
+        /// Payload field name to expression
+        #[serde(default)]
+        payload: BTreeMap<String, String>,
+    },
+    /// Appends a message; `{expression}` placeholders are interpolated
+    Message {
+        /// Message template
+        text: String,
+    },
+    /// Calls a callback registered with the engine
+    Call {
+        /// Name the callback was registered under
+        callback: String,
+        /// Argument expressions
+        #[serde(default)]
+        args: Vec<String>,
+    },
+    /// Runs a Rust closure attached with [`Rule::action`](crate::rule::Rule::action);
+    /// cannot be serialized
+    #[serde(skip)]
+    Native(NativeAction),
+}
+
+impl Action {
+    /// Creates a [`Action::Set`]
+    pub fn set(path: impl Into<String>, value: impl Into<String>) -> Self {
+        Action::Set {
+            path: path.into(),
+            value: value.into(),
+        }
+    }
+
+    /// Creates a [`Action::Remove`]
+    pub fn remove(path: impl Into<String>) -> Self {
+        Action::Remove { path: path.into() }
+    }
+
+    /// Creates an [`Action::Emit`] with an empty payload
+    pub fn emit(event: impl Into<String>) -> Self {
+        Action::Emit {
+            event: event.into(),
+            payload: BTreeMap::new(),
+        }
+    }
+
+    /// Creates an [`Action::Message`]
+    pub fn message(text: impl Into<String>) -> Self {
+        Action::Message { text: text.into() }
+    }
+
+    /// Creates an [`Action::Call`]
+    pub fn call(callback: impl Into<String>, args: Vec<String>) -> Self {
+        Action::Call {
+            callback: callback.into(),
+            args,
+        }
+    }
+
+    /// Parses every expression in the action
+    pub fn compile(&self) -> Result<CompiledAction> {
+        Ok(match self {
+            Action::Set { path, value } => CompiledAction::Set {
+                path: path.parse()?,
+                value: expr::parse(value)?,
+            },
+            Action::Remove { path } => CompiledAction::Remove { path: path.parse()? },
+            Action::Emit { event, payload } => CompiledAction::Emit {
+                event: event.clone(),
+                payload: payload
+                    .iter()
+                    .map(|(name, source)| Ok((name.clone(), expr::parse(source)?)))
+                    .collect::<Result<_>>()?,
+            },
+            Action::Message { text } => CompiledAction::Message {
+                parts: parse_template(text)?,
+            },
+            Action::Call { callback, args } => CompiledAction::Call {
+                callback: callback.clone(),
+                args: args.iter().map(|arg| expr::parse(arg)).collect::<Result<_>>()?,
+            },
+            Action::Native(native) => CompiledAction::Native(native.clone()),
+        })
+    }
+}
+
+/// Signature of closures used as native actions
+pub type NativeFn = dyn Fn(&RuleContext) -> Result<Value> + Send + Sync;
+
+/// A Rust closure used as a rule action
+#[derive(Clone)]
+pub struct NativeAction(Arc<NativeFn>);
+
+impl NativeAction {
+    /// Wraps a closure; its return value becomes the output of the
+    /// resulting [`Effect::Callback`]
+    pub fn new<F, R>(action: F) -> Self
+    where
+        F: Fn(&RuleContext) -> Result<R> + Send + Sync + 'static,
+        R: Into<Value>,
+    {
+        Self(Arc::new(move |context| action(context).map(Into::into)))
+    }
+}
+
+impl fmt::Debug for NativeAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("NativeAction(..)")
+    }
+}
+
+impl PartialEq for NativeAction {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Signature of callbacks registered with a [`CallbackRegistry`]
+pub type CallbackFn = dyn Fn(&RuleContext, &[Value]) -> Result<Value> + Send + Sync;
+
+/// Named Rust callbacks that [`Action::Call`] can invoke
+#[derive(Clone, Default)]
+pub struct CallbackRegistry {
+    callbacks: HashMap<String, Arc<CallbackFn>>,
+}
+
+impl CallbackRegistry {
+    /// Creates an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `callback` under `name`, replacing any previous registration
+    pub fn register<F>(&mut self, name: impl Into<String>, callback: F)
+    where
+        F: Fn(&RuleContext, &[Value]) -> Result<Value> + Send + Sync + 'static,
+    {
+        self.callbacks.insert(name.into(), Arc::new(callback));
+    }
+
+    /// Returns true if a callback is registered under `name`
+    pub fn contains(&self, name: &str) -> bool {
+        self.callbacks.contains_key(name)
+    }
+
+    fn call(&self, name: &str, context: &RuleContext, args: &[Value]) -> Result<Value> {
+        let callback = self
+            .callbacks
+            .get(name)
+            .ok_or_else(|| Error::Custom(format!("No callback registered as `{}`", name)))?;
+        callback(context, args)
+    }
+}
+
+impl fmt::Debug for CallbackRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names: Vec<_> = self.callbacks.keys().collect();
+        names.sort();
+        f.debug_struct("CallbackRegistry").field("callbacks", &names).finish()
+    }
+}
+
+/// The data produced by running an action
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Effect {
+    /// A fact should be set to a new value
+    SetFact {
+        /// Fact to set
+        path: Path,
+        /// New value
+        value: Value,
+    },
+    /// A fact should be removed
+    RemoveFact {
+        /// Fact to remove
+        path: Path,
+    },
+    /// An event was emitted
+    Event {
+        /// Event name
+        name: String,
+        /// Evaluated payload
+        payload: BTreeMap<String, Value>,
+    },
+    /// A message was appended
+    Message {
+        /// Interpolated message text
+        text: String,
+    },
+    /// A callback or native action ran
+    Callback {
+        /// Callback name, or `"native"` for closures attached to the rule
+        name: String,
+        /// Value returned by the callback
+        output: Value,
+    },
+}
+
+/// An [`Action`] with its expressions parsed
+#[derive(Debug, Clone)]
+pub enum CompiledAction {
+    /// See [`Action::Set`]
+    Set {
+        /// Fact to set
+        path: Path,
+        /// Value expression
+        value: Expr,
+    },
+    /// See [`Action::Remove`]
+    Remove {
+        /// Fact to remove
+        path: Path,
+    },
+    /// See [`Action::Emit`]
+    Emit {
+        /// Event name
+        event: String,
+        /// Payload expressions
+        payload: BTreeMap<String, Expr>,
+    },
+    /// See [`Action::Message`]
+    Message {
+        /// Literal text and interpolated expressions
+        parts: Vec<TemplatePart>,
+    },
+    /// See [`Action::Call`]
+    Call {
+        /// Callback name
+        callback: String,
+        /// Argument expressions
+        args: Vec<Expr>,
+    },
+    /// See [`Action::Native`]
+    Native(NativeAction),
+}
+
+/// A piece of a message template
+#[derive(Debug, Clone, PartialEq)]
+pub enum TemplatePart {
+    /// Literal text
+    Text(String),
+    /// An interpolated `{expression}`
+    Expr(Expr),
+}
+
+fn parse_template(text: &str) -> Result<Vec<TemplatePart>> {
+    let mut parts = Vec::new();
+    let mut rest = text;
+    while let Some(open) = rest.find('{') {
+        let close = rest[open..]
+            .find('}')
+            .map(|offset| open + offset)
+            .ok_or_else(|| Error::Custom(format!("Unclosed `{{` in message `{}`", text)))?;
+        if open > 0 {
+            parts.push(TemplatePart::Text(rest[..open].to_string()));
+        }
+        parts.push(TemplatePart::Expr(expr::parse(&rest[open + 1..close])?));
+        rest = &rest[close + 1..];
+    }
+    if !rest.is_empty() {
+        parts.push(TemplatePart::Text(rest.to_string()));
+    }
+    Ok(parts)
+}
+
+impl CompiledAction {
+    /// Field paths the action's expressions read, in source order
+    pub fn reads(&self) -> Vec<&Path> {
+        let exprs: Vec<&Expr> = match self {
+            CompiledAction::Set { value, .. } => vec![value],
+            CompiledAction::Emit { payload, .. } => payload.values().collect(),
+            CompiledAction::Message { parts } => parts
+                .iter()
+                .filter_map(|part| match part {
+                    TemplatePart::Expr(expr) => Some(expr),
+                    TemplatePart::Text(_) => None,
+                })
+                .collect(),
+            CompiledAction::Call { args, .. } => args.iter().collect(),
+            CompiledAction::Remove { .. } | CompiledAction::Native(_) => Vec::new(),
+        };
+        exprs.into_iter().flat_map(Expr::paths).collect()
+    }
+
+    /// The fact path the action sets or removes, if any
+    pub fn writes(&self) -> Option<&Path> {
+        match self {
+            CompiledAction::Set { path, .. } | CompiledAction::Remove { path } => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Runs the action against `context`, producing an effect
+    pub fn run(&self, context: &RuleContext, callbacks: &CallbackRegistry) -> Result<Effect> {
+        let evaluator = Evaluator::new(context);
+        Ok(match self {
+            CompiledAction::Set { path, value } => Effect::SetFact {
+                path: path.clone(),
+                value: evaluator.evaluate(value)?,
+            },
+            CompiledAction::Remove { path } => Effect::RemoveFact { path: path.clone() },
+            CompiledAction::Emit { event, payload } => Effect::Event {
+                name: event.clone(),
+                payload: payload
+                    .iter()
+                    .map(|(name, expr)| Ok((name.clone(), evaluator.evaluate(expr)?)))
+                    .collect::<Result<_>>()?,
+            },
+            CompiledAction::Message { parts } => {
+                let mut text = String::new();
+                for part in parts {
+                    match part {
+                        TemplatePart::Text(literal) => text.push_str(literal),
+                        TemplatePart::Expr(expr) => match evaluator.evaluate(expr)? {
+                            Value::String(s) => text.push_str(&s),
+                            other => text.push_str(&other.to_string()),
+                        },
+                    }
+                }
+                Effect::Message { text }
+            }
+            CompiledAction::Call { callback, args } => {
+                let args = args
+                    .iter()
+                    .map(|arg| evaluator.evaluate(arg))
+                    .collect::<Result<Vec<_>>>()?;
+                Effect::Callback {
+                    name: callback.clone(),
+                    output: callbacks.call(callback, context, &args)?,
+                }
+            }
+            CompiledAction::Native(NativeAction(action)) => Effect::Callback {
+                name: "native".to_string(),
+                output: action(context)?,
+            },
+        })
+    }
+}
+
+/// Runs a list of compiled actions in order, stopping at the first failure
+pub fn run_actions(
+    actions: &[CompiledAction],
+    context: &RuleContext,
+    callbacks: &CallbackRegistry,
+) -> Result<Vec<Effect>> {
+    actions.iter().map(|action| action.run(context, callbacks)).collect()
+}
+
+impl RuleContext {
+    /// Applies a single effect: fact effects modify the context, all other
+    /// effects are informational and leave it untouched
+    pub fn apply(&mut self, effect: &Effect) -> Result<()> {
+        match effect {
+            Effect::SetFact { path, value } => self.set_path(path, value.clone()).map(drop),
+            Effect::RemoveFact { path } => {
+                self.remove_path(path);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Applies effects in order
+    pub fn apply_all<'a>(&mut self, effects: impl IntoIterator<Item = &'a Effect>) -> Result<()> {
+        effects.into_iter().try_for_each(|effect| self.apply(effect))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> RuleContext {
+        let customer: Value = [("name", Value::from("Ada")), ("score", Value::from(5))]
+            .into_iter()
+            .collect();
+        RuleContext::new().with_fact("customer", customer)
+    }
+
+    fn run(action: Action, callbacks: &CallbackRegistry) -> Result<Effect> {
+        action.compile()?.run(&context(), callbacks)
+    }
+
+    #[test]
+    fn test_actions_produce_effects() {
+        let mut callbacks = CallbackRegistry::new();
+        callbacks.register("double", |_, args| {
+            Ok(Value::Int(args[0].as_i64().unwrap_or(0) * 2))
+        });
+
+        assert_eq!(
+            run(Action::set("customer.score", "customer.score + 10"), &callbacks).unwrap(),
+            Effect::SetFact {
+                path: "customer.score".parse().unwrap(),
+                value: Value::Int(15),
+            }
+        );
+        assert_eq!(
+            run(Action::message("Hello {customer.name}, score {customer.score}!"), &callbacks)
+                .unwrap(),
+            Effect::Message {
+                text: "Hello Ada, score 5!".to_string()
+            }
+        );
+        assert_eq!(
+            run(Action::call("double", vec!["customer.score".into()]), &callbacks).unwrap(),
+            Effect::Callback {
+                name: "double".to_string(),
+                output: Value::Int(10),
+            }
+        );
+        assert!(run(Action::call("missing", vec![]), &callbacks).is_err());
+    }
+
+    #[test]
+    fn test_apply_effects_to_context() {
+        let mut ctx = context();
+        let callbacks = CallbackRegistry::new();
+        let effects = [
+            run(Action::set("customer.tier", "'gold'"), &callbacks).unwrap(),
+            run(Action::set("audit.checked", "true"), &callbacks).unwrap(),
+            run(Action::remove("customer.score"), &callbacks).unwrap(),
+        ];
+        ctx.apply_all(&effects).unwrap();
+        assert_eq!(ctx.get_path("customer.tier").unwrap(), Some(&Value::from("gold")));
+        assert_eq!(ctx.get_path("audit.checked").unwrap(), Some(&Value::Bool(true)));
+        assert_eq!(ctx.get_path("customer.score").unwrap(), None);
+    }
+
+    #[test]
+    fn test_actions_deserialize_from_yaml() {
+        let actions: Vec<Action> = serde_yaml::from_str(
+            "- type: set\n  path: customer.tier\n  value: \"'gold'\"\n- type: emit\n  event: upgraded\n  payload:\n    name: customer.name\n",
+        )
+        .unwrap();
+        assert_eq!(actions[0], Action::set("customer.tier", "'gold'"));
+        assert!(matches!(&actions[1], Action::Emit { payload, .. } if payload.len() == 1));
+    }
+}
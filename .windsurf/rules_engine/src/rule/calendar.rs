@@ -0,0 +1,252 @@
+//! Recurring calendars restricting when a rule applies
+//!
+//! A [`Calendar`] narrows a rule's validity window to particular weekdays,
+//! days of the month, months and times of day. Every non-empty criterion must
+//! match; an empty calendar matches any time. Calendars are evaluated in a
+//! time zone, or else a fixed UTC offset, so that "09:00 to 17:00" means
+//! local business hours all year round. A time window that wraps past
+//! midnight belongs to the day it opens: Friday 22:00 to 02:00 covers early
+//! Saturday, not early Friday.
+//!
+//! ```
+//! use chrono::{NaiveTime, TimeZone, Utc, Weekday};
+//! use windsurf_rules::rule::Calendar;
+//!
+//! let business_hours = Calendar::new()
+//!     .on_weekdays([Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri])
+//!     .between(NaiveTime::from_hms_opt(9, 0, 0).unwrap(), NaiveTime::from_hms_opt(17, 0, 0).unwrap())
+//!     .with_utc_offset(60);
+//!
+//! // Monday 08:30 UTC is 09:30 at UTC+1
+//! assert!(business_hours.contains(Utc.with_ymd_and_hms(2024, 3, 4, 8, 30, 0).unwrap()));
+//! assert!(!business_hours.contains(Utc.with_ymd_and_hms(2024, 3, 9, 8, 30, 0).unwrap()));
+//! ```
+
+use chrono::{DateTime, Datelike, NaiveDate, NaiveTime, TimeDelta, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+/// A daily time range; `end` is exclusive, and a range whose end is not after
+/// its start wraps past midnight
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeWindow {
+    /// Time of day the window opens
+    pub start: NaiveTime,
+    /// Time of day the window closes
+    pub end: NaiveTime,
+}
+
+impl TimeWindow {
+    /// Creates a window from `start` until `end`
+    pub fn new(start: NaiveTime, end: NaiveTime) -> Self {
+        Self { start, end }
+    }
+
+    /// Returns true if `time` falls within the window
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        self.days_open(time).is_some()
+    }
+
+    /// Days since the window containing `time` opened: 0 on the day it
+    /// opens, 1 past midnight of a wrapping window; `None` outside it
+    fn days_open(&self, time: NaiveTime) -> Option<i64> {
+        if self.start < self.end {
+            (self.start <= time && time < self.end).then_some(0)
+        } else if time >= self.start {
+            Some(0)
+        } else {
+            (time < self.end).then_some(1)
+        }
+    }
+}
+
+/// Recurring times at which a rule applies
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Calendar {
+    /// Days of the week, e.g. `Mon`; empty means every day
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub weekdays: Vec<Weekday>,
+    /// Days of the month, 1 to 31; empty means every day
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub days_of_month: Vec<u32>,
+    /// Months, 1 to 12; empty means every month
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub months: Vec<u32>,
+    /// Times of day; empty means all day
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub times: Vec<TimeWindow>,
+    /// Offset from UTC, in minutes, of the local time the calendar is written
+    /// in; ignored when a time zone is set
+    #[serde(default)]
+    pub utc_offset_minutes: i32,
+    /// Time zone the calendar is written in, e.g. `Europe/Copenhagen`
+    #[serde(default, with = "zone", skip_serializing_if = "Option::is_none")]
+    pub time_zone: Option<Tz>,
+}
+
+impl Calendar {
+    /// Creates a calendar that matches any time
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Restricts the calendar to the given weekdays
+    pub fn on_weekdays(mut self, weekdays: impl IntoIterator<Item = Weekday>) -> Self {
+        self.weekdays.extend(weekdays);
+        self
+    }
+
+    /// Restricts the calendar to the given days of the month
+    pub fn on_days_of_month(mut self, days: impl IntoIterator<Item = u32>) -> Self {
+        self.days_of_month.extend(days);
+        self
+    }
+
+    /// Restricts the calendar to the given months
+    pub fn in_months(mut self, months: impl IntoIterator<Item = u32>) -> Self {
+        self.months.extend(months);
+        self
+    }
+
+    /// Adds a daily time window; several windows match if any of them does
+    pub fn between(mut self, start: NaiveTime, end: NaiveTime) -> Self {
+        self.times.push(TimeWindow::new(start, end));
+        self
+    }
+
+    /// Sets the UTC offset, in minutes, of the calendar's local time
+    pub fn with_utc_offset(mut self, minutes: i32) -> Self {
+        self.utc_offset_minutes = minutes;
+        self
+    }
+
+    /// Evaluates the calendar in `zone`, following its daylight saving time
+    pub fn in_time_zone(mut self, zone: Tz) -> Self {
+        self.time_zone = Some(zone);
+        self
+    }
+
+    /// Returns true if `at` falls on one of the calendar's recurring times
+    pub fn contains(&self, at: DateTime<Utc>) -> bool {
+        let local = match self.time_zone {
+            Some(zone) => at.with_timezone(&zone).naive_local(),
+            None => at.naive_utc() + TimeDelta::minutes(self.utc_offset_minutes.into()),
+        };
+        if self.times.is_empty() {
+            return self.on_day(local.date());
+        }
+        // The day criteria apply to the day the matching window opened
+        self.times.iter().any(|window| {
+            window
+                .days_open(local.time())
+                .is_some_and(|days| self.on_day(local.date() - TimeDelta::days(days)))
+        })
+    }
+
+    fn on_day(&self, date: NaiveDate) -> bool {
+        (self.weekdays.is_empty() || self.weekdays.contains(&date.weekday()))
+            && (self.days_of_month.is_empty() || self.days_of_month.contains(&date.day()))
+            && (self.months.is_empty() || self.months.contains(&date.month()))
+    }
+}
+
+/// Serializes a time zone by its IANA name
+mod zone {
+    use chrono_tz::Tz;
+    use serde::de::Error as _;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(zone: &Option<Tz>, serializer: S) -> Result<S::Ok, S::Error> {
+        match zone {
+            Some(zone) => serializer.serialize_some(zone.name()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Tz>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|name| {
+                name.parse()
+                    .map_err(|_| D::Error::custom(format!("unknown time zone `{}`", name)))
+            })
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, day, hour, minute, 0).unwrap()
+    }
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_windows_wrap_midnight() {
+        let night = Calendar::new().between(time(22, 0), time(6, 0));
+        assert!(night.contains(at(1, 23, 0)));
+        assert!(night.contains(at(2, 5, 59)));
+        assert!(!night.contains(at(2, 6, 0)));
+        assert!(!night.contains(at(2, 12, 0)));
+        assert!(Calendar::new().contains(at(2, 12, 0)));
+
+        // 2024-06-07 is a Friday; the early hours of Saturday belong to it
+        let friday_night = Calendar::new()
+            .on_weekdays([Weekday::Fri])
+            .between(time(22, 0), time(2, 0));
+        assert!(friday_night.contains(at(7, 23, 0)));
+        assert!(friday_night.contains(at(8, 1, 30)));
+        assert!(!friday_night.contains(at(7, 1, 30)));
+        assert!(!friday_night.contains(at(8, 23, 0)));
+    }
+
+    #[test]
+    fn test_time_zones_follow_daylight_saving() {
+        let opening = Calendar::new()
+            .between(time(9, 0), time(10, 0))
+            .in_time_zone(chrono_tz::Europe::Copenhagen);
+        // 09:30 in Copenhagen is 07:30 UTC in summer and 08:30 UTC in winter
+        assert!(opening.contains(at(3, 7, 30)));
+        assert!(!opening.contains(at(3, 8, 30)));
+        assert!(opening.contains(Utc.with_ymd_and_hms(2024, 1, 3, 8, 30, 0).unwrap()));
+
+        let parsed: Calendar = serde_yaml::from_str(
+            "time_zone: Europe/Copenhagen\ntimes: [{start: '09:00:00', end: '10:00:00'}]",
+        )
+        .unwrap();
+        assert_eq!(parsed, opening);
+        assert_eq!(
+            serde_yaml::from_str::<Calendar>(&serde_yaml::to_string(&parsed).unwrap()).unwrap(),
+            opening
+        );
+        assert!(serde_yaml::from_str::<Calendar>("time_zone: Mars/Olympus")
+            .unwrap_err()
+            .to_string()
+            .contains("unknown time zone `Mars/Olympus`"));
+    }
+
+    #[test]
+    fn test_criteria_use_local_time() {
+        // 2024-06-01 is a Saturday
+        let calendar = Calendar::new()
+            .on_weekdays([Weekday::Sat])
+            .on_days_of_month([1])
+            .in_months([6])
+            .with_utc_offset(-120);
+        assert!(calendar.contains(at(1, 12, 0)));
+        assert!(!calendar.contains(at(1, 1, 0)));
+        assert!(calendar.contains(at(2, 1, 0)));
+
+        let parsed: Calendar = serde_yaml::from_str(
+            "weekdays: [Sat]\ntimes: [{start: '09:00:00', end: '17:00:00'}]",
+        )
+        .unwrap();
+        assert!(parsed.contains(at(1, 9, 0)));
+        assert!(!parsed.contains(at(3, 9, 0)));
+    }
+}
@@ -0,0 +1,318 @@
+//! Fact context that rules are evaluated against
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+use crate::expr::{FunctionRegistry, Path, PathSegment};
+use crate::rule::value::Value;
+
+/// A set of named, typed facts that rules are evaluated against
+///
+/// Facts are addressed by field paths: the first segment names a top-level
+/// fact and the remaining segments walk into nested maps and lists, so
+/// `customer.orders[0].total` reads the `total` of the first order of the
+/// `customer` fact.
+///
+/// A context can also carry the [`FunctionRegistry`] that expressions call
+/// into and the [`MissingPolicy`] for paths that resolve to nothing; neither
+/// is part of equality or the serialized form.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RuleContext {
+    facts: BTreeMap<String, Value>,
+    #[serde(skip)]
+    functions: Option<Arc<FunctionRegistry>>,
+    #[serde(skip)]
+    missing: MissingPolicy,
+}
+
+/// How expressions treat fact paths that resolve to nothing
+///
+/// Whatever the policy, a missing fact differs from one explicitly set to
+/// `null` only in `exists()` and under [`MissingPolicy::Error`]; otherwise
+/// both read as `null`, which makes comparisons and arithmetic unknown.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MissingPolicy {
+    /// Missing facts read as `null`; conditions that end up `null` are
+    /// reported as unknown rather than as not matched
+    #[default]
+    Unknown,
+    /// Missing facts read as `null`; conditions that end up `null` do not
+    /// match
+    False,
+    /// Reading a missing fact is an evaluation error; guard optional facts
+    /// with `exists()`
+    Error,
+}
+
+impl PartialEq for RuleContext {
+    fn eq(&self, other: &Self) -> bool {
+        self.facts == other.facts
+    }
+}
+
+impl RuleContext {
+    /// Creates an empty context
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a top-level fact, returning the context for chaining
+    pub fn with_fact(mut self, name: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.insert(name, value);
+        self
+    }
+
+    /// Attaches the functions that expressions evaluated against this context
+    /// can call
+    pub fn with_functions(mut self, functions: impl Into<Arc<FunctionRegistry>>) -> Self {
+        self.functions = Some(functions.into());
+        self
+    }
+
+    /// The attached functions, if any
+    pub fn functions(&self) -> Option<&FunctionRegistry> {
+        self.functions.as_deref()
+    }
+
+    /// Sets how expressions evaluated against this context treat missing
+    /// facts
+    pub fn with_missing_policy(mut self, policy: MissingPolicy) -> Self {
+        self.missing = policy;
+        self
+    }
+
+    /// How missing facts are treated; defaults to [`MissingPolicy::Unknown`]
+    pub fn missing_policy(&self) -> MissingPolicy {
+        self.missing
+    }
+
+    /// Inserts or replaces a top-level fact, returning the previous value
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<Value>) -> Option<Value> {
+        self.facts.insert(name.into(), value.into())
+    }
+
+    /// Removes a top-level fact, returning its value
+    pub fn remove(&mut self, name: &str) -> Option<Value> {
+        self.facts.remove(name)
+    }
+
+    /// Returns a top-level fact by name
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.facts.get(name)
+    }
+
+    /// Resolves a field path against the facts
+    pub fn lookup(&self, path: &Path) -> Option<&Value> {
+        let (root, rest) = path.segments.split_first()?;
+        let PathSegment::Key(name) = root else {
+            return None;
+        };
+        self.facts.get(name)?.get_path(rest)
+    }
+
+    /// Sets the value at a field path, creating intermediate maps as needed,
+    /// and returns the previous value
+    ///
+    /// List elements can be replaced, or appended by using the index one past
+    /// the end of the list.
+    pub fn set_path(&mut self, path: &Path, value: Value) -> Result<Option<Value>> {
+        let invalid = |reason: String| Error::InvalidPath {
+            path: path.to_string(),
+            reason,
+        };
+        let Some((PathSegment::Key(name), rest)) = path.segments.split_first() else {
+            return Err(invalid("path must start with a fact name".to_string()));
+        };
+        let Some((last, parents)) = rest.split_last() else {
+            return Ok(self.facts.insert(name.clone(), value));
+        };
+
+        // Work on a copy of the fact so a path that fails halfway leaves no
+        // intermediate maps behind
+        let mut root = self.facts.get(name).cloned().unwrap_or(Value::Null);
+        let mut current = &mut root;
+        for segment in parents {
+            let type_name = current.type_name();
+            current = descend_mut(current, segment).ok_or_else(|| {
+                invalid(format!("cannot follow `{}` into {}", segment_name(segment), type_name))
+            })?;
+        }
+
+        if current.is_null() && matches!(last, PathSegment::Key(_)) {
+            *current = Value::Map(BTreeMap::new());
+        }
+        let previous = match (current, last) {
+            (Value::Map(map), PathSegment::Key(key)) => map.insert(key.clone(), value),
+            (Value::List(items), PathSegment::Index(index)) if *index < items.len() => {
+                Some(std::mem::replace(&mut items[*index], value))
+            }
+            (Value::List(items), PathSegment::Index(index)) if *index == items.len() => {
+                items.push(value);
+                None
+            }
+            (other, segment) => {
+                return Err(invalid(format!(
+                    "cannot set `{}` on {}",
+                    segment_name(segment),
+                    other.type_name()
+                )))
+            }
+        };
+        self.facts.insert(name.clone(), root);
+        Ok(previous)
+    }
+
+    /// Removes the value at a field path, returning it if it existed
+    pub fn remove_path(&mut self, path: &Path) -> Option<Value> {
+        let (PathSegment::Key(name), rest) = path.segments.split_first()? else {
+            return None;
+        };
+        let Some((last, parents)) = rest.split_last() else {
+            return self.facts.remove(name);
+        };
+        let mut current = self.facts.get_mut(name)?;
+        for segment in parents {
+            current = match (current, segment) {
+                (Value::Map(map), PathSegment::Key(key)) => map.get_mut(key)?,
+                (Value::List(items), PathSegment::Index(index)) => items.get_mut(*index)?,
+                _ => return None,
+            };
+        }
+        match (current, last) {
+            (Value::Map(map), PathSegment::Key(key)) => map.remove(key),
+            (Value::List(items), PathSegment::Index(index)) if *index < items.len() => {
+                Some(items.remove(*index))
+            }
+            _ => None,
+        }
+    }
+
+    /// Parses `path` (e.g. `"customer.address.city"`) and resolves it
+    pub fn get_path(&self, path: &str) -> Result<Option<&Value>> {
+        Ok(self.lookup(&path.parse()?))
+    }
+
+    /// Returns true if the field path resolves to a value
+    pub fn contains(&self, path: &Path) -> bool {
+        self.lookup(path).is_some()
+    }
+
+    /// Iterates over the top-level facts in name order
+    pub fn facts(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.facts.iter().map(|(name, value)| (name.as_str(), value))
+    }
+
+    /// Number of top-level facts
+    pub fn len(&self) -> usize {
+        self.facts.len()
+    }
+
+    /// Returns true if the context holds no facts
+    pub fn is_empty(&self) -> bool {
+        self.facts.is_empty()
+    }
+}
+
+fn segment_name(segment: &PathSegment) -> String {
+    match segment {
+        PathSegment::Key(key) => key.clone(),
+        PathSegment::Index(index) => format!("[{}]", index),
+    }
+}
+
+/// Steps into a map or list for writing, turning `null` into an empty map
+fn descend_mut<'a>(value: &'a mut Value, segment: &PathSegment) -> Option<&'a mut Value> {
+    if value.is_null() && matches!(segment, PathSegment::Key(_)) {
+        *value = Value::Map(BTreeMap::new());
+    }
+    match (value, segment) {
+        (Value::Map(map), PathSegment::Key(key)) => Some(map.entry(key.clone()).or_insert(Value::Null)),
+        (Value::List(items), PathSegment::Index(index)) => items.get_mut(*index),
+        _ => None,
+    }
+}
+
+impl<K: Into<String>, V: Into<Value>> FromIterator<(K, V)> for RuleContext {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self {
+            facts: iter.into_iter().map(|(k, v)| (k.into(), v.into())).collect(),
+            functions: None,
+            missing: MissingPolicy::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn context() -> RuleContext {
+        let address: Value = [("city", "Aarhus")].into_iter().collect();
+        let customer: Value = [
+            ("age", Value::from(42)),
+            ("address", address),
+            ("tags", Value::from(vec!["vip", "newsletter"])),
+        ]
+        .into_iter()
+        .collect();
+
+        RuleContext::new()
+            .with_fact("customer", customer)
+            .with_fact("signup", Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap())
+    }
+
+    #[test]
+    fn test_context_path_lookup() {
+        let ctx = context();
+        assert_eq!(ctx.get_path("customer.age").unwrap(), Some(&Value::Int(42)));
+        assert_eq!(
+            ctx.get_path("customer.address.city").unwrap(),
+            Some(&Value::from("Aarhus"))
+        );
+        assert_eq!(ctx.get_path("customer.tags[1]").unwrap(), Some(&Value::from("newsletter")));
+        assert_eq!(ctx.get_path("customer.missing").unwrap(), None);
+        assert_eq!(ctx.get_path("signup").unwrap().map(Value::type_name), Some("timestamp"));
+        assert!(ctx.get_path("customer.").is_err());
+    }
+
+    #[test]
+    fn test_context_insert_and_remove() {
+        let mut ctx = context();
+        assert_eq!(ctx.len(), 2);
+        assert_eq!(ctx.insert("flag", true), None);
+        assert_eq!(ctx.insert("flag", false), Some(Value::Bool(true)));
+        assert_eq!(ctx.remove("flag"), Some(Value::Bool(false)));
+        assert_eq!(ctx.facts().map(|(name, _)| name).collect::<Vec<_>>(), ["customer", "signup"]);
+    }
+
+    #[test]
+    fn test_context_set_and_remove_paths() {
+        let mut ctx = context();
+        let path = |s: &str| s.parse::<Path>().unwrap();
+
+        assert_eq!(ctx.set_path(&path("customer.age"), Value::from(43)).unwrap(), Some(Value::from(42)));
+        assert_eq!(ctx.set_path(&path("order.lines.first"), Value::from(1)).unwrap(), None);
+        assert_eq!(ctx.get_path("order.lines.first").unwrap(), Some(&Value::from(1)));
+        assert_eq!(ctx.set_path(&path("customer.tags[2]"), Value::from("new")).unwrap(), None);
+        assert_eq!(ctx.get_path("customer.tags[2]").unwrap(), Some(&Value::from("new")));
+        assert!(matches!(
+            ctx.set_path(&path("customer.age.years"), Value::from(1)),
+            Err(Error::InvalidPath { .. })
+        ));
+
+        let before = ctx.clone();
+        assert!(ctx.set_path(&path("order.items[0].qty"), Value::from(1)).is_err());
+        assert!(ctx.set_path(&path("customer.address.city.zip"), Value::from(1)).is_err());
+        assert!(ctx.set_path(&path("refund.lines[0]"), Value::from(1)).is_err());
+        assert_eq!(ctx, before);
+        assert_eq!(ctx.get("refund"), None);
+
+        assert_eq!(ctx.remove_path(&path("customer.tags[0]")), Some(Value::from("vip")));
+        assert_eq!(ctx.remove_path(&path("customer.address.city")), Some(Value::from("Aarhus")));
+        assert_eq!(ctx.remove_path(&path("customer.address.city")), None);
+    }
+}
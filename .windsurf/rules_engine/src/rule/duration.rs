@@ -0,0 +1,174 @@
+//! Text form and conversions of [`Value::Duration`](crate::rule::Value::Duration)
+//!
+//! Durations print as `P90D`, `PT1H30M` or `-PT0.5S`: days, hours, minutes
+//! and seconds, omitting zero components. Parsing also accepts weeks (`P2W`)
+//! and components that overflow into the next unit (`PT90M`). Years and
+//! months are rejected because their length depends on the date.
+
+use chrono::TimeDelta;
+use std::fmt::Write;
+
+const SECONDS_PER_UNIT: [(char, i64); 4] = [('D', 86_400), ('H', 3_600), ('M', 60), ('S', 1)];
+
+/// Formats `duration` in ISO 8601 form, e.g. `P1DT2H`
+pub fn format_duration(duration: TimeDelta) -> String {
+    let mut out = String::new();
+    if duration < TimeDelta::zero() {
+        out.push('-');
+    }
+    out.push('P');
+    let duration = duration.abs();
+    let mut secs = duration.num_seconds();
+    let nanos = duration.subsec_nanos();
+    if secs == 0 && nanos == 0 {
+        out.push_str("T0S");
+        return out;
+    }
+
+    let days = secs / 86_400;
+    secs %= 86_400;
+    if days > 0 {
+        let _ = write!(out, "{}D", days);
+    }
+    if secs == 0 && nanos == 0 {
+        return out;
+    }
+    out.push('T');
+    for (unit, size) in &SECONDS_PER_UNIT[1..3] {
+        if secs >= *size {
+            let _ = write!(out, "{}{}", secs / size, unit);
+            secs %= size;
+        }
+    }
+    if secs > 0 || nanos > 0 {
+        let _ = write!(out, "{}", secs);
+        if nanos > 0 {
+            let fraction = format!("{:09}", nanos);
+            let _ = write!(out, ".{}", fraction.trim_end_matches('0'));
+        }
+        out.push('S');
+    }
+    out
+}
+
+/// Parses an ISO 8601 duration such as `P90D`, `PT36H` or `-P1W`; returns
+/// `None` for malformed input, years, months and out-of-range values
+pub fn parse_duration(source: &str) -> Option<TimeDelta> {
+    let (negative, rest) = match source.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, source),
+    };
+    let rest = rest.strip_prefix('P')?;
+    let (date, time) = match rest.split_once('T') {
+        Some((date, time)) if !time.is_empty() => (date, Some(time)),
+        Some(_) => return None,
+        None => (rest, None),
+    };
+    if date.is_empty() && time.is_none() {
+        return None;
+    }
+
+    let mut total = TimeDelta::zero();
+    for (part, units) in [
+        (date, &['W', 'D'][..]),
+        (time.unwrap_or(""), &['H', 'M', 'S'][..]),
+    ] {
+        let mut remaining = part;
+        let mut allowed = units;
+        while !remaining.is_empty() {
+            let end = remaining.find(|c: char| !c.is_ascii_digit() && c != '.')?;
+            let (number, unit) = (&remaining[..end], remaining[end..].chars().next()?);
+            let position = allowed.iter().position(|&u| u == unit)?;
+            allowed = &allowed[position + 1..];
+            total = total.checked_add(&component(number, unit)?)?;
+            remaining = &remaining[end + 1..];
+        }
+    }
+    Some(if negative { -total } else { total })
+}
+
+fn component(number: &str, unit: char) -> Option<TimeDelta> {
+    let size = match unit {
+        'W' => 7 * 86_400,
+        _ => SECONDS_PER_UNIT.iter().find(|(u, _)| *u == unit)?.1,
+    };
+    match number.split_once('.') {
+        // Only seconds may carry a fraction, as in `PT0.25S`
+        Some((whole, fraction)) if unit == 'S' && !fraction.is_empty() && fraction.len() <= 9 => {
+            let whole: i64 = if whole.is_empty() {
+                0
+            } else {
+                whole.parse().ok()?
+            };
+            let nanos: i64 = format!("{:0<9}", fraction).parse().ok()?;
+            TimeDelta::try_seconds(whole)?.checked_add(&TimeDelta::nanoseconds(nanos))
+        }
+        Some(_) => None,
+        None => TimeDelta::try_seconds(number.parse::<i64>().ok()?.checked_mul(size)?),
+    }
+}
+
+/// Converts a number of seconds to a duration, rounding to the nanosecond;
+/// returns `None` for non-finite and out-of-range input
+pub fn from_secs_f64(secs: f64) -> Option<TimeDelta> {
+    if !secs.is_finite() {
+        return None;
+    }
+    let whole = secs.trunc();
+    let nanos = ((secs - whole) * 1e9).round() as i64;
+    TimeDelta::try_seconds(whole as i64)?.checked_add(&TimeDelta::nanoseconds(nanos))
+}
+
+/// Length of `duration` in seconds
+pub fn as_secs_f64(duration: TimeDelta) -> f64 {
+    duration.num_seconds() as f64 + f64::from(duration.subsec_nanos()) * 1e-9
+}
+
+/// Serde adapter storing durations in their ISO 8601 form
+pub(crate) mod iso8601 {
+    use chrono::TimeDelta;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        duration: &TimeDelta,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&super::format_duration(*duration))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<TimeDelta, D::Error> {
+        let source = String::deserialize(deserializer)?;
+        super::parse_duration(&source)
+            .ok_or_else(|| D::Error::custom(format!("invalid ISO 8601 duration `{}`", source)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_durations_round_trip() {
+        let cases = [
+            (TimeDelta::days(90), "P90D"),
+            (TimeDelta::minutes(90), "PT1H30M"),
+            (TimeDelta::days(1) + TimeDelta::seconds(7), "P1DT7S"),
+            (-TimeDelta::milliseconds(250), "-PT0.25S"),
+            (TimeDelta::zero(), "PT0S"),
+        ];
+        for (duration, text) in cases {
+            assert_eq!(format_duration(duration), text);
+            assert_eq!(parse_duration(text), Some(duration), "{}", text);
+        }
+        assert_eq!(parse_duration("P2W"), Some(TimeDelta::days(14)));
+        assert_eq!(parse_duration("PT36H"), Some(TimeDelta::hours(36)));
+        assert_eq!(from_secs_f64(-1.5), Some(-TimeDelta::milliseconds(1500)));
+        assert_eq!(as_secs_f64(-TimeDelta::milliseconds(1500)), -1.5);
+        assert_eq!(from_secs_f64(f64::INFINITY), None);
+        for invalid in [
+            "", "P", "PT", "90D", "P1M", "P1Y", "PT1H2H", "PT1.5H", "P1DT",
+        ] {
+            assert_eq!(parse_duration(invalid), None, "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,511 @@
+//! Evaluation contract shared by native Rust rules and expression rules
+//!
+//! Anything implementing [`Rule`] can be evaluated against a [`RuleContext`],
+//! so hand-written Rust rules and [`RuleDefinition`]s loaded from data, once
+//! compiled into [`CompiledRule`]s, can sit side by side in the same rule
+//! set:
+//!
+//! ```
+//! use windsurf_rules::prelude::*;
+//!
+//! struct AlwaysMatches;
+//!
+//! impl Rule for AlwaysMatches {
+//!     fn evaluate(&self, _context: &RuleContext) -> RuleResult {
+//!         RuleResult::new(true)
+//!     }
+//! }
+//!
+//! let adult = RuleDefinition::new("adult").with_expression("customer.age >= 18");
+//! let rules: Vec<Box<dyn Rule>> = vec![Box::new(AlwaysMatches), Box::new(adult.compile()?)];
+//!
+//! let context = RuleContext::new().with_fact("customer", [("age", 30)].into_iter().collect::<Value>());
+//! assert!(rules.iter().all(|rule| rule.evaluate(&context).is_match()));
+//! # Ok::<(), windsurf_rules::Error>(())
+//! ```
+
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+
+use crate::diagnostic::Diagnostic;
+use crate::error::{Error, Result};
+use crate::expr::{Evaluator, Expr, Path};
+use crate::rule::action::{run_actions, CallbackRegistry, CompiledAction, Effect};
+use crate::rule::{MissingPolicy, Rule as RuleDefinition, RuleContext, RuleResult, Value};
+
+/// A rule that can be evaluated against a fact context
+pub trait Rule: Send + Sync {
+    /// Evaluates the rule against `context`
+    fn evaluate(&self, context: &RuleContext) -> RuleResult;
+
+    /// Unique identifier of the rule; defaults to the implementing type's name
+    fn id(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+
+    /// Human-readable name of the rule; defaults to the id
+    fn name(&self) -> &str {
+        self.id()
+    }
+
+    /// Priority of the rule (higher = more important)
+    fn priority(&self) -> i32 {
+        0
+    }
+
+    /// Whether the rule should currently be evaluated
+    fn is_active(&self) -> bool {
+        true
+    }
+
+    /// Whether the rule applies at `at`; rules without a validity window
+    /// always do
+    fn is_valid_at(&self, _at: DateTime<Utc>) -> bool {
+        true
+    }
+
+    /// Revision number of the rule's definition, if it is versioned
+    fn revision(&self) -> Option<u64> {
+        None
+    }
+
+    /// Labels used to select the rule
+    fn tags(&self) -> &[String] {
+        &[]
+    }
+
+    /// Value of a metadata entry
+    fn metadata(&self, _key: &str) -> Option<&str> {
+        None
+    }
+
+    /// Agenda group the rule belongs to; `None` means the default group
+    fn agenda_group(&self) -> Option<&str> {
+        None
+    }
+
+    /// Activation group in which only the first firing rule fires
+    fn activation_group(&self) -> Option<&str> {
+        None
+    }
+
+    /// The parsed condition, for rules whose match is fully described by an
+    /// expression; network-based evaluators use it to share condition nodes
+    fn condition(&self) -> Option<&Expr> {
+        None
+    }
+
+    /// Fact paths the rule reads; defaults to the paths in its condition
+    fn reads(&self) -> Vec<&Path> {
+        self.condition().map(Expr::paths).unwrap_or_default()
+    }
+
+    /// Fact paths the rule's actions may set or remove; unknown for native
+    /// rules, which report none
+    fn writes(&self) -> Vec<&Path> {
+        Vec::new()
+    }
+
+    /// Runs the rule's then-actions (`matched == true`) or else-actions and
+    /// returns their effects; rules without actions produce none
+    fn consequences(
+        &self,
+        _matched: bool,
+        _context: &RuleContext,
+        _callbacks: &CallbackRegistry,
+    ) -> Result<Vec<Effect>> {
+        Ok(Vec::new())
+    }
+}
+
+impl<R: Rule + ?Sized> Rule for Box<R> {
+    fn evaluate(&self, context: &RuleContext) -> RuleResult {
+        (**self).evaluate(context)
+    }
+
+    fn id(&self) -> &str {
+        (**self).id()
+    }
+
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn priority(&self) -> i32 {
+        (**self).priority()
+    }
+
+    fn is_active(&self) -> bool {
+        (**self).is_active()
+    }
+
+    fn is_valid_at(&self, at: DateTime<Utc>) -> bool {
+        (**self).is_valid_at(at)
+    }
+
+    fn revision(&self) -> Option<u64> {
+        (**self).revision()
+    }
+
+    fn tags(&self) -> &[String] {
+        (**self).tags()
+    }
+
+    fn metadata(&self, key: &str) -> Option<&str> {
+        (**self).metadata(key)
+    }
+
+    fn agenda_group(&self) -> Option<&str> {
+        (**self).agenda_group()
+    }
+
+    fn activation_group(&self) -> Option<&str> {
+        (**self).activation_group()
+    }
+
+    fn condition(&self) -> Option<&Expr> {
+        (**self).condition()
+    }
+
+    fn reads(&self) -> Vec<&Path> {
+        (**self).reads()
+    }
+
+    fn writes(&self) -> Vec<&Path> {
+        (**self).writes()
+    }
+
+    fn consequences(
+        &self,
+        matched: bool,
+        context: &RuleContext,
+        callbacks: &CallbackRegistry,
+    ) -> Result<Vec<Effect>> {
+        (**self).consequences(matched, context, callbacks)
+    }
+}
+
+impl<R: Rule + ?Sized> Rule for Arc<R> {
+    fn evaluate(&self, context: &RuleContext) -> RuleResult {
+        (**self).evaluate(context)
+    }
+
+    fn id(&self) -> &str {
+        (**self).id()
+    }
+
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn priority(&self) -> i32 {
+        (**self).priority()
+    }
+
+    fn is_active(&self) -> bool {
+        (**self).is_active()
+    }
+
+    fn is_valid_at(&self, at: DateTime<Utc>) -> bool {
+        (**self).is_valid_at(at)
+    }
+
+    fn revision(&self) -> Option<u64> {
+        (**self).revision()
+    }
+
+    fn tags(&self) -> &[String] {
+        (**self).tags()
+    }
+
+    fn metadata(&self, key: &str) -> Option<&str> {
+        (**self).metadata(key)
+    }
+
+    fn agenda_group(&self) -> Option<&str> {
+        (**self).agenda_group()
+    }
+
+    fn activation_group(&self) -> Option<&str> {
+        (**self).activation_group()
+    }
+
+    fn condition(&self) -> Option<&Expr> {
+        (**self).condition()
+    }
+
+    fn reads(&self) -> Vec<&Path> {
+        (**self).reads()
+    }
+
+    fn writes(&self) -> Vec<&Path> {
+        (**self).writes()
+    }
+
+    fn consequences(
+        &self,
+        matched: bool,
+        context: &RuleContext,
+        callbacks: &CallbackRegistry,
+    ) -> Result<Vec<Effect>> {
+        (**self).consequences(matched, context, callbacks)
+    }
+}
+
+/// Converts an evaluation failure into an error diagnostic for `rule_id`
+pub(crate) fn error_diagnostic(rule_id: &str, error: &Error) -> Diagnostic {
+    diagnostic_for(error).with_rule(rule_id)
+}
+
+/// Converts an evaluation failure into an error diagnostic not tied to a rule
+pub(crate) fn diagnostic_for(error: &Error) -> Diagnostic {
+    match error {
+        Error::Syntax { message, span, .. } => {
+            Diagnostic::error("syntax-error", message.clone()).with_span(*span)
+        }
+        Error::Evaluation { message, span } => {
+            Diagnostic::error("eval-error", message.clone()).with_span(*span)
+        }
+        other => Diagnostic::error("eval-error", other.to_string()),
+    }
+}
+
+/// Evaluates a parsed condition, turning failures into diagnostics; a `null`
+/// condition is unknown unless the context's policy treats it as false
+fn evaluate_condition(rule_id: &str, condition: &Expr, context: &RuleContext) -> RuleResult {
+    match Evaluator::new(context).evaluate(condition) {
+        Ok(Value::Bool(matched)) => RuleResult::new(matched),
+        Ok(Value::Null) if context.missing_policy() == MissingPolicy::False => {
+            RuleResult::not_matched()
+        }
+        Ok(Value::Null) => RuleResult::unknown(),
+        Ok(other) => RuleResult::from_diagnostic(
+            Diagnostic::error(
+                "condition-not-bool",
+                format!("condition evaluated to {} `{}`, expected bool", other.type_name(), other),
+            )
+            .with_rule(rule_id)
+            .with_span(condition.span),
+        ),
+        Err(error) => RuleResult::from_diagnostic(error_diagnostic(rule_id, &error)),
+    }
+}
+
+/// A [`RuleDefinition`] whose expression and actions have been parsed ahead of time
+#[derive(Debug, Clone)]
+pub struct CompiledRule {
+    definition: RuleDefinition,
+    condition: Expr,
+    then_actions: Vec<CompiledAction>,
+    else_actions: Vec<CompiledAction>,
+}
+
+impl CompiledRule {
+    /// Parses the definition's expression and action expressions
+    pub fn new(definition: RuleDefinition) -> Result<Self> {
+        let condition = definition.parse_expression()?;
+        let compile = |actions: &[crate::rule::Action]| {
+            actions.iter().map(|action| action.compile()).collect::<Result<Vec<_>>>()
+        };
+        Ok(Self {
+            then_actions: compile(&definition.then_actions)?,
+            else_actions: compile(&definition.else_actions)?,
+            definition,
+            condition,
+        })
+    }
+
+    /// The underlying rule definition
+    pub fn definition(&self) -> &RuleDefinition {
+        &self.definition
+    }
+
+    /// The parsed condition
+    pub fn condition(&self) -> &Expr {
+        &self.condition
+    }
+
+    /// The parsed actions run when the rule matches
+    pub fn then_actions(&self) -> &[CompiledAction] {
+        &self.then_actions
+    }
+
+    /// The parsed actions run when the rule does not match
+    pub fn else_actions(&self) -> &[CompiledAction] {
+        &self.else_actions
+    }
+}
+
+impl Rule for CompiledRule {
+    fn evaluate(&self, context: &RuleContext) -> RuleResult {
+        evaluate_condition(&self.definition.id, &self.condition, context)
+    }
+
+    fn id(&self) -> &str {
+        &self.definition.id
+    }
+
+    fn name(&self) -> &str {
+        &self.definition.name
+    }
+
+    fn priority(&self) -> i32 {
+        self.definition.priority
+    }
+
+    fn is_active(&self) -> bool {
+        self.definition.is_active
+    }
+
+    fn is_valid_at(&self, at: DateTime<Utc>) -> bool {
+        self.definition.is_valid_at(at)
+    }
+
+    fn revision(&self) -> Option<u64> {
+        self.definition.revision.as_ref().map(|revision| revision.number)
+    }
+
+    fn tags(&self) -> &[String] {
+        &self.definition.tags
+    }
+
+    fn metadata(&self, key: &str) -> Option<&str> {
+        self.definition.metadata.get(key).map(String::as_str)
+    }
+
+    fn agenda_group(&self) -> Option<&str> {
+        self.definition.agenda_group.as_deref()
+    }
+
+    fn activation_group(&self) -> Option<&str> {
+        self.definition.activation_group.as_deref()
+    }
+
+    fn condition(&self) -> Option<&Expr> {
+        Some(&self.condition)
+    }
+
+    fn reads(&self) -> Vec<&Path> {
+        let actions = self.then_actions.iter().chain(&self.else_actions);
+        let mut paths = self.condition.paths();
+        paths.extend(actions.flat_map(CompiledAction::reads));
+        paths
+    }
+
+    fn writes(&self) -> Vec<&Path> {
+        let actions = self.then_actions.iter().chain(&self.else_actions);
+        actions.filter_map(CompiledAction::writes).collect()
+    }
+
+    fn consequences(
+        &self,
+        matched: bool,
+        context: &RuleContext,
+        callbacks: &CallbackRegistry,
+    ) -> Result<Vec<Effect>> {
+        let actions = if matched { &self.then_actions } else { &self.else_actions };
+        run_actions(actions, context, callbacks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct NativeRule;
+
+    impl Rule for NativeRule {
+        fn evaluate(&self, context: &RuleContext) -> RuleResult {
+            RuleResult::new(context.get("flag").and_then(Value::as_bool).unwrap_or(false))
+        }
+    }
+
+    #[test]
+    fn test_heterogeneous_rule_set() {
+        let rules: Vec<Box<dyn Rule>> = vec![
+            Box::new(NativeRule),
+            Box::new(
+                RuleDefinition::new("expr").with_expression("flag && count > 2").compile().unwrap(),
+            ),
+            Box::new(RuleDefinition::new("expr").with_expression("count > 2").compile().unwrap()),
+        ];
+        let context = RuleContext::new().with_fact("flag", true).with_fact("count", 3);
+        for rule in &rules {
+            assert!(rule.evaluate(&context).is_match(), "{}", rule.id());
+        }
+        assert!(rules[0].id().ends_with("NativeRule"));
+        assert_eq!(rules[1].id(), "expr");
+    }
+
+    #[test]
+    fn test_failures_become_diagnostics() {
+        let context = RuleContext::new().with_fact("count", 3);
+
+        let evaluate = |id: &str, expression: &str| {
+            RuleDefinition::new(id)
+                .with_expression(expression)
+                .compile()
+                .unwrap()
+                .evaluate(&context)
+        };
+        assert!(matches!(
+            RuleDefinition::new("broken").with_expression("count >").compile(),
+            Err(Error::Syntax { .. })
+        ));
+
+        let result = evaluate("not_bool", "count + 1");
+        assert_eq!(result.diagnostics[0].code, "condition-not-bool");
+        assert_eq!(result.diagnostics[0].rule_id.as_deref(), Some("not_bool"));
+
+        let result = evaluate("bad_types", "count > 'x'");
+        assert_eq!(result.diagnostics[0].code, "eval-error");
+        assert!(result.diagnostics[0].span.is_some());
+    }
+
+    #[test]
+    fn test_missing_facts_follow_the_policy() {
+        let rule = RuleDefinition::new("big")
+            .with_expression("count > 2 || total > 100")
+            .compile()
+            .unwrap();
+        let context = RuleContext::new().with_fact("count", 1);
+
+        let result = rule.evaluate(&context);
+        assert!(result.is_unknown() && !result.is_match() && !result.has_errors());
+        let result = rule.evaluate(&context.clone().with_missing_policy(MissingPolicy::False));
+        assert!(!result.is_unknown() && !result.is_match());
+        let result = rule.evaluate(&context.clone().with_missing_policy(MissingPolicy::Error));
+        assert_eq!(result.diagnostics[0].message, "missing fact `total`");
+
+        let guarded = RuleDefinition::new("guarded")
+            .with_expression("exists(total) && total > 100 || count < 2")
+            .compile()
+            .unwrap();
+        let strict = context.with_missing_policy(MissingPolicy::Error);
+        assert!(guarded.evaluate(&strict).is_match());
+    }
+
+    #[test]
+    fn test_consequences_follow_match() {
+        let rule = RuleDefinition::new("big")
+            .with_expression("count > 2")
+            .then(crate::rule::Action::set("flag", "true"))
+            .otherwise(crate::rule::Action::message("count is {count}"))
+            .compile()
+            .unwrap();
+        let callbacks = CallbackRegistry::new();
+
+        let small = RuleContext::new().with_fact("count", 1);
+        let matched = rule.evaluate(&small).matched;
+        assert_eq!(
+            rule.consequences(matched, &small, &callbacks).unwrap(),
+            [Effect::Message { text: "count is 1".into() }]
+        );
+        let big = RuleContext::new().with_fact("count", 3);
+        assert!(matches!(
+            rule.consequences(true, &big, &callbacks).unwrap()[0],
+            Effect::SetFact { .. }
+        ));
+        assert!(NativeRule.consequences(true, &big, &callbacks).unwrap().is_empty());
+    }
+}
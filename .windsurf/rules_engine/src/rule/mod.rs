@@ -1,9 +1,37 @@
 //! Rule definition and processing module
 
+use chrono::{DateTime, Utc};
 use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
 use std::fmt;
 
+pub mod action;
+pub mod calendar;
+pub mod context;
+pub mod duration;
+pub mod evaluate;
+pub mod result;
+pub mod revision;
+pub mod value;
+
+pub use self::action::{Action, CallbackRegistry, Effect};
+pub use self::calendar::{Calendar, TimeWindow};
+pub use self::context::{MissingPolicy, RuleContext};
+pub use self::evaluate::CompiledRule;
+pub use self::result::RuleResult;
+pub use self::revision::Revision;
+pub use self::value::Value;
+
+use crate::error::Result;
+use crate::expr::{self, Expr};
+use self::action::NativeAction;
+
 /// Represents a rule in the rules engine
+///
+/// This is the data form of a rule, as loaded from configuration.
+/// [`compile`](Self::compile) turns it into a [`CompiledRule`], which
+/// implements the [`evaluate::Rule`] trait so it can be evaluated alongside
+/// native Rust rules.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Rule {
     /// Unique identifier for the rule
@@ -16,6 +44,36 @@ pub struct Rule {
     pub priority: i32,
     /// Whether the rule is currently active
     pub is_active: bool,
+    /// Actions to run when the expression matches
+    #[serde(default, rename = "then", skip_serializing_if = "Vec::is_empty")]
+    pub then_actions: Vec<Action>,
+    /// Actions to run when the expression does not match
+    #[serde(default, rename = "else", skip_serializing_if = "Vec::is_empty")]
+    pub else_actions: Vec<Action>,
+    /// Agenda group the rule belongs to; `None` means the default group
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agenda_group: Option<String>,
+    /// Activation group in which only the first firing rule fires
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub activation_group: Option<String>,
+    /// Free-form labels for selecting rules, e.g. `fraud`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Arbitrary key/value metadata, e.g. the owning team
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+    /// Time from which the rule applies; `None` means always has
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_from: Option<DateTime<Utc>>,
+    /// Time from which the rule no longer applies; `None` means never expires
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_until: Option<DateTime<Utc>>,
+    /// Recurring times within the validity window at which the rule applies
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub calendar: Option<Calendar>,
+    /// The change that produced this version of the rule
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<Revision>,
 }
 
 impl Default for Rule {
@@ -26,10 +84,148 @@ impl Default for Rule {
             expression: String::new(),
             priority: 0,
             is_active: true,
+            then_actions: Vec::new(),
+            else_actions: Vec::new(),
+            agenda_group: None,
+            activation_group: None,
+            tags: Vec::new(),
+            metadata: BTreeMap::new(),
+            valid_from: None,
+            valid_until: None,
+            calendar: None,
+            revision: None,
         }
     }
 }
 
+impl Rule {
+    /// Creates an active rule with the given id, using the id as its name
+    pub fn new(id: impl Into<String>) -> Self {
+        let id = id.into();
+        Self {
+            name: id.clone(),
+            id,
+            ..Self::default()
+        }
+    }
+
+    /// Sets the human-readable name
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Sets the condition expression
+    pub fn with_expression(mut self, expression: impl Into<String>) -> Self {
+        self.expression = expression.into();
+        self
+    }
+
+    /// Sets the priority
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Places the rule in an agenda group
+    pub fn in_agenda_group(mut self, group: impl Into<String>) -> Self {
+        self.agenda_group = Some(group.into());
+        self
+    }
+
+    /// Places the rule in an activation group
+    pub fn in_activation_group(mut self, group: impl Into<String>) -> Self {
+        self.activation_group = Some(group.into());
+        self
+    }
+
+    /// Adds a tag, unless the rule already has it
+    pub fn with_tag(mut self, tag: impl Into<String>) -> Self {
+        let tag = tag.into();
+        if !self.tags.contains(&tag) {
+            self.tags.push(tag);
+        }
+        self
+    }
+
+    /// Sets a metadata entry
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    /// Makes the rule apply from `at` onwards
+    pub fn valid_from(mut self, at: DateTime<Utc>) -> Self {
+        self.valid_from = Some(at);
+        self
+    }
+
+    /// Makes the rule stop applying at `at`
+    pub fn valid_until(mut self, at: DateTime<Utc>) -> Self {
+        self.valid_until = Some(at);
+        self
+    }
+
+    /// Restricts the rule to the recurring times of `calendar`
+    pub fn with_calendar(mut self, calendar: Calendar) -> Self {
+        self.calendar = Some(calendar);
+        self
+    }
+
+    /// Records the change that produced this version of the rule
+    pub fn with_revision(mut self, revision: Revision) -> Self {
+        self.revision = Some(revision);
+        self
+    }
+
+    /// Returns true if `at` is inside the rule's validity window and calendar
+    pub fn is_valid_at(&self, at: DateTime<Utc>) -> bool {
+        self.valid_from.is_none_or(|from| from <= at)
+            && self.valid_until.is_none_or(|until| at < until)
+            && self.calendar.as_ref().is_none_or(|calendar| calendar.contains(at))
+    }
+
+    /// Adds an action to run when the rule matches
+    pub fn then(mut self, action: Action) -> Self {
+        self.then_actions.push(action);
+        self
+    }
+
+    /// Adds an action to run when the rule does not match
+    pub fn otherwise(mut self, action: Action) -> Self {
+        self.else_actions.push(action);
+        self
+    }
+
+    /// Adds a Rust closure to run when the rule matches
+    pub fn action<F, R>(self, action: F) -> Self
+    where
+        F: Fn(&RuleContext) -> Result<R> + Send + Sync + 'static,
+        R: Into<Value>,
+    {
+        self.then(Action::Native(NativeAction::new(action)))
+    }
+
+    /// Adds a Rust closure to run when the rule does not match
+    pub fn else_action<F, R>(self, action: F) -> Self
+    where
+        F: Fn(&RuleContext) -> Result<R> + Send + Sync + 'static,
+        R: Into<Value>,
+    {
+        self.otherwise(Action::Native(NativeAction::new(action)))
+    }
+
+    /// Parses the rule's expression into a typed AST
+    pub fn parse_expression(&self) -> Result<Expr> {
+        expr::parse(&self.expression)
+    }
+
+    /// Parses the expression once so the rule can be evaluated repeatedly
+    pub fn compile(self) -> Result<CompiledRule> {
+        CompiledRule::new(self)
+    }
+}
+
 impl fmt::Display for Rule {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -62,6 +258,7 @@ mod tests {
             expression: "1 == 1".to_string(),
             priority: 1,
             is_active: true,
+            ..Rule::default()
         };
         
         let display = format!("{}", rule);
@@ -69,4 +266,31 @@ mod tests {
         assert!(display.contains("Test Rule"));
         assert!(display.contains('1'));
     }
+
+    #[test]
+    fn test_rule_builder() {
+        let rule = Rule::new("vip")
+            .with_expression("customer.orders > 10")
+            .with_priority(5)
+            .then(Action::set("customer.tier", "'gold'"))
+            .otherwise(Action::message("not yet"))
+            .action(|_ctx| Ok(()))
+            .in_agenda_group("pricing");
+        assert_eq!(rule.name, "vip");
+        assert_eq!(rule.agenda_group.as_deref(), Some("pricing"));
+        assert_eq!(rule.then_actions.len(), 2);
+        assert_eq!(rule.else_actions.len(), 1);
+    }
+
+    #[test]
+    fn test_rule_parse_expression() {
+        let rule = Rule {
+            expression: "amount > 100 &&".to_string(),
+            ..Rule::default()
+        };
+        assert!(matches!(
+            rule.parse_expression(),
+            Err(crate::error::Error::Syntax { column: 16, .. })
+        ));
+    }
 }
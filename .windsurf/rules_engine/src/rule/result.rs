@@ -0,0 +1,112 @@
+//! Outcome of evaluating a single rule
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::diagnostic::Diagnostic;
+use crate::rule::action::Effect;
+use crate::rule::value::Value;
+
+/// The result of evaluating a rule against a [`RuleContext`](crate::rule::RuleContext)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RuleResult {
+    /// Whether the rule's condition matched
+    pub matched: bool,
+    /// Whether the condition was unknown (`null`) because of missing or null
+    /// facts; unknown results never match
+    #[serde(default)]
+    pub unknown: bool,
+    /// Named values produced by the rule
+    pub outputs: BTreeMap<String, Value>,
+    /// Messages produced while evaluating the rule
+    pub diagnostics: Vec<Diagnostic>,
+    /// Effects produced by the rule's then/else actions
+    #[serde(default)]
+    pub effects: Vec<Effect>,
+}
+
+impl RuleResult {
+    /// Creates a result with the given match flag and no outputs
+    pub fn new(matched: bool) -> Self {
+        Self {
+            matched,
+            ..Self::default()
+        }
+    }
+
+    /// Creates a matching result
+    pub fn matched() -> Self {
+        Self::new(true)
+    }
+
+    /// Creates a non-matching result
+    pub fn not_matched() -> Self {
+        Self::new(false)
+    }
+
+    /// Creates a result whose condition was unknown
+    pub fn unknown() -> Self {
+        Self {
+            unknown: true,
+            ..Self::default()
+        }
+    }
+
+    /// Creates a non-matching result carrying a single diagnostic
+    pub fn from_diagnostic(diagnostic: Diagnostic) -> Self {
+        Self::new(false).with_diagnostic(diagnostic)
+    }
+
+    /// Adds a named output value
+    pub fn with_output(mut self, name: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.outputs.insert(name.into(), value.into());
+        self
+    }
+
+    /// Adds an effect
+    pub fn with_effect(mut self, effect: Effect) -> Self {
+        self.effects.push(effect);
+        self
+    }
+
+    /// Adds a diagnostic
+    pub fn with_diagnostic(mut self, diagnostic: Diagnostic) -> Self {
+        self.diagnostics.push(diagnostic);
+        self
+    }
+
+    /// Returns true if the rule matched
+    pub fn is_match(&self) -> bool {
+        self.matched
+    }
+
+    /// Returns true if the condition was unknown
+    pub fn is_unknown(&self) -> bool {
+        self.unknown
+    }
+
+    /// Returns true if any error diagnostics were produced
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(Diagnostic::is_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rule_result_builders() {
+        let result = RuleResult::matched().with_output("discount", 10);
+        assert!(result.is_match());
+        assert_eq!(result.outputs.get("discount"), Some(&Value::Int(10)));
+        assert!(!result.has_errors());
+
+        let failed = RuleResult::from_diagnostic(Diagnostic::error("eval-error", "boom"));
+        assert!(!failed.is_match());
+        assert!(failed.has_errors());
+
+        let unknown = RuleResult::unknown();
+        assert!(unknown.is_unknown() && !unknown.is_match());
+    }
+}
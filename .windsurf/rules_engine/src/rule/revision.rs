@@ -0,0 +1,40 @@
+//! Revision metadata recorded on rule definitions
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Who changed a rule, when, and why
+///
+/// Revision numbers increase monotonically across a
+/// [`RuleStore`](crate::store::RuleStore), so a single number identifies the
+/// state of every rule at the time of a change.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Revision {
+    /// Revision number, starting at 1
+    pub number: u64,
+    /// Author of the change
+    pub author: String,
+    /// Time the change was recorded
+    pub timestamp: DateTime<Utc>,
+    /// Why the change was made
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub note: String,
+}
+
+impl Revision {
+    /// Creates revision `number` by `author` at `timestamp`
+    pub fn new(number: u64, author: impl Into<String>, timestamp: DateTime<Utc>) -> Self {
+        Self {
+            number,
+            author: author.into(),
+            timestamp,
+            note: String::new(),
+        }
+    }
+
+    /// Sets the change note
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.note = note.into();
+        self
+    }
+}
@@ -0,0 +1,356 @@
+//! Typed fact values used by rule contexts and expression evaluation
+
+use chrono::{DateTime, TimeDelta, Utc};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::expr::PathSegment;
+use crate::rule::duration::format_duration;
+
+/// A typed fact value
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+pub enum Value {
+    /// Absence of a value
+    #[default]
+    Null,
+    /// Boolean
+    Bool(bool),
+    /// 64-bit signed integer
+    Int(i64),
+    /// 64-bit floating point number
+    Float(f64),
+    /// Exact decimal number, e.g. for monetary amounts
+    Decimal(Decimal),
+    /// UTF-8 string
+    String(String),
+    /// Point in time (UTC)
+    Timestamp(DateTime<Utc>),
+    /// Signed length of time, serialized in ISO 8601 form such as `P90D`
+    #[serde(with = "crate::rule::duration::iso8601")]
+    Duration(TimeDelta),
+    /// Ordered list of values
+    List(Vec<Value>),
+    /// Nested map of named values
+    Map(BTreeMap<String, Value>),
+}
+
+impl Value {
+    /// Name of the value's type, as used in diagnostics
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Bool(_) => "bool",
+            Value::Int(_) => "int",
+            Value::Float(_) => "float",
+            Value::Decimal(_) => "decimal",
+            Value::String(_) => "string",
+            Value::Timestamp(_) => "timestamp",
+            Value::Duration(_) => "duration",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+        }
+    }
+
+    /// Returns true if the value is `Null`
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    /// Returns true for the numeric variants (`Int`, `Float`, `Decimal`)
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, Value::Int(_) | Value::Float(_) | Value::Decimal(_))
+    }
+
+    /// Returns the boolean if this is a `Bool`
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// Returns the integer if this is an `Int`
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Int(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    /// Returns any numeric value widened to `f64`
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Int(i) => Some(*i as f64),
+            Value::Float(x) => Some(*x),
+            Value::Decimal(d) => d.to_f64(),
+            _ => None,
+        }
+    }
+
+    /// Returns the string slice if this is a `String`
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns the point in time if this is a `Timestamp`
+    pub fn as_timestamp(&self) -> Option<DateTime<Utc>> {
+        match self {
+            Value::Timestamp(ts) => Some(*ts),
+            _ => None,
+        }
+    }
+
+    /// Returns the length of time if this is a `Duration`
+    pub fn as_duration(&self) -> Option<TimeDelta> {
+        match self {
+            Value::Duration(d) => Some(*d),
+            _ => None,
+        }
+    }
+
+    /// Returns the items if this is a `List`
+    pub fn as_list(&self) -> Option<&[Value]> {
+        match self {
+            Value::List(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// Returns the entries if this is a `Map`
+    pub fn as_map(&self) -> Option<&BTreeMap<String, Value>> {
+        match self {
+            Value::Map(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    /// Compares two values for equality, treating equal numbers as equal
+    /// whether they are held as integers, floats or decimals
+    pub fn equals(&self, other: &Value) -> bool {
+        match (self, other) {
+            (a, b) if a.is_numeric() && b.is_numeric() => a.compare(b) == Some(Ordering::Equal),
+            (Value::List(a), Value::List(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(x, y)| x.equals(y))
+            }
+            (Value::Map(a), Value::Map(b)) => {
+                a.len() == b.len()
+                    && a.iter().all(|(key, x)| b.get(key).is_some_and(|y| x.equals(y)))
+            }
+            (a, b) => a == b,
+        }
+    }
+
+    /// Orders two values of compatible types; returns `None` when the values
+    /// cannot be ordered against each other
+    pub fn compare(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Int(a), Value::Int(b)) => Some(a.cmp(b)),
+            (Value::Decimal(a), Value::Decimal(b)) => Some(a.cmp(b)),
+            (Value::Int(a), Value::Decimal(b)) => Some(Decimal::from(*a).cmp(b)),
+            (Value::Decimal(a), Value::Int(b)) => Some(a.cmp(&Decimal::from(*b))),
+            (a, b) if a.is_numeric() && b.is_numeric() => a.as_f64()?.partial_cmp(&b.as_f64()?),
+            (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+            (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+            (Value::Timestamp(a), Value::Timestamp(b)) => Some(a.cmp(b)),
+            (Value::Duration(a), Value::Duration(b)) => Some(a.cmp(b)),
+            _ => None,
+        }
+    }
+
+    /// Follows a single path segment into a map or list
+    pub fn get_segment(&self, segment: &PathSegment) -> Option<&Value> {
+        match (self, segment) {
+            (Value::Map(map), PathSegment::Key(key)) => map.get(key),
+            (Value::List(items), PathSegment::Index(index)) => items.get(*index),
+            _ => None,
+        }
+    }
+
+    /// Follows a sequence of path segments into nested maps and lists
+    pub fn get_path(&self, segments: &[PathSegment]) -> Option<&Value> {
+        segments
+            .iter()
+            .try_fold(self, |value, segment| value.get_segment(segment))
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => f.write_str("null"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Int(i) => write!(f, "{}", i),
+            Value::Float(x) => write!(f, "{}", x),
+            Value::Decimal(d) => write!(f, "{}", d),
+            Value::String(s) => write!(f, "{:?}", s),
+            Value::Timestamp(ts) => write!(f, "{}", ts.to_rfc3339()),
+            Value::Duration(d) => f.write_str(&format_duration(*d)),
+            Value::List(items) => {
+                f.write_str("[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                f.write_str("]")
+            }
+            Value::Map(map) => {
+                f.write_str("{")?;
+                for (i, (key, value)) in map.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}: {}", key, value)?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+impl From<()> for Value {
+    fn from(_: ()) -> Self {
+        Value::Null
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(i: i32) -> Self {
+        Value::Int(i64::from(i))
+    }
+}
+
+impl From<i64> for Value {
+    fn from(i: i64) -> Self {
+        Value::Int(i)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(x: f64) -> Self {
+        Value::Float(x)
+    }
+}
+
+impl From<Decimal> for Value {
+    fn from(d: Decimal) -> Self {
+        Value::Decimal(d)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::String(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::String(s)
+    }
+}
+
+impl From<DateTime<Utc>> for Value {
+    fn from(ts: DateTime<Utc>) -> Self {
+        Value::Timestamp(ts)
+    }
+}
+
+impl From<TimeDelta> for Value {
+    fn from(d: TimeDelta) -> Self {
+        Value::Duration(d)
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(items: Vec<T>) -> Self {
+        Value::List(items.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Null, Into::into)
+    }
+}
+
+impl<K: Into<String>, V: Into<Value>> FromIterator<(K, V)> for Value {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Value::Map(iter.into_iter().map(|(k, v)| (k.into(), v.into())).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_value_conversions() {
+        assert_eq!(Value::from(3), Value::Int(3));
+        assert_eq!(Value::from("a"), Value::String("a".into()));
+        assert_eq!(Value::from(None::<i64>), Value::Null);
+        assert_eq!(Value::from(vec![1, 2]).as_list().map(<[Value]>::len), Some(2));
+        let price = Decimal::from_str("19.99").unwrap();
+        assert_eq!(Value::from(price).as_f64(), Some(19.99));
+        assert_eq!(Value::from(price).type_name(), "decimal");
+    }
+
+    #[test]
+    fn test_value_numeric_comparison() {
+        let half = Value::Decimal(Decimal::from_str("0.5").unwrap());
+        assert!(Value::Int(1).equals(&Value::Float(1.0)));
+        assert!(Value::Float(0.5).equals(&half));
+        assert_eq!(Value::Int(1).compare(&half), Some(Ordering::Greater));
+        assert_eq!(Value::from("a").compare(&Value::Int(1)), None);
+        assert!(!Value::from("1").equals(&Value::Int(1)));
+    }
+
+    #[test]
+    fn test_value_durations() {
+        let quarter = Value::from(TimeDelta::days(90));
+        assert_eq!(quarter.to_string(), "P90D");
+        assert_eq!(
+            quarter.compare(&Value::from(TimeDelta::hours(36))),
+            Some(Ordering::Greater)
+        );
+        let yaml = serde_yaml::to_string(&quarter).unwrap();
+        assert_eq!(yaml.trim(), "!Duration P90D");
+        assert_eq!(serde_yaml::from_str::<Value>(&yaml).unwrap(), quarter);
+    }
+
+    #[test]
+    fn test_value_get_path() {
+        let customer: Value = [
+            ("name", Value::from("Ada")),
+            ("orders", Value::from(vec![[("total", 42)]
+                .into_iter()
+                .collect::<Value>()])),
+        ]
+        .into_iter()
+        .collect();
+
+        let path = [
+            PathSegment::Key("orders".into()),
+            PathSegment::Index(0),
+            PathSegment::Key("total".into()),
+        ];
+        assert_eq!(customer.get_path(&path), Some(&Value::Int(42)));
+        assert_eq!(customer.get_path(&path[..1]).map(Value::type_name), Some("list"));
+        assert_eq!(customer.get_path(&[PathSegment::Index(0)]), None);
+    }
+}
@@ -0,0 +1,530 @@
+//! Selector queries over rule tags, metadata and attributes
+//!
+//! A [`Selector`] picks a subset of rules, for example to evaluate only the
+//! rules of one product area or to disable them all at once:
+//!
+//! ```text
+//! tag:fraud && owner=team-a && priority>5
+//! (tag:pricing || tag:promo*) && !active=false
+//! ```
+//!
+//! - `tag:<pattern>` matches rules with a matching tag
+//! - `<key><op><value>` compares an attribute, where `op` is one of `=`, `!=`,
+//!   `<`, `<=`, `>` or `>=`. The keys `id`, `name`, `priority`, `active`,
+//!   `agenda_group`, `activation_group` and `revision` read the rule itself;
+//!   any other key reads the rule's metadata. Values that both parse as
+//!   numbers are compared numerically.
+//! - `=`, `!=` and tags accept `*` as a wildcard
+//! - `&&`, `||`, `!` and parentheses combine selectors
+//! - values containing other characters are quoted with `'` or `"`
+//!
+//! An empty selector, or `*`, matches every rule.
+//!
+//! ```
+//! use windsurf_rules::prelude::*;
+//! use windsurf_rules::selector::Selector;
+//!
+//! let rule = RuleDefinition::new("velocity")
+//!     .with_priority(10)
+//!     .with_tag("fraud")
+//!     .with_metadata("owner", "team-a");
+//! let selector: Selector = "tag:fraud && owner=team-a && priority>5".parse()?;
+//! assert!(selector.matches(&rule));
+//! assert!(!Selector::parse("owner=team-b")?.matches(&rule));
+//! # Ok::<(), windsurf_rules::Error>(())
+//! ```
+
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fmt::{self, Write};
+use std::str::FromStr;
+
+use crate::error::{Error, Result};
+use crate::expr::lexer::syntax_error;
+use crate::expr::Span;
+use crate::rule::evaluate::Rule;
+use crate::rule::Rule as RuleDefinition;
+
+/// Comparison operator of a [`Selector::Compare`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    /// `=`
+    Eq,
+    /// `!=`
+    Ne,
+    /// `<`
+    Lt,
+    /// `<=`
+    Le,
+    /// `>`
+    Gt,
+    /// `>=`
+    Ge,
+}
+
+impl CompareOp {
+    fn as_str(self) -> &'static str {
+        match self {
+            CompareOp::Eq => "=",
+            CompareOp::Ne => "!=",
+            CompareOp::Lt => "<",
+            CompareOp::Le => "<=",
+            CompareOp::Gt => ">",
+            CompareOp::Ge => ">=",
+        }
+    }
+}
+
+/// A parsed selector query
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Selector {
+    /// Matches every rule
+    #[default]
+    All,
+    /// Matches rules with a tag matching the pattern
+    Tag(String),
+    /// Compares a rule attribute or metadata entry with a value
+    Compare {
+        /// Attribute or metadata key
+        key: String,
+        /// Comparison operator
+        op: CompareOp,
+        /// Value or `*` pattern to compare with
+        value: String,
+    },
+    /// Matches rules the inner selector does not match
+    Not(Box<Selector>),
+    /// Matches rules both selectors match
+    And(Box<Selector>, Box<Selector>),
+    /// Matches rules either selector matches
+    Or(Box<Selector>, Box<Selector>),
+}
+
+impl Selector {
+    /// Parses a selector, failing with [`Error::Syntax`] on malformed input
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut parser = Parser { source, pos: 0 };
+        parser.skip_whitespace();
+        if parser.pos == source.len() {
+            return Ok(Selector::All);
+        }
+        let selector = parser.or()?;
+        parser.skip_whitespace();
+        if parser.pos < source.len() {
+            return Err(parser.error("unexpected input after selector"));
+        }
+        Ok(selector)
+    }
+
+    /// Matches rules with a tag matching `pattern`
+    pub fn tag(pattern: impl Into<String>) -> Self {
+        Selector::Tag(pattern.into())
+    }
+
+    /// Matches rules whose attribute or metadata `key` compares to `value`
+    pub fn compare(key: impl Into<String>, op: CompareOp, value: impl Into<String>) -> Self {
+        Selector::Compare {
+            key: key.into(),
+            op,
+            value: value.into(),
+        }
+    }
+
+    /// Matches rules both `self` and `other` match
+    pub fn and(self, other: Selector) -> Self {
+        Selector::And(Box::new(self), Box::new(other))
+    }
+
+    /// Matches rules either `self` or `other` matches
+    pub fn or(self, other: Selector) -> Self {
+        Selector::Or(Box::new(self), Box::new(other))
+    }
+
+    /// Returns true if `rule` is selected
+    pub fn matches<R: Selectable + ?Sized>(&self, rule: &R) -> bool {
+        match self {
+            Selector::All => true,
+            Selector::Tag(pattern) => rule.tags().iter().any(|tag| glob(pattern, tag)),
+            Selector::Compare { key, op, value } => {
+                let Some(actual) = rule.attribute(key) else {
+                    return *op == CompareOp::Ne;
+                };
+                match op {
+                    CompareOp::Eq => glob(value, &actual),
+                    CompareOp::Ne => !glob(value, &actual),
+                    CompareOp::Lt => compare(&actual, value) == Ordering::Less,
+                    CompareOp::Le => compare(&actual, value) != Ordering::Greater,
+                    CompareOp::Gt => compare(&actual, value) == Ordering::Greater,
+                    CompareOp::Ge => compare(&actual, value) != Ordering::Less,
+                }
+            }
+            Selector::Not(inner) => !inner.matches(rule),
+            Selector::And(lhs, rhs) => lhs.matches(rule) && rhs.matches(rule),
+            Selector::Or(lhs, rhs) => lhs.matches(rule) || rhs.matches(rule),
+        }
+    }
+}
+
+impl FromStr for Selector {
+    type Err = Error;
+
+    fn from_str(source: &str) -> Result<Self> {
+        Selector::parse(source)
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let grouped = |f: &mut fmt::Formatter<'_>, selector: &Selector, group: bool| {
+            if group {
+                write!(f, "({})", selector)
+            } else {
+                write!(f, "{}", selector)
+            }
+        };
+        match self {
+            Selector::All => f.write_str("*"),
+            Selector::Tag(pattern) => write!(f, "tag:{}", Quoted(pattern)),
+            Selector::Compare { key, op, value } => {
+                write!(f, "{}{}{}", Quoted(key), op.as_str(), Quoted(value))
+            }
+            Selector::Not(inner) => {
+                f.write_str("!")?;
+                grouped(
+                    f,
+                    inner,
+                    matches!(**inner, Selector::And(..) | Selector::Or(..)),
+                )
+            }
+            Selector::And(lhs, rhs) => {
+                grouped(f, lhs, matches!(**lhs, Selector::Or(..)))?;
+                f.write_str(" && ")?;
+                grouped(f, rhs, matches!(**rhs, Selector::Or(..)))
+            }
+            Selector::Or(lhs, rhs) => write!(f, "{} || {}", lhs, rhs),
+        }
+    }
+}
+
+/// Writes a key or value, quoting it unless it is a plain word
+struct Quoted<'a>(&'a str);
+
+impl fmt::Display for Quoted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.0.is_empty() && self.0.chars().all(is_word_char) {
+            return f.write_str(self.0);
+        }
+        f.write_char('"')?;
+        for c in self.0.chars() {
+            if matches!(c, '"' | '\\') {
+                f.write_char('\\')?;
+            }
+            f.write_char(c)?;
+        }
+        f.write_char('"')
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '*' | '/')
+}
+
+/// What a [`Selector`] reads from a rule; implemented for every [`Rule`] and
+/// for uncompiled [`RuleDefinition`]s
+pub trait Selectable {
+    /// Labels matched by `tag:` selectors
+    fn tags(&self) -> &[String];
+
+    /// The value of a built-in attribute or metadata entry
+    fn attribute(&self, key: &str) -> Option<Cow<'_, str>>;
+}
+
+impl<R: Rule + ?Sized> Selectable for R {
+    fn tags(&self) -> &[String] {
+        Rule::tags(self)
+    }
+
+    fn attribute(&self, key: &str) -> Option<Cow<'_, str>> {
+        match key {
+            "id" => Some(self.id().into()),
+            "name" => Some(self.name().into()),
+            "priority" => Some(self.priority().to_string().into()),
+            "active" => Some(self.is_active().to_string().into()),
+            "agenda_group" => self.agenda_group().map(Into::into),
+            "activation_group" => self.activation_group().map(Into::into),
+            "revision" => self.revision().map(|revision| revision.to_string().into()),
+            other => self.metadata(other).map(Into::into),
+        }
+    }
+}
+
+impl Selectable for RuleDefinition {
+    fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    fn attribute(&self, key: &str) -> Option<Cow<'_, str>> {
+        match key {
+            "id" => Some(self.id.as_str().into()),
+            "name" => Some(self.name.as_str().into()),
+            "priority" => Some(self.priority.to_string().into()),
+            "active" => Some(self.is_active.to_string().into()),
+            "agenda_group" => self.agenda_group.as_deref().map(Into::into),
+            "activation_group" => self.activation_group.as_deref().map(Into::into),
+            "revision" => self
+                .revision
+                .as_ref()
+                .map(|revision| revision.number.to_string().into()),
+            other => self.metadata.get(other).map(|value| value.as_str().into()),
+        }
+    }
+}
+
+fn compare(actual: &str, expected: &str) -> Ordering {
+    match (actual.parse::<f64>(), expected.parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.total_cmp(&b),
+        _ => actual.cmp(expected),
+    }
+}
+
+/// Matches `text` against `pattern`, where `*` matches any run of characters
+fn glob(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn or(&mut self) -> Result<Selector> {
+        let mut selector = self.and()?;
+        while self.eat("||") {
+            selector = selector.or(self.and()?);
+        }
+        Ok(selector)
+    }
+
+    fn and(&mut self) -> Result<Selector> {
+        let mut selector = self.unary()?;
+        while self.eat("&&") {
+            selector = selector.and(self.unary()?);
+        }
+        Ok(selector)
+    }
+
+    fn unary(&mut self) -> Result<Selector> {
+        if self.eat("!") {
+            return Ok(Selector::Not(Box::new(self.unary()?)));
+        }
+        if self.eat("(") {
+            let selector = self.or()?;
+            if !self.eat(")") {
+                return Err(self.error("expected `)`"));
+            }
+            return Ok(selector);
+        }
+        if self.eat("*") {
+            return Ok(Selector::All);
+        }
+
+        let key = self.word("a tag or attribute")?;
+        if self.source[self.pos..].starts_with(':') {
+            self.pos += 1;
+            return match key.as_str() {
+                "tag" => Ok(Selector::Tag(self.word("a tag")?)),
+                _ => Err(self.error(format!("unknown selector prefix `{}:`", key))),
+            };
+        }
+        let op = [
+            ("!=", CompareOp::Ne),
+            ("<=", CompareOp::Le),
+            (">=", CompareOp::Ge),
+            ("==", CompareOp::Eq),
+            ("=", CompareOp::Eq),
+            ("<", CompareOp::Lt),
+            (">", CompareOp::Gt),
+        ]
+        .into_iter()
+        .find(|(token, _)| self.eat(token))
+        .map(|(_, op)| op)
+        .ok_or_else(|| self.error(format!("expected a comparison after `{}`", key)))?;
+        let value = self.word("a value")?;
+        Ok(Selector::Compare { key, op, value })
+    }
+
+    /// A plain word or a quoted string, in which `\\`, `\"` and `\'` escape
+    /// the backslash and the quotes
+    fn word(&mut self, expected: &str) -> Result<String> {
+        self.skip_whitespace();
+        let rest = &self.source[self.pos..];
+        match rest.chars().next() {
+            Some(quote @ ('\'' | '"')) => {
+                let mut value = String::new();
+                let mut chars = rest.char_indices().skip(1);
+                while let Some((index, c)) = chars.next() {
+                    match c {
+                        '\\' => match chars.next() {
+                            Some((_, escaped @ ('\\' | '"' | '\''))) => value.push(escaped),
+                            _ => {
+                                self.pos += index;
+                                return Err(self.error("expected `\\`, `\"` or `'` after `\\`"));
+                            }
+                        },
+                        c if c == quote => {
+                            self.pos += index + 1;
+                            return Ok(value);
+                        }
+                        c => value.push(c),
+                    }
+                }
+                Err(self.error("unterminated string"))
+            }
+            _ => {
+                let len = rest.find(|c: char| !is_word_char(c)).unwrap_or(rest.len());
+                if len == 0 {
+                    return Err(self.error(format!("expected {}", expected)));
+                }
+                self.pos += len;
+                Ok(rest[..len].to_string())
+            }
+        }
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        if self.source[self.pos..].starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.source[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn error(&self, message: impl Into<String>) -> Error {
+        let end = self.source[self.pos..]
+            .chars()
+            .next()
+            .map_or(self.pos, |c| self.pos + c.len_utf8());
+        syntax_error(self.source, Span::new(self.pos, end), message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::Rule as RuleDefinition;
+
+    fn rules() -> Vec<RuleDefinition> {
+        vec![
+            RuleDefinition::new("velocity")
+                .with_priority(10)
+                .with_tag("fraud")
+                .with_metadata("owner", "team-a"),
+            RuleDefinition::new("geo_mismatch")
+                .with_priority(3)
+                .with_tag("fraud")
+                .with_tag("geo")
+                .with_metadata("owner", "team-b"),
+            RuleDefinition::new("promo_spring")
+                .with_priority(7)
+                .with_tag("promo-2024")
+                .in_agenda_group("pricing"),
+        ]
+    }
+
+    fn select(selector: &str) -> Vec<String> {
+        let selector = Selector::parse(selector).unwrap();
+        rules()
+            .into_iter()
+            .filter(|rule| selector.matches(rule))
+            .map(|rule| rule.id)
+            .collect()
+    }
+
+    #[test]
+    fn test_selectors_match_rules() {
+        assert_eq!(
+            select("tag:fraud && owner=team-a && priority>5"),
+            ["velocity"]
+        );
+        assert_eq!(select("tag:fraud && !tag:geo"), ["velocity"]);
+        assert_eq!(
+            select("tag:promo* || priority <= 3"),
+            ["geo_mismatch", "promo_spring"]
+        );
+        assert_eq!(select("owner != team-a"), ["geo_mismatch", "promo_spring"]);
+        assert_eq!(select("id=*_* && agenda_group='pricing'"), ["promo_spring"]);
+        assert_eq!(select("priority>=7"), ["velocity", "promo_spring"]);
+        assert_eq!(select("").len(), 3);
+        assert_eq!(select("*").len(), 3);
+    }
+
+    #[test]
+    fn test_display_roundtrips() {
+        for source in [
+            "tag:fraud && owner=team-a && priority>5",
+            "(tag:a || tag:b) && !(x=1 && y!=\"two words\")",
+            "!tag:geo || *",
+        ] {
+            let selector = Selector::parse(source).unwrap();
+            assert_eq!(selector.to_string(), source);
+            assert_eq!(Selector::parse(&selector.to_string()).unwrap(), selector);
+        }
+
+        let quoted = Selector::Compare {
+            key: "note".to_string(),
+            op: CompareOp::Eq,
+            value: "say \"hi\", it's C:\\temp\n".to_string(),
+        };
+        assert_eq!(
+            quoted.to_string(),
+            "note=\"say \\\"hi\\\", it's C:\\\\temp\n\""
+        );
+        assert_eq!(Selector::parse(&quoted.to_string()).unwrap(), quoted);
+        assert_eq!(
+            Selector::parse(r"note='it\'s'").unwrap(),
+            Selector::parse(r#"note="it's""#).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        for (source, column) in [
+            ("tag:", 5),
+            ("owner team-a", 7),
+            ("(tag:a", 7),
+            ("kind:x", 6),
+            ("a='b", 3),
+            (r#"a="x\q""#, 5),
+        ] {
+            match Selector::parse(source) {
+                Err(Error::Syntax { column: actual, .. }) => {
+                    assert_eq!(actual, column, "{}", source)
+                }
+                other => panic!("expected a syntax error for {:?}, got {:?}", source, other),
+            }
+        }
+    }
+}
@@ -0,0 +1,517 @@
+//! Versioned rule storage with revision history
+//!
+//! A [`RuleStore`] records every change to its rules. Each save or removal
+//! gets the next store-wide revision number, stamped on the saved rule as a
+//! [`Revision`] with its author, time and note. Any past revision number
+//! identifies the complete rule set as it was right after that change, so it
+//! can be loaded into an engine again or compared with another revision.
+//! Engines report the revision of each evaluated rule in
+//! [`RuleOutcome::revision`](crate::engine::RuleOutcome::revision).
+//!
+//! ```
+//! use windsurf_rules::prelude::*;
+//! use windsurf_rules::store::{DiffKind, RuleStore};
+//!
+//! let mut store = RuleStore::new();
+//! let v1 = store.save(
+//!     RuleDefinition::new("adult").with_expression("customer.age >= 18"),
+//!     "alice",
+//!     "initial version",
+//! )?;
+//! store.save(
+//!     RuleDefinition::new("adult").with_expression("customer.age >= 21"),
+//!     "bob",
+//!     "raise the age limit",
+//! )?;
+//!
+//! let mut engine = RulesEngine::new();
+//! store.load_as_of(v1, &mut engine)?;
+//! let context = RuleContext::new().with_fact("customer", [("age", 19)].into_iter().collect::<Value>());
+//! let result = engine.execute(&context)?;
+//! assert_eq!(result.matched_rules(), ["adult"]);
+//! assert_eq!(result.outcomes[0].revision, Some(v1));
+//!
+//! let diff = store.diff(v1, store.revision());
+//! assert_eq!(diff[0].kind, DiffKind::Modified);
+//! assert_eq!(diff[0].fields[0].field, "expression");
+//! # Ok::<(), windsurf_rules::Error>(())
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::clock::{Clock, SystemClock};
+use crate::engine::RulesEngine;
+use crate::error::Result;
+use crate::rule::{Action, Revision, Rule as RuleDefinition};
+use crate::selector::Selector;
+
+/// One recorded change to a rule
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Change {
+    /// The revision that made the change
+    pub revision: Revision,
+    /// The rule as saved, or `None` if the change removed it
+    pub rule: Option<RuleDefinition>,
+    /// Revision that added the rule, kept while later saves replace it;
+    /// rule sets list and load rules in this order
+    #[serde(default)]
+    pub sequence: u64,
+}
+
+/// How a rule differs between two revisions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffKind {
+    /// The rule exists only in the later revision
+    Added,
+    /// The rule exists only in the earlier revision
+    Removed,
+    /// The rule exists in both, with different fields
+    Modified,
+}
+
+/// A field whose value differs between two versions of a rule
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldChange {
+    /// Field name as serialized, e.g. `expression` or `then`
+    pub field: String,
+    /// Value in the earlier version, rendered as YAML; `None` if unset
+    pub before: Option<String>,
+    /// Value in the later version, rendered as YAML; `None` if unset
+    pub after: Option<String>,
+}
+
+/// The difference in one rule between two revisions
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleDiff {
+    /// Id of the rule
+    pub rule_id: String,
+    /// Whether the rule was added, removed or modified
+    pub kind: DiffKind,
+    /// Revision of the rule's earlier version, if it existed
+    pub before: Option<u64>,
+    /// Revision of the rule's later version, if it exists
+    pub after: Option<u64>,
+    /// Changed fields of a modified rule, in field-name order
+    pub fields: Vec<FieldChange>,
+}
+
+/// Rule definitions with their full revision history
+pub struct RuleStore {
+    revision: u64,
+    history: BTreeMap<String, Vec<Change>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for RuleStore {
+    fn default() -> Self {
+        Self {
+            revision: 0,
+            history: BTreeMap::new(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+}
+
+impl RuleStore {
+    /// Creates an empty store at revision 0
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Timestamps revisions with `clock`; defaults to [`SystemClock`]
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// The latest revision number; 0 before the first change
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Saves a new version of a rule, replacing any current version with the
+    /// same id, and returns its revision number. Fails without recording
+    /// anything if the rule does not compile.
+    pub fn save(
+        &mut self,
+        mut rule: RuleDefinition,
+        author: impl Into<String>,
+        note: impl Into<String>,
+    ) -> Result<u64> {
+        rule.clone().compile()?;
+        let current = self.change_as_of(&rule.id, self.revision);
+        let added = current.filter(|change| change.rule.is_some());
+        let sequence = added.map_or(self.revision + 1, |change| change.sequence);
+        let revision = self.next_revision(author.into(), note.into());
+        rule.revision = Some(revision.clone());
+        self.history
+            .entry(rule.id.clone())
+            .or_default()
+            .push(Change {
+                revision,
+                rule: Some(rule),
+                sequence,
+            });
+        Ok(self.revision)
+    }
+
+    /// Removes the current version of a rule and returns the revision number
+    /// of the removal, or `None` if there is no such rule
+    pub fn remove(
+        &mut self,
+        rule_id: &str,
+        author: impl Into<String>,
+        note: impl Into<String>,
+    ) -> Option<u64> {
+        self.get(rule_id)?;
+        let revision = self.next_revision(author.into(), note.into());
+        self.history.get_mut(rule_id)?.push(Change {
+            sequence: revision.number,
+            revision,
+            rule: None,
+        });
+        Some(self.revision)
+    }
+
+    /// The current version of a rule
+    pub fn get(&self, rule_id: &str) -> Option<&RuleDefinition> {
+        self.get_as_of(rule_id, self.revision)
+    }
+
+    /// The version of a rule that was current at `revision`
+    pub fn get_as_of(&self, rule_id: &str, revision: u64) -> Option<&RuleDefinition> {
+        self.change_as_of(rule_id, revision)?.rule.as_ref()
+    }
+
+    /// Every change to a rule, oldest first
+    pub fn history(&self, rule_id: &str) -> &[Change] {
+        self.history.get(rule_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// The current rules, in the order they were added
+    pub fn rules(&self) -> Vec<&RuleDefinition> {
+        self.as_of(self.revision)
+    }
+
+    /// The rules that were current at `revision`, in the order they were
+    /// added
+    pub fn as_of(&self, revision: u64) -> Vec<&RuleDefinition> {
+        let mut changes: Vec<&Change> = self
+            .history
+            .keys()
+            .filter_map(|rule_id| self.change_as_of(rule_id, revision))
+            .filter(|change| change.rule.is_some())
+            .collect();
+        changes.sort_by_key(|change| change.sequence);
+        changes
+            .into_iter()
+            .filter_map(|change| change.rule.as_ref())
+            .collect()
+    }
+
+    /// The current rules matching `selector`, in the order they were added
+    pub fn select(&self, selector: &Selector) -> Vec<&RuleDefinition> {
+        self.rules()
+            .into_iter()
+            .filter(|rule| selector.matches(*rule))
+            .collect()
+    }
+
+    /// Adds the rules that were current at `revision` to `engine`, in the
+    /// order they were added; if any of them fails, none is added
+    pub fn load_as_of(&self, revision: u64, engine: &mut RulesEngine) -> Result<()> {
+        engine.add_definitions(self.as_of(revision).into_iter().cloned())
+    }
+
+    /// Rules that differ between revisions `from` and `to`, by id. Rules that
+    /// were saved again without changes and Rust closure actions are not
+    /// reported.
+    pub fn diff(&self, from: u64, to: u64) -> Vec<RuleDiff> {
+        self.history
+            .keys()
+            .filter_map(|rule_id| {
+                let before = self.get_as_of(rule_id, from);
+                let after = self.get_as_of(rule_id, to);
+                let number = |rule: Option<&RuleDefinition>| {
+                    rule.and_then(|rule| rule.revision.as_ref())
+                        .map(|revision| revision.number)
+                };
+                let (kind, fields) = match (before, after) {
+                    (None, None) => return None,
+                    (None, Some(_)) => (DiffKind::Added, Vec::new()),
+                    (Some(_), None) => (DiffKind::Removed, Vec::new()),
+                    (Some(before), Some(after)) => {
+                        let fields = field_changes(before, after);
+                        if fields.is_empty() {
+                            return None;
+                        }
+                        (DiffKind::Modified, fields)
+                    }
+                };
+                Some(RuleDiff {
+                    rule_id: rule_id.clone(),
+                    kind,
+                    before: number(before),
+                    after: number(after),
+                    fields,
+                })
+            })
+            .collect()
+    }
+
+    fn change_as_of(&self, rule_id: &str, revision: u64) -> Option<&Change> {
+        self.history
+            .get(rule_id)?
+            .iter()
+            .rev()
+            .find(|change| change.revision.number <= revision)
+    }
+
+    fn next_revision(&mut self, author: String, note: String) -> Revision {
+        self.revision += 1;
+        Revision::new(self.revision, author, self.clock.now()).with_note(note)
+    }
+}
+
+fn field_changes(before: &RuleDefinition, after: &RuleDefinition) -> Vec<FieldChange> {
+    let mut before = fields(before);
+    let after = fields(after);
+    let mut changes: Vec<FieldChange> = after
+        .into_iter()
+        .filter_map(|(field, value)| {
+            let previous = before.remove(&field);
+            (previous.as_ref() != Some(&value)).then_some(FieldChange {
+                field,
+                before: previous,
+                after: Some(value),
+            })
+        })
+        .collect();
+    changes.extend(before.into_iter().map(|(field, value)| FieldChange {
+        field,
+        before: Some(value),
+        after: None,
+    }));
+    changes.sort_by(|a, b| a.field.cmp(&b.field));
+    changes
+}
+
+/// A rule's serialized fields, other than its revision
+fn fields(rule: &RuleDefinition) -> BTreeMap<String, String> {
+    let mut rule = rule.clone();
+    rule.revision = None;
+    for actions in [&mut rule.then_actions, &mut rule.else_actions] {
+        actions.retain(|action| !matches!(action, Action::Native(_)));
+    }
+    let Ok(serde_yaml::Value::Mapping(mapping)) = serde_yaml::to_value(&rule) else {
+        return BTreeMap::new();
+    };
+    mapping
+        .into_iter()
+        .filter_map(|(field, value)| {
+            let rendered = match value {
+                serde_yaml::Value::String(s) => s,
+                other => serde_yaml::to_string(&other).ok()?.trim_end().to_string(),
+            };
+            Some((field.as_str()?.to_string(), rendered))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::rule::RuleContext;
+    use chrono::{TimeDelta, TimeZone, Utc};
+
+    #[test]
+    fn test_revisions_are_recorded() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 9, 0, 0).unwrap();
+        let clock = Arc::new(ManualClock::new(start));
+        let mut store = RuleStore::new().with_clock(clock.clone());
+
+        assert_eq!(
+            store
+                .save(
+                    RuleDefinition::new("a").with_expression("true"),
+                    "alice",
+                    "add a"
+                )
+                .unwrap(),
+            1
+        );
+        clock.advance(TimeDelta::hours(1));
+        store
+            .save(
+                RuleDefinition::new("b").with_expression("true"),
+                "bob",
+                "add b",
+            )
+            .unwrap();
+        assert!(store
+            .save(
+                RuleDefinition::new("c").with_expression("true &&"),
+                "bob",
+                "broken"
+            )
+            .is_err());
+        assert_eq!(store.remove("a", "carol", "retire a"), Some(3));
+        assert_eq!(store.remove("a", "carol", "again"), None);
+
+        assert_eq!(store.revision(), 3);
+        assert!(store.get("a").is_none());
+        assert_eq!(
+            store
+                .get_as_of("a", 2)
+                .unwrap()
+                .revision
+                .as_ref()
+                .unwrap()
+                .author,
+            "alice"
+        );
+        let history = store.history("a");
+        assert_eq!(history.len(), 2);
+        assert!(history[1].rule.is_none());
+        assert_eq!(history[1].revision.note, "retire a");
+        assert_eq!(
+            store.get("b").unwrap().revision.as_ref().unwrap().timestamp,
+            start + TimeDelta::hours(1)
+        );
+
+        let ids = |rules: Vec<&RuleDefinition>| {
+            rules
+                .into_iter()
+                .map(|rule| rule.id.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(store.as_of(0)), Vec::<String>::new());
+        assert_eq!(ids(store.as_of(2)), ["a", "b"]);
+        assert_eq!(ids(store.rules()), ["b"]);
+
+        let mut engine = RulesEngine::new();
+        store.load_as_of(2, &mut engine).unwrap();
+        let result = engine.execute(&RuleContext::new()).unwrap();
+        assert_eq!(result.get("a").unwrap().revision, Some(1));
+        assert_eq!(result.get("b").unwrap().revision, Some(2));
+    }
+
+    #[test]
+    fn test_rules_load_in_the_order_they_were_added() {
+        let mut store = RuleStore::new();
+        for id in ["zeta", "alpha", "mid"] {
+            store
+                .save(RuleDefinition::new(id).with_expression("true"), "a", "")
+                .unwrap();
+        }
+        store
+            .save(RuleDefinition::new("zeta").with_expression("false"), "a", "")
+            .unwrap();
+        store.remove("alpha", "a", "");
+        store
+            .save(RuleDefinition::new("alpha").with_expression("true"), "a", "")
+            .unwrap();
+        let ids = |revision| {
+            store
+                .as_of(revision)
+                .into_iter()
+                .map(|rule| rule.id.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(3), ["zeta", "alpha", "mid"]);
+        assert_eq!(ids(store.revision()), ["zeta", "mid", "alpha"]);
+
+        let mut engine = RulesEngine::new();
+        engine
+            .add_definition(RuleDefinition::new("mid").with_expression("true"))
+            .unwrap();
+        assert!(store.load_as_of(3, &mut engine).is_err());
+        assert_eq!(engine.len(), 1);
+
+        let mut engine = RulesEngine::new();
+        store.load_as_of(3, &mut engine).unwrap();
+        let result = engine.execute(&RuleContext::new()).unwrap();
+        let evaluated: Vec<&str> = result
+            .outcomes
+            .iter()
+            .map(|outcome| outcome.rule_id.as_str())
+            .collect();
+        assert_eq!(evaluated, ["zeta", "alpha", "mid"]);
+    }
+
+    #[test]
+    fn test_diff_between_revisions() {
+        let mut store = RuleStore::new();
+        store
+            .save(
+                RuleDefinition::new("limit")
+                    .with_expression("total > 100")
+                    .with_priority(1),
+                "a",
+                "",
+            )
+            .unwrap();
+        store
+            .save(RuleDefinition::new("old").with_expression("true"), "a", "")
+            .unwrap();
+        store
+            .save(
+                RuleDefinition::new("limit")
+                    .with_expression("total > 200")
+                    .in_agenda_group("checks"),
+                "b",
+                "",
+            )
+            .unwrap();
+        store
+            .save(RuleDefinition::new("new").with_expression("true"), "b", "")
+            .unwrap();
+        store.remove("old", "b", "");
+        store
+            .save(
+                RuleDefinition::new("new").with_expression("true"),
+                "c",
+                "no-op",
+            )
+            .unwrap();
+
+        let diff = store.diff(2, store.revision());
+        let kinds: Vec<(&str, DiffKind)> =
+            diff.iter().map(|d| (d.rule_id.as_str(), d.kind)).collect();
+        assert_eq!(
+            kinds,
+            [
+                ("limit", DiffKind::Modified),
+                ("new", DiffKind::Added),
+                ("old", DiffKind::Removed)
+            ]
+        );
+        assert_eq!((diff[0].before, diff[0].after), (Some(1), Some(3)));
+        assert_eq!(
+            diff[0].fields,
+            [
+                FieldChange {
+                    field: "agenda_group".to_string(),
+                    before: None,
+                    after: Some("checks".to_string()),
+                },
+                FieldChange {
+                    field: "expression".to_string(),
+                    before: Some("total > 100".to_string()),
+                    after: Some("total > 200".to_string()),
+                },
+                FieldChange {
+                    field: "priority".to_string(),
+                    before: Some("1".to_string()),
+                    after: Some("0".to_string()),
+                },
+            ]
+        );
+        assert!(store.diff(4, 6).iter().all(|d| d.rule_id != "new"));
+        assert!(store.diff(3, 3).is_empty());
+    }
+}
@@ -0,0 +1,1154 @@
+//! Decision tables compiled into rule sets
+//!
+//! A [`DecisionTable`] has condition columns, each headed by an expression
+//! such as `customer.age`, and action columns, each headed by the fact path
+//! it sets. Every row compiles into one [`RuleDefinition`] whose id is the
+//! table id followed by the row number. Condition cells are written relative
+//! to their column:
+//!
+//! | Cell | Meaning |
+//! |------|---------|
+//! | empty or `-` | any value |
+//! | `>= 18`, `!= 'gold'` | comparison with the column |
+//! | `in ['gold', 'silver']`, `not in [...]` | membership, only with a `[` list |
+//! | `[18..65)` | numeric range, `[`/`]` inclusive and `(`/`)` exclusive |
+//! | `gold`, `in stock` | a bare word or phrase is a string |
+//! | anything else, e.g. `42` | equality with the column |
+//!
+//! Action cells are expressions for the column's fact; bare words are strings
+//! and empty cells leave the fact alone. The [`HitPolicy`] decides how many
+//! matching rows fire. [`DecisionTable::check`] reports rows that overlap
+//! where the policy forbids it and inputs that no row matches.
+//!
+//! ```
+//! use windsurf_rules::prelude::*;
+//! use windsurf_rules::table::DecisionTable;
+//!
+//! let table = DecisionTable::from_yaml(r#"
+//! id: discount
+//! hit_policy: first
+//! conditions: [customer.tier, order.total]
+//! actions: [order.discount]
+//! rows:
+//!   - when: [gold, ">= 100"]
+//!     then: [0.15]
+//!   - when: [gold, "-"]
+//!     then: [0.1]
+//!   - when: ["-", "-"]
+//!     then: [0]
+//! "#)?;
+//!
+//! let mut engine = RulesEngine::new();
+//! engine.add_table(&table)?;
+//! let context = RuleContext::new()
+//!     .with_fact("customer", [("tier", "gold")].into_iter().collect::<Value>())
+//!     .with_fact("order", [("total", 250)].into_iter().collect::<Value>());
+//! assert_eq!(engine.execute(&context)?.fired_rules(), ["discount_row1"]);
+//! # Ok::<(), windsurf_rules::Error>(())
+//! ```
+
+use serde::{Deserialize, Deserializer, Serialize};
+use std::io;
+
+use crate::diagnostic::Diagnostic;
+use crate::error::{Error, Result};
+use crate::expr::{self, BinaryOp, Evaluator, Expr, ExprKind, Literal, Span};
+use crate::rule::{Action, Rule as RuleDefinition, RuleContext, Value};
+
+/// Most input combinations [`DecisionTable::check`] enumerates looking for gaps
+pub const MAX_GAP_COMBINATIONS: usize = 10_000;
+
+/// How many of the matching rows of a table fire
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HitPolicy {
+    /// Rows must not overlap; the single matching row fires
+    #[default]
+    Unique,
+    /// The first matching row, in table order, fires
+    First,
+    /// The matching row with the highest priority fires; ties go to table order
+    Priority,
+    /// Every matching row fires, in table order
+    Collect,
+}
+
+/// One row of a [`DecisionTable`]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct TableRow {
+    /// Condition cells, one per condition column
+    #[serde(deserialize_with = "cells")]
+    pub when: Vec<String>,
+    /// Action cells, one per action column
+    #[serde(default, deserialize_with = "cells")]
+    pub then: Vec<String>,
+    /// Priority under [`HitPolicy::Priority`]; defaults to 0
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
+    /// Used as the generated rule's name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+impl TableRow {
+    /// Creates a row from its condition and action cells
+    pub fn new<I, J, S, T>(when: I, then: J) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        J: IntoIterator<Item = T>,
+        S: Into<String>,
+        T: Into<String>,
+    {
+        Self {
+            when: when.into_iter().map(Into::into).collect(),
+            then: then.into_iter().map(Into::into).collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Sets the row's priority
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+}
+
+/// A table of rules sharing the same condition and action columns
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DecisionTable {
+    /// Table id, used as the prefix of the generated rule ids
+    pub id: String,
+    /// Human-readable name; defaults to the id
+    #[serde(default)]
+    pub name: String,
+    /// How many matching rows fire
+    #[serde(default)]
+    pub hit_policy: HitPolicy,
+    /// Expression heading each condition column
+    pub conditions: Vec<String>,
+    /// Fact path set by each action column
+    #[serde(default)]
+    pub actions: Vec<String>,
+    /// The rows, in table order
+    #[serde(default)]
+    pub rows: Vec<TableRow>,
+}
+
+impl DecisionTable {
+    /// Creates an empty table with the given columns
+    pub fn new<I, J, S, T>(
+        id: impl Into<String>,
+        hit_policy: HitPolicy,
+        conditions: I,
+        actions: J,
+    ) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        J: IntoIterator<Item = T>,
+        S: Into<String>,
+        T: Into<String>,
+    {
+        Self {
+            id: id.into(),
+            name: String::new(),
+            hit_policy,
+            conditions: conditions.into_iter().map(Into::into).collect(),
+            actions: actions.into_iter().map(Into::into).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Appends a row
+    pub fn with_row(mut self, row: TableRow) -> Self {
+        self.rows.push(row);
+        self
+    }
+
+    /// Parses a table from YAML (or JSON)
+    pub fn from_yaml(source: &str) -> Result<Self> {
+        serde_yaml::from_str(source).map_err(|err| Error::Serialization(Box::new(err)))
+    }
+
+    /// Reads a table from CSV. The header names every column: `when:<expr>`
+    /// for conditions, `then:<path>` for actions, and optionally `priority`
+    /// and `description`.
+    pub fn from_csv(
+        id: impl Into<String>,
+        hit_policy: HitPolicy,
+        reader: impl io::Read,
+    ) -> Result<Self> {
+        enum Column {
+            When,
+            Then,
+            Priority,
+            Description,
+        }
+
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+        let serialization = |err: csv::Error| Error::Serialization(Box::new(err));
+        let mut table =
+            DecisionTable::new(id, hit_policy, Vec::<String>::new(), Vec::<String>::new());
+
+        let mut columns = Vec::new();
+        for header in reader.headers().map_err(serialization)? {
+            let column = if let Some(condition) = header.strip_prefix("when:") {
+                table.conditions.push(condition.trim().to_string());
+                Column::When
+            } else if let Some(action) = header.strip_prefix("then:") {
+                table.actions.push(action.trim().to_string());
+                Column::Then
+            } else if header == "priority" {
+                Column::Priority
+            } else if header == "description" {
+                Column::Description
+            } else {
+                return Err(Error::Config(format!(
+                    "decision table `{}`: unknown column `{}`; expected `when:<expr>`, `then:<path>`, `priority` or `description`",
+                    table.id, header
+                )));
+            };
+            columns.push(column);
+        }
+
+        for (index, record) in reader.records().enumerate() {
+            let record = record.map_err(serialization)?;
+            let mut row = TableRow::default();
+            for (column, cell) in columns.iter().zip(record.iter()) {
+                match column {
+                    Column::When => row.when.push(cell.to_string()),
+                    Column::Then => row.then.push(cell.to_string()),
+                    Column::Priority if cell.is_empty() => {}
+                    Column::Priority => {
+                        row.priority = Some(cell.parse().map_err(|_| {
+                            Error::Config(format!(
+                                "decision table `{}` row {}: invalid priority `{}`",
+                                table.id,
+                                index + 1,
+                                cell
+                            ))
+                        })?)
+                    }
+                    Column::Description if cell.is_empty() => {}
+                    Column::Description => row.description = Some(cell.to_string()),
+                }
+            }
+            table.rows.push(row);
+        }
+        Ok(table)
+    }
+
+    /// Id of the rule generated for the row at `index` (zero-based)
+    pub fn rule_id(&self, index: usize) -> String {
+        format!("{}_row{}", self.id, index + 1)
+    }
+
+    /// Compiles every row into a rule definition. Under every policy but
+    /// [`HitPolicy::Collect`] a row's condition also requires that no row
+    /// winning over it matches, so the policy holds whatever conflict
+    /// strategy the engine uses; the rows still share an activation group
+    /// named after the table. Fails if rows of a [`HitPolicy::Unique`] table
+    /// overlap.
+    pub fn compile(&self) -> Result<Vec<RuleDefinition>> {
+        let rows = self.parse()?;
+        if let Some(overlap) = self.check_overlaps(&rows).iter().find(|d| d.is_error()) {
+            return Err(self.error(None, &overlap.message));
+        }
+        let name = if self.name.is_empty() {
+            &self.id
+        } else {
+            &self.name
+        };
+        Ok(rows
+            .iter()
+            .enumerate()
+            .map(|(index, parsed)| {
+                let row = &self.rows[index];
+                let priority = match self.hit_policy {
+                    HitPolicy::Priority => row.priority.unwrap_or(0),
+                    _ => (self.rows.len() - index) as i32,
+                };
+                let mut conditions = parsed.conditions.clone();
+                for (other, winner) in rows.iter().enumerate() {
+                    if self.outranks(other, index) && !winner.disjoint(parsed) {
+                        conditions.push(not_true(conjunction(winner.conditions.clone())));
+                    }
+                }
+                let mut rule = RuleDefinition::new(self.rule_id(index))
+                    .with_name(
+                        row.description
+                            .clone()
+                            .unwrap_or_else(|| format!("{} row {}", name, index + 1)),
+                    )
+                    .with_expression(conjunction(conditions).to_string())
+                    .with_priority(priority);
+                for (path, value) in &parsed.actions {
+                    rule = rule.then(Action::set(path.clone(), value.clone()));
+                }
+                if self.hit_policy != HitPolicy::Collect {
+                    rule = rule.in_activation_group(self.id.clone());
+                }
+                rule
+            })
+            .collect())
+    }
+
+    /// Whether the row at `winner` fires instead of the row at `loser` when
+    /// both match
+    fn outranks(&self, winner: usize, loser: usize) -> bool {
+        let priority = |index: usize| self.rows[index].priority.unwrap_or(0);
+        match self.hit_policy {
+            HitPolicy::Collect => false,
+            HitPolicy::Priority => (priority(winner), loser) > (priority(loser), winner),
+            HitPolicy::Unique | HitPolicy::First => winner < loser,
+        }
+    }
+
+    /// Reports overlapping rows and unmatched inputs. Overlaps are errors
+    /// under [`HitPolicy::Unique`] and warnings between equal priorities under
+    /// [`HitPolicy::Priority`]; gaps are warnings. Cells that are not simple
+    /// comparisons with literals are only compared textually, and their
+    /// columns are left out of the gap check.
+    pub fn check(&self) -> Result<Vec<Diagnostic>> {
+        let rows = self.parse()?;
+        let mut diagnostics = self.check_overlaps(&rows);
+        diagnostics.extend(self.check_gaps(&rows));
+        Ok(diagnostics)
+    }
+
+    fn check_overlaps(&self, rows: &[ParsedRow]) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        for (second, later) in rows.iter().enumerate() {
+            for (first, earlier) in rows[..second].iter().enumerate() {
+                let overlaps = earlier
+                    .constraints
+                    .iter()
+                    .zip(&later.constraints)
+                    .all(|(a, b)| a.intersects(b) == Some(true));
+                if !overlaps {
+                    continue;
+                }
+                let diagnostic = match self.hit_policy {
+                    HitPolicy::Unique => Diagnostic::error(
+                        "table-overlap",
+                        format!("rows {} and {} can match the same input", first + 1, second + 1),
+                    ),
+                    HitPolicy::Priority
+                        if self.rows[first].priority.unwrap_or(0) == self.rows[second].priority.unwrap_or(0) =>
+                    {
+                        Diagnostic::warning(
+                            "table-overlap",
+                            format!(
+                                "rows {} and {} can match the same input with equal priority; row {} wins by order",
+                                first + 1,
+                                second + 1,
+                                first + 1
+                            ),
+                        )
+                    }
+                    _ => continue,
+                };
+                diagnostics.push(diagnostic.with_rule(self.rule_id(second)));
+            }
+        }
+        diagnostics
+    }
+
+    fn check_gaps(&self, rows: &[ParsedRow]) -> Vec<Diagnostic> {
+        let skipped = |reason: String| {
+            vec![Diagnostic::info(
+                "table-gap-skipped",
+                format!("gap check skipped: {}", reason),
+            )
+            .with_rule(self.id.clone())]
+        };
+
+        let mut columns = Vec::with_capacity(self.conditions.len());
+        for (column, header) in self.conditions.iter().enumerate() {
+            let constraints: Vec<&Constraint> =
+                rows.iter().map(|row| &row.constraints[column]).collect();
+            match regions(header, &constraints) {
+                Some(regions) => columns.push(regions),
+                None => {
+                    return skipped(format!(
+                        "column `{}` has cells that cannot be analyzed",
+                        header
+                    ))
+                }
+            }
+        }
+        let combinations = columns
+            .iter()
+            .try_fold(1usize, |total, regions| total.checked_mul(regions.len()))
+            .filter(|&total| total <= MAX_GAP_COMBINATIONS);
+        if combinations.is_none() {
+            return skipped(format!(
+                "more than {} input combinations",
+                MAX_GAP_COMBINATIONS
+            ));
+        }
+
+        let mut diagnostics = Vec::new();
+        let mut choice = vec![0; columns.len()];
+        loop {
+            let covered = rows.iter().any(|row| {
+                row.constraints.iter().zip(&choice).zip(&columns).all(
+                    |((constraint, &region), regions)| constraint.covers(&regions[region].sample),
+                )
+            });
+            if !covered {
+                let described: Vec<&str> = choice
+                    .iter()
+                    .zip(&columns)
+                    .filter_map(|(&region, regions)| regions[region].description.as_deref())
+                    .collect();
+                let when = if described.is_empty() {
+                    "for any input".to_string()
+                } else {
+                    format!("when {}", described.join(" and "))
+                };
+                diagnostics.push(
+                    Diagnostic::warning("table-gap", format!("no row matches {}", when))
+                        .with_rule(self.id.clone()),
+                );
+            }
+
+            // Advance to the next combination, odometer style
+            let mut column = choice.len();
+            loop {
+                if column == 0 {
+                    return diagnostics;
+                }
+                column -= 1;
+                choice[column] += 1;
+                if choice[column] < columns[column].len() {
+                    break;
+                }
+                choice[column] = 0;
+            }
+        }
+    }
+
+    fn parse(&self) -> Result<Vec<ParsedRow>> {
+        let columns = self
+            .conditions
+            .iter()
+            .map(|header| Ok(expr::parse(header)?.to_string()))
+            .collect::<Result<Vec<_>>>()
+            .map_err(|err| self.error(None, &err.to_string()))?;
+
+        self.rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| {
+                if row.when.len() != columns.len() || row.then.len() > self.actions.len() {
+                    return Err(self.error(
+                        Some(index),
+                        &format!(
+                            "expected {} condition and {} action cells, found {} and {}",
+                            columns.len(),
+                            self.actions.len(),
+                            row.when.len(),
+                            row.then.len()
+                        ),
+                    ));
+                }
+                let mut parsed = ParsedRow::default();
+                for (column, cell) in columns.iter().zip(&row.when) {
+                    let condition = condition_cell(column, cell)
+                        .map_err(|err| self.error(Some(index), &format!("`{}`: {}", cell, err)))?;
+                    parsed.constraints.push(match &condition {
+                        Some(condition) => Constraint::of(condition, column),
+                        None => Constraint::Any,
+                    });
+                    parsed.conditions.extend(condition);
+                }
+                for (path, cell) in self.actions.iter().zip(&row.then) {
+                    let cell = cell.trim();
+                    if cell.is_empty() || cell == "-" {
+                        continue;
+                    }
+                    let value = value_cell(cell);
+                    expr::parse(&value)
+                        .map_err(|err| self.error(Some(index), &format!("`{}`: {}", cell, err)))?;
+                    parsed.actions.push((path.clone(), value));
+                }
+                Ok(parsed)
+            })
+            .collect()
+    }
+
+    fn error(&self, row: Option<usize>, message: &str) -> Error {
+        match row {
+            Some(row) => Error::Config(format!(
+                "decision table `{}` row {}: {}",
+                self.id,
+                row + 1,
+                message
+            )),
+            None => Error::Config(format!("decision table `{}`: {}", self.id, message)),
+        }
+    }
+}
+
+/// Accepts cells written as YAML or JSON scalars of any type
+fn cells<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Scalar {
+        Bool(bool),
+        Int(i64),
+        Float(f64),
+        String(String),
+        Null(()),
+    }
+
+    Ok(Vec::<Scalar>::deserialize(deserializer)?
+        .into_iter()
+        .map(|cell| match cell {
+            Scalar::Bool(b) => b.to_string(),
+            Scalar::Int(i) => i.to_string(),
+            Scalar::Float(x) => x.to_string(),
+            Scalar::String(s) => s,
+            Scalar::Null(()) => String::new(),
+        })
+        .collect())
+}
+
+#[derive(Debug, Default)]
+struct ParsedRow {
+    /// Conditions of the non-empty cells
+    conditions: Vec<Expr>,
+    /// One constraint per condition column
+    constraints: Vec<Constraint>,
+    /// Fact path and value expression of the non-empty action cells
+    actions: Vec<(String, String)>,
+}
+
+impl ParsedRow {
+    /// Whether no input can match both rows
+    fn disjoint(&self, other: &ParsedRow) -> bool {
+        self.constraints
+            .iter()
+            .zip(&other.constraints)
+            .any(|(a, b)| a.intersects(b) == Some(false))
+    }
+}
+
+/// Joins conditions with `&&`; no conditions at all is `true`
+fn conjunction(conditions: Vec<Expr>) -> Expr {
+    let mut conditions = conditions.into_iter();
+    match conditions.next() {
+        Some(first) => conditions.fold(first, |lhs, rhs| {
+            let span = lhs.span.merge(rhs.span);
+            Expr::new(
+                ExprKind::Binary {
+                    op: BinaryOp::And,
+                    lhs: Box::new(lhs),
+                    rhs: Box::new(rhs),
+                },
+                span,
+            )
+        }),
+        None => Expr::new(ExprKind::Literal(Literal::Bool(true)), Span::default()),
+    }
+}
+
+/// `(condition) != true`, which unlike `!condition` holds when the condition
+/// is unknown
+fn not_true(condition: Expr) -> Expr {
+    let span = condition.span;
+    Expr::new(
+        ExprKind::Binary {
+            op: BinaryOp::Ne,
+            lhs: Box::new(condition),
+            rhs: Box::new(Expr::new(ExprKind::Literal(Literal::Bool(true)), span)),
+        },
+        span,
+    )
+}
+
+const COMPARISONS: [&str; 6] = [">=", "<=", "!=", "==", ">", "<"];
+
+fn condition_cell(column: &str, cell: &str) -> Result<Option<Expr>> {
+    let cell = cell.trim();
+    if cell.is_empty() || cell == "-" {
+        return Ok(None);
+    }
+    let source = if let Some(range) = range_cell(column, cell) {
+        range
+    } else if is_membership(cell) || COMPARISONS.iter().any(|op| cell.starts_with(op)) {
+        format!("({}) {}", column, cell)
+    } else {
+        format!("({}) == {}", column, value_cell(cell))
+    };
+    expr::parse(&source).map(Some)
+}
+
+/// Whether a cell is `in [...]` or `not in [...]`; `in stock` is a phrase
+fn is_membership(cell: &str) -> bool {
+    cell.strip_prefix("not in ")
+        .or_else(|| cell.strip_prefix("in "))
+        .is_some_and(|list| list.trim_start().starts_with('['))
+}
+
+/// Translates `[low..high]`-style cells into a pair of comparisons
+fn range_cell(column: &str, cell: &str) -> Option<String> {
+    let low = match cell.chars().next()? {
+        '[' => ">=",
+        '(' => ">",
+        _ => return None,
+    };
+    let high = match cell.chars().last()? {
+        ']' => "<=",
+        ')' => "<",
+        _ => return None,
+    };
+    let (from, to) = cell[1..cell.len() - 1].split_once("..")?;
+    Some(format!(
+        "({column}) {low} {} && ({column}) {high} {}",
+        from.trim(),
+        to.trim()
+    ))
+}
+
+/// Quotes bare words and phrases; leaves other cells as expressions
+fn value_cell(cell: &str) -> String {
+    let bare = cell.starts_with(|c: char| c.is_alphabetic())
+        && cell
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | ' '))
+        && !matches!(cell, "true" | "false" | "null");
+    if bare {
+        format!("{:?}", cell)
+    } else {
+        cell.to_string()
+    }
+}
+
+/// What a condition cell allows, for overlap and gap analysis
+#[derive(Debug, Clone, PartialEq)]
+enum Constraint {
+    Any,
+    Interval(Interval),
+    /// Any of the values, or with `negated` anything but them
+    Values {
+        values: Vec<Value>,
+        negated: bool,
+    },
+    /// A cell that is not a simple comparison, by source text
+    Opaque(String),
+}
+
+impl Constraint {
+    fn of(condition: &Expr, column: &str) -> Self {
+        match &condition.kind {
+            ExprKind::Binary {
+                op: BinaryOp::And,
+                lhs,
+                rhs,
+            } => match (Self::of(lhs, column), Self::of(rhs, column)) {
+                (Constraint::Interval(a), Constraint::Interval(b)) => {
+                    Constraint::Interval(a.intersect(b))
+                }
+                _ => Constraint::Opaque(condition.to_string()),
+            },
+            ExprKind::Binary { op, lhs, rhs } if lhs.to_string() == column => {
+                match (op, constant(rhs)) {
+                    (BinaryOp::Eq, Some(value)) => match value.as_f64() {
+                        Some(x) if value.is_numeric() => Constraint::Interval(Interval::point(x)),
+                        _ => Constraint::Values {
+                            values: vec![value],
+                            negated: false,
+                        },
+                    },
+                    (BinaryOp::Ne, Some(value)) => Constraint::Values {
+                        values: vec![value],
+                        negated: true,
+                    },
+                    (BinaryOp::In | BinaryOp::NotIn, Some(Value::List(values))) => {
+                        Constraint::Values {
+                            values,
+                            negated: *op == BinaryOp::NotIn,
+                        }
+                    }
+                    (op, Some(value)) if value.is_numeric() => {
+                        let bound = value
+                            .as_f64()
+                            .map(|x| (x, matches!(op, BinaryOp::Le | BinaryOp::Ge)));
+                        match op {
+                            BinaryOp::Lt | BinaryOp::Le => Constraint::Interval(Interval {
+                                lo: None,
+                                hi: bound,
+                            }),
+                            BinaryOp::Gt | BinaryOp::Ge => Constraint::Interval(Interval {
+                                lo: bound,
+                                hi: None,
+                            }),
+                            _ => Constraint::Opaque(condition.to_string()),
+                        }
+                    }
+                    _ => Constraint::Opaque(condition.to_string()),
+                }
+            }
+            _ => Constraint::Opaque(condition.to_string()),
+        }
+    }
+
+    /// Whether some input satisfies both constraints; `None` if unknown
+    fn intersects(&self, other: &Constraint) -> Option<bool> {
+        match (self, other) {
+            (Constraint::Any, _) | (_, Constraint::Any) => Some(true),
+            (Constraint::Opaque(a), Constraint::Opaque(b)) if a == b => Some(true),
+            (Constraint::Opaque(_), _) | (_, Constraint::Opaque(_)) => None,
+            (Constraint::Interval(a), Constraint::Interval(b)) => Some(!a.intersect(*b).is_empty()),
+            (Constraint::Interval(interval), Constraint::Values { values, negated })
+            | (Constraint::Values { values, negated }, Constraint::Interval(interval)) => {
+                let inside = |value: &Value| {
+                    value
+                        .as_f64()
+                        .is_some_and(|x| value.is_numeric() && interval.contains(x))
+                };
+                Some(if *negated {
+                    interval
+                        .as_point()
+                        .is_none_or(|x| !values.iter().any(|v| v.equals(&Value::Float(x))))
+                } else {
+                    values.iter().any(inside)
+                })
+            }
+            (
+                Constraint::Values {
+                    values: a,
+                    negated: a_negated,
+                },
+                Constraint::Values {
+                    values: b,
+                    negated: b_negated,
+                },
+            ) => Some(match (a_negated, b_negated) {
+                (false, false) => a.iter().any(|x| b.iter().any(|y| x.equals(y))),
+                (false, true) => a.iter().any(|x| !b.iter().any(|y| x.equals(y))),
+                (true, false) => b.iter().any(|y| !a.iter().any(|x| x.equals(y))),
+                (true, true) => true,
+            }),
+        }
+    }
+
+    /// Whether the constraint allows `sample`
+    fn covers(&self, sample: &Sample) -> bool {
+        match (self, sample) {
+            (Constraint::Any, _) => true,
+            (Constraint::Interval(interval), Sample::Value(value)) => {
+                value.is_numeric() && value.as_f64().is_some_and(|x| interval.contains(x))
+            }
+            (Constraint::Values { values, negated }, Sample::Value(value)) => {
+                values.iter().any(|v| v.equals(value)) != *negated
+            }
+            (Constraint::Values { negated, .. }, Sample::Other) => *negated,
+            (Constraint::Interval(_) | Constraint::Opaque(_), _) => false,
+        }
+    }
+}
+
+/// A numeric interval; bounds are `(value, inclusive)`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+struct Interval {
+    lo: Option<(f64, bool)>,
+    hi: Option<(f64, bool)>,
+}
+
+impl Interval {
+    fn point(x: f64) -> Self {
+        Self {
+            lo: Some((x, true)),
+            hi: Some((x, true)),
+        }
+    }
+
+    fn as_point(&self) -> Option<f64> {
+        match (self.lo, self.hi) {
+            (Some((lo, true)), Some((hi, true))) if lo == hi => Some(lo),
+            _ => None,
+        }
+    }
+
+    fn contains(&self, x: f64) -> bool {
+        let above = self
+            .lo
+            .is_none_or(|(lo, inclusive)| x > lo || (inclusive && x == lo));
+        let below = self
+            .hi
+            .is_none_or(|(hi, inclusive)| x < hi || (inclusive && x == hi));
+        above && below
+    }
+
+    fn intersect(self, other: Interval) -> Interval {
+        let tighter = |a: Option<(f64, bool)>, b: Option<(f64, bool)>, low: bool| match (a, b) {
+            (Some(a), Some(b)) if a.0 == b.0 => Some((a.0, a.1 && b.1)),
+            (Some(a), Some(b)) => Some(if (a.0 > b.0) == low { a } else { b }),
+            (a, None) => a,
+            (None, b) => b,
+        };
+        Interval {
+            lo: tighter(self.lo, other.lo, true),
+            hi: tighter(self.hi, other.hi, false),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        match (self.lo, self.hi) {
+            (Some((lo, lo_inclusive)), Some((hi, hi_inclusive))) => {
+                lo > hi || (lo == hi && !(lo_inclusive && hi_inclusive))
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A representative input value for one region of a column
+#[derive(Debug, Clone)]
+enum Sample {
+    Value(Value),
+    /// A value none of the column's cells mention
+    Other,
+}
+
+#[derive(Debug)]
+struct Region {
+    sample: Sample,
+    /// `None` for columns whose cells are all empty
+    description: Option<String>,
+}
+
+/// Splits a column's input domain into regions that every cell either fully
+/// allows or fully rejects; `None` if a cell cannot be analyzed
+fn regions(column: &str, constraints: &[&Constraint]) -> Option<Vec<Region>> {
+    let mut points = Vec::new();
+    let mut values: Vec<Value> = Vec::new();
+    for constraint in constraints {
+        match constraint {
+            Constraint::Any => {}
+            Constraint::Opaque(_) => return None,
+            Constraint::Interval(interval) => {
+                points.extend(interval.lo.iter().chain(&interval.hi).map(|(x, _)| *x));
+            }
+            Constraint::Values { values: listed, .. } => {
+                for value in listed {
+                    if !values.iter().any(|v| v.equals(value)) {
+                        values.push(value.clone());
+                    }
+                }
+            }
+        }
+    }
+    if points.is_empty() && values.is_empty() {
+        return Some(vec![Region {
+            sample: Sample::Other,
+            description: None,
+        }]);
+    }
+
+    let numeric = values.iter().all(Value::is_numeric);
+    if numeric {
+        points.extend(values.iter().filter_map(Value::as_f64));
+        points.sort_by(f64::total_cmp);
+        points.dedup();
+        let region = |x: f64, description: String| Region {
+            sample: Sample::Value(Value::Float(x)),
+            description: Some(description),
+        };
+        let mut regions = vec![region(
+            points[0] - 1.0,
+            format!("{} < {}", column, points[0]),
+        )];
+        for (i, &x) in points.iter().enumerate() {
+            regions.push(region(x, format!("{} == {}", column, x)));
+            match points.get(i + 1) {
+                Some(&next) => regions.push(region(
+                    (x + next) / 2.0,
+                    format!("{} < {} < {}", x, column, next),
+                )),
+                None => regions.push(region(x + 1.0, format!("{} > {}", column, x))),
+            }
+        }
+        return Some(regions);
+    }
+
+    let mut regions: Vec<Region> = values
+        .iter()
+        .map(|value| Region {
+            sample: Sample::Value(value.clone()),
+            description: Some(format!("{} == {}", column, literal(value))),
+        })
+        .collect();
+    let booleans = values
+        .iter()
+        .filter(|value| matches!(value, Value::Bool(_)))
+        .count();
+    if booleans < 2 || booleans < values.len() {
+        let listed: Vec<String> = values.iter().map(literal).collect();
+        regions.push(Region {
+            sample: Sample::Other,
+            description: Some(format!("{} not in [{}]", column, listed.join(", "))),
+        });
+    }
+    Some(regions)
+}
+
+fn literal(value: &Value) -> String {
+    match value {
+        Value::String(s) => format!("{:?}", s),
+        other => other.to_string(),
+    }
+}
+
+/// The value of a fact-independent expression
+fn constant(expr: &Expr) -> Option<Value> {
+    if !expr.paths().is_empty() {
+        return None;
+    }
+    Evaluator::new(&RuleContext::new()).evaluate(expr).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{ConflictStrategy, RulesEngine};
+
+    fn shipping(hit_policy: HitPolicy) -> DecisionTable {
+        DecisionTable::new(
+            "shipping",
+            hit_policy,
+            ["customer.tier", "order.weight"],
+            ["order.shipping", "order.carrier"],
+        )
+        .with_row(TableRow::new(["gold", "< 10"], ["0", "express"]))
+        .with_row(TableRow::new(
+            ["in ['silver', 'bronze']", "[0..10)"],
+            ["5", "-"],
+        ))
+        .with_row(TableRow::new(
+            ["-", ">= 10"],
+            ["order.weight * 2", "freight"],
+        ))
+    }
+
+    fn order(tier: &str, weight: i64) -> RuleContext {
+        RuleContext::new()
+            .with_fact("customer", [("tier", tier)].into_iter().collect::<Value>())
+            .with_fact("order", [("weight", weight)].into_iter().collect::<Value>())
+    }
+
+    #[test]
+    fn test_rows_compile_to_rules() {
+        let rules = shipping(HitPolicy::First).compile().unwrap();
+        assert_eq!(rules[1].id, "shipping_row2");
+        assert_eq!(
+            rules[1].expression,
+            r#"customer.tier in ["silver", "bronze"] && (order.weight >= 0 && order.weight < 10)"#
+        );
+        assert_eq!(
+            rules[0].then_actions[1],
+            Action::set("order.carrier", r#""express""#)
+        );
+        assert_eq!(rules[1].then_actions.len(), 1);
+        assert_eq!(
+            rules.iter().map(|rule| rule.priority).collect::<Vec<_>>(),
+            [3, 2, 1]
+        );
+        assert!(rules
+            .iter()
+            .all(|rule| rule.activation_group.as_deref() == Some("shipping")));
+
+        let flags = DecisionTable::new(
+            "flags",
+            HitPolicy::Collect,
+            ["order.weight > 10", "customer.tier || order.tier"],
+            ["order.heavy"],
+        )
+        .with_row(TableRow::new(["true", "gold"], ["true"]))
+        .with_row(TableRow::new(["-", "[1..2]"], ["false"]));
+        let rules = flags.compile().unwrap();
+        assert_eq!(
+            rules[0].expression,
+            r#"(order.weight > 10) == true && (customer.tier || order.tier) == "gold""#
+        );
+        assert_eq!(
+            rules[1].expression,
+            "(customer.tier || order.tier) >= 1 && (customer.tier || order.tier) <= 2"
+        );
+
+        let mut engine = RulesEngine::new();
+        engine.add_table(&shipping(HitPolicy::First)).unwrap();
+        let mut context = order("gold", 12);
+        let result = engine.execute(&context).unwrap();
+        assert_eq!(result.fired_rules(), ["shipping_row3"]);
+        result.apply_to(&mut context).unwrap();
+        assert_eq!(
+            context.get_path("order.shipping").unwrap(),
+            Some(&Value::Int(24))
+        );
+    }
+
+    #[test]
+    fn test_phrases_starting_with_in_are_strings() {
+        let table = DecisionTable::new("stock", HitPolicy::Collect, ["item.status"], ["item.ok"])
+            .with_row(TableRow::new(["in stock"], ["true"]))
+            .with_row(TableRow::new(["not in stock"], ["false"]))
+            .with_row(TableRow::new(["not in ['lost']"], ["true"]));
+        let rules = table.compile().unwrap();
+        assert_eq!(rules[0].expression, r#"item.status == "in stock""#);
+        assert_eq!(rules[1].expression, r#"item.status == "not in stock""#);
+        assert_eq!(rules[2].expression, r#"item.status not in ["lost"]"#);
+    }
+
+    #[test]
+    fn test_hit_policy_holds_under_any_strategy() {
+        let discount = |hit_policy| {
+            DecisionTable::new(
+                "discount",
+                hit_policy,
+                ["customer.tier"],
+                ["order.discount"],
+            )
+            .with_row(TableRow::new(["-"], ["0"]).with_priority(1))
+            .with_row(TableRow::new(["gold"], ["0.1"]).with_priority(5))
+            .with_row(TableRow::new(["in ['gold', 'silver']"], ["0.05"]).with_priority(5))
+        };
+        // Fires the lowest priority first, the opposite of what the table asks
+        let reversed = ConflictStrategy::custom(|a, b| a.priority.cmp(&b.priority));
+        let fired = |hit_policy, tier: &str| {
+            let mut engine = RulesEngine::new().with_strategy(reversed.clone());
+            engine.add_table(&discount(hit_policy)).unwrap();
+            let result = engine.execute(&order(tier, 1)).unwrap();
+            result
+                .fired_rules()
+                .iter()
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(fired(HitPolicy::First, "gold"), ["discount_row1"]);
+        assert_eq!(fired(HitPolicy::Priority, "gold"), ["discount_row2"]);
+        assert_eq!(fired(HitPolicy::Priority, "silver"), ["discount_row3"]);
+        assert_eq!(fired(HitPolicy::Priority, "bronze"), ["discount_row1"]);
+        assert_eq!(fired(HitPolicy::Collect, "gold").len(), 3);
+
+        let rules = discount(HitPolicy::Priority).compile().unwrap();
+        assert_eq!(
+            rules[2].expression,
+            r#"customer.tier in ["gold", "silver"] && (customer.tier == "gold") != true"#
+        );
+    }
+
+    #[test]
+    fn test_csv_tables() {
+        let csv = "\
+when:customer.tier, when:order.weight, then:order.shipping, priority, description
+gold,               -,                 0,                  10,       Gold ships free
+-,                  -,                 7,                  ,
+";
+        let table =
+            DecisionTable::from_csv("shipping", HitPolicy::Priority, csv.as_bytes()).unwrap();
+        assert_eq!(table.conditions, ["customer.tier", "order.weight"]);
+        assert_eq!(table.rows[0].priority, Some(10));
+        assert_eq!(table.rows[1].priority, None);
+
+        let rules = table.compile().unwrap();
+        assert_eq!(rules[0].name, "Gold ships free");
+        assert_eq!(rules[0].expression, r#"customer.tier == "gold""#);
+        assert_eq!(rules[1].expression, r#"(customer.tier == "gold") != true"#);
+
+        let mut engine = RulesEngine::new();
+        engine.add_table(&table).unwrap();
+        assert_eq!(
+            engine.execute(&order("gold", 1)).unwrap().fired_rules(),
+            ["shipping_row1"]
+        );
+
+        let bad = "when:x, total\n1, 2\n";
+        assert!(matches!(
+            DecisionTable::from_csv("bad", HitPolicy::First, bad.as_bytes()),
+            Err(Error::Config(_))
+        ));
+    }
+
+    #[test]
+    fn test_overlaps_and_gaps() {
+        let diagnostics = shipping(HitPolicy::Unique).check().unwrap();
+        assert!(diagnostics.iter().all(|d| d.code == "table-gap"));
+        let other = r#"customer.tier not in ["gold", "silver", "bronze"]"#;
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|d| d.message.as_str())
+                .collect::<Vec<_>>(),
+            [
+                r#"no row matches when customer.tier == "silver" and order.weight < 0"#.to_string(),
+                r#"no row matches when customer.tier == "bronze" and order.weight < 0"#.to_string(),
+                format!("no row matches when {} and order.weight < 0", other),
+                format!("no row matches when {} and order.weight == 0", other),
+                format!("no row matches when {} and 0 < order.weight < 10", other),
+            ]
+        );
+
+        let overlapping = DecisionTable::new(
+            "tiers",
+            HitPolicy::Unique,
+            ["customer.age"],
+            ["customer.band"],
+        )
+        .with_row(TableRow::new(["[0..18]"], ["minor"]))
+        .with_row(TableRow::new(["[18..65)"], ["adult"]))
+        .with_row(TableRow::new([">= 65"], ["senior"]))
+        .with_row(TableRow::new(["-"], ["unknown"]));
+        let codes: Vec<(String, Option<String>)> = overlapping
+            .check()
+            .unwrap()
+            .into_iter()
+            .map(|d| (d.message, d.rule_id))
+            .collect();
+        assert_eq!(
+            codes,
+            [
+                (
+                    "rows 1 and 2 can match the same input".to_string(),
+                    Some("tiers_row2".to_string())
+                ),
+                (
+                    "rows 1 and 4 can match the same input".to_string(),
+                    Some("tiers_row4".to_string())
+                ),
+                (
+                    "rows 2 and 4 can match the same input".to_string(),
+                    Some("tiers_row4".to_string())
+                ),
+                (
+                    "rows 3 and 4 can match the same input".to_string(),
+                    Some("tiers_row4".to_string())
+                ),
+            ]
+        );
+
+        let err = overlapping.compile().unwrap_err().to_string();
+        assert!(
+            err.contains("decision table `tiers`: rows 1 and 2 can match the same input"),
+            "{}",
+            err
+        );
+
+        let collect = DecisionTable {
+            hit_policy: HitPolicy::Collect,
+            rows: overlapping.rows[..3].to_vec(),
+            ..overlapping.clone()
+        };
+        let diagnostics = collect.check().unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "no row matches when customer.age < 0"
+        );
+    }
+}
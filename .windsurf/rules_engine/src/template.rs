@@ -0,0 +1,735 @@
+//! Parameterized rule templates
+//!
+//! A [`RuleTemplate`] is one rule written against typed parameters, such as a
+//! `threshold` in `order.total >= {{threshold}}`. Instantiating it with a list
+//! of [`ParameterSet`]s, read from YAML or CSV, produces one
+//! [`RuleDefinition`] per set, with the template id followed by the set's
+//! number as its id.
+//!
+//! Parameters are checked against their declared [`Field`] type, `min`/`max`
+//! bounds and `one_of` values before they are substituted. In expressions,
+//! including those of `set`, `emit` and `call` actions, a placeholder becomes
+//! a literal: strings are quoted, timestamps, durations and decimals become
+//! `timestamp('...')`, `duration('...')` and `decimal('...')` calls.
+//! Everywhere else, such as
+//! the name, tags and message text, the value is inserted as plain text.
+//!
+//! ```
+//! use windsurf_rules::prelude::*;
+//! use windsurf_rules::template::RuleTemplate;
+//!
+//! let template = RuleTemplate::from_yaml(r#"
+//! id: minimum
+//! name: "Minimum {{threshold}} for {{tier}}"
+//! parameters:
+//!   tier: {type: string, one_of: [gold, silver]}
+//!   threshold: {type: float, min: 0, max: 1000}
+//! expression: customer.tier == {{tier}} && order.total >= {{threshold}}
+//! then:
+//!   - {type: set, path: order.approved, value: "true"}
+//! "#)?;
+//! let sets = template.parameter_sets_from_csv("tier,threshold\ngold,100\nsilver,250\n".as_bytes())?;
+//!
+//! let mut engine = RulesEngine::new();
+//! engine.add_template(&template, &sets)?;
+//! let context = RuleContext::new()
+//!     .with_fact("customer", [("tier", "silver")].into_iter().collect::<Value>())
+//!     .with_fact("order", [("total", 300)].into_iter().collect::<Value>());
+//! assert_eq!(engine.execute(&context)?.fired_rules(), ["minimum_2"]);
+//! # Ok::<(), windsurf_rules::Error>(())
+//! ```
+
+use chrono::{DateTime, Utc};
+use rust_decimal::prelude::FromPrimitive;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::io;
+use std::str::FromStr;
+
+use crate::analysis::{Field, Type};
+use crate::error::{Error, Result};
+use crate::expr::Literal;
+use crate::rule::duration::{format_duration, parse_duration};
+use crate::rule::{Action, Rule as RuleDefinition, Value};
+
+/// Parameter values for one instance of a template, by parameter name
+pub type ParameterSet = BTreeMap<String, Value>;
+
+/// A typed template parameter
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Parameter {
+    /// Type of the parameter; nullable parameters may be omitted
+    #[serde(flatten)]
+    pub field: Field,
+    /// Value used when a set omits the parameter
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_plain",
+        deserialize_with = "deserialize_plain"
+    )]
+    pub default: Option<Value>,
+    /// Smallest allowed value
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_plain",
+        deserialize_with = "deserialize_plain"
+    )]
+    pub min: Option<Value>,
+    /// Largest allowed value
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_plain",
+        deserialize_with = "deserialize_plain"
+    )]
+    pub max: Option<Value>,
+    /// The allowed values; empty allows any value of the type
+    #[serde(
+        default,
+        skip_serializing_if = "Vec::is_empty",
+        serialize_with = "serialize_plain_list",
+        deserialize_with = "deserialize_plain_list"
+    )]
+    pub one_of: Vec<Value>,
+    /// What the parameter controls
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+}
+
+impl Parameter {
+    /// A required parameter of type `ty`
+    pub fn new(ty: Type) -> Self {
+        Self {
+            field: Field::new(ty),
+            ..Self::default()
+        }
+    }
+
+    /// Sets the value used when a set omits the parameter
+    pub fn with_default(mut self, value: impl Into<Value>) -> Self {
+        self.default = Some(value.into());
+        self
+    }
+
+    /// Sets the inclusive bounds of the parameter
+    pub fn with_range(mut self, min: impl Into<Value>, max: impl Into<Value>) -> Self {
+        self.min = Some(min.into());
+        self.max = Some(max.into());
+        self
+    }
+
+    /// Restricts the parameter to the given values
+    pub fn one_of<I, V>(mut self, values: I) -> Self
+    where
+        I: IntoIterator<Item = V>,
+        V: Into<Value>,
+    {
+        self.one_of = values.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Converts `value` to the declared type and checks it against the
+    /// bounds and allowed values
+    fn resolve(&self, value: Option<Value>) -> std::result::Result<Value, String> {
+        let value = match value.or_else(|| self.default.clone()) {
+            Some(value) => value,
+            None if self.field.nullable => Value::Null,
+            None => return Err("is required".to_string()),
+        };
+        let value = coerce(&self.field, value.clone()).ok_or_else(|| {
+            format!(
+                "expects {}, got {} `{}`",
+                self.field,
+                value.type_name(),
+                value
+            )
+        })?;
+        if value.is_null() {
+            return Ok(value);
+        }
+
+        for (bound, below, relation) in [
+            (&self.min, Ordering::Less, "below the minimum"),
+            (&self.max, Ordering::Greater, "above the maximum"),
+        ] {
+            let Some(bound) = bound else { continue };
+            let bound = coerce(&self.field, bound.clone()).unwrap_or_else(|| bound.clone());
+            match value.compare(&bound) {
+                Some(ordering) if ordering == below => {
+                    return Err(format!("is {}, {} {}", value, relation, bound))
+                }
+                Some(_) => {}
+                None => return Err(format!("cannot be compared with the bound {}", bound)),
+            }
+        }
+
+        if !self.one_of.is_empty()
+            && !self
+                .one_of
+                .iter()
+                .filter_map(|allowed| coerce(&self.field, allowed.clone()))
+                .any(|allowed| allowed.equals(&value))
+        {
+            let allowed: Vec<String> = self.one_of.iter().map(ToString::to_string).collect();
+            return Err(format!("is {}, not one of {}", value, allowed.join(", ")));
+        }
+        Ok(value)
+    }
+}
+
+/// A rule with `{{name}}` placeholders for typed parameters
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleTemplate {
+    /// Template id, used as the prefix of the generated rule ids
+    pub id: String,
+    /// Name of the generated rules, may contain placeholders; defaults to the
+    /// rule id
+    #[serde(default)]
+    pub name: String,
+    /// The declared parameters, by name
+    #[serde(default)]
+    pub parameters: BTreeMap<String, Parameter>,
+    /// Condition expression with placeholders
+    pub expression: String,
+    /// Priority of the generated rules
+    #[serde(default)]
+    pub priority: i32,
+    /// Actions run when the condition matches
+    #[serde(default, rename = "then", skip_serializing_if = "Vec::is_empty")]
+    pub then_actions: Vec<Action>,
+    /// Actions run when the condition does not match
+    #[serde(default, rename = "else", skip_serializing_if = "Vec::is_empty")]
+    pub else_actions: Vec<Action>,
+    /// Agenda group of the generated rules
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agenda_group: Option<String>,
+    /// Activation group of the generated rules
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub activation_group: Option<String>,
+    /// Tags of the generated rules, may contain placeholders
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Metadata of the generated rules, values may contain placeholders
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl RuleTemplate {
+    /// Creates a template without parameters
+    pub fn new(id: impl Into<String>, expression: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            name: String::new(),
+            parameters: BTreeMap::new(),
+            expression: expression.into(),
+            priority: 0,
+            then_actions: Vec::new(),
+            else_actions: Vec::new(),
+            agenda_group: None,
+            activation_group: None,
+            tags: Vec::new(),
+            metadata: BTreeMap::new(),
+        }
+    }
+
+    /// Declares a parameter
+    pub fn with_parameter(mut self, name: impl Into<String>, parameter: Parameter) -> Self {
+        self.parameters.insert(name.into(), parameter);
+        self
+    }
+
+    /// Adds an action run when the condition matches
+    pub fn then(mut self, action: Action) -> Self {
+        self.then_actions.push(action);
+        self
+    }
+
+    /// Parses a template from YAML (or JSON)
+    pub fn from_yaml(source: &str) -> Result<Self> {
+        serde_yaml::from_str(source).map_err(|err| Error::Serialization(Box::new(err)))
+    }
+
+    /// Parses parameter sets from a YAML (or JSON) list of maps with plain
+    /// values, e.g. `[{threshold: 100}, {threshold: 250}]`
+    pub fn parameter_sets_from_yaml(&self, source: &str) -> Result<Vec<ParameterSet>> {
+        let sets: Vec<BTreeMap<String, Plain>> =
+            serde_yaml::from_str(source).map_err(|err| Error::Serialization(Box::new(err)))?;
+        Ok(sets
+            .into_iter()
+            .map(|set| {
+                set.into_iter()
+                    .map(|(name, value)| (name, value.into()))
+                    .collect()
+            })
+            .collect())
+    }
+
+    /// Reads parameter sets from CSV, one per record. The header names the
+    /// parameters; empty cells leave a parameter to its default. Cells of
+    /// `string` parameters are taken verbatim, others are read as YAML
+    /// scalars or flow lists, e.g. `42`, `true` or `[a, b]`.
+    pub fn parameter_sets_from_csv(&self, reader: impl io::Read) -> Result<Vec<ParameterSet>> {
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+        let serialization = |err: csv::Error| Error::Serialization(Box::new(err));
+
+        let mut columns = Vec::new();
+        for header in reader.headers().map_err(serialization)? {
+            let parameter = self.parameters.get(header).ok_or_else(|| {
+                Error::Config(format!(
+                    "rule template `{}`: unknown parameter column `{}`",
+                    self.id, header
+                ))
+            })?;
+            columns.push((header.to_string(), parameter.field.ty == Type::String));
+        }
+
+        let mut sets = Vec::new();
+        for record in reader.records() {
+            let record = record.map_err(serialization)?;
+            let mut set = ParameterSet::new();
+            for ((name, verbatim), cell) in columns.iter().zip(record.iter()) {
+                if cell.is_empty() {
+                    continue;
+                }
+                let value = match serde_yaml::from_str::<Plain>(cell) {
+                    Ok(plain) if !verbatim => plain.into(),
+                    _ => Value::from(cell),
+                };
+                set.insert(name.clone(), value);
+            }
+            sets.push(set);
+        }
+        Ok(sets)
+    }
+
+    /// Id of the rule generated for the set at `index` (zero-based)
+    pub fn rule_id(&self, index: usize) -> String {
+        format!("{}_{}", self.id, index + 1)
+    }
+
+    /// Instantiates the template once per parameter set. Every rule is
+    /// compiled to check the substituted expressions, and carries the
+    /// template id in its `template` metadata.
+    pub fn instantiate(&self, sets: &[ParameterSet]) -> Result<Vec<RuleDefinition>> {
+        sets.iter()
+            .enumerate()
+            .map(|(index, set)| {
+                let error = |reason: String| {
+                    Error::Config(format!(
+                        "rule template `{}` instance {}: {}",
+                        self.id,
+                        index + 1,
+                        reason
+                    ))
+                };
+                let values = self.resolve(set).map_err(error)?;
+                let rule = self.fill(index, &values).map_err(error)?;
+                rule.clone()
+                    .compile()
+                    .map_err(|err| error(err.to_string()))?;
+                Ok(rule)
+            })
+            .collect()
+    }
+
+    /// Checks the set against the declared parameters, filling in defaults
+    fn resolve(&self, set: &ParameterSet) -> std::result::Result<ParameterSet, String> {
+        if let Some(unknown) = set.keys().find(|name| !self.parameters.contains_key(*name)) {
+            return Err(format!("unknown parameter `{}`", unknown));
+        }
+        self.parameters
+            .iter()
+            .map(|(name, parameter)| {
+                parameter
+                    .resolve(set.get(name).cloned())
+                    .map(|value| (name.clone(), value))
+                    .map_err(|reason| format!("parameter `{}` {}", name, reason))
+            })
+            .collect()
+    }
+
+    fn fill(
+        &self,
+        index: usize,
+        values: &ParameterSet,
+    ) -> std::result::Result<RuleDefinition, String> {
+        let text = |s: &str| substitute(s, values, plain_text);
+        let literal = |s: &str| substitute(s, values, literal_source);
+        let action = |action: &Action| -> std::result::Result<Action, String> {
+            Ok(match action {
+                Action::Set { path, value } => Action::Set {
+                    path: text(path)?,
+                    value: literal(value)?,
+                },
+                Action::Remove { path } => Action::Remove { path: text(path)? },
+                Action::Emit { event, payload } => Action::Emit {
+                    event: text(event)?,
+                    payload: payload
+                        .iter()
+                        .map(|(key, value)| Ok((key.clone(), literal(value)?)))
+                        .collect::<std::result::Result<_, String>>()?,
+                },
+                Action::Message { text: message } => Action::Message {
+                    text: text(message)?,
+                },
+                Action::Call { callback, args } => Action::Call {
+                    callback: callback.clone(),
+                    args: args
+                        .iter()
+                        .map(|arg| literal(arg))
+                        .collect::<std::result::Result<_, _>>()?,
+                },
+                Action::Native(native) => Action::Native(native.clone()),
+            })
+        };
+
+        let id = self.rule_id(index);
+        let name = if self.name.is_empty() {
+            id.clone()
+        } else {
+            text(&self.name)?
+        };
+        let mut rule = RuleDefinition::new(id)
+            .with_name(name)
+            .with_expression(literal(&self.expression)?)
+            .with_priority(self.priority);
+        rule.then_actions = self
+            .then_actions
+            .iter()
+            .map(action)
+            .collect::<std::result::Result<_, _>>()?;
+        rule.else_actions = self
+            .else_actions
+            .iter()
+            .map(action)
+            .collect::<std::result::Result<_, _>>()?;
+        rule.agenda_group = self.agenda_group.as_deref().map(text).transpose()?;
+        rule.activation_group = self.activation_group.as_deref().map(text).transpose()?;
+        for tag in &self.tags {
+            rule = rule.with_tag(text(tag)?);
+        }
+        for (key, value) in &self.metadata {
+            rule = rule.with_metadata(key.clone(), text(value)?);
+        }
+        // Last, so user metadata cannot hide where the rule came from
+        Ok(rule.with_metadata("template", self.id.clone()))
+    }
+}
+
+/// Replaces every `{{name}}` in `source` with the rendered parameter value
+fn substitute(
+    source: &str,
+    values: &ParameterSet,
+    render: fn(&Value) -> std::result::Result<String, String>,
+) -> std::result::Result<String, String> {
+    let mut out = String::with_capacity(source.len());
+    let mut rest = source;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find("}}")
+            .ok_or_else(|| format!("unterminated placeholder in `{}`", source))?;
+        let name = rest[start + 2..start + end].trim();
+        let value = values
+            .get(name)
+            .ok_or_else(|| format!("placeholder `{{{{{}}}}}` names no parameter", name))?;
+        out.push_str(&render(value).map_err(|reason| format!("parameter `{}` {}", name, reason))?);
+        rest = &rest[start + end + 2..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Renders a value as text, strings without quotes
+fn plain_text(value: &Value) -> std::result::Result<String, String> {
+    Ok(match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    })
+}
+
+/// Renders a value as an expression that evaluates to it
+fn literal_source(value: &Value) -> std::result::Result<String, String> {
+    Ok(match value {
+        Value::Null => Literal::Null.to_string(),
+        Value::Bool(b) => Literal::Bool(*b).to_string(),
+        Value::Int(i) => Literal::Int(*i).to_string(),
+        Value::Float(x) if x.is_finite() => Literal::Float(*x).to_string(),
+        Value::Float(x) => return Err(format!("{} has no literal form", x)),
+        // Expressions have no decimal literal; a number would turn into a float
+        Value::Decimal(d) => format!("decimal('{}')", d),
+        Value::String(s) => Literal::String(s.clone()).to_string(),
+        Value::Timestamp(ts) => format!("timestamp('{}')", ts.to_rfc3339()),
+        Value::Duration(d) => format!("duration('{}')", format_duration(*d)),
+        Value::List(items) => {
+            let items = items
+                .iter()
+                .map(literal_source)
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            format!("[{}]", items.join(", "))
+        }
+        Value::Map(_) => {
+            return Err("is a map, which cannot be written in an expression".to_string())
+        }
+    })
+}
+
+/// Converts a plain YAML or CSV value to the declared type, or `None` if it
+/// does not fit. Strings are parsed where a timestamp, duration or decimal is
+/// declared.
+fn coerce(field: &Field, value: Value) -> Option<Value> {
+    let coerced = match (&field.ty, value) {
+        (Type::Float, Value::Int(i)) => Value::Float(i as f64),
+        (Type::Decimal, Value::Int(i)) => Value::Decimal(Decimal::from(i)),
+        (Type::Decimal, Value::Float(x)) => Value::Decimal(Decimal::from_f64(x)?),
+        (Type::Decimal, Value::String(s)) => Value::Decimal(Decimal::from_str(&s).ok()?),
+        (Type::Timestamp, Value::String(s)) => {
+            Value::Timestamp(DateTime::parse_from_rfc3339(&s).ok()?.with_timezone(&Utc))
+        }
+        (Type::Duration, Value::String(s)) => Value::Duration(parse_duration(&s)?),
+        (Type::List { items }, Value::List(values)) => Value::List(
+            values
+                .into_iter()
+                .map(|value| coerce(items, value))
+                .collect::<Option<_>>()?,
+        ),
+        (Type::Map { fields }, Value::Map(mut map)) => {
+            for (name, field) in fields {
+                let value = map.remove(name).unwrap_or(Value::Null);
+                map.insert(name.clone(), coerce(field, value)?);
+            }
+            Value::Map(map)
+        }
+        (_, value) => value,
+    };
+    field.admits(&coerced).then_some(coerced)
+}
+
+/// An untagged value, as written in YAML and CSV; [`Value`] itself is
+/// externally tagged
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Plain {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    List(Vec<Plain>),
+    Map(BTreeMap<String, Plain>),
+    Null(()),
+}
+
+impl From<Plain> for Value {
+    fn from(plain: Plain) -> Self {
+        match plain {
+            Plain::Bool(b) => Value::Bool(b),
+            Plain::Int(i) => Value::Int(i),
+            Plain::Float(x) => Value::Float(x),
+            Plain::String(s) => Value::String(s),
+            Plain::List(items) => Value::List(items.into_iter().map(Into::into).collect()),
+            Plain::Map(map) => Value::Map(map.into_iter().map(|(k, v)| (k, v.into())).collect()),
+            Plain::Null(()) => Value::Null,
+        }
+    }
+}
+
+impl From<&Value> for Plain {
+    fn from(value: &Value) -> Self {
+        match value {
+            Value::Null => Plain::Null(()),
+            Value::Bool(b) => Plain::Bool(*b),
+            Value::Int(i) => Plain::Int(*i),
+            Value::Float(x) => Plain::Float(*x),
+            Value::Decimal(d) => Plain::String(d.to_string()),
+            Value::String(s) => Plain::String(s.clone()),
+            Value::Timestamp(ts) => Plain::String(ts.to_rfc3339()),
+            Value::Duration(d) => Plain::String(format_duration(*d)),
+            Value::List(items) => Plain::List(items.iter().map(Into::into).collect()),
+            Value::Map(map) => Plain::Map(map.iter().map(|(k, v)| (k.clone(), v.into())).collect()),
+        }
+    }
+}
+
+fn serialize_plain<S: Serializer>(
+    value: &Option<Value>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    value.as_ref().map(Plain::from).serialize(serializer)
+}
+
+fn deserialize_plain<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<Value>, D::Error> {
+    Ok(Option::<Plain>::deserialize(deserializer)?.map(Into::into))
+}
+
+fn serialize_plain_list<S: Serializer>(
+    values: &[Value],
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    values
+        .iter()
+        .map(Plain::from)
+        .collect::<Vec<_>>()
+        .serialize(serializer)
+}
+
+fn deserialize_plain_list<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Vec<Value>, D::Error> {
+    Ok(Vec::<Plain>::deserialize(deserializer)?
+        .into_iter()
+        .map(Into::into)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::evaluate::Rule;
+    use crate::rule::RuleContext;
+
+    fn template() -> RuleTemplate {
+        RuleTemplate::from_yaml(
+            r#"
+id: minimum
+name: "Minimum {{threshold}}"
+parameters:
+  threshold: {type: float, min: 0, max: 1}
+  tier: {type: string, default: gold, one_of: [gold, silver]}
+  grace: {type: duration, default: P7D}
+expression: customer.tier == {{tier}} && customer.score >= {{threshold}}
+then:
+  - {type: message, text: "{{tier}} customer above {{threshold}}"}
+  - {type: set, path: customer.grace, value: "{{grace}}"}
+tags: ["tier:{{tier}}"]
+"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_template_instantiates_rules() {
+        let template = template();
+        let sets = template
+            .parameter_sets_from_yaml(
+                "[{threshold: 0.5}, {threshold: 1, tier: silver, grace: PT12H}]",
+            )
+            .unwrap();
+        let rules = template.instantiate(&sets).unwrap();
+
+        assert_eq!(rules[0].id, "minimum_1");
+        assert_eq!(rules[0].name, "Minimum 0.5");
+        assert_eq!(
+            rules[0].expression,
+            r#"customer.tier == "gold" && customer.score >= 0.5"#
+        );
+        assert_eq!(
+            rules[1].expression,
+            r#"customer.tier == "silver" && customer.score >= 1.0"#
+        );
+        assert_eq!(
+            rules[1].then_actions[0],
+            Action::message("silver customer above 1")
+        );
+        assert_eq!(
+            rules[1].then_actions[1],
+            Action::set("customer.grace", "duration('PT12H')")
+        );
+        assert_eq!(rules[1].tags, ["tier:silver"]);
+        assert_eq!(rules[1].metadata["template"], "minimum");
+
+        let customer: Value = [("tier", Value::from("silver")), ("score", Value::from(1))]
+            .into_iter()
+            .collect();
+        let context = RuleContext::new().with_fact("customer", customer);
+        let compiled = rules[1].clone().compile().unwrap();
+        assert!(compiled.evaluate(&context).matched);
+    }
+
+    #[test]
+    fn test_template_reads_csv_sets() {
+        let template =
+            template().with_parameter("code", Parameter::new(Type::String).with_default("none"));
+        let sets = template
+            .parameter_sets_from_csv("threshold,tier,code\n0.25,silver,007\n1,,\n".as_bytes())
+            .unwrap();
+        assert_eq!(sets[0]["threshold"], Value::Float(0.25));
+        assert_eq!(sets[0]["code"], Value::from("007"));
+        assert!(!sets[1].contains_key("tier"));
+        assert_eq!(template.instantiate(&sets).unwrap().len(), 2);
+
+        let err = template
+            .parameter_sets_from_csv("limit\n1\n".as_bytes())
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("unknown parameter column `limit`"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_template_validates_parameters() {
+        let template = template();
+        let error = |sets: &str| {
+            let sets = template.parameter_sets_from_yaml(sets).unwrap();
+            template.instantiate(&sets).unwrap_err().to_string()
+        };
+
+        assert!(error("[{}]").contains("instance 1: parameter `threshold` is required"));
+        assert!(error("[{threshold: 0.5}, {threshold: 2}]")
+            .contains("instance 2: parameter `threshold` is 2, above the maximum 1"));
+        assert!(
+            error("[{threshold: low}]").contains("parameter `threshold` expects float, got string")
+        );
+        assert!(error("[{threshold: 0.5, tier: bronze}]").contains("is \"bronze\", not one of"));
+        assert!(error("[{threshold: 0.5, grace: 3 days}]")
+            .contains("parameter `grace` expects duration"));
+        assert!(error("[{threshold: 0.5, limit: 1}]").contains("unknown parameter `limit`"));
+
+        let broken = RuleTemplate::new("broken", "order.total > {{limit}}");
+        let err = broken.instantiate(&[ParameterSet::new()]).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("placeholder `{{limit}}` names no parameter"),
+            "{}",
+            err
+        );
+
+        let typed = RuleTemplate::new("typed", "order.placed < {{cutoff}}")
+            .with_parameter("cutoff", Parameter::new(Type::Timestamp));
+        let sets = typed
+            .parameter_sets_from_yaml("[{cutoff: '2024-01-01T00:00:00Z'}]")
+            .unwrap();
+        assert_eq!(
+            typed.instantiate(&sets).unwrap()[0].expression,
+            "order.placed < timestamp('2024-01-01T00:00:00+00:00')"
+        );
+
+        let mut priced = RuleTemplate::new("priced", "order.total > {{limit}}")
+            .with_parameter("limit", Parameter::new(Type::Decimal));
+        priced.metadata.insert("limit".into(), "{{limit}}".into());
+        priced.metadata.insert("template".into(), "other".into());
+        let sets = priced
+            .parameter_sets_from_yaml("[{limit: '10.10'}]")
+            .unwrap();
+        let rule = &priced.instantiate(&sets).unwrap()[0];
+        assert_eq!(rule.expression, "order.total > decimal('10.10')");
+        assert_eq!(rule.metadata["limit"], "10.10");
+        assert_eq!(rule.metadata["template"], "priced");
+        let order = RuleContext::new().with_fact(
+            "order",
+            [("total", Value::Decimal("10.11".parse().unwrap()))]
+                .into_iter()
+                .collect::<Value>(),
+        );
+        assert!(rule.clone().compile().unwrap().evaluate(&order).is_match());
+    }
+}